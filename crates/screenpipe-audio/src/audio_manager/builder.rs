@@ -1,5 +1,11 @@
 use anyhow::Result;
-use std::{collections::HashSet, env, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
 use screenpipe_core::Language;
 use screenpipe_db::DatabaseManager;
@@ -7,22 +13,34 @@ use screenpipe_db::DatabaseManager;
 use crate::{
     core::{
         device::{default_input_device, default_output_device},
-        engine::AudioTranscriptionEngine,
+        engine::{AudioTranscriptionEngine, RealtimeTranscriptionEngine},
+    },
+    echo_cancellation::{EchoCancellationMode, DEFAULT_ECHO_THRESHOLD},
+    preprocessing::PreprocessingChain,
+    speaker::embedding_manager::DEFAULT_ENROLLED_SPEAKER_THRESHOLD,
+    transcription::{
+        deepgram::CUSTOM_DEEPGRAM_API_TOKEN,
+        engine::{transcription_chain, OpenAiCompatibleConfig},
     },
-    transcription::deepgram::CUSTOM_DEEPGRAM_API_TOKEN,
     vad::{VadEngineEnum, VadSensitivity},
 };
 
-use crate::audio_manager::AudioManager;
+use crate::audio_manager::{AudioManager, DeviceAudioSettings};
 
 #[derive(Clone)]
 pub struct AudioManagerOptions {
     pub transcription_engine: Arc<AudioTranscriptionEngine>,
+    /// Engines tried in order when `transcription_engine` fails, `None` for its defaults
+    pub transcription_fallbacks: Option<Vec<AudioTranscriptionEngine>>,
+    /// Server used by the `OpenAiCompatible` engine
+    pub openai_compatible: OpenAiCompatibleConfig,
     pub vad_engine: VadEngineEnum,
     pub languages: Vec<Language>,
     pub deepgram_api_key: Option<String>,
     pub enable_diarization: bool,
     pub enable_realtime: bool,
+    /// Engine of realtime transcription, when enabled
+    pub realtime_engine: RealtimeTranscriptionEngine,
     pub audio_chunk_duration: Duration,
     pub vad_sensitivity: VadSensitivity,
     pub health_check_grace_period: u64,
@@ -37,6 +55,88 @@ pub struct AudioManagerOptions {
     /// When true, automatically follow system default audio devices
     /// and switch when the system default changes (e.g., device plug/unplug)
     pub use_system_default_audio: bool,
+    /// Minimum cosine similarity for speech to be attributed to an enrolled speaker
+    pub enrolled_speaker_threshold: f32,
+    /// What to do with microphone speech that echoes an output device
+    pub echo_cancellation: EchoCancellationMode,
+    /// Minimum correlation of a microphone segment with an output device to be an echo
+    pub echo_threshold: f32,
+    /// Preprocessing of the audio of devices without their own chain
+    pub audio_preprocessing: PreprocessingChain,
+    /// Settings of specific devices overriding the ones above, by device name
+    pub device_settings: HashMap<String, DeviceAudioSettings>,
+}
+
+impl AudioManagerOptions {
+    /// Preprocessing chain of the audio of `device`.
+    pub fn preprocessing_for(&self, device: &str) -> &PreprocessingChain {
+        self.device_settings
+            .get(device)
+            .and_then(|settings| settings.preprocessing.as_ref())
+            .unwrap_or(&self.audio_preprocessing)
+    }
+
+    pub fn languages_for(&self, device: &str) -> Vec<Language> {
+        self.device_settings
+            .get(device)
+            .and_then(|settings| settings.languages.clone())
+            .unwrap_or_else(|| self.languages.clone())
+    }
+
+    pub fn vad_sensitivity_for(&self, device: &str) -> VadSensitivity {
+        self.device_settings
+            .get(device)
+            .and_then(|settings| settings.vad_sensitivity)
+            .unwrap_or(self.vad_sensitivity)
+    }
+
+    pub fn transcription_engine_for(&self, device: &str) -> Arc<AudioTranscriptionEngine> {
+        self.device_settings
+            .get(device)
+            .and_then(|settings| settings.transcription_engine.clone())
+            .map(Arc::new)
+            .unwrap_or_else(|| self.transcription_engine.clone())
+    }
+
+    pub fn diarization_for(&self, device: &str) -> bool {
+        self.device_settings
+            .get(device)
+            .and_then(|settings| settings.enable_diarization)
+            .unwrap_or(self.enable_diarization)
+    }
+
+    pub fn audio_chunk_duration_for(&self, device: &str) -> Duration {
+        self.device_settings
+            .get(device)
+            .and_then(DeviceAudioSettings::audio_chunk_duration)
+            .unwrap_or(self.audio_chunk_duration)
+    }
+
+    /// Check that the engines of `settings` can be used with these options.
+    pub fn validate_device_settings(&self, settings: &DeviceAudioSettings) -> Result<()> {
+        if settings.audio_chunk_duration_secs == Some(0) {
+            return Err(anyhow::anyhow!(
+                "audio chunk duration must be at least 1 second"
+            ));
+        }
+        match settings.transcription_engine {
+            Some(AudioTranscriptionEngine::Deepgram)
+                if self.deepgram_api_key.is_none() && CUSTOM_DEEPGRAM_API_TOKEN.is_empty() =>
+            {
+                Err(anyhow::anyhow!(
+                    "Deepgram API key is required for Deepgram transcription engine"
+                ))
+            }
+            Some(AudioTranscriptionEngine::OpenAiCompatible)
+                if self.openai_compatible.url.is_empty() =>
+            {
+                Err(anyhow::anyhow!(
+                    "A server url is required for the OpenAI-compatible transcription engine"
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 impl Default for AudioManagerOptions {
@@ -48,11 +148,14 @@ impl Default for AudioManagerOptions {
         Self {
             output_path: None,
             transcription_engine: Arc::new(AudioTranscriptionEngine::default()),
+            transcription_fallbacks: None,
+            openai_compatible: OpenAiCompatibleConfig::default(),
             vad_engine: VadEngineEnum::Silero,
             languages: vec![],
             deepgram_api_key,
             enable_diarization: true,
             enable_realtime: false,
+            realtime_engine: RealtimeTranscriptionEngine::default(),
             audio_chunk_duration: Duration::from_secs(30),
             vad_sensitivity: VadSensitivity::High,
             health_check_grace_period: 15,
@@ -63,6 +166,11 @@ impl Default for AudioManagerOptions {
             deepgram_websocket_url,
             use_pii_removal: false,
             use_system_default_audio: true,
+            enrolled_speaker_threshold: DEFAULT_ENROLLED_SPEAKER_THRESHOLD,
+            echo_cancellation: EchoCancellationMode::default(),
+            echo_threshold: DEFAULT_ECHO_THRESHOLD,
+            audio_preprocessing: PreprocessingChain::default(),
+            device_settings: HashMap::new(),
         }
     }
 }
@@ -84,6 +192,19 @@ impl AudioManagerBuilder {
         self
     }
 
+    pub fn transcription_fallbacks(
+        mut self,
+        transcription_fallbacks: Vec<AudioTranscriptionEngine>,
+    ) -> Self {
+        self.options.transcription_fallbacks = Some(transcription_fallbacks);
+        self
+    }
+
+    pub fn openai_compatible(mut self, openai_compatible: OpenAiCompatibleConfig) -> Self {
+        self.options.openai_compatible = openai_compatible;
+        self
+    }
+
     pub fn vad_engine(mut self, vad_engine: VadEngineEnum) -> Self {
         self.options.vad_engine = vad_engine;
         self
@@ -109,6 +230,11 @@ impl AudioManagerBuilder {
         self
     }
 
+    pub fn realtime_engine(mut self, realtime_engine: RealtimeTranscriptionEngine) -> Self {
+        self.options.realtime_engine = realtime_engine;
+        self
+    }
+
     pub fn audio_chunk_duration(mut self, audio_chunk_duration: Duration) -> Self {
         self.options.audio_chunk_duration = audio_chunk_duration;
         self
@@ -154,6 +280,36 @@ impl AudioManagerBuilder {
         self
     }
 
+    pub fn enrolled_speaker_threshold(mut self, enrolled_speaker_threshold: f32) -> Self {
+        self.options.enrolled_speaker_threshold = enrolled_speaker_threshold;
+        self
+    }
+
+    pub fn echo_cancellation(mut self, echo_cancellation: EchoCancellationMode) -> Self {
+        self.options.echo_cancellation = echo_cancellation;
+        self
+    }
+
+    pub fn echo_threshold(mut self, echo_threshold: f32) -> Self {
+        self.options.echo_threshold = echo_threshold;
+        self
+    }
+
+    pub fn audio_preprocessing(mut self, audio_preprocessing: PreprocessingChain) -> Self {
+        self.options.audio_preprocessing = audio_preprocessing;
+        self
+    }
+
+    /// Override the settings of `device`, merged with those set before for it.
+    pub fn device_settings(mut self, device: String, settings: DeviceAudioSettings) -> Self {
+        self.options
+            .device_settings
+            .entry(device)
+            .or_default()
+            .merge(settings);
+        self
+    }
+
     pub async fn build(&mut self, db: Arc<DatabaseManager>) -> Result<AudioManager> {
         self.validate_options()?;
         let options = &mut self.options;
@@ -175,7 +331,11 @@ impl AudioManagerBuilder {
 
     // TODO: Make sure the custom urls work
     pub fn validate_options(&self) -> Result<()> {
-        if self.options.transcription_engine == Arc::new(AudioTranscriptionEngine::Deepgram)
+        let chain = transcription_chain(
+            &self.options.transcription_engine,
+            self.options.transcription_fallbacks.as_deref(),
+        );
+        if chain.contains(&AudioTranscriptionEngine::Deepgram)
             && (self.options.deepgram_api_key.is_none() && CUSTOM_DEEPGRAM_API_TOKEN.is_empty())
         {
             return Err(anyhow::anyhow!(
@@ -183,11 +343,24 @@ impl AudioManagerBuilder {
             ));
         }
 
+        if chain.contains(&AudioTranscriptionEngine::OpenAiCompatible)
+            && self.options.openai_compatible.url.is_empty()
+        {
+            return Err(anyhow::anyhow!(
+                "A server url is required for the OpenAI-compatible transcription engine"
+            ));
+        }
+
+        for settings in self.options.device_settings.values() {
+            self.options.validate_device_settings(settings)?;
+        }
+
         if self.options.output_path.is_none() {
             return Err(anyhow::anyhow!("Output path is required for audio manager"));
         }
 
         if self.options.enable_realtime
+            && self.options.realtime_engine == RealtimeTranscriptionEngine::Deepgram
             && (self.options.deepgram_api_key.is_none() && CUSTOM_DEEPGRAM_API_TOKEN.is_empty())
         {
             return Err(anyhow::anyhow!(
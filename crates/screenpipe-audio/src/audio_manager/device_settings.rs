@@ -0,0 +1,155 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::warn;
+
+use screenpipe_core::Language;
+use screenpipe_db::DatabaseManager;
+
+use crate::{
+    core::engine::AudioTranscriptionEngine, preprocessing::PreprocessingChain, vad::VadSensitivity,
+};
+
+/// Settings of a device overriding those of the audio manager, unset ones fall back to them.
+///
+/// A microphone close to one speaker and the system output mixing many compressed voices
+/// often need different languages, VAD sensitivity, engines or preprocessing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceAudioSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub languages: Option<Vec<Language>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vad_sensitivity: Option<VadSensitivity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcription_engine: Option<AudioTranscriptionEngine>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable_diarization: Option<bool>,
+    /// Length of the chunks the device audio is transcribed by, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_chunk_duration_secs: Option<u64>,
+    /// Preprocessing of the device audio, replacing the chain of the audio manager
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preprocessing: Option<PreprocessingChain>,
+}
+
+impl DeviceAudioSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Override the settings set in `other`.
+    pub fn merge(&mut self, other: DeviceAudioSettings) {
+        if other.languages.is_some() {
+            self.languages = other.languages;
+        }
+        if other.vad_sensitivity.is_some() {
+            self.vad_sensitivity = other.vad_sensitivity;
+        }
+        if other.transcription_engine.is_some() {
+            self.transcription_engine = other.transcription_engine;
+        }
+        if other.enable_diarization.is_some() {
+            self.enable_diarization = other.enable_diarization;
+        }
+        if other.audio_chunk_duration_secs.is_some() {
+            self.audio_chunk_duration_secs = other.audio_chunk_duration_secs;
+        }
+        if other.preprocessing.is_some() {
+            self.preprocessing = other.preprocessing;
+        }
+    }
+
+    pub fn audio_chunk_duration(&self) -> Option<Duration> {
+        self.audio_chunk_duration_secs.map(Duration::from_secs)
+    }
+
+    /// Set one setting from its command line name and value, e.g. `vad` and `low`.
+    /// `language` adds to the languages of the device. The stages of `preprocessing` are
+    /// separated by `+` as commas separate settings, e.g. `preprocessing=denoise+agc`.
+    pub fn set(&mut self, setting: &str, value: &str) -> Result<(), String> {
+        match setting {
+            "language" => self.languages.get_or_insert_with(Vec::new).push(
+                Language::from_str(value, true)
+                    .map_err(|_| format!("unknown language '{}'", value))?,
+            ),
+            "vad" => {
+                self.vad_sensitivity = Some(from_cli_name(value).ok_or_else(|| {
+                    format!(
+                        "unknown VAD sensitivity '{}' (expected low, medium or high)",
+                        value
+                    )
+                })?)
+            }
+            "engine" => {
+                self.transcription_engine = Some(
+                    from_cli_name(value)
+                        .ok_or_else(|| format!("unknown transcription engine '{}'", value))?,
+                )
+            }
+            "diarization" => {
+                self.enable_diarization = Some(match value {
+                    "on" | "true" => true,
+                    "off" | "false" => false,
+                    _ => return Err(format!("expected diarization=on or off, got '{}'", value)),
+                })
+            }
+            "chunk" => {
+                self.audio_chunk_duration_secs = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|secs| *secs > 0)
+                        .ok_or_else(|| format!("invalid chunk duration '{}' (seconds)", value))?,
+                )
+            }
+            "preprocessing" => {
+                self.preprocessing = Some(value.replace('+', ",").parse()?);
+            }
+            other => {
+                return Err(format!(
+                    "unknown device setting '{}' (expected language, vad, engine, diarization, chunk or preprocessing)",
+                    other
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Engines and VAD sensitivities are serialized with their command line names.
+fn from_cli_name<T: DeserializeOwned>(value: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(value.to_ascii_lowercase())).ok()
+}
+
+/// Settings of the devices stored by [`save_device_settings`], by device name.
+pub(crate) async fn load_device_settings(
+    db: &DatabaseManager,
+) -> Result<HashMap<String, DeviceAudioSettings>> {
+    let mut device_settings = HashMap::new();
+    for (device, settings) in db.get_audio_device_settings().await? {
+        match serde_json::from_str(&settings) {
+            Ok(settings) => {
+                device_settings.insert(device, settings);
+            }
+            Err(e) => warn!("ignoring invalid stored settings of {}: {}", device, e),
+        }
+    }
+    Ok(device_settings)
+}
+
+/// Store the settings of `device` so they are applied again after a restart.
+pub(crate) async fn save_device_settings(
+    db: &DatabaseManager,
+    device: &str,
+    settings: &DeviceAudioSettings,
+) -> Result<()> {
+    if settings.is_empty() {
+        db.delete_audio_device_settings(device).await?;
+    } else {
+        db.set_audio_device_settings(device, &serde_json::to_string(settings)?)
+            .await?;
+    }
+    Ok(())
+}
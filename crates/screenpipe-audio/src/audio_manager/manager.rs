@@ -2,7 +2,6 @@ use anyhow::{anyhow, Result};
 use dashmap::DashMap;
 use std::{
     collections::HashSet,
-    path::PathBuf,
     sync::{atomic::Ordering, Arc},
 };
 use tokio::{
@@ -11,23 +10,29 @@ use tokio::{
     task::JoinHandle,
 };
 use tracing::{error, info, warn};
-use whisper_rs::WhisperContext;
 
-use screenpipe_db::DatabaseManager;
+use screenpipe_db::{DatabaseManager, EnrolledSpeaker};
 
-use super::{start_device_monitor, stop_device_monitor, AudioManagerOptions};
+use super::{
+    load_device_settings, save_device_settings, start_device_monitor, stop_device_monitor,
+    AudioManagerOptions, DeviceAudioSettings,
+};
 use crate::{
     core::{
-        device::{parse_audio_device, AudioDevice},
+        device::{parse_audio_device, AudioDevice, DeviceType},
+        engine::{AudioTranscriptionEngine, RealtimeTranscriptionEngine},
         record_and_transcribe,
     },
     device::device_manager::DeviceManager,
+    echo_cancellation::{EchoCancellationMode, EchoCanceller},
     segmentation::segmentation_manager::SegmentationManager,
+    speaker::enrollment::{self, load_enrolled_speakers, EnrollmentAudio},
     transcription::{
         deepgram::streaming::stream_transcription_deepgram,
+        engine::{create_transcription_engine, TranscriptionEngine, WhisperEngine},
         handle_new_transcript,
         stt::process_audio_input,
-        whisper::model::{create_whisper_context_parameters, download_whisper_model},
+        whisper::streaming::StreamingTranscriber,
     },
     vad::{silero::SileroVad, webrtc::WebRtcVad, VadEngine, VadEngineEnum},
     AudioInput, TranscriptionResult,
@@ -41,6 +46,7 @@ pub enum AudioManagerStatus {
 }
 
 type RecordingHandlesMap = DashMap<AudioDevice, Arc<Mutex<JoinHandle<Result<()>>>>>;
+type TranscriptionEnginesMap = DashMap<String, Arc<dyn TranscriptionEngine>>;
 
 #[derive(Clone)]
 pub struct AudioManager {
@@ -57,13 +63,46 @@ pub struct AudioManager {
     transcription_sender: Arc<crossbeam::channel::Sender<TranscriptionResult>>,
     transcription_receiver_handle: Arc<RwLock<Option<JoinHandle<()>>>>,
     recording_receiver_handle: Arc<RwLock<Option<JoinHandle<()>>>>,
-    stt_model_path: PathBuf,
+    transcription_engine: Arc<dyn TranscriptionEngine>,
+    /// Engines of the devices with their own, by engine name, loaded when first used
+    device_transcription_engines: Arc<TranscriptionEnginesMap>,
+    /// Reference of the output devices for microphones, unless echo cancellation is off
+    echo_canceller: Option<Arc<EchoCanceller>>,
+    /// Local realtime transcription, unless realtime transcription is off or uses Deepgram
+    streaming_transcriber: Option<StreamingTranscriber>,
 }
 
 impl AudioManager {
-    pub async fn new(options: AudioManagerOptions, db: Arc<DatabaseManager>) -> Result<Self> {
+    pub async fn new(mut options: AudioManagerOptions, db: Arc<DatabaseManager>) -> Result<Self> {
+        // Settings changed while running win over the startup ones
+        match load_device_settings(&db).await {
+            Ok(device_settings) => {
+                for (device, settings) in device_settings {
+                    if let Err(e) = options.validate_device_settings(&settings) {
+                        warn!("ignoring stored settings of {}: {}", device, e);
+                        continue;
+                    }
+                    options
+                        .device_settings
+                        .entry(device)
+                        .or_default()
+                        .merge(settings);
+                }
+            }
+            Err(e) => warn!("failed to load audio device settings: {}", e),
+        }
         let device_manager = DeviceManager::new().await?;
         let segmentation_manager = Arc::new(SegmentationManager::new().await?);
+        segmentation_manager
+            .embedding_manager
+            .lock()
+            .map_err(|_| anyhow!("embedding manager lock poisoned"))?
+            .set_enrolled_threshold(options.enrolled_speaker_threshold);
+        match load_enrolled_speakers(&db, &segmentation_manager.embedding_manager).await {
+            Ok(count) if count > 0 => info!("loaded {} enrolled speakers", count),
+            Ok(_) => {}
+            Err(e) => warn!("failed to load enrolled speakers: {}", e),
+        }
         let status = RwLock::new(AudioManagerStatus::Stopped);
         let vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>> = match options.vad_engine {
             VadEngineEnum::Silero => Arc::new(Mutex::new(Box::new(SileroVad::new().await?))),
@@ -74,9 +113,37 @@ impl AudioManager {
         let (transcription_sender, transcription_receiver) = crossbeam::channel::bounded(1000);
 
         let recording_handles = DashMap::new();
-        let stt_model_path = download_whisper_model(options.transcription_engine.clone())?;
-
         whisper_rs::install_logging_hooks();
+        let transcription_engine = create_transcription_engine(&options)?;
+        let echo_canceller = (options.echo_cancellation != EchoCancellationMode::Off).then(|| {
+            Arc::new(EchoCanceller::new(
+                options.echo_cancellation,
+                options.echo_threshold,
+            ))
+        });
+        let streaming_transcriber = if options.enable_realtime
+            && options.realtime_engine == RealtimeTranscriptionEngine::Whisper
+        {
+            // Recording already loads a local model, otherwise a fast one is needed
+            let engine: Arc<dyn TranscriptionEngine> = if options.transcription_engine.is_whisper()
+            {
+                transcription_engine.clone()
+            } else {
+                Arc::new(WhisperEngine::load(
+                    AudioTranscriptionEngine::WhisperLargeV3TurboQuantized,
+                )?)
+            };
+            Some(StreamingTranscriber {
+                engine,
+                vad_engine: options.vad_engine.clone(),
+                languages: options.languages.clone(),
+                embedding_manager: segmentation_manager.embedding_manager.clone(),
+                embedding_extractor: segmentation_manager.embedding_extractor.clone(),
+                db: db.clone(),
+            })
+        } else {
+            None
+        };
 
         let manager = Self {
             options: Arc::new(RwLock::new(options)),
@@ -92,7 +159,10 @@ impl AudioManager {
             recording_handles: Arc::new(recording_handles),
             recording_receiver_handle: Arc::new(RwLock::new(None)),
             transcription_receiver_handle: Arc::new(RwLock::new(None)),
-            stt_model_path,
+            transcription_engine,
+            device_transcription_engines: Arc::new(DashMap::new()),
+            echo_canceller,
+            streaming_transcriber,
         };
 
         Ok(manager)
@@ -123,6 +193,33 @@ impl AudioManager {
         Ok(())
     }
 
+    /// Enroll the voice in `audio` for a named speaker, see [`enrollment::enroll_speaker`], and
+    /// have diarization recognize it from now on.
+    pub async fn enroll_speaker(
+        &self,
+        speaker_id: Option<i64>,
+        name: Option<&str>,
+        audio: &[EnrollmentAudio],
+    ) -> Result<EnrolledSpeaker> {
+        let speaker = enrollment::enroll_speaker(
+            &self.db,
+            &self.segmentation_manager.embedding_extractor,
+            speaker_id,
+            name,
+            audio,
+        )
+        .await?;
+        self.reload_enrolled_speakers().await?;
+        Ok(speaker)
+    }
+
+    /// Reload the enrolled speakers from the database, e.g. after speakers were merged or
+    /// deleted.
+    pub async fn reload_enrolled_speakers(&self) -> Result<()> {
+        load_enrolled_speakers(&self.db, &self.segmentation_manager.embedding_manager).await?;
+        Ok(())
+    }
+
     pub async fn restart(&self) -> Result<()> {
         self.stop_internal().await?;
         self.start_internal().await?;
@@ -230,6 +327,11 @@ impl AudioManager {
         Ok(())
     }
 
+    /// Returns a copy of the options the manager records with
+    pub async fn options(&self) -> AudioManagerOptions {
+        self.options.read().await.clone()
+    }
+
     pub async fn use_all_devices(&self) -> bool {
         self.options.read().await.use_all_devices
     }
@@ -239,18 +341,85 @@ impl AudioManager {
         self.options.read().await.use_system_default_audio
     }
 
+    /// Settings `device` overrides, empty if it uses the audio manager ones.
+    pub async fn device_settings(&self, device: &str) -> DeviceAudioSettings {
+        self.options
+            .read()
+            .await
+            .device_settings
+            .get(device)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Override the settings of `device` set in `settings`, the others are kept. They are
+    /// stored with the ones set before so they apply again after a restart, and a device
+    /// that is recording restarts with them.
+    pub async fn set_device_settings(
+        &self,
+        device: &AudioDevice,
+        settings: DeviceAudioSettings,
+    ) -> Result<()> {
+        let device_name = device.to_string();
+        self.options
+            .read()
+            .await
+            .validate_device_settings(&settings)?;
+
+        // Only what was set at runtime is stored, startup flags keep applying to the rest
+        let mut stored = load_device_settings(&self.db)
+            .await?
+            .remove(&device_name)
+            .unwrap_or_default();
+        stored.merge(settings.clone());
+        save_device_settings(&self.db, &device_name, &stored).await?;
+
+        self.options
+            .write()
+            .await
+            .device_settings
+            .entry(device_name.clone())
+            .or_default()
+            .merge(settings);
+
+        // Transcription reads the settings for each chunk, recording only when it starts
+        if self.recording_handles.contains_key(device) {
+            self.stop_device(&device_name).await?;
+            self.start_device(device).await?;
+            info!(
+                "restarted recording of {} with its new settings",
+                device_name
+            );
+        }
+        Ok(())
+    }
+
     async fn record_device(&self, device: &AudioDevice) -> Result<JoinHandle<Result<()>>> {
         let options = self.options.read().await;
         let stream = self.device_manager.stream(device).unwrap();
-        let audio_chunk_duration = options.audio_chunk_duration;
+        let audio_chunk_duration = options.audio_chunk_duration_for(&device.to_string());
         let recording_sender = self.recording_sender.clone();
         let is_running = self.device_manager.is_running_mut(device).unwrap();
-        let languages = options.languages.clone();
+        let languages = options.languages_for(&device.to_string());
         let deepgram_api_key = options.deepgram_api_key.clone();
         let realtime_enabled = options.enable_realtime;
         let device_clone = device.clone();
+        let echo_canceller = self
+            .echo_canceller
+            .clone()
+            .filter(|_| device.device_type == DeviceType::Output);
+        let streaming_transcriber =
+            self.streaming_transcriber
+                .clone()
+                .map(|streaming_transcriber| StreamingTranscriber {
+                    languages: languages.clone(),
+                    ..streaming_transcriber
+                });
 
         let recording_handle = tokio::spawn(async move {
+            let echo_reference_handle = echo_canceller.map(|echo_canceller| {
+                echo_canceller.spawn_output_reference(stream.clone(), is_running.clone())
+            });
             let record_and_transcribe_handle = tokio::spawn(record_and_transcribe(
                 stream.clone(),
                 audio_chunk_duration,
@@ -258,15 +427,19 @@ impl AudioManager {
                 is_running.clone(),
             ));
 
-            let realtime_handle = if realtime_enabled {
+            let realtime_handle = if !realtime_enabled {
+                None
+            } else if let Some(streaming_transcriber) = streaming_transcriber {
+                Some(tokio::spawn(async move {
+                    streaming_transcriber.stream(stream, is_running).await
+                }))
+            } else {
                 Some(tokio::spawn(stream_transcription_deepgram(
                     stream,
                     languages,
                     is_running,
                     deepgram_api_key,
                 )))
-            } else {
-                None
             };
 
             let (record_result, realtime_result) = if let Some(handle) = realtime_handle {
@@ -274,6 +447,9 @@ impl AudioManager {
             } else {
                 (record_and_transcribe_handle.await, Ok(Ok(())))
             };
+            if let Some(handle) = echo_reference_handle {
+                handle.abort();
+            }
 
             if record_result.is_err() || realtime_result.is_err() {
                 let mut e = anyhow!("record_device failed");
@@ -310,24 +486,31 @@ impl AudioManager {
         let segmentation_model_path = segmentation_manager.segmentation_model_path.clone();
         let embedding_manager = segmentation_manager.embedding_manager.clone();
         let embedding_extractor = segmentation_manager.embedding_extractor.clone();
-        let options = self.options.read().await;
-        let output_path = options.output_path.clone();
-        let languages = options.languages.clone();
-        let deepgram_api_key = options.deepgram_api_key.clone();
-        let audio_transcription_engine = options.transcription_engine.clone();
+        let options = self.options.clone();
+        let output_path = options.read().await.output_path.clone();
+        let transcription_engine = self.transcription_engine.clone();
+        let device_transcription_engines = self.device_transcription_engines.clone();
         let vad_engine = self.vad_engine.clone();
         let whisper_receiver = self.recording_receiver.clone();
-        let context_param = create_whisper_context_parameters(audio_transcription_engine.clone())?;
-
-        let quantized_path = self.stt_model_path.clone();
-        let whisper_context = Arc::new(
-            WhisperContext::new_with_params(&quantized_path.to_string_lossy(), context_param)
-                .expect("failed to load model"),
-        );
+        let echo_canceller = self.echo_canceller.clone();
 
         Ok(tokio::spawn(async move {
             while let Ok(audio) = whisper_receiver.recv() {
                 info!("Received audio from device: {:?}", audio.device.name);
+                // Read for each chunk, device settings can change while recording
+                let options = options.read().await.clone();
+                let device = audio.device.to_string();
+                let transcription_engine = device_transcription_engine(
+                    &options,
+                    &device,
+                    &transcription_engine,
+                    &device_transcription_engines,
+                )
+                .await;
+                vad_engine
+                    .lock()
+                    .await
+                    .set_sensitivity(options.vad_sensitivity_for(&device));
                 if let Err(e) = process_audio_input(
                     audio.clone(),
                     vad_engine.clone(),
@@ -335,11 +518,12 @@ impl AudioManager {
                     embedding_manager.clone(),
                     embedding_extractor.clone(),
                     &output_path.clone().unwrap(),
-                    audio_transcription_engine.clone(),
-                    deepgram_api_key.clone(),
-                    languages.clone(),
+                    transcription_engine,
+                    options.languages_for(&device),
                     &transcription_sender.clone(),
-                    whisper_context.clone(),
+                    echo_canceller.as_deref(),
+                    options.preprocessing_for(&device),
+                    options.diarization_for(&device),
                 )
                 .await
                 {
@@ -437,6 +621,51 @@ impl AudioManager {
     }
 }
 
+/// Engine transcribing `device`, `default_engine` unless the device has its own.
+/// An engine failing to load is tried once, the device then keeps the default one.
+async fn device_transcription_engine(
+    options: &AudioManagerOptions,
+    device: &str,
+    default_engine: &Arc<dyn TranscriptionEngine>,
+    device_engines: &TranscriptionEnginesMap,
+) -> Arc<dyn TranscriptionEngine> {
+    let engine = options.transcription_engine_for(device);
+    if engine == options.transcription_engine {
+        return default_engine.clone();
+    }
+    if let Some(loaded) = device_engines.get(&engine.to_string()) {
+        return loaded.clone();
+    }
+
+    // The device engine gets the default fallbacks of its kind
+    let device_options = AudioManagerOptions {
+        transcription_engine: engine.clone(),
+        transcription_fallbacks: None,
+        ..options.clone()
+    };
+    // Loading reads and initializes the model, keep it off the runtime threads
+    let loaded = tokio::task::spawn_blocking(move || create_transcription_engine(&device_options))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|loaded| loaded);
+    match loaded {
+        Ok(loaded) => {
+            info!("loaded transcription engine {} for {}", engine, device);
+            device_engines.insert(engine.to_string(), loaded.clone());
+            loaded
+        }
+        Err(e) => {
+            error!(
+                "failed to load transcription engine {} for {}, using the default one: {}",
+                engine, device, e
+            );
+            // Remember the failure, retrying the load on every chunk would stall transcription
+            device_engines.insert(engine.to_string(), default_engine.clone());
+            default_engine.clone()
+        }
+    }
+}
+
 impl Drop for AudioManager {
     fn drop(&mut self) {
         let rec = self.recording_handles.clone();
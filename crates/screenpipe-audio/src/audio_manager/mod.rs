@@ -1,6 +1,8 @@
 mod builder;
 mod device_monitor;
+mod device_settings;
 mod manager;
 pub use builder::*;
 pub use device_monitor::*;
+pub use device_settings::*;
 pub use manager::*;
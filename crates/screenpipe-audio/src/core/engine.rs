@@ -1,6 +1,10 @@
 use std::fmt;
 
-#[derive(Clone, Debug, PartialEq, Default)]
+use serde::{Deserialize, Serialize};
+
+/// Serialized with the names of the command line
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum AudioTranscriptionEngine {
     Deepgram,
     WhisperTiny,
@@ -8,8 +12,13 @@ pub enum AudioTranscriptionEngine {
     #[default]
     WhisperLargeV3Turbo,
     WhisperLargeV3TurboQuantized,
+    #[serde(rename = "whisper-large")]
     WhisperLargeV3,
+    #[serde(rename = "whisper-large-quantized")]
     WhisperLargeV3Quantized,
+    /// Any server implementing OpenAI's `/v1/audio/transcriptions`
+    #[serde(rename = "openai-compatible")]
+    OpenAiCompatible,
 }
 
 impl fmt::Display for AudioTranscriptionEngine {
@@ -26,6 +35,27 @@ impl fmt::Display for AudioTranscriptionEngine {
             AudioTranscriptionEngine::WhisperLargeV3TurboQuantized => {
                 write!(f, "WhisperLargeV3TurboQuantized")
             }
+            AudioTranscriptionEngine::OpenAiCompatible => write!(f, "OpenAiCompatible"),
         }
     }
 }
+
+impl AudioTranscriptionEngine {
+    /// Whether the engine is a local whisper model.
+    pub fn is_whisper(&self) -> bool {
+        !matches!(
+            self,
+            AudioTranscriptionEngine::Deepgram | AudioTranscriptionEngine::OpenAiCompatible
+        )
+    }
+}
+
+/// Engine transcribing audio as it is recorded, for live captions.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum RealtimeTranscriptionEngine {
+    /// Deepgram's streaming API
+    #[default]
+    Deepgram,
+    /// A local whisper model on the audio so far, works offline
+    Whisper,
+}
@@ -3,7 +3,7 @@ use std::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Result};
@@ -36,6 +36,7 @@ pub async fn run_record_and_transcribe(
 
     const OVERLAP_SECONDS: usize = 2;
     let mut collected_audio = Vec::new();
+    let mut captured_at = Instant::now();
     let sample_rate = audio_stream.device_config.sample_rate().0 as usize;
     let audio_samples_len = sample_rate * duration.as_secs() as usize;
     let overlap_samples = OVERLAP_SECONDS * sample_rate;
@@ -56,6 +57,7 @@ pub async fn run_record_and_transcribe(
             match recv_result {
                 Ok(Ok(chunk)) => {
                     collected_audio.extend(chunk);
+                    captured_at = Instant::now();
                     update_device_capture_time(&device_name);
                 }
                 Ok(Err(broadcast::error::RecvError::Lagged(n))) => {
@@ -95,6 +97,7 @@ pub async fn run_record_and_transcribe(
                 device: audio_stream.device.clone(),
                 sample_rate: audio_stream.device_config.sample_rate().0,
                 channels: audio_stream.device_config.channels(),
+                captured_at: Some(captured_at),
             }) {
                 Ok(_) => {
                     debug!("sent audio segment to audio model");
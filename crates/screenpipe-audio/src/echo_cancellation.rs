@@ -0,0 +1,320 @@
+//! Detection of system output picked up by microphones.
+//!
+//! When speakers play audio while a microphone records, the microphone hears it too and the
+//! same speech would be transcribed from both devices. The output devices are used as a
+//! reference: the loudness envelope of their streams is kept for the last minutes, and a
+//! microphone segment whose envelope follows that of an output at a small delay is an echo.
+//! Echoes are dropped before transcription or transcribed without a speaker, depending on
+//! the [`EchoCancellationMode`].
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex as StdMutex,
+};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::{sync::broadcast, task::JoinHandle};
+use tracing::debug;
+
+use crate::{core::stream::AudioStream, speaker::segment::SpeechSegment};
+
+/// Minimum correlation of the envelopes of a microphone segment and an output for the
+/// segment to be an echo
+pub const DEFAULT_ECHO_THRESHOLD: f32 = 0.6;
+
+/// Length of the frames envelopes are computed on
+const FRAME: Duration = Duration::from_millis(10);
+/// Largest delay between output and microphone looked at, in frames either way
+const MAX_LAG_FRAMES: usize = 50;
+/// How long output envelopes are kept, longer than a chunk waits to be transcribed
+const REFERENCE_RETENTION: Duration = Duration::from_secs(180);
+/// RMS below which an output frame is silence
+const SILENCE_RMS: f32 = 0.001;
+/// Share of the frames of a segment the output must be playing in to be compared
+const MIN_ACTIVE_RATIO: f32 = 0.2;
+/// Shortest segment worth comparing, in frames
+const MIN_SEGMENT_FRAMES: usize = 50;
+
+/// What to do with microphone speech that echoes an output device
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EchoCancellationMode {
+    /// Transcribe microphones as they are
+    #[default]
+    Off,
+    /// Transcribe echoes without attributing them to a speaker
+    Tag,
+    /// Drop echoes before transcription
+    Suppress,
+}
+
+/// Loudness envelope of an output device stream
+#[derive(Default)]
+struct OutputEnvelope {
+    /// Samples received after the last full frame
+    pending: Vec<f32>,
+    /// RMS of the frames, with the time each frame ended
+    frames: VecDeque<(Instant, f32)>,
+}
+
+/// Compares microphone segments to the audio played on output devices.
+pub struct EchoCanceller {
+    mode: EchoCancellationMode,
+    threshold: f32,
+    outputs: StdMutex<HashMap<String, OutputEnvelope>>,
+}
+
+impl EchoCanceller {
+    pub fn new(mode: EchoCancellationMode, threshold: f32) -> Self {
+        Self {
+            mode,
+            threshold,
+            outputs: StdMutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn mode(&self) -> EchoCancellationMode {
+        self.mode
+    }
+
+    /// Add `samples` (mono) of the output `device`, the last of which was played at
+    /// `received_at`.
+    pub fn push_output(
+        &self,
+        device: &str,
+        samples: &[f32],
+        sample_rate: u32,
+        received_at: Instant,
+    ) {
+        let frame_len = frame_len(sample_rate);
+        let mut outputs = self.outputs.lock().unwrap();
+        let output = outputs.entry(device.to_string()).or_default();
+
+        output.pending.extend_from_slice(samples);
+        let total = output.pending.len();
+        let full_frames = total / frame_len;
+        for (index, frame) in output.pending.chunks_exact(frame_len).enumerate() {
+            let samples_after = total - (index + 1) * frame_len;
+            let ended_at = received_at
+                .checked_sub(Duration::from_secs_f64(
+                    samples_after as f64 / sample_rate as f64,
+                ))
+                .unwrap_or(received_at);
+            output.frames.push_back((ended_at, rms(frame)));
+        }
+        output.pending.drain(..full_frames * frame_len);
+
+        while output.frames.front().is_some_and(|(ended_at, _)| {
+            received_at.duration_since(*ended_at) > REFERENCE_RETENTION
+        }) {
+            output.frames.pop_front();
+        }
+    }
+
+    /// Forget the output `device`, e.g. when it stops recording.
+    pub fn remove_output(&self, device: &str) {
+        self.outputs.lock().unwrap().remove(device);
+    }
+
+    /// Feed the audio of the output device `stream` as a reference until `is_running` is unset.
+    pub fn spawn_output_reference(
+        self: Arc<Self>,
+        stream: Arc<AudioStream>,
+        is_running: Arc<AtomicBool>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let device = stream.device.to_string();
+            let sample_rate = stream.device_config.sample_rate().0;
+            let mut receiver = stream.subscribe().await;
+            // Holding on to the stream would keep it from being closed
+            drop(stream);
+
+            while is_running.load(Ordering::Relaxed) {
+                match tokio::time::timeout(Duration::from_secs(1), receiver.recv()).await {
+                    Ok(Ok(chunk)) => self.push_output(&device, &chunk, sample_rate, Instant::now()),
+                    Ok(Err(broadcast::error::RecvError::Lagged(_))) | Err(_) => continue,
+                    Ok(Err(broadcast::error::RecvError::Closed)) => break,
+                }
+            }
+            self.remove_output(&device);
+        })
+    }
+
+    /// Whether `samples` recorded by a microphone, the last of which was captured at
+    /// `ended_at`, echo the audio played on an output device.
+    pub fn is_echo(&self, samples: &[f32], sample_rate: u32, ended_at: Instant) -> bool {
+        let frame_len = frame_len(sample_rate);
+        let envelope: Vec<f32> = samples
+            .chunks_exact(frame_len)
+            .map(|frame| (rms(frame) + 1e-4).ln())
+            .collect();
+        let len = envelope.len();
+        if len < MIN_SEGMENT_FRAMES {
+            return false;
+        }
+
+        // Grid of output frames aligned with the microphone frames, padded by the largest lag
+        let grid_len = len + 2 * MAX_LAG_FRAMES;
+        let Some(grid_start) = ended_at.checked_sub(FRAME * (len + MAX_LAG_FRAMES) as u32) else {
+            return false;
+        };
+
+        let outputs = self.outputs.lock().unwrap();
+        for (device, output) in outputs.iter() {
+            let mut grid = vec![0.0f32; grid_len];
+            for (frame_ended_at, rms) in &output.frames {
+                let Some(offset) = frame_ended_at.checked_duration_since(grid_start) else {
+                    continue;
+                };
+                let index = (offset.as_secs_f64() / FRAME.as_secs_f64()).round() as usize;
+                if let Some(slot) = index.checked_sub(1).and_then(|i| grid.get_mut(i)) {
+                    *slot = slot.max(*rms);
+                }
+            }
+
+            let active = grid[MAX_LAG_FRAMES..MAX_LAG_FRAMES + len]
+                .iter()
+                .filter(|rms| **rms > SILENCE_RMS)
+                .count();
+            if (active as f32) < MIN_ACTIVE_RATIO * len as f32 {
+                continue;
+            }
+
+            let reference: Vec<f32> = grid.iter().map(|rms| (rms + 1e-4).ln()).collect();
+            // The microphone hears the output after it is played, give or take buffering
+            let correlation = (0..=2 * MAX_LAG_FRAMES)
+                .map(|start| pearson(&envelope, &reference[start..start + len]))
+                .fold(f32::MIN, f32::max);
+            debug!(
+                "echo correlation of microphone segment with {}: {:.2}",
+                device, correlation
+            );
+            if correlation >= self.threshold {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Check `segment` of a microphone chunk of `chunk_secs` seconds captured until
+    /// `chunk_ended_at`, tagging it if it is an echo.
+    ///
+    /// Returns false if the segment should be dropped instead.
+    pub fn process_segment(
+        &self,
+        segment: &mut SpeechSegment,
+        chunk_ended_at: Instant,
+        chunk_secs: f64,
+    ) -> bool {
+        if self.mode == EchoCancellationMode::Off {
+            return true;
+        }
+        let Some(ended_at) = chunk_ended_at
+            .checked_sub(Duration::from_secs_f64((chunk_secs - segment.end).max(0.0)))
+        else {
+            return true;
+        };
+        if !self.is_echo(&segment.samples, segment.sample_rate, ended_at) {
+            return true;
+        }
+
+        match self.mode {
+            EchoCancellationMode::Suppress => {
+                debug!(
+                    "dropping microphone segment {:.1}s-{:.1}s echoing an output device",
+                    segment.start, segment.end
+                );
+                false
+            }
+            _ => {
+                segment.is_echo = true;
+                true
+            }
+        }
+    }
+}
+
+fn frame_len(sample_rate: u32) -> usize {
+    ((sample_rate as f64 * FRAME.as_secs_f64()) as usize).max(1)
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len().max(1) as f32).sqrt()
+}
+
+/// Pearson correlation of `a` and `b`, 0 if either is constant.
+fn pearson(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len() as f32;
+    let mean_a = a.iter().sum::<f32>() / n;
+    let mean_b = b.iter().sum::<f32>() / n;
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        let (dx, dy) = (x - mean_a, y - mean_b);
+        covariance += dx * dy;
+        variance_a += dx * dx;
+        variance_b += dy * dy;
+    }
+    if variance_a <= f32::EPSILON || variance_b <= f32::EPSILON {
+        return 0.0;
+    }
+    covariance / (variance_a * variance_b).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+
+    /// Speech-like audio: a tone switched on and off at an irregular pace
+    fn bursts(secs: f32, seed: u32) -> Vec<f32> {
+        let len = (secs * RATE as f32) as usize;
+        let mut state = seed;
+        let mut on = false;
+        let mut samples = Vec::with_capacity(len);
+        while samples.len() < len {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let burst = 800 + (state >> 16) as usize % 4000;
+            on = !on;
+            for i in 0..burst.min(len - samples.len()) {
+                let amplitude = if on { 0.3 } else { 0.0 };
+                samples.push(amplitude * (i as f32 * 0.05).sin());
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn test_detects_delayed_quieter_output() {
+        let canceller = EchoCanceller::new(EchoCancellationMode::Suppress, DEFAULT_ECHO_THRESHOLD);
+        let output = bursts(6.0, 7);
+        let played_at = Instant::now();
+        canceller.push_output("speakers (output)", &output, RATE, played_at);
+
+        // The microphone hears the output 120 ms later, quieter
+        let mic: Vec<f32> = output[..5 * RATE as usize]
+            .iter()
+            .map(|s| s * 0.2)
+            .collect();
+        let mic_ended_at = played_at - Duration::from_secs(1) + Duration::from_millis(120);
+        assert!(canceller.is_echo(&mic, RATE, mic_ended_at));
+    }
+
+    #[test]
+    fn test_ignores_unrelated_speech_and_silent_output() {
+        let canceller = EchoCanceller::new(EchoCancellationMode::Suppress, DEFAULT_ECHO_THRESHOLD);
+        let played_at = Instant::now();
+        canceller.push_output("speakers (output)", &bursts(6.0, 7), RATE, played_at);
+        canceller.push_output(
+            "headphones (output)",
+            &vec![0.0; 6 * RATE as usize],
+            RATE,
+            played_at,
+        );
+
+        let mic = bursts(5.0, 99);
+        assert!(!canceller.is_echo(&mic, RATE, played_at - Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,271 @@
+//! Transcription of audio files, as opposed to live device streams.
+//!
+//! Files go through the same speech detection, segmentation, speaker identification and
+//! transcription as recorded audio, in windows of the length of a recorded chunk. Used to
+//! re-transcribe stored chunks and to import recordings made by other tools.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use screenpipe_core::{pii_removal::remove_pii, Language};
+use screenpipe_db::{
+    AudioDevice, AudioTranscriptionWord, DatabaseManager, DeviceType, NewAudioTranscription,
+};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::{
+    audio_manager::AudioManagerOptions,
+    core::engine::AudioTranscriptionEngine,
+    segmentation::segmentation_manager::SegmentationManager,
+    speaker::{
+        embedding_manager::DEFAULT_ENROLLED_SPEAKER_THRESHOLD, enrollment::load_enrolled_speakers,
+        prepare_segments,
+    },
+    transcription::{
+        engine::{load_transcription_engine, OpenAiCompatibleConfig, TranscriptionEngine},
+        get_or_create_speaker_from_embedding,
+        stt::SAMPLE_RATE,
+    },
+    utils::audio::{pcm_decode, resample},
+    vad::{silero::SileroVad, webrtc::WebRtcVad, VadEngine, VadEngineEnum},
+};
+
+/// Length of the windows files are processed in, that of a recorded chunk
+const WINDOW_SECS: usize = 30;
+
+/// Device imported audio files are stored under
+pub const IMPORTED_AUDIO_DEVICE: &str = "imported_files";
+
+/// Engine and settings used to transcribe audio files
+#[derive(Clone)]
+pub struct FileTranscriptionConfig {
+    pub engine: AudioTranscriptionEngine,
+    pub languages: Vec<Language>,
+    pub deepgram_api_key: Option<String>,
+    pub openai_compatible: OpenAiCompatibleConfig,
+    pub vad_engine: VadEngineEnum,
+    pub use_pii_removal: bool,
+    /// Minimum cosine similarity for speech to be attributed to an enrolled speaker
+    pub enrolled_speaker_threshold: f32,
+}
+
+impl Default for FileTranscriptionConfig {
+    fn default() -> Self {
+        Self {
+            engine: AudioTranscriptionEngine::default(),
+            languages: Vec::new(),
+            deepgram_api_key: None,
+            openai_compatible: OpenAiCompatibleConfig::default(),
+            vad_engine: VadEngineEnum::Silero,
+            use_pii_removal: false,
+            enrolled_speaker_threshold: DEFAULT_ENROLLED_SPEAKER_THRESHOLD,
+        }
+    }
+}
+
+impl FileTranscriptionConfig {
+    /// Transcribe with `engine` and the languages, API keys, VAD and PII removal the audio
+    /// is recorded with.
+    pub fn from_options(options: &AudioManagerOptions, engine: AudioTranscriptionEngine) -> Self {
+        Self {
+            engine,
+            languages: options.languages.clone(),
+            deepgram_api_key: options.deepgram_api_key.clone(),
+            openai_compatible: options.openai_compatible.clone(),
+            vad_engine: options.vad_engine.clone(),
+            use_pii_removal: options.use_pii_removal,
+            enrolled_speaker_threshold: options.enrolled_speaker_threshold,
+        }
+    }
+}
+
+/// Models audio files go through
+pub struct FileTranscriber {
+    engine: Arc<dyn TranscriptionEngine>,
+    segmentation: SegmentationManager,
+    vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>>,
+    languages: Vec<Language>,
+    use_pii_removal: bool,
+}
+
+impl FileTranscriber {
+    /// Load the models of `config`, downloading them if needed, and the speakers enrolled in
+    /// `db`.
+    pub async fn new(db: &DatabaseManager, config: FileTranscriptionConfig) -> Result<Self> {
+        // Loading whisper models is blocking, and may download them
+        let engine = {
+            let engine = config.engine.clone();
+            let deepgram_api_key = config.deepgram_api_key.clone();
+            let openai_compatible = config.openai_compatible.clone();
+            tokio::task::spawn_blocking(move || {
+                load_transcription_engine(engine, deepgram_api_key, openai_compatible)
+            })
+            .await??
+        };
+        let vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>> = match config.vad_engine {
+            VadEngineEnum::Silero => Arc::new(Mutex::new(Box::new(SileroVad::new().await?))),
+            VadEngineEnum::WebRtc => Arc::new(Mutex::new(Box::new(WebRtcVad::new()))),
+        };
+
+        let segmentation = SegmentationManager::new().await?;
+        segmentation
+            .embedding_manager
+            .lock()
+            .map_err(|_| anyhow!("embedding manager lock poisoned"))?
+            .set_enrolled_threshold(config.enrolled_speaker_threshold);
+        load_enrolled_speakers(db, &segmentation.embedding_manager).await?;
+
+        Ok(Self {
+            engine,
+            segmentation,
+            vad_engine,
+            languages: config.languages,
+            use_pii_removal: config.use_pii_removal,
+        })
+    }
+
+    /// Name stored with the transcriptions of the engine.
+    pub fn engine_name(&self) -> String {
+        self.engine.name()
+    }
+
+    /// Transcribe the first audio track of the file at `path`, recorded by `device`.
+    ///
+    /// Start and end times of the transcriptions and their words are in seconds from the
+    /// start of the file.
+    pub async fn transcribe_file(
+        &self,
+        db: &DatabaseManager,
+        path: impl AsRef<Path>,
+        device: &str,
+    ) -> Result<Vec<NewAudioTranscription>> {
+        let path = path.as_ref().to_path_buf();
+        let (audio, sample_rate) = tokio::task::spawn_blocking(move || pcm_decode(path)).await??;
+        let audio = if sample_rate != SAMPLE_RATE {
+            resample(&audio, sample_rate, SAMPLE_RATE)?
+        } else {
+            audio
+        };
+
+        let mut transcriptions = Vec::new();
+        for (index, window) in audio.chunks(WINDOW_SECS * SAMPLE_RATE as usize).enumerate() {
+            let offset = (index * WINDOW_SECS) as f64;
+            self.transcribe_window(db, window, offset, device, &mut transcriptions)
+                .await?;
+        }
+        Ok(transcriptions)
+    }
+
+    async fn transcribe_window(
+        &self,
+        db: &DatabaseManager,
+        audio: &[f32],
+        offset: f64,
+        device: &str,
+        transcriptions: &mut Vec<NewAudioTranscription>,
+    ) -> Result<()> {
+        let (mut segments, speech_ratio_ok) = prepare_segments(
+            audio,
+            self.vad_engine.clone(),
+            &self.segmentation.segmentation_model_path,
+            self.segmentation.embedding_manager.clone(),
+            self.segmentation.embedding_extractor.clone(),
+            device,
+        )
+        .await?;
+        if !speech_ratio_ok {
+            debug!("not enough speech at {}s, skipping", offset);
+            return Ok(());
+        }
+
+        while let Some(segment) = segments.recv().await {
+            let transcript = self
+                .engine
+                .transcribe(
+                    &segment.samples,
+                    segment.sample_rate,
+                    device,
+                    &self.languages,
+                )
+                .await?;
+            if transcript.text.trim().is_empty() {
+                continue;
+            }
+            let speaker = get_or_create_speaker_from_embedding(
+                db,
+                &segment.embedding,
+                segment.enrolled_speaker_id,
+            )
+            .await?;
+
+            let transcription = if self.use_pii_removal {
+                remove_pii(&transcript.text)
+            } else {
+                transcript.text.clone()
+            };
+            let start = offset + segment.start;
+            // The words would give away what PII removal took out of the transcription
+            let words = if transcription == transcript.text {
+                transcript
+                    .words
+                    .into_iter()
+                    .map(|word| AudioTranscriptionWord {
+                        word: word.text,
+                        start_time: word.start + start,
+                        end_time: word.end + start,
+                        confidence: word.confidence.map(f64::from),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            transcriptions.push(NewAudioTranscription {
+                transcription,
+                speaker_id: Some(speaker.id),
+                start_time: Some(start),
+                end_time: Some(offset + segment.end),
+                words,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Transcribe an audio file recorded outside of screenpipe, or the audio track of a video,
+/// and store it as an audio chunk recorded at `timestamp`.
+///
+/// The file is stored under `device_name`, [`IMPORTED_AUDIO_DEVICE`] if not set. Returns the id
+/// of the audio chunk and the number of transcriptions stored.
+pub async fn import_audio_file(
+    db: &DatabaseManager,
+    transcriber: &FileTranscriber,
+    path: &Path,
+    timestamp: DateTime<Utc>,
+    device_name: Option<String>,
+) -> Result<(i64, usize)> {
+    let device = AudioDevice {
+        name: device_name.unwrap_or_else(|| IMPORTED_AUDIO_DEVICE.to_string()),
+        device_type: DeviceType::Output,
+    };
+    let transcriptions = transcriber.transcribe_file(db, path, &device.name).await?;
+    let (audio_chunk_id, ids) = db
+        .insert_imported_audio(
+            &path.to_string_lossy(),
+            timestamp,
+            &transcriber.engine_name(),
+            &device,
+            &transcriptions,
+        )
+        .await?;
+    debug!(
+        "imported {} into audio chunk {} with {} transcriptions",
+        path.display(),
+        audio_chunk_id,
+        ids.len()
+    );
+    Ok((audio_chunk_id, ids.len()))
+}
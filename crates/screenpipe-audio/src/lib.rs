@@ -9,4 +9,8 @@ pub use utils::audio::pcm_decode;
 pub use utils::audio::resample;
 pub mod audio_manager;
 mod device;
+pub mod echo_cancellation;
+pub mod file_transcription;
+pub mod preprocessing;
+pub mod retranscribe;
 mod segmentation;
@@ -0,0 +1,133 @@
+//! Preprocessing of recorded audio before voice activity detection and transcription.
+//!
+//! A chain of stages, configured globally and per device, cleans up the resampled audio of
+//! each chunk. Only what is transcribed goes through it, the stored audio stays as recorded.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::utils::audio::{
+    automatic_gain_control, high_pass_filter, normalize_v2, spectral_denoise,
+};
+
+/// Frequency below which the high-pass filter removes sound, under the lowest voices
+pub const HIGH_PASS_CUTOFF_HZ: f32 = 80.0;
+
+/// A step of the preprocessing chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PreprocessingStage {
+    /// Remove rumble, hum and DC offset below the voice range
+    HighPass,
+    /// Reduce stationary background noise
+    Denoise,
+    /// Even out the loudness of quiet and loud speech
+    Agc,
+    /// Scale the whole chunk to a target level
+    Normalize,
+}
+
+impl PreprocessingStage {
+    pub const ALL: [PreprocessingStage; 4] = [
+        PreprocessingStage::HighPass,
+        PreprocessingStage::Denoise,
+        PreprocessingStage::Agc,
+        PreprocessingStage::Normalize,
+    ];
+
+    fn apply(&self, audio: &[f32], sample_rate: u32) -> Vec<f32> {
+        match self {
+            PreprocessingStage::HighPass => {
+                high_pass_filter(audio, sample_rate, HIGH_PASS_CUTOFF_HZ)
+            }
+            PreprocessingStage::Denoise => spectral_denoise(audio).unwrap_or_else(|e| {
+                warn!("noise reduction failed, skipping it: {}", e);
+                audio.to_vec()
+            }),
+            PreprocessingStage::Agc => automatic_gain_control(audio, sample_rate),
+            PreprocessingStage::Normalize => normalize_v2(audio),
+        }
+    }
+}
+
+impl fmt::Display for PreprocessingStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreprocessingStage::HighPass => write!(f, "high-pass"),
+            PreprocessingStage::Denoise => write!(f, "denoise"),
+            PreprocessingStage::Agc => write!(f, "agc"),
+            PreprocessingStage::Normalize => write!(f, "normalize"),
+        }
+    }
+}
+
+impl FromStr for PreprocessingStage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "high-pass" | "highpass" => Ok(PreprocessingStage::HighPass),
+            "denoise" | "noise-reduction" => Ok(PreprocessingStage::Denoise),
+            "agc" => Ok(PreprocessingStage::Agc),
+            "normalize" | "normalization" => Ok(PreprocessingStage::Normalize),
+            other => Err(format!(
+                "unknown preprocessing stage '{}' (expected high-pass, denoise, agc or normalize)",
+                other
+            )),
+        }
+    }
+}
+
+/// Stages applied in order to the audio of a device, none by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreprocessingChain {
+    pub stages: Vec<PreprocessingStage>,
+}
+
+impl PreprocessingChain {
+    pub fn new(stages: Vec<PreprocessingStage>) -> Self {
+        Self { stages }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Run `audio` (mono) through the stages.
+    pub fn process(&self, audio: &[f32], sample_rate: u32) -> Vec<f32> {
+        let mut audio = audio.to_vec();
+        for stage in &self.stages {
+            audio = stage.apply(&audio, sample_rate);
+        }
+        audio
+    }
+}
+
+impl fmt::Display for PreprocessingChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.stages.is_empty() {
+            return write!(f, "none");
+        }
+        let stages: Vec<String> = self.stages.iter().map(ToString::to_string).collect();
+        write!(f, "{}", stages.join(","))
+    }
+}
+
+impl FromStr for PreprocessingChain {
+    type Err = String;
+
+    /// Comma separated stages, or `none`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("none") {
+            return Ok(Self::default());
+        }
+        s.split(',')
+            .filter(|stage| !stage.trim().is_empty())
+            .map(PreprocessingStage::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self::new)
+    }
+}
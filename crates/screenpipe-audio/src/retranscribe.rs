@@ -0,0 +1,430 @@
+//! Re-transcription of recorded audio with another engine.
+//!
+//! Walks the audio chunks recorded in a time range, runs speech detection, speaker
+//! identification and transcription again on their files and replaces their transcriptions.
+//! Like the database [`MigrationWorker`](screenpipe_db::MigrationWorker), the job is driven
+//! by commands and can be paused, resumed and stopped. Chunks already transcribed by the
+//! target engine are skipped, so an interrupted job picks up where it left off.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use screenpipe_db::{AudioChunkToRetranscribe, DatabaseManager};
+use serde::Serialize;
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+    time,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    audio_manager::AudioManagerOptions,
+    core::engine::AudioTranscriptionEngine,
+    file_transcription::{FileTranscriber, FileTranscriptionConfig},
+};
+
+/// Status of a re-transcription job
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RetranscriptionStatus {
+    /// The job has not been started yet
+    NotStarted,
+    /// Chunks are being re-transcribed
+    Running {
+        total_chunks: i64,
+        processed_chunks: i64,
+        failed_chunks: i64,
+    },
+    /// The job is paused and can be resumed
+    Paused {
+        total_chunks: i64,
+        processed_chunks: i64,
+        failed_chunks: i64,
+    },
+    /// Every chunk of the time range was processed
+    Completed {
+        total_chunks: i64,
+        failed_chunks: i64,
+        duration_secs: u64,
+    },
+    /// The job was stopped before the end of the time range
+    Stopped {
+        total_chunks: i64,
+        processed_chunks: i64,
+        failed_chunks: i64,
+    },
+    /// The job failed with an error
+    Failed {
+        total_chunks: i64,
+        processed_chunks: i64,
+        error: String,
+    },
+}
+
+impl RetranscriptionStatus {
+    /// Whether the job is done, one way or another.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            RetranscriptionStatus::Completed { .. }
+                | RetranscriptionStatus::Stopped { .. }
+                | RetranscriptionStatus::Failed { .. }
+        )
+    }
+}
+
+/// Commands that can be sent to control the re-transcription worker
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetranscriptionCommand {
+    /// Start or resume the job
+    Start,
+    /// Pause the job after the chunk being processed
+    Pause,
+    /// Stop the job after the chunk being processed (cannot be resumed)
+    Stop,
+}
+
+/// Configuration of a re-transcription job
+#[derive(Clone)]
+pub struct RetranscriptionConfig {
+    /// Re-transcribe the audio recorded from then
+    pub start_time: DateTime<Utc>,
+    /// Until then, when the job starts if not set
+    pub end_time: Option<DateTime<Utc>>,
+    /// Engine replacing the previous transcriptions and its settings
+    pub transcription: FileTranscriptionConfig,
+    /// Number of chunks fetched from the database at once
+    pub batch_size: i64,
+    /// Delay between chunks to leave resources to the recording
+    pub chunk_delay_ms: u64,
+    /// Whether to go on with the next chunks when one fails
+    pub continue_on_error: bool,
+}
+
+impl Default for RetranscriptionConfig {
+    fn default() -> Self {
+        Self {
+            start_time: DateTime::<Utc>::MIN_UTC,
+            end_time: None,
+            transcription: FileTranscriptionConfig::default(),
+            batch_size: 50,
+            chunk_delay_ms: 0,
+            continue_on_error: true,
+        }
+    }
+}
+
+impl RetranscriptionConfig {
+    /// Re-transcribe with `engine` the audio recorded between `start_time` and `end_time`,
+    /// with the languages, API keys, VAD and PII removal the audio is recorded with.
+    pub fn from_options(
+        options: &AudioManagerOptions,
+        engine: AudioTranscriptionEngine,
+        start_time: DateTime<Utc>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            start_time,
+            end_time,
+            transcription: FileTranscriptionConfig::from_options(options, engine),
+            ..Default::default()
+        }
+    }
+}
+
+/// Worker that re-transcribes audio chunks in the background
+pub struct RetranscriptionWorker {
+    db: Arc<DatabaseManager>,
+    config: RetranscriptionConfig,
+    is_running: Arc<AtomicBool>,
+    is_paused: Arc<AtomicBool>,
+    cmd_rx: mpsc::Receiver<RetranscriptionCommand>,
+    status_tx: Arc<watch::Sender<RetranscriptionStatus>>,
+    worker_handle: Option<JoinHandle<()>>,
+}
+
+impl RetranscriptionWorker {
+    /// Create a new re-transcription worker
+    pub fn new(
+        db: Arc<DatabaseManager>,
+        config: RetranscriptionConfig,
+        cmd_rx: mpsc::Receiver<RetranscriptionCommand>,
+        status_tx: watch::Sender<RetranscriptionStatus>,
+    ) -> Self {
+        Self {
+            db,
+            config,
+            is_running: Arc::new(AtomicBool::new(false)),
+            is_paused: Arc::new(AtomicBool::new(false)),
+            cmd_rx,
+            status_tx: Arc::new(status_tx),
+            worker_handle: None,
+        }
+    }
+
+    /// Start the worker to process commands
+    pub fn start(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            info!("re-transcription worker started");
+            while let Some(cmd) = self.cmd_rx.recv().await {
+                match cmd {
+                    RetranscriptionCommand::Start => self.start_job(),
+                    RetranscriptionCommand::Pause => self.pause_job(),
+                    RetranscriptionCommand::Stop => {
+                        self.stop_job();
+                        break;
+                    }
+                }
+            }
+            // Let the job finish its chunk
+            if let Some(handle) = self.worker_handle.take() {
+                let _ = handle.await;
+            }
+            info!("re-transcription worker stopped");
+        })
+    }
+
+    fn start_job(&mut self) {
+        if self.is_running.load(Ordering::SeqCst) {
+            if self.is_paused.swap(false, Ordering::SeqCst) {
+                info!("resuming re-transcription");
+            } else {
+                warn!("re-transcription is already running");
+            }
+            return;
+        }
+        if self.status_tx.borrow().is_finished() {
+            warn!("re-transcription is over, start a new job");
+            return;
+        }
+
+        self.is_running.store(true, Ordering::SeqCst);
+        let db = self.db.clone();
+        let config = self.config.clone();
+        let is_running = self.is_running.clone();
+        let is_paused = self.is_paused.clone();
+        let status_tx = self.status_tx.clone();
+
+        self.worker_handle = Some(tokio::spawn(async move {
+            let status =
+                match retranscribe(&db, config, is_running.clone(), is_paused, &status_tx).await {
+                    Ok(status) => status,
+                    Err(e) => {
+                        error!("re-transcription failed: {}", e);
+                        let (total_chunks, processed_chunks) = match *status_tx.borrow() {
+                            RetranscriptionStatus::Running {
+                                total_chunks,
+                                processed_chunks,
+                                ..
+                            }
+                            | RetranscriptionStatus::Paused {
+                                total_chunks,
+                                processed_chunks,
+                                ..
+                            } => (total_chunks, processed_chunks),
+                            _ => (0, 0),
+                        };
+                        RetranscriptionStatus::Failed {
+                            total_chunks,
+                            processed_chunks,
+                            error: e.to_string(),
+                        }
+                    }
+                };
+            info!("re-transcription finished: {:?}", status);
+            status_tx.send_replace(status);
+            is_running.store(false, Ordering::SeqCst);
+        }));
+    }
+
+    fn pause_job(&self) {
+        if self.is_running.load(Ordering::SeqCst) {
+            info!("pausing re-transcription");
+            self.is_paused.store(true, Ordering::SeqCst);
+        } else {
+            warn!("cannot pause re-transcription: not running");
+        }
+    }
+
+    fn stop_job(&self) {
+        if self.is_running.load(Ordering::SeqCst) {
+            info!("stopping re-transcription");
+            self.is_running.store(false, Ordering::SeqCst);
+        } else {
+            warn!("cannot stop re-transcription: not running");
+        }
+    }
+}
+
+/// Create a re-transcription worker, returning its command sender, its status and the
+/// handle of the worker task. Send [`RetranscriptionCommand::Start`] to begin.
+pub fn create_retranscription_worker(
+    db: Arc<DatabaseManager>,
+    config: RetranscriptionConfig,
+) -> (
+    mpsc::Sender<RetranscriptionCommand>,
+    watch::Receiver<RetranscriptionStatus>,
+    JoinHandle<()>,
+) {
+    let (cmd_tx, cmd_rx) = mpsc::channel(32);
+    let (status_tx, status_rx) = watch::channel(RetranscriptionStatus::NotStarted);
+    let worker = RetranscriptionWorker::new(db, config, cmd_rx, status_tx);
+    let handle = worker.start();
+    (cmd_tx, status_rx, handle)
+}
+
+async fn retranscribe(
+    db: &DatabaseManager,
+    config: RetranscriptionConfig,
+    is_running: Arc<AtomicBool>,
+    is_paused: Arc<AtomicBool>,
+    status_tx: &watch::Sender<RetranscriptionStatus>,
+) -> Result<RetranscriptionStatus> {
+    let started = Instant::now();
+    let engine_name = config.transcription.engine.to_string();
+    // Leave out what gets recorded while the job runs
+    let end_time = config.end_time.unwrap_or_else(Utc::now);
+
+    let total_chunks = db
+        .count_audio_chunks_to_retranscribe(config.start_time, Some(end_time), &engine_name)
+        .await?;
+    info!(
+        "re-transcribing {} audio chunks from {} to {} with {}",
+        total_chunks, config.start_time, end_time, engine_name
+    );
+    if total_chunks == 0 {
+        return Ok(RetranscriptionStatus::Completed {
+            total_chunks,
+            failed_chunks: 0,
+            duration_secs: 0,
+        });
+    }
+
+    let mut processed_chunks = 0;
+    let mut failed_chunks = 0;
+    status_tx.send_replace(RetranscriptionStatus::Running {
+        total_chunks,
+        processed_chunks,
+        failed_chunks,
+    });
+
+    let transcriber = FileTranscriber::new(db, config.transcription.clone()).await?;
+
+    let mut last_id = 0;
+    'batches: while is_running.load(Ordering::SeqCst) {
+        let chunks = db
+            .get_audio_chunks_to_retranscribe(
+                config.start_time,
+                Some(end_time),
+                &engine_name,
+                last_id,
+                config.batch_size,
+            )
+            .await?;
+        if chunks.is_empty() {
+            break;
+        }
+
+        for chunk in chunks {
+            while is_paused.load(Ordering::SeqCst) && is_running.load(Ordering::SeqCst) {
+                status_tx.send_if_modified(|status| {
+                    let paused = RetranscriptionStatus::Paused {
+                        total_chunks,
+                        processed_chunks,
+                        failed_chunks,
+                    };
+                    let modified = *status != paused;
+                    *status = paused;
+                    modified
+                });
+                time::sleep(Duration::from_millis(500)).await;
+            }
+            status_tx.send_if_modified(|status| {
+                let resumed = matches!(status, RetranscriptionStatus::Paused { .. });
+                if resumed {
+                    *status = RetranscriptionStatus::Running {
+                        total_chunks,
+                        processed_chunks,
+                        failed_chunks,
+                    };
+                }
+                resumed
+            });
+            if !is_running.load(Ordering::SeqCst) {
+                break 'batches;
+            }
+
+            last_id = chunk.id;
+            match retranscribe_chunk(db, &chunk, &transcriber).await {
+                Ok(count) => debug!(
+                    "re-transcribed audio chunk {} into {} transcriptions",
+                    chunk.id, count
+                ),
+                Err(e) => {
+                    error!(
+                        "failed to re-transcribe audio chunk {} ({}): {}",
+                        chunk.id, chunk.file_path, e
+                    );
+                    if !config.continue_on_error {
+                        return Err(e);
+                    }
+                    failed_chunks += 1;
+                }
+            }
+            processed_chunks += 1;
+            status_tx.send_replace(RetranscriptionStatus::Running {
+                total_chunks,
+                processed_chunks,
+                failed_chunks,
+            });
+
+            if config.chunk_delay_ms > 0 {
+                time::sleep(Duration::from_millis(config.chunk_delay_ms)).await;
+            }
+        }
+    }
+
+    if !is_running.load(Ordering::SeqCst) {
+        return Ok(RetranscriptionStatus::Stopped {
+            total_chunks,
+            processed_chunks,
+            failed_chunks,
+        });
+    }
+    Ok(RetranscriptionStatus::Completed {
+        total_chunks,
+        failed_chunks,
+        duration_secs: started.elapsed().as_secs(),
+    })
+}
+
+/// Re-transcribe the file of `chunk`, returning the number of transcriptions stored.
+async fn retranscribe_chunk(
+    db: &DatabaseManager,
+    chunk: &AudioChunkToRetranscribe,
+    transcriber: &FileTranscriber,
+) -> Result<usize> {
+    let transcriptions = transcriber
+        .transcribe_file(db, &chunk.file_path, &chunk.device)
+        .await?;
+
+    // Keep what was there rather than leave the chunk without any transcription
+    if transcriptions.is_empty() {
+        debug!(
+            "nothing transcribed in audio chunk {}, keeping its transcriptions",
+            chunk.id
+        );
+        return Ok(0);
+    }
+
+    let ids = db
+        .replace_audio_transcriptions(chunk.id, &transcriber.engine_name(), &transcriptions)
+        .await?;
+    Ok(ids.len())
+}
@@ -2,11 +2,17 @@ use anyhow::{bail, Result};
 use ndarray::Array1;
 use std::collections::HashMap;
 
+/// Default minimum cosine similarity for a segment to be attributed to an enrolled speaker
+pub const DEFAULT_ENROLLED_SPEAKER_THRESHOLD: f32 = 0.45;
+
 #[derive(Debug, Clone)]
 pub struct EmbeddingManager {
     max_speakers: usize,
     speakers: HashMap<usize, Array1<f32>>,
     next_speaker_id: usize,
+    /// Centroid and enrolled embeddings of enrolled speakers, by database speaker id
+    enrolled_speakers: HashMap<i64, Vec<Array1<f32>>>,
+    enrolled_threshold: f32,
 }
 
 impl EmbeddingManager {
@@ -15,7 +21,43 @@ impl EmbeddingManager {
             max_speakers,
             speakers: HashMap::new(),
             next_speaker_id: 1,
+            enrolled_speakers: HashMap::new(),
+            enrolled_threshold: DEFAULT_ENROLLED_SPEAKER_THRESHOLD,
+        }
+    }
+
+    /// Replace the enrolled speakers, given as their database id with their centroid and
+    /// enrolled embeddings.
+    pub fn set_enrolled_speakers(&mut self, speakers: Vec<(i64, Vec<Vec<f32>>)>) {
+        self.enrolled_speakers = speakers
+            .into_iter()
+            .map(|(id, embeddings)| (id, embeddings.into_iter().map(Array1::from_vec).collect()))
+            .collect();
+    }
+
+    /// Minimum cosine similarity for a segment to be attributed to an enrolled speaker.
+    pub fn set_enrolled_threshold(&mut self, threshold: f32) {
+        self.enrolled_threshold = threshold;
+    }
+
+    /// Database id of the enrolled speaker closest to `embedding`, if any is similar enough.
+    ///
+    /// Enrolled speakers are matched before the speakers met so far, with their own threshold.
+    pub fn search_enrolled_speaker(&self, embedding: &[f32]) -> Option<i64> {
+        let embedding_array = Array1::from_vec(embedding.to_vec());
+        let mut best_speaker_id = None;
+        let mut best_similarity = self.enrolled_threshold;
+
+        for (&speaker_id, speaker_embeddings) in &self.enrolled_speakers {
+            for speaker_embedding in speaker_embeddings {
+                let similarity = Self::cosine_similarity(&embedding_array, speaker_embedding);
+                if similarity > best_similarity {
+                    best_speaker_id = Some(speaker_id);
+                    best_similarity = similarity;
+                }
+            }
         }
+        best_speaker_id
     }
 
     fn cosine_similarity(a: &Array1<f32>, b: &Array1<f32>) -> f32 {
@@ -0,0 +1,159 @@
+//! Enrollment of known voices for named speakers.
+//!
+//! A few seconds of a person's voice, uploaded or picked from recorded chunks, are split in
+//! short windows with an embedding each. The embeddings are stored with the speaker, and live
+//! diarization attributes speech to the speaker when it is close enough to one of them or to
+//! their centroid.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::{anyhow, bail, Result};
+use screenpipe_db::{DatabaseManager, EnrolledSpeaker};
+use tracing::{debug, info};
+
+use super::{embedding::EmbeddingExtractor, embedding_manager::EmbeddingManager};
+use crate::{
+    transcription::stt::SAMPLE_RATE,
+    utils::audio::{pcm_decode, resample},
+};
+
+/// Length of the windows enrollment audio is split in, in seconds
+const WINDOW_SECS: f64 = 3.0;
+/// Shortest trailing window worth an embedding, in seconds
+const MIN_WINDOW_SECS: f64 = 1.0;
+/// RMS below which a window is considered silence
+const SILENCE_RMS: f32 = 0.001;
+
+/// Audio of the voice to enroll
+#[derive(Debug, Clone)]
+pub enum EnrollmentAudio {
+    /// An audio file, e.g. an upload
+    File(PathBuf),
+    /// A recorded audio chunk, optionally a part of it in seconds from its start
+    AudioChunk {
+        id: i64,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
+    },
+}
+
+/// Decode `audio` at the sample rate of the embedding model.
+async fn load_enrollment_audio(db: &DatabaseManager, audio: &EnrollmentAudio) -> Result<Vec<f32>> {
+    let (path, range) = match audio {
+        EnrollmentAudio::File(path) => (path.clone(), None),
+        EnrollmentAudio::AudioChunk {
+            id,
+            start_time,
+            end_time,
+        } => {
+            let path = db
+                .get_audio_chunk_path(*id)
+                .await?
+                .ok_or_else(|| anyhow!("audio chunk {} not found", id))?;
+            (PathBuf::from(path), Some((*start_time, *end_time)))
+        }
+    };
+
+    let (samples, sample_rate) = tokio::task::spawn_blocking(move || pcm_decode(path)).await??;
+    let samples = if sample_rate != SAMPLE_RATE {
+        resample(&samples, sample_rate, SAMPLE_RATE)?
+    } else {
+        samples
+    };
+
+    Ok(match range {
+        Some((start_time, end_time)) => {
+            let to_index =
+                |secs: f64| ((secs.max(0.0) * SAMPLE_RATE as f64) as usize).min(samples.len());
+            let start = start_time.map_or(0, to_index);
+            let end = end_time.map_or(samples.len(), to_index);
+            samples.get(start..end).unwrap_or_default().to_vec()
+        }
+        None => samples,
+    })
+}
+
+/// Embeddings of the windows of `samples` (16 kHz mono) that are not silence.
+pub fn compute_enrollment_embeddings(
+    embedding_extractor: &StdMutex<EmbeddingExtractor>,
+    samples: &[f32],
+) -> Result<Vec<Vec<f32>>> {
+    let window_len = (WINDOW_SECS * SAMPLE_RATE as f64) as usize;
+    let min_window_len = (MIN_WINDOW_SECS * SAMPLE_RATE as f64) as usize;
+
+    let mut embeddings = Vec::new();
+    for window in samples.chunks(window_len) {
+        if window.len() < min_window_len {
+            continue;
+        }
+        let rms = (window.iter().map(|s| s * s).sum::<f32>() / window.len() as f32).sqrt();
+        if rms < SILENCE_RMS {
+            debug!("skipping silent enrollment window");
+            continue;
+        }
+        let embedding: Vec<f32> = embedding_extractor
+            .lock()
+            .map_err(|_| anyhow!("embedding extractor lock poisoned"))?
+            .compute(window)?
+            .collect();
+        embeddings.push(embedding);
+    }
+    Ok(embeddings)
+}
+
+/// Enroll the voice in `audio` for the speaker `speaker_id`, or a new speaker if not set, and
+/// name it `name` if set.
+pub async fn enroll_speaker(
+    db: &DatabaseManager,
+    embedding_extractor: &StdMutex<EmbeddingExtractor>,
+    speaker_id: Option<i64>,
+    name: Option<&str>,
+    audio: &[EnrollmentAudio],
+) -> Result<EnrolledSpeaker> {
+    let mut embeddings = Vec::new();
+    for audio in audio {
+        let samples = load_enrollment_audio(db, audio).await?;
+        embeddings.extend(compute_enrollment_embeddings(
+            embedding_extractor,
+            &samples,
+        )?);
+    }
+    if embeddings.is_empty() {
+        bail!(
+            "no speech to enroll, at least {}s of voice is needed",
+            MIN_WINDOW_SECS
+        );
+    }
+
+    let speaker = db.enroll_speaker(speaker_id, name, &embeddings).await?;
+    info!(
+        "enrolled {} voice embeddings for speaker {} ({} in total)",
+        embeddings.len(),
+        speaker.id,
+        speaker.embeddings.len()
+    );
+    Ok(speaker)
+}
+
+/// Load the enrolled speakers of `db` into `embedding_manager`, returning how many there are.
+pub async fn load_enrolled_speakers(
+    db: &DatabaseManager,
+    embedding_manager: &Arc<StdMutex<EmbeddingManager>>,
+) -> Result<usize> {
+    let speakers = db.get_enrolled_speakers().await?;
+    let count = speakers.len();
+    let speakers = speakers
+        .into_iter()
+        .map(|speaker| {
+            let mut embeddings = speaker.embeddings;
+            embeddings.push(speaker.centroid);
+            (speaker.id, embeddings)
+        })
+        .collect();
+    embedding_manager
+        .lock()
+        .map_err(|_| anyhow!("embedding manager lock poisoned"))?
+        .set_enrolled_speakers(speakers);
+    Ok(count)
+}
@@ -14,6 +14,10 @@ pub struct SpeechSegment {
     pub speaker: String,
     pub embedding: Vec<f32>,
     pub sample_rate: u32,
+    /// Database id of the enrolled speaker the segment was attributed to
+    pub enrolled_speaker_id: Option<i64>,
+    /// Whether the segment echoes audio played on an output device
+    pub is_echo: bool,
 }
 
 fn find_max_index(row: ArrayBase<ViewRepr<&f32>, IxDyn>) -> Result<usize> {
@@ -76,9 +80,15 @@ fn create_speech_segment(
             vec![0.0; 512]
         }
     };
-    let speaker = {
+    let (speaker, enrolled_speaker_id) = {
         let mut manager = embedding_manager.lock().unwrap();
-        get_speaker_from_embedding(&mut manager, embedding.clone())
+        match manager.search_enrolled_speaker(&embedding) {
+            Some(id) => (format!("enrolled-{}", id), Some(id)),
+            None => (
+                get_speaker_from_embedding(&mut manager, embedding.clone()),
+                None,
+            ),
+        }
     };
 
     Ok(SpeechSegment {
@@ -88,6 +98,8 @@ fn create_speech_segment(
         speaker,
         embedding,
         sample_rate,
+        enrolled_speaker_id,
+        is_echo: false,
     })
 }
 
@@ -7,6 +7,7 @@ use std::io::Cursor;
 use tracing::{debug, error, info};
 
 use crate::transcription::deepgram::{CUSTOM_DEEPGRAM_API_TOKEN, DEEPGRAM_API_URL};
+use crate::transcription::engine::TranscriptWord;
 
 pub async fn transcribe_with_deepgram(
     api_key: &str,
@@ -14,7 +15,7 @@ pub async fn transcribe_with_deepgram(
     device: &str,
     sample_rate: u32,
     languages: Vec<Language>,
-) -> Result<String> {
+) -> Result<(String, Vec<TranscriptWord>)> {
     debug!("starting deepgram transcription");
 
     // Use token from env var
@@ -113,7 +114,7 @@ async fn get_deepgram_response(
 async fn handle_deepgram_response(
     response: Result<Response, reqwest::Error>,
     device: &str,
-) -> Result<String> {
+) -> Result<(String, Vec<TranscriptWord>)> {
     match response {
         Ok(resp) => {
             debug!("received response from deepgram api");
@@ -127,10 +128,9 @@ async fn handle_deepgram_response(
                         );
                         return Err(anyhow::anyhow!("Deepgram API error: {:?}", result));
                     }
-                    let transcription = result["results"]["channels"][0]["alternatives"][0]
-                        ["transcript"]
-                        .as_str()
-                        .unwrap_or("");
+                    let alternative = &result["results"]["channels"][0]["alternatives"][0];
+                    let transcription = alternative["transcript"].as_str().unwrap_or("");
+                    let words = parse_words(&alternative["words"]);
 
                     if transcription.is_empty() {
                         info!("device: {}, transcription is empty.", device);
@@ -142,7 +142,7 @@ async fn handle_deepgram_response(
                         );
                     }
 
-                    Ok(transcription.to_string())
+                    Ok((transcription.to_string(), words))
                 }
                 Err(e) => {
                     error!("Failed to parse JSON response: {:?}", e);
@@ -159,3 +159,26 @@ async fn handle_deepgram_response(
         }
     }
 }
+
+/// Words of a deepgram alternative, punctuated like the smart formatted transcript.
+fn parse_words(words: &Value) -> Vec<TranscriptWord> {
+    words
+        .as_array()
+        .map(|words| {
+            words
+                .iter()
+                .filter_map(|word| {
+                    let text = word["punctuated_word"]
+                        .as_str()
+                        .or_else(|| word["word"].as_str())?;
+                    Some(TranscriptWord {
+                        text: text.to_string(),
+                        start: word["start"].as_f64()?,
+                        end: word["end"].as_f64()?,
+                        confidence: word["confidence"].as_f64().map(|c| c as f32),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,432 @@
+//! Speech-to-text backends.
+//!
+//! Every backend implements [`TranscriptionEngine`]. The audio manager builds one from
+//! its options with [`create_transcription_engine`]: the configured engine followed by
+//! its fallbacks, each tried in turn when the previous one fails.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use reqwest::multipart::{Form, Part};
+use screenpipe_core::Language;
+use serde_json::Value;
+use std::io::Cursor;
+use tracing::{debug, error, info};
+use whisper_rs::WhisperContext;
+
+use crate::audio_manager::AudioManagerOptions;
+use crate::core::engine::AudioTranscriptionEngine;
+use crate::transcription::deepgram::batch::transcribe_with_deepgram;
+use crate::transcription::whisper::batch::process_with_whisper;
+use crate::transcription::whisper::model::{
+    create_whisper_context_parameters, download_whisper_model,
+};
+
+/// Text of a transcribed segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcript {
+    pub text: String,
+    /// Name of the engine that produced the text, see [`TranscriptionEngine::name`]
+    pub engine: String,
+    /// Timings of the words of `text`, empty when the engine doesn't provide them
+    pub words: Vec<TranscriptWord>,
+}
+
+/// A word of a transcript.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptWord {
+    pub text: String,
+    /// Seconds from the start of the transcribed audio
+    pub start: f64,
+    pub end: f64,
+    /// Between 0 and 1
+    pub confidence: Option<f32>,
+}
+
+#[async_trait]
+pub trait TranscriptionEngine: Send + Sync {
+    /// Name stored with the transcriptions of this engine.
+    fn name(&self) -> String;
+
+    /// Transcribe mono `audio` sampled at `sample_rate`.
+    async fn transcribe(
+        &self,
+        audio: &[f32],
+        sample_rate: u32,
+        device: &str,
+        languages: &[Language],
+    ) -> Result<Transcript>;
+}
+
+pub struct DeepgramEngine {
+    api_key: String,
+}
+
+impl DeepgramEngine {
+    /// `api_key` may be empty when `CUSTOM_DEEPGRAM_API_TOKEN` is set.
+    pub fn new(api_key: String) -> Self {
+        Self { api_key }
+    }
+}
+
+#[async_trait]
+impl TranscriptionEngine for DeepgramEngine {
+    fn name(&self) -> String {
+        AudioTranscriptionEngine::Deepgram.to_string()
+    }
+
+    async fn transcribe(
+        &self,
+        audio: &[f32],
+        sample_rate: u32,
+        device: &str,
+        languages: &[Language],
+    ) -> Result<Transcript> {
+        let (text, words) = transcribe_with_deepgram(
+            &self.api_key,
+            audio,
+            device,
+            sample_rate,
+            languages.to_vec(),
+        )
+        .await?;
+        Ok(Transcript {
+            text,
+            engine: self.name(),
+            words,
+        })
+    }
+}
+
+/// Local whisper.cpp model.
+pub struct WhisperEngine {
+    model: AudioTranscriptionEngine,
+    context: Arc<WhisperContext>,
+}
+
+impl WhisperEngine {
+    pub fn new(model: AudioTranscriptionEngine, context: Arc<WhisperContext>) -> Self {
+        Self { model, context }
+    }
+
+    /// Download `model` if needed and load it.
+    pub fn load(model: AudioTranscriptionEngine) -> Result<Self> {
+        let model = Arc::new(model);
+        let model_path = download_whisper_model(model.clone())?;
+        let context_param = create_whisper_context_parameters(model.clone())?;
+        let context = WhisperContext::new_with_params(&model_path.to_string_lossy(), context_param)
+            .map_err(|e| anyhow::anyhow!("failed to load whisper model {}: {}", model, e))?;
+        Ok(Self::new((*model).clone(), Arc::new(context)))
+    }
+}
+
+#[async_trait]
+impl TranscriptionEngine for WhisperEngine {
+    fn name(&self) -> String {
+        self.model.to_string()
+    }
+
+    async fn transcribe(
+        &self,
+        audio: &[f32],
+        _sample_rate: u32,
+        _device: &str,
+        languages: &[Language],
+    ) -> Result<Transcript> {
+        let (text, words) =
+            process_with_whisper(audio, languages.to_vec(), self.context.clone()).await?;
+        Ok(Transcript {
+            text,
+            engine: self.name(),
+            words,
+        })
+    }
+}
+
+/// Server implementing OpenAI's `/v1/audio/transcriptions`, such as faster-whisper-server,
+/// whisper.cpp's server or a hosted API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenAiCompatibleConfig {
+    /// Base url the `/audio/transcriptions` path is appended to, e.g. `http://localhost:8000/v1`
+    pub url: String,
+    pub model: String,
+    pub api_key: Option<String>,
+}
+
+impl Default for OpenAiCompatibleConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:8000/v1".to_string(),
+            model: "whisper-1".to_string(),
+            api_key: None,
+        }
+    }
+}
+
+pub struct OpenAiCompatibleEngine {
+    config: OpenAiCompatibleConfig,
+    client: reqwest::Client,
+}
+
+impl OpenAiCompatibleEngine {
+    pub fn new(config: OpenAiCompatibleConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(120))
+            .build()?;
+        Ok(Self { config, client })
+    }
+
+    fn endpoint(&self) -> String {
+        format!(
+            "{}/audio/transcriptions",
+            self.config.url.trim_end_matches('/')
+        )
+    }
+}
+
+#[async_trait]
+impl TranscriptionEngine for OpenAiCompatibleEngine {
+    fn name(&self) -> String {
+        AudioTranscriptionEngine::OpenAiCompatible.to_string()
+    }
+
+    async fn transcribe(
+        &self,
+        audio: &[f32],
+        sample_rate: u32,
+        device: &str,
+        languages: &[Language],
+    ) -> Result<Transcript> {
+        let file = Part::bytes(encode_pcm16_wav(audio, sample_rate)?)
+            .file_name("audio.wav")
+            .mime_str("audio/wav")?;
+        let mut form = Form::new()
+            .part("file", file)
+            .text("model", self.config.model.clone())
+            .text("response_format", "verbose_json")
+            .text("timestamp_granularities[]", "word");
+        // The API takes a single language, let the server detect it otherwise
+        if let [language] = languages {
+            form = form.text("language", language.as_lang_code());
+        }
+
+        let endpoint = self.endpoint();
+        let mut request = self.client.post(&endpoint).multipart(form);
+        if let Some(api_key) = &self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to send request to {}: {}", endpoint, e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "{} returned {}: {}",
+                endpoint,
+                status,
+                body
+            ));
+        }
+        let result: Value = response.json().await?;
+        let text = result["text"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("no text in response from {}", endpoint))?
+            .trim()
+            .to_string();
+
+        // Servers without word timestamps leave them out
+        let words = result["words"]
+            .as_array()
+            .map(|words| {
+                words
+                    .iter()
+                    .filter_map(|word| {
+                        Some(TranscriptWord {
+                            text: word["word"].as_str()?.trim().to_string(),
+                            start: word["start"].as_f64()?,
+                            end: word["end"].as_f64()?,
+                            confidence: word["probability"].as_f64().map(|p| p as f32),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if text.is_empty() {
+            info!("device: {}, transcription is empty.", device);
+        } else {
+            debug!(
+                "device: {}, transcribed {} characters with {}",
+                device,
+                text.len(),
+                self.config.model
+            );
+        }
+
+        Ok(Transcript {
+            text,
+            engine: self.name(),
+            words,
+        })
+    }
+}
+
+/// Tries engines in order until one succeeds.
+pub struct FallbackEngine {
+    engines: Vec<Arc<dyn TranscriptionEngine>>,
+}
+
+impl FallbackEngine {
+    pub fn new(engines: Vec<Arc<dyn TranscriptionEngine>>) -> Result<Self> {
+        if engines.is_empty() {
+            anyhow::bail!("a fallback chain needs at least one transcription engine");
+        }
+        Ok(Self { engines })
+    }
+}
+
+#[async_trait]
+impl TranscriptionEngine for FallbackEngine {
+    fn name(&self) -> String {
+        self.engines[0].name()
+    }
+
+    async fn transcribe(
+        &self,
+        audio: &[f32],
+        sample_rate: u32,
+        device: &str,
+        languages: &[Language],
+    ) -> Result<Transcript> {
+        let mut last_error = None;
+        for (i, engine) in self.engines.iter().enumerate() {
+            match engine
+                .transcribe(audio, sample_rate, device, languages)
+                .await
+            {
+                Ok(transcript) => return Ok(transcript),
+                Err(e) => {
+                    match self.engines.get(i + 1) {
+                        Some(next) => error!(
+                            "device: {}, {} transcription failed, falling back to {}: {:?}",
+                            device,
+                            engine.name(),
+                            next.name(),
+                            e
+                        ),
+                        None => error!(
+                            "device: {}, {} transcription failed: {:?}",
+                            device,
+                            engine.name(),
+                            e
+                        ),
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap())
+    }
+}
+
+/// Engines tried in order for `engine`, when no fallbacks were configured.
+pub fn default_fallbacks(engine: &AudioTranscriptionEngine) -> Vec<AudioTranscriptionEngine> {
+    match engine {
+        // Cloud transcription falls back to the local model
+        AudioTranscriptionEngine::Deepgram => {
+            vec![AudioTranscriptionEngine::WhisperLargeV3TurboQuantized]
+        }
+        _ => vec![],
+    }
+}
+
+/// The configured engine followed by its fallbacks, without duplicates.
+pub fn transcription_chain(
+    engine: &AudioTranscriptionEngine,
+    fallbacks: Option<&[AudioTranscriptionEngine]>,
+) -> Vec<AudioTranscriptionEngine> {
+    let fallbacks = match fallbacks {
+        Some(fallbacks) => fallbacks.to_vec(),
+        None => default_fallbacks(engine),
+    };
+    let mut chain = vec![engine.clone()];
+    for fallback in fallbacks {
+        if !chain.contains(&fallback) {
+            chain.push(fallback);
+        }
+    }
+    chain
+}
+
+/// Build the engine described by the audio manager options, loading local models.
+pub fn create_transcription_engine(
+    options: &AudioManagerOptions,
+) -> Result<Arc<dyn TranscriptionEngine>> {
+    let chain = transcription_chain(
+        &options.transcription_engine,
+        options.transcription_fallbacks.as_deref(),
+    );
+
+    let mut engines: Vec<Arc<dyn TranscriptionEngine>> = Vec::with_capacity(chain.len());
+    for engine in chain {
+        engines.push(load_transcription_engine(
+            engine,
+            options.deepgram_api_key.clone(),
+            options.openai_compatible.clone(),
+        )?);
+    }
+
+    if engines.len() == 1 {
+        return Ok(engines.remove(0));
+    }
+    info!(
+        "transcription engines: {}",
+        engines
+            .iter()
+            .map(|e| e.name())
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    );
+    Ok(Arc::new(FallbackEngine::new(engines)?))
+}
+
+/// Build a single engine, without fallbacks, loading local models.
+pub fn load_transcription_engine(
+    engine: AudioTranscriptionEngine,
+    deepgram_api_key: Option<String>,
+    openai_compatible: OpenAiCompatibleConfig,
+) -> Result<Arc<dyn TranscriptionEngine>> {
+    Ok(match engine {
+        AudioTranscriptionEngine::Deepgram => {
+            Arc::new(DeepgramEngine::new(deepgram_api_key.unwrap_or_default()))
+        }
+        AudioTranscriptionEngine::OpenAiCompatible => {
+            Arc::new(OpenAiCompatibleEngine::new(openai_compatible)?)
+        }
+        whisper => Arc::new(WhisperEngine::load(whisper)?),
+    })
+}
+
+fn encode_pcm16_wav(audio: &[f32], sample_rate: u32) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::new(&mut cursor, spec)?;
+        for &sample in audio {
+            writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+        }
+        writer.finalize()?;
+    }
+    Ok(cursor.into_inner())
+}
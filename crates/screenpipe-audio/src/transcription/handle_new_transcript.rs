@@ -126,6 +126,7 @@ pub async fn handle_new_transcript(
 
             // Use the cleaned current transcript (with overlap removed)
             if current != current_transcript.clone().unwrap_or_default() {
+                transcription.trim_words(&current);
                 current_transcript = Some(current);
                 was_trimmed = true;
                 TRANSCRIPTS_OVERLAP_TRIMMED.fetch_add(1, Ordering::SeqCst);
@@ -1,8 +1,9 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use crate::core::device::AudioDevice;
 
 pub mod deepgram;
+pub mod engine;
 pub mod stt;
 pub mod whisper;
 
@@ -12,12 +13,15 @@ pub struct AudioInput {
     pub sample_rate: u32,
     pub channels: u16,
     pub device: Arc<AudioDevice>,
+    /// When the last sample was captured, for recorded device streams
+    pub captured_at: Option<Instant>,
 }
 
 pub mod text_utils;
 
 mod transcription_result;
 
+pub(crate) use transcription_result::get_or_create_speaker_from_embedding;
 pub use transcription_result::process_transcription_result;
 pub use transcription_result::TranscriptionResult;
 mod handle_new_transcript;
@@ -1,11 +1,14 @@
-use crate::core::device::AudioDevice;
+use crate::core::device::{AudioDevice, DeviceType};
 use crate::core::engine::AudioTranscriptionEngine;
+use crate::echo_cancellation::EchoCanceller;
+use crate::preprocessing::PreprocessingChain;
 use crate::speaker::embedding::EmbeddingExtractor;
 use crate::speaker::embedding_manager::EmbeddingManager;
 use crate::speaker::prepare_segments;
 use crate::speaker::segment::SpeechSegment;
-use crate::transcription::deepgram::batch::transcribe_with_deepgram;
-use crate::transcription::whisper::batch::process_with_whisper;
+use crate::transcription::engine::{
+    DeepgramEngine, FallbackEngine, TranscriptWord, TranscriptionEngine, WhisperEngine,
+};
 use crate::utils::audio::resample;
 use crate::utils::ffmpeg::{get_new_file_path, write_audio_to_file};
 use crate::vad::VadEngine;
@@ -27,32 +30,10 @@ use crate::{AudioInput, TranscriptionResult};
 
 pub const SAMPLE_RATE: u32 = 16000;
 
-#[allow(clippy::too_many_arguments)]
-pub async fn stt_sync(
-    audio: &[f32],
-    sample_rate: u32,
-    device: &str,
-    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
-    deepgram_api_key: Option<String>,
-    languages: Vec<Language>,
-    whisper_context: Arc<WhisperContext>,
-) -> Result<String> {
-    let audio = audio.to_vec();
-
-    let device = device.to_string();
-
-    stt(
-        &audio,
-        sample_rate,
-        &device,
-        audio_transcription_engine,
-        deepgram_api_key,
-        languages,
-        whisper_context,
-    )
-    .await
-}
-
+/// Transcribe with one of the built-in engines, Deepgram falling back to `whisper_context`.
+///
+/// Other engines and custom fallback chains go through a [`TranscriptionEngine`], see
+/// [`create_transcription_engine`](crate::transcription::engine::create_transcription_engine).
 #[allow(clippy::too_many_arguments)]
 pub async fn stt(
     audio: &[f32],
@@ -63,30 +44,24 @@ pub async fn stt(
     languages: Vec<Language>,
     whisper_context: Arc<WhisperContext>,
 ) -> Result<String> {
-    let transcription: Result<String> =
-        if audio_transcription_engine == AudioTranscriptionEngine::Deepgram.into() {
-            // Deepgram implementation
-            let api_key = deepgram_api_key.unwrap_or_default();
-
-            match transcribe_with_deepgram(&api_key, audio, device, sample_rate, languages.clone())
-                .await
-            {
-                Ok(transcription) => Ok(transcription),
-                Err(e) => {
-                    error!(
-                        "device: {}, deepgram transcription failed, falling back to Whisper: {:?}",
-                        device, e
-                    );
-                    // Fallback to Whisper
-                    process_with_whisper(audio, languages.clone(), whisper_context).await
-                }
-            }
-        } else {
-            // Existing Whisper implementation
-            process_with_whisper(audio, languages, whisper_context).await
-        };
+    let engine: Arc<dyn TranscriptionEngine> = match *audio_transcription_engine {
+        AudioTranscriptionEngine::Deepgram => Arc::new(FallbackEngine::new(vec![
+            Arc::new(DeepgramEngine::new(deepgram_api_key.unwrap_or_default())),
+            Arc::new(WhisperEngine::new(
+                AudioTranscriptionEngine::WhisperLargeV3TurboQuantized,
+                whisper_context,
+            )),
+        ])?),
+        AudioTranscriptionEngine::OpenAiCompatible => {
+            anyhow::bail!("the OpenAI-compatible engine needs a server, use OpenAiCompatibleEngine")
+        }
+        ref whisper => Arc::new(WhisperEngine::new(whisper.clone(), whisper_context)),
+    };
 
-    transcription
+    let transcript = engine
+        .transcribe(audio, sample_rate, device, &languages)
+        .await?;
+    Ok(transcript.text)
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -97,11 +72,12 @@ pub async fn process_audio_input(
     embedding_manager: Arc<StdMutex<EmbeddingManager>>,
     embedding_extractor: Arc<StdMutex<EmbeddingExtractor>>,
     output_path: &PathBuf,
-    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
-    deepgram_api_key: Option<String>,
+    transcription_engine: Arc<dyn TranscriptionEngine>,
     languages: Vec<Language>,
     output_sender: &crossbeam::channel::Sender<TranscriptionResult>,
-    whisper_context: Arc<WhisperContext>,
+    echo_canceller: Option<&EchoCanceller>,
+    preprocessing: &PreprocessingChain,
+    enable_diarization: bool,
 ) -> Result<()> {
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -120,8 +96,15 @@ pub async fn process_audio_input(
         ..audio
     };
 
+    // The stored audio stays as recorded, only what is transcribed is preprocessed
+    let processed_audio = if preprocessing.is_empty() {
+        audio_data
+    } else {
+        preprocessing.process(&audio_data, SAMPLE_RATE)
+    };
+
     let (mut segments, speech_ratio_ok) = prepare_segments(
-        &audio_data,
+        &processed_audio,
         vad_engine,
         &segmentation_model_path,
         embedding_manager,
@@ -145,7 +128,28 @@ pub async fn process_audio_input(
         error!("Error writing audio to file: {:?}", e);
     }
 
-    while let Some(segment) = segments.recv().await {
+    // Microphone segments are checked against what output devices played meanwhile
+    let echo_reference = match (echo_canceller, audio.captured_at) {
+        (Some(echo_canceller), Some(captured_at))
+            if audio.device.device_type == DeviceType::Input =>
+        {
+            Some((echo_canceller, captured_at))
+        }
+        _ => None,
+    };
+    let chunk_secs = processed_audio.len() as f64 / SAMPLE_RATE as f64;
+
+    while let Some(mut segment) = segments.recv().await {
+        if let Some((echo_canceller, captured_at)) = echo_reference {
+            if !echo_canceller.process_segment(&mut segment, captured_at, chunk_secs) {
+                continue;
+            }
+        }
+        // Without diarization the speech is attributed to no one
+        if !enable_diarization {
+            segment.embedding.clear();
+            segment.enrolled_speaker_id = None;
+        }
         let path = new_file_path.clone();
         let transcription_result = if cfg!(target_os = "macos") {
             #[cfg(target_os = "macos")]
@@ -155,12 +159,10 @@ pub async fn process_audio_input(
                     run_stt(
                         segment,
                         audio.device.clone(),
-                        audio_transcription_engine.clone(),
-                        deepgram_api_key.clone(),
+                        transcription_engine.clone(),
                         languages.clone(),
                         path,
                         timestamp,
-                        whisper_context.clone(),
                     )
                 })
                 .await?
@@ -173,12 +175,10 @@ pub async fn process_audio_input(
             run_stt(
                 segment,
                 audio.device.clone(),
-                audio_transcription_engine.clone(),
-                deepgram_api_key.clone(),
+                transcription_engine.clone(),
                 languages.clone(),
                 path,
                 timestamp,
-                whisper_context.clone(),
             )
             .await?
         };
@@ -191,42 +191,46 @@ pub async fn process_audio_input(
     Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
 pub async fn run_stt(
     segment: SpeechSegment,
     device: Arc<AudioDevice>,
-    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
-    deepgram_api_key: Option<String>,
+    transcription_engine: Arc<dyn TranscriptionEngine>,
     languages: Vec<Language>,
     path: String,
     timestamp: u64,
-    whisper_context: Arc<WhisperContext>,
 ) -> Result<TranscriptionResult> {
     let audio = segment.samples.clone();
     let sample_rate = segment.sample_rate;
-    match stt_sync(
-        &audio,
-        sample_rate,
-        &device.to_string(),
-        audio_transcription_engine.clone(),
-        deepgram_api_key.clone(),
-        languages.clone(),
-        whisper_context,
-    )
-    .await
+    match transcription_engine
+        .transcribe(&audio, sample_rate, &device.to_string(), &languages)
+        .await
     {
-        Ok(transcription) => Ok(TranscriptionResult {
+        Ok(transcript) => Ok(TranscriptionResult {
             input: AudioInput {
                 data: Arc::new(audio),
                 sample_rate,
                 channels: 1,
                 device: device.clone(),
+                captured_at: None,
             },
-            transcription: Some(transcription),
+            transcription: Some(transcript.text),
+            engine: Some(transcript.engine),
+            // Word timings are relative to the segment, the stored ones to the audio file
+            words: transcript
+                .words
+                .into_iter()
+                .map(|word| TranscriptWord {
+                    start: word.start + segment.start,
+                    end: word.end + segment.start,
+                    ..word
+                })
+                .collect(),
             path,
             timestamp,
             error: None,
             speaker_embedding: segment.embedding.clone(),
+            enrolled_speaker_id: segment.enrolled_speaker_id,
+            is_echo: segment.is_echo,
             start_time: segment.start,
             end_time: segment.end,
         }),
@@ -238,12 +242,17 @@ pub async fn run_stt(
                     sample_rate: segment.sample_rate,
                     channels: 1,
                     device: device.clone(),
+                    captured_at: None,
                 },
                 transcription: None,
+                engine: None,
+                words: Vec::new(),
                 path,
                 timestamp,
                 error: Some(e.to_string()),
                 speaker_embedding: Vec::new(),
+                enrolled_speaker_id: None,
+                is_echo: segment.is_echo,
                 start_time: segment.start,
                 end_time: segment.end,
             })
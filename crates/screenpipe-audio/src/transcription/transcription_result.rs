@@ -1,19 +1,27 @@
 use std::sync::Arc;
 
 use screenpipe_core::pii_removal::remove_pii;
-use screenpipe_db::{DatabaseManager, Speaker};
+use screenpipe_db::{AudioTranscriptionWord, DatabaseManager, Speaker};
 use tracing::{debug, error, info};
 
 use crate::core::engine::AudioTranscriptionEngine;
 
-use super::{text_utils::longest_common_word_substring, AudioInput};
+use super::{engine::TranscriptWord, text_utils::longest_common_word_substring, AudioInput};
 
 #[derive(Debug, Clone)]
 pub struct TranscriptionResult {
     pub path: String,
     pub input: AudioInput,
     pub speaker_embedding: Vec<f32>,
+    /// Enrolled speaker diarization attributed the audio to, if any
+    pub enrolled_speaker_id: Option<i64>,
+    /// Whether the audio echoes an output device, and is not attributed to a speaker
+    pub is_echo: bool,
     pub transcription: Option<String>,
+    /// Engine that produced `transcription`, the configured one if unset
+    pub engine: Option<String>,
+    /// Timings of the words of `transcription`, from the start of the audio file at `path`
+    pub words: Vec<TranscriptWord>,
     pub timestamp: u64,
     pub error: Option<String>,
     pub start_time: f64,
@@ -47,6 +55,22 @@ impl TranscriptionResult {
 
         None
     }
+
+    /// Keep the timings of the words left in `trimmed`, the end of the transcription once
+    /// [`cleanup_overlap`](Self::cleanup_overlap) cut its start off.
+    pub fn trim_words(&mut self, trimmed: &str) {
+        let total = self
+            .transcription
+            .as_deref()
+            .map_or(0, |t| t.split_whitespace().count());
+        // Timings that don't line up with the text can't be trimmed
+        if self.words.len() != total {
+            self.words.clear();
+            return;
+        }
+        let kept = trimmed.split_whitespace().count();
+        self.words.drain(..total.saturating_sub(kept));
+    }
 }
 
 pub async fn process_transcription_result(
@@ -65,7 +89,22 @@ pub async fn process_transcription_result(
         return Ok(None);
     }
 
-    let speaker = get_or_create_speaker_from_embedding(db, &result.speaker_embedding).await?;
+    // The voice of an echo is that of whoever spoke on the output device, and audio
+    // transcribed without diarization has no voice to match
+    let speaker = if result.is_echo
+        || (result.speaker_embedding.is_empty() && result.enrolled_speaker_id.is_none())
+    {
+        None
+    } else {
+        Some(
+            get_or_create_speaker_from_embedding(
+                db,
+                &result.speaker_embedding,
+                result.enrolled_speaker_id,
+            )
+            .await?,
+        )
+    };
 
     info!("Detected speaker: {:?}", speaker);
 
@@ -74,9 +113,27 @@ pub async fn process_transcription_result(
     let transcription = if use_pii_removal {
         remove_pii(&raw_transcription)
     } else {
-        raw_transcription
+        raw_transcription.clone()
+    };
+    // The words would give away what PII removal took out of the transcription
+    let words: Vec<AudioTranscriptionWord> = if transcription == raw_transcription {
+        result
+            .words
+            .into_iter()
+            .map(|word| AudioTranscriptionWord {
+                word: word.text,
+                start_time: word.start,
+                end_time: word.end,
+                confidence: word.confidence.map(f64::from),
+            })
+            .collect()
+    } else {
+        Vec::new()
     };
-    let transcription_engine = audio_transcription_engine.to_string();
+    let transcription_engine = result
+        .engine
+        .clone()
+        .unwrap_or_else(|| audio_transcription_engine.to_string());
     let mut chunk_id: Option<i64> = None;
 
     info!(
@@ -109,7 +166,7 @@ pub async fn process_transcription_result(
                 return Ok(Some(audio_chunk_id));
             }
 
-            if let Err(e) = db
+            match db
                 .insert_audio_transcription(
                     audio_chunk_id,
                     &transcription,
@@ -126,23 +183,39 @@ pub async fn process_transcription_result(
                             }
                         },
                     },
-                    Some(speaker.id),
+                    speaker.map(|speaker| speaker.id),
                     Some(result.start_time),
                     Some(result.end_time),
                 )
                 .await
             {
-                error!(
-                    "Failed to insert audio transcription for device {}: {}",
-                    result.input.device, e
-                );
-                return Ok(Some(audio_chunk_id));
-            } else {
-                debug!(
-                    "Inserted audio transcription for chunk {} from device {} using {}",
-                    audio_chunk_id, result.input.device, transcription_engine
-                );
-                chunk_id = Some(audio_chunk_id);
+                Err(e) => {
+                    error!(
+                        "Failed to insert audio transcription for device {}: {}",
+                        result.input.device, e
+                    );
+                    return Ok(Some(audio_chunk_id));
+                }
+                Ok(transcription_id) => {
+                    debug!(
+                        "Inserted audio transcription for chunk {} from device {} using {}",
+                        audio_chunk_id, result.input.device, transcription_engine
+                    );
+                    chunk_id = Some(audio_chunk_id);
+
+                    // 0 when the transcription was a duplicate
+                    if transcription_id > 0 {
+                        if let Err(e) = db
+                            .insert_audio_transcription_words(transcription_id, &words)
+                            .await
+                        {
+                            error!(
+                                "Failed to insert word timings for transcription {}: {}",
+                                transcription_id, e
+                            );
+                        }
+                    }
+                }
             }
         }
         Err(e) => error!(
@@ -153,10 +226,17 @@ pub async fn process_transcription_result(
     Ok(chunk_id)
 }
 
-async fn get_or_create_speaker_from_embedding(
+pub(crate) async fn get_or_create_speaker_from_embedding(
     db: &DatabaseManager,
     embedding: &[f32],
+    enrolled_speaker_id: Option<i64>,
 ) -> Result<Speaker, anyhow::Error> {
+    // Diarization already matched an enrolled speaker, unless it was deleted since
+    if let Some(speaker_id) = enrolled_speaker_id {
+        if let Ok(speaker) = db.get_speaker_by_id(speaker_id).await {
+            return Ok(speaker);
+        }
+    }
     let speaker = db.get_speaker_from_embedding(embedding).await?;
     if let Some(speaker) = speaker {
         Ok(speaker)
@@ -1,17 +1,19 @@
 use super::detect_language;
+use crate::transcription::engine::TranscriptWord;
 use anyhow::Result;
 use screenpipe_core::Language;
 use std::sync::Arc;
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext};
+use tracing::debug;
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperSegment};
 /// Processes audio data using the Whisper model to generate transcriptions.
 ///
 /// # Returns
-/// A string containing the processed transcript
+/// The processed transcript and the timings of its words, in seconds from the start of `audio`
 pub async fn process_with_whisper(
     audio: &[f32],
     languages: Vec<Language>,
     whisper_context: Arc<WhisperContext>,
-) -> Result<String> {
+) -> Result<(String, Vec<TranscriptWord>)> {
     let mut whisper_state = whisper_context
         .create_state()
         .map_err(|e| anyhow::anyhow!("failed to create whisper state: {}", e))?;
@@ -32,8 +34,8 @@ pub async fn process_with_whisper(
     params.set_print_progress(false);
     params.set_print_realtime(false);
     params.set_print_timestamps(false);
-    // Disable token-level timestamps since DTW is disabled for stability.
-    params.set_token_timestamps(false);
+    // Token timestamps come from whisper's timestamp tokens, DTW stays disabled (see model.rs).
+    params.set_token_timestamps(true);
     whisper_state.pcm_to_mel(&audio, 2)?;
     let (_, lang_tokens) = whisper_state.lang_detect(0, 2)?;
     let lang = detect_language(lang_tokens, languages);
@@ -49,15 +51,75 @@ pub async fn process_with_whisper(
     let num_segments = whisper_state.full_n_segments();
 
     let mut transcript = String::new();
+    let mut words = Vec::new();
 
     for i in 0..num_segments {
         // Get the transcribed text and timestamps for the current segment.
         if let Some(segment) = whisper_state.get_segment(i) {
             if let Ok(text) = segment.to_str() {
                 transcript.push_str(text);
+                words.extend(segment_words(&segment, text, &whisper_context));
             }
         }
     }
 
-    Ok(transcript)
+    Ok((transcript, words))
+}
+
+/// Group the tokens of a segment into the words of `text`.
+///
+/// A token starting with a space starts a new word. Text is taken from the segment rather
+/// than the tokens, which can split multi-byte characters.
+fn segment_words(
+    segment: &WhisperSegment,
+    text: &str,
+    whisper_context: &WhisperContext,
+) -> Vec<TranscriptWord> {
+    let segment_start = segment.start_timestamp();
+    let segment_end = segment.end_timestamp().max(segment_start);
+    // (start, end, probabilities) of each word, timestamps in centiseconds
+    let mut timings: Vec<(i64, i64, Vec<f32>)> = Vec::new();
+    for t in 0..segment.n_tokens() {
+        let Some(token) = segment.get_token(t) else {
+            continue;
+        };
+        // Special and timestamp tokens come after the text tokens
+        if token.token_id() >= whisper_context.token_eot() {
+            continue;
+        }
+        let data = token.token_data();
+        let t0 = data.t0.clamp(segment_start, segment_end);
+        let t1 = data.t1.clamp(t0, segment_end);
+        let starts_word = token
+            .to_str_lossy()
+            .map(|s| s.starts_with(' '))
+            .unwrap_or(false);
+        match timings.last_mut() {
+            Some((_, end, probabilities)) if !starts_word => {
+                *end = t1;
+                probabilities.push(token.token_probability());
+            }
+            _ => timings.push((t0, t1, vec![token.token_probability()])),
+        }
+    }
+
+    let texts: Vec<&str> = text.split_whitespace().collect();
+    if texts.len() != timings.len() {
+        debug!(
+            "whisper tokens don't line up with the words of {:?}, skipping word timings",
+            text
+        );
+        return Vec::new();
+    }
+
+    texts
+        .into_iter()
+        .zip(timings)
+        .map(|(text, (start, end, probabilities))| TranscriptWord {
+            text: text.to_string(),
+            start: start as f64 / 100.0,
+            end: end as f64 / 100.0,
+            confidence: Some(probabilities.iter().sum::<f32>() / probabilities.len() as f32),
+        })
+        .collect()
 }
@@ -0,0 +1,313 @@
+//! Local streaming transcription, the offline counterpart of Deepgram's streaming.
+//!
+//! The audio of a device is cut in utterances by voice activity detection. While someone
+//! speaks, the utterance so far is transcribed about every second and sent as a partial
+//! hypothesis. Once they pause, or the utterance reaches the length whisper handles well, it
+//! is transcribed a last time, attributed to a speaker and sent as final. Results are
+//! [`RealtimeTranscriptionEvent`]s, the same events Deepgram's streaming sends.
+
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex as StdMutex,
+};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use screenpipe_core::Language;
+use screenpipe_db::DatabaseManager;
+use screenpipe_events::send_event;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+use vad_rs::VadStatus;
+
+use crate::{
+    core::{device::AudioDevice, device::DeviceType, stream::AudioStream},
+    speaker::{embedding::EmbeddingExtractor, embedding_manager::EmbeddingManager},
+    transcription::{
+        deepgram::streaming::RealtimeTranscriptionEvent, engine::TranscriptionEngine,
+        stt::SAMPLE_RATE,
+    },
+    utils::audio::resample,
+    vad::{create_vad_engine, VadEngineEnum},
+};
+
+/// Audio voice activity is detected on, 100 ms as for recorded chunks
+const STEP_SAMPLES: usize = 1600;
+/// Silence ending an utterance, in steps
+const ENDPOINT_SILENCE_STEPS: usize = 7;
+/// Longest utterance, whisper gets less accurate on longer windows
+const MAX_UTTERANCE_SAMPLES: usize = 15 * SAMPLE_RATE as usize;
+/// Speech an utterance needs to be transcribed, in steps, shorter bursts are noise
+const MIN_SPEECH_STEPS: usize = 3;
+/// Silence kept before an utterance so its first word is not cut, in steps
+const PRE_ROLL_STEPS: usize = 3;
+/// Time between partial hypotheses of an utterance
+const PARTIAL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What a step of audio did to the utterance
+#[derive(Debug, PartialEq, Eq)]
+enum Endpoint {
+    /// No one is speaking
+    Idle,
+    /// The utterance goes on
+    Speaking,
+    /// The utterance ended, after a pause or at the longest length
+    Ended,
+}
+
+/// Cuts a stream of audio steps in utterances.
+#[derive(Default)]
+struct Endpointer {
+    /// Audio of the current utterance
+    samples: Vec<f32>,
+    /// Latest silent steps, while no one speaks
+    pre_roll: VecDeque<Vec<f32>>,
+    speech_steps: usize,
+    silent_steps: usize,
+    in_utterance: bool,
+}
+
+impl Endpointer {
+    fn push(&mut self, step: &[f32], is_speech: bool) -> Endpoint {
+        if !self.in_utterance {
+            if !is_speech {
+                self.pre_roll.push_back(step.to_vec());
+                if self.pre_roll.len() > PRE_ROLL_STEPS {
+                    self.pre_roll.pop_front();
+                }
+                return Endpoint::Idle;
+            }
+            self.in_utterance = true;
+            self.samples = self.pre_roll.drain(..).flatten().collect();
+        }
+
+        self.samples.extend_from_slice(step);
+        if is_speech {
+            self.speech_steps += 1;
+            self.silent_steps = 0;
+        } else {
+            self.silent_steps += 1;
+        }
+        if self.silent_steps >= ENDPOINT_SILENCE_STEPS
+            || self.samples.len() >= MAX_UTTERANCE_SAMPLES
+        {
+            Endpoint::Ended
+        } else {
+            Endpoint::Speaking
+        }
+    }
+
+    /// Whether the current utterance has enough speech to be transcribed
+    fn has_speech(&self) -> bool {
+        self.in_utterance && self.speech_steps >= MIN_SPEECH_STEPS
+    }
+
+    /// Take the audio of the utterance, `None` if it had too little speech.
+    fn take(&mut self) -> Option<Vec<f32>> {
+        let has_speech = self.has_speech();
+        let samples = std::mem::take(&mut self.samples);
+        self.speech_steps = 0;
+        self.silent_steps = 0;
+        self.in_utterance = false;
+        has_speech.then_some(samples)
+    }
+}
+
+/// Transcribes device streams locally as they are recorded.
+#[derive(Clone)]
+pub struct StreamingTranscriber {
+    pub engine: Arc<dyn TranscriptionEngine>,
+    pub vad_engine: VadEngineEnum,
+    pub languages: Vec<Language>,
+    pub embedding_manager: Arc<StdMutex<EmbeddingManager>>,
+    pub embedding_extractor: Arc<StdMutex<EmbeddingExtractor>>,
+    pub db: Arc<DatabaseManager>,
+}
+
+impl StreamingTranscriber {
+    /// Transcribe `stream` until `is_running` is unset, sending partial and final results as
+    /// `transcription` events.
+    pub async fn stream(
+        &self,
+        stream: Arc<AudioStream>,
+        is_running: Arc<AtomicBool>,
+    ) -> Result<()> {
+        let device = stream.device.clone();
+        let sample_rate = stream.device_config.sample_rate().0;
+        let mut receiver = stream.subscribe().await;
+        drop(stream);
+
+        // Each stream has its own VAD, whose state follows the audio
+        let mut vad = create_vad_engine(self.vad_engine.clone()).await?;
+        // Resampling goes by half seconds, endpointing by steps
+        let block_len = (sample_rate / 2) as usize;
+        let mut pending = Vec::new();
+        let mut resampled = Vec::new();
+        let mut endpointer = Endpointer::default();
+        let mut last_partial = Instant::now();
+        let mut partial_text = String::new();
+
+        info!("starting local streaming transcription for {}", device);
+        while is_running.load(Ordering::Relaxed) {
+            match tokio::time::timeout(Duration::from_secs(1), receiver.recv()).await {
+                Ok(Ok(chunk)) => pending.extend(chunk),
+                Ok(Err(RecvError::Lagged(n))) => {
+                    debug!("local streaming for {} lagged by {} chunks", device, n);
+                    continue;
+                }
+                Ok(Err(RecvError::Closed)) => break,
+                Err(_) => continue,
+            }
+            if pending.len() < block_len {
+                continue;
+            }
+
+            let block = std::mem::take(&mut pending);
+            if sample_rate != SAMPLE_RATE {
+                resampled.extend(resample(&block, sample_rate, SAMPLE_RATE)?);
+            } else {
+                resampled.extend(block);
+            }
+
+            let full_steps = resampled.len() / STEP_SAMPLES * STEP_SAMPLES;
+            let steps: Vec<f32> = resampled.drain(..full_steps).collect();
+            for step in steps.chunks_exact(STEP_SAMPLES) {
+                let is_speech = matches!(vad.audio_type(step), Ok(VadStatus::Speech));
+                if endpointer.push(step, is_speech) == Endpoint::Ended {
+                    if let Some(utterance) = endpointer.take() {
+                        self.send_final(&device, &utterance).await;
+                    }
+                    partial_text.clear();
+                    last_partial = Instant::now();
+                }
+            }
+
+            if endpointer.has_speech() && last_partial.elapsed() >= PARTIAL_INTERVAL {
+                last_partial = Instant::now();
+                if let Some(text) = self.transcribe(&device, &endpointer.samples).await {
+                    if text != partial_text {
+                        send_transcription(&device, &text, false, None);
+                        partial_text = text;
+                    }
+                }
+            }
+        }
+
+        // What was said until the stream stopped
+        if let Some(utterance) = endpointer.take() {
+            self.send_final(&device, &utterance).await;
+        }
+        info!("stopped local streaming transcription for {}", device);
+        Ok(())
+    }
+
+    async fn send_final(&self, device: &AudioDevice, utterance: &[f32]) {
+        if let Some(text) = self.transcribe(device, utterance).await {
+            let speaker = self.speaker_label(utterance).await;
+            send_transcription(device, &text, true, speaker);
+        }
+    }
+
+    async fn transcribe(&self, device: &AudioDevice, samples: &[f32]) -> Option<String> {
+        match self
+            .engine
+            .transcribe(samples, SAMPLE_RATE, &device.to_string(), &self.languages)
+            .await
+        {
+            Ok(transcript) => Some(transcript.text.trim().to_string()).filter(|t| !t.is_empty()),
+            Err(e) => {
+                warn!("local streaming transcription failed for {}: {}", device, e);
+                None
+            }
+        }
+    }
+
+    /// Name of the known speaker of `samples`, or their id if they have no name.
+    async fn speaker_label(&self, samples: &[f32]) -> Option<String> {
+        let embedding: Vec<f32> = self
+            .embedding_extractor
+            .lock()
+            .ok()?
+            .compute(samples)
+            .ok()?
+            .collect();
+        let enrolled_speaker_id = self
+            .embedding_manager
+            .lock()
+            .ok()?
+            .search_enrolled_speaker(&embedding);
+
+        let speaker = match enrolled_speaker_id {
+            Some(speaker_id) => self.db.get_speaker_by_id(speaker_id).await.ok(),
+            None => self
+                .db
+                .get_speaker_from_embedding(&embedding)
+                .await
+                .ok()
+                .flatten(),
+        }?;
+        Some(if speaker.name.is_empty() {
+            speaker.id.to_string()
+        } else {
+            speaker.name
+        })
+    }
+}
+
+fn send_transcription(device: &AudioDevice, text: &str, is_final: bool, speaker: Option<String>) {
+    let _ = send_event(
+        "transcription",
+        RealtimeTranscriptionEvent {
+            timestamp: chrono::Utc::now(),
+            device: device.to_string(),
+            transcription: text.to_string(),
+            is_final,
+            is_input: device.device_type == DeviceType::Input,
+            speaker,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_steps(endpointer: &mut Endpointer, steps: usize, is_speech: bool) -> Vec<Endpoint> {
+        (0..steps)
+            .map(|_| endpointer.push(&[0.0; STEP_SAMPLES], is_speech))
+            .collect()
+    }
+
+    #[test]
+    fn test_endpointer_ends_utterance_after_pause() {
+        let mut endpointer = Endpointer::default();
+        assert!(push_steps(&mut endpointer, 5, false)
+            .iter()
+            .all(|e| *e == Endpoint::Idle));
+        assert!(push_steps(&mut endpointer, 10, true)
+            .iter()
+            .all(|e| *e == Endpoint::Speaking));
+
+        let endpoints = push_steps(&mut endpointer, ENDPOINT_SILENCE_STEPS, false);
+        assert_eq!(endpoints.last(), Some(&Endpoint::Ended));
+        let utterance = endpointer.take().unwrap();
+        assert_eq!(
+            utterance.len(),
+            (PRE_ROLL_STEPS + 10 + ENDPOINT_SILENCE_STEPS) * STEP_SAMPLES
+        );
+        assert_eq!(push_steps(&mut endpointer, 1, false), vec![Endpoint::Idle]);
+    }
+
+    #[test]
+    fn test_endpointer_drops_short_bursts_and_caps_length() {
+        let mut endpointer = Endpointer::default();
+        push_steps(&mut endpointer, 1, true);
+        push_steps(&mut endpointer, ENDPOINT_SILENCE_STEPS, false);
+        assert_eq!(endpointer.take(), None);
+
+        let endpoints = push_steps(&mut endpointer, MAX_UTTERANCE_SAMPLES / STEP_SAMPLES, true);
+        assert_eq!(endpoints.last(), Some(&Endpoint::Ended));
+        assert_eq!(endpointer.take().unwrap().len(), MAX_UTTERANCE_SAMPLES);
+    }
+}
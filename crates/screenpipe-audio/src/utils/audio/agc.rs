@@ -0,0 +1,38 @@
+/// Evens out the loudness of `audio`, raising quiet speech and lowering loud speech toward a
+/// target level.
+///
+/// The gain follows the level of 10 ms blocks, falling quickly on loud sounds and rising
+/// slowly, and is held through silence so background noise is not amplified.
+pub fn automatic_gain_control(audio: &[f32], sample_rate: u32) -> Vec<f32> {
+    const TARGET_RMS: f32 = 0.1;
+    const MIN_GAIN: f32 = 0.1;
+    const MAX_GAIN: f32 = 10.0;
+    /// Level below which a block is silence
+    const GATE_RMS: f32 = 0.002;
+    const ATTACK_SECS: f32 = 0.05;
+    const RELEASE_SECS: f32 = 1.0;
+
+    let block_len = (sample_rate as usize / 100).max(1);
+    let block_secs = block_len as f32 / sample_rate as f32;
+    let attack = (-block_secs / ATTACK_SECS).exp();
+    let release = (-block_secs / RELEASE_SECS).exp();
+
+    let mut gain = 1.0f32;
+    let mut output = Vec::with_capacity(audio.len());
+    for block in audio.chunks(block_len) {
+        let rms = (block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32).sqrt();
+        let previous_gain = gain;
+        if rms > GATE_RMS {
+            let desired = (TARGET_RMS / rms).clamp(MIN_GAIN, MAX_GAIN);
+            let smoothing = if desired < gain { attack } else { release };
+            gain = desired + (gain - desired) * smoothing;
+        }
+
+        // Ramp the gain over the block to avoid clicks
+        let step = (gain - previous_gain) / block.len() as f32;
+        output.extend(block.iter().enumerate().map(|(i, &sample)| {
+            (sample * (previous_gain + step * (i + 1) as f32)).clamp(-1.0, 1.0)
+        }));
+    }
+    output
+}
@@ -0,0 +1,28 @@
+use std::f32::consts::PI;
+
+/// Removes rumble, hum and DC offset below `cutoff_hz` with a second-order Butterworth
+/// high-pass filter.
+pub fn high_pass_filter(audio: &[f32], sample_rate: u32, cutoff_hz: f32) -> Vec<f32> {
+    let w0 = 2.0 * PI * cutoff_hz / sample_rate as f32;
+    let (sin, cos) = w0.sin_cos();
+    let alpha = sin / (2.0 * std::f32::consts::FRAC_1_SQRT_2);
+
+    let a0 = 1.0 + alpha;
+    let b0 = (1.0 + cos) / 2.0 / a0;
+    let b1 = -(1.0 + cos) / a0;
+    let b2 = b0;
+    let a1 = -2.0 * cos / a0;
+    let a2 = (1.0 - alpha) / a0;
+
+    // Transposed direct form II
+    let (mut z1, mut z2) = (0.0f32, 0.0f32);
+    audio
+        .iter()
+        .map(|&x| {
+            let y = b0 * x + z1;
+            z1 = b1 * x - a1 * y + z2;
+            z2 = b2 * x - a2 * y;
+            y
+        })
+        .collect()
+}
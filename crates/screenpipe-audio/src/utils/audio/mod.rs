@@ -1,11 +1,15 @@
+mod agc;
 mod convert;
+mod high_pass;
 mod normalization;
 mod pcm_decode;
 mod resample;
 mod spectral_subtraction;
 
+pub use agc::automatic_gain_control;
 pub use convert::audio_to_mono;
+pub use high_pass::high_pass_filter;
 pub use normalization::normalize_v2;
 pub use pcm_decode::pcm_decode;
 pub use resample::resample;
-pub use spectral_subtraction::{average_noise_spectrum, spectral_subtraction};
+pub use spectral_subtraction::{average_noise_spectrum, spectral_denoise, spectral_subtraction};
@@ -1,3 +1,5 @@
+use std::f32::consts::PI;
+
 use anyhow::Result;
 use realfft::num_complex::{Complex32, ComplexFloat};
 use realfft::RealFftPlanner;
@@ -58,3 +60,91 @@ pub fn average_noise_spectrum(audio: &[f32]) -> f32 {
 
     total_sum / audio.len() as f32
 }
+
+/// Reduces stationary background noise (fans, hum, hiss) in `audio`.
+///
+/// The noise spectrum is estimated from the quietest frames, and subtracted from every frame
+/// of a short-time Fourier transform with 50% overlapping Hann windows.
+pub fn spectral_denoise(audio: &[f32]) -> Result<Vec<f32>> {
+    const FRAME: usize = 512;
+    const HOP: usize = FRAME / 2;
+    /// Share of the quietest frames the noise is estimated from
+    const NOISE_QUANTILE: f32 = 0.1;
+    /// Noise is over-subtracted a bit, leaving less "musical" residue
+    const OVER_SUBTRACTION: f32 = 1.5;
+    /// Lowest gain of a frequency, keeping some of the noise sounds more natural
+    const FLOOR: f32 = 0.05;
+
+    if audio.len() < FRAME {
+        return Ok(audio.to_vec());
+    }
+
+    // Padding by a hop on both sides covers every sample with two windows
+    let frame_count = (audio.len() + HOP).div_ceil(HOP);
+    let mut padded = vec![0.0f32; HOP];
+    padded.extend_from_slice(audio);
+    padded.resize((frame_count - 1) * HOP + FRAME, 0.0);
+
+    // Periodic Hann windows at 50% overlap add up to one
+    let window: Vec<f32> = (0..FRAME)
+        .map(|i| (PI * i as f32 / FRAME as f32).sin().powi(2))
+        .collect();
+
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(FRAME);
+    let inverse = planner.plan_fft_inverse(FRAME);
+
+    let mut spectra = Vec::with_capacity(frame_count);
+    for start in (0..frame_count).map(|frame| frame * HOP) {
+        let mut frame: Vec<f32> = padded[start..start + FRAME]
+            .iter()
+            .zip(&window)
+            .map(|(sample, weight)| sample * weight)
+            .collect();
+        let mut spectrum = forward.make_output_vec();
+        forward.process(&mut frame, &mut spectrum)?;
+        spectra.push(spectrum);
+    }
+
+    let mut by_energy: Vec<(f32, usize)> = spectra
+        .iter()
+        .enumerate()
+        .map(|(index, spectrum)| (spectrum.iter().map(|x| x.norm_sqr()).sum(), index))
+        .collect();
+    by_energy.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let noise_frames = ((frame_count as f32 * NOISE_QUANTILE) as usize).max(1);
+    let mut noise = vec![0.0f32; FRAME / 2 + 1];
+    for &(_, index) in &by_energy[..noise_frames] {
+        for (power, x) in noise.iter_mut().zip(&spectra[index]) {
+            *power += x.norm_sqr() / noise_frames as f32;
+        }
+    }
+
+    let mut output = vec![0.0f32; padded.len()];
+    for (frame, spectrum) in spectra.iter_mut().enumerate() {
+        for (x, noise_power) in spectrum.iter_mut().zip(&noise) {
+            let power = x.norm_sqr();
+            let gain = if power > 0.0 {
+                (1.0 - OVER_SUBTRACTION * noise_power / power)
+                    .max(FLOOR * FLOOR)
+                    .sqrt()
+            } else {
+                FLOOR
+            };
+            *x *= gain;
+        }
+        // The DC and Nyquist bins of a real signal have no imaginary part
+        spectrum[0].im = 0.0;
+        spectrum[FRAME / 2].im = 0.0;
+
+        let mut samples = inverse.make_output_vec();
+        inverse.process(spectrum, &mut samples)?;
+        let start = frame * HOP;
+        for (out, sample) in output[start..start + FRAME].iter_mut().zip(samples) {
+            // The inverse transform is not normalized
+            *out += sample / FRAME as f32;
+        }
+    }
+
+    Ok(output[HOP..HOP + audio.len()].to_vec())
+}
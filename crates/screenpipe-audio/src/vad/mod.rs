@@ -3,6 +3,7 @@ pub mod webrtc;
 
 use anyhow;
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 use silero::SileroVad;
 use std::path::PathBuf;
 use std::sync::Once;
@@ -10,7 +11,8 @@ use tokio::sync::Mutex;
 use vad_rs::VadStatus;
 use webrtc::WebRtcVad;
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum VadSensitivity {
     Low,
     #[default]
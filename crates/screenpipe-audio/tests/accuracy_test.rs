@@ -102,6 +102,7 @@ async fn test_transcription_accuracy() {
                 sample_rate: 44100, // hardcoded based on test data sample rate
                 channels: 1,
                 device: Arc::new(default_input_device().unwrap()),
+                captured_at: None,
             };
 
             let audio_data = if audio_input.sample_rate != SAMPLE_RATE {
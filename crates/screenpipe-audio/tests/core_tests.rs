@@ -234,6 +234,7 @@ mod tests {
             sample_rate: 44100, // hardcoded based on test data sample rate
             channels: 1,
             device: Arc::new(default_input_device().unwrap()),
+            captured_at: None,
         };
 
         // Create the missing parameters
@@ -317,6 +318,7 @@ mod tests {
             sample_rate: 16000, // Adjust this based on your test audio
             channels: 1,
             device: Arc::new(default_output_device().await.unwrap()),
+            captured_at: None,
         };
 
         let project_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use screenpipe_audio::audio_manager::{AudioManagerOptions, DeviceAudioSettings};
+use screenpipe_audio::core::engine::AudioTranscriptionEngine;
+use screenpipe_audio::preprocessing::{PreprocessingChain, PreprocessingStage};
+use screenpipe_audio::vad::VadSensitivity;
+use screenpipe_core::Language;
+
+const MIC: &str = "MacBook Pro Microphone (input)";
+const SPEAKERS: &str = "Display 1 (output)";
+
+fn speaker_settings() -> DeviceAudioSettings {
+    DeviceAudioSettings {
+        languages: Some(vec![Language::English, Language::French]),
+        vad_sensitivity: Some(VadSensitivity::Low),
+        transcription_engine: Some(AudioTranscriptionEngine::WhisperLargeV3),
+        enable_diarization: Some(true),
+        audio_chunk_duration_secs: Some(15),
+        preprocessing: None,
+    }
+}
+
+#[test]
+fn test_settings_are_stored_with_command_line_names() {
+    let json = serde_json::to_string(&speaker_settings()).unwrap();
+    assert_eq!(
+        json,
+        r#"{"languages":["english","french"],"vad_sensitivity":"low","transcription_engine":"whisper-large","enable_diarization":true,"audio_chunk_duration_secs":15}"#
+    );
+    let settings: DeviceAudioSettings = serde_json::from_str(&json).unwrap();
+    assert_eq!(settings, speaker_settings());
+
+    // Unset settings are left out
+    assert_eq!(
+        serde_json::to_string(&DeviceAudioSettings::default()).unwrap(),
+        "{}"
+    );
+    assert!(serde_json::from_str::<DeviceAudioSettings>("{}")
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_device_settings_override_global_ones() {
+    let mut options = AudioManagerOptions {
+        languages: vec![Language::English],
+        vad_sensitivity: VadSensitivity::High,
+        enable_diarization: false,
+        ..Default::default()
+    };
+    options
+        .device_settings
+        .insert(SPEAKERS.to_string(), speaker_settings());
+    options.device_settings.insert(
+        MIC.to_string(),
+        DeviceAudioSettings {
+            enable_diarization: Some(true),
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(
+        options.languages_for(SPEAKERS),
+        vec![Language::English, Language::French]
+    );
+    assert_eq!(options.vad_sensitivity_for(SPEAKERS), VadSensitivity::Low);
+    assert_eq!(
+        *options.transcription_engine_for(SPEAKERS),
+        AudioTranscriptionEngine::WhisperLargeV3
+    );
+    assert_eq!(
+        options.audio_chunk_duration_for(SPEAKERS),
+        Duration::from_secs(15)
+    );
+
+    // Unset settings fall back to the global ones
+    assert_eq!(options.languages_for(MIC), vec![Language::English]);
+    assert_eq!(options.vad_sensitivity_for(MIC), VadSensitivity::High);
+    assert_eq!(
+        options.transcription_engine_for(MIC),
+        options.transcription_engine
+    );
+    assert_eq!(
+        options.audio_chunk_duration_for(MIC),
+        options.audio_chunk_duration
+    );
+    assert!(options.diarization_for(MIC));
+    assert!(!options.diarization_for("Other (input)"));
+}
+
+#[test]
+fn test_set_from_command_line_names() {
+    let mut settings = DeviceAudioSettings::default();
+    for (setting, value) in [
+        ("language", "english"),
+        ("language", "French"),
+        ("vad", "LOW"),
+        ("engine", "whisper-large"),
+        ("diarization", "on"),
+        ("chunk", "15"),
+    ] {
+        settings.set(setting, value).unwrap();
+    }
+    assert_eq!(settings, speaker_settings());
+
+    assert!(settings.set("vad", "loud").is_err());
+    assert!(settings.set("engine", "whisper-huge").is_err());
+    assert!(settings.set("chunk", "0").is_err());
+    assert!(settings.set("volume", "11").is_err());
+    assert_eq!(settings, speaker_settings());
+}
+
+#[test]
+fn test_merge_keeps_unset_settings() {
+    let mut settings = speaker_settings();
+    settings.merge(DeviceAudioSettings {
+        vad_sensitivity: Some(VadSensitivity::Medium),
+        enable_diarization: Some(false),
+        ..Default::default()
+    });
+    assert_eq!(settings.vad_sensitivity, Some(VadSensitivity::Medium));
+    assert_eq!(settings.enable_diarization, Some(false));
+    assert_eq!(settings.audio_chunk_duration_secs, Some(15));
+    assert_eq!(
+        settings.languages,
+        Some(vec![Language::English, Language::French])
+    );
+}
+
+#[test]
+fn test_validate_device_settings() {
+    let options = AudioManagerOptions {
+        deepgram_api_key: None,
+        ..Default::default()
+    };
+    assert!(options
+        .validate_device_settings(&speaker_settings())
+        .is_ok());
+    assert!(options
+        .validate_device_settings(&DeviceAudioSettings {
+            audio_chunk_duration_secs: Some(0),
+            ..Default::default()
+        })
+        .is_err());
+    assert!(options
+        .validate_device_settings(&DeviceAudioSettings {
+            transcription_engine: Some(AudioTranscriptionEngine::OpenAiCompatible),
+            ..Default::default()
+        })
+        .is_err());
+}
+
+#[test]
+fn test_device_preprocessing_overrides_global_chain() {
+    let mut options = AudioManagerOptions {
+        audio_preprocessing: PreprocessingChain::new(vec![PreprocessingStage::HighPass]),
+        ..Default::default()
+    };
+    let mut settings = DeviceAudioSettings::default();
+    settings.set("preprocessing", "denoise+agc").unwrap();
+    assert_eq!(
+        settings.preprocessing,
+        Some(PreprocessingChain::new(vec![
+            PreprocessingStage::Denoise,
+            PreprocessingStage::Agc
+        ]))
+    );
+    assert!(settings.set("preprocessing", "reverb").is_err());
+    options.device_settings.insert(MIC.to_string(), settings);
+
+    assert_eq!(
+        options.preprocessing_for(MIC).stages,
+        vec![PreprocessingStage::Denoise, PreprocessingStage::Agc]
+    );
+    assert_eq!(
+        options.preprocessing_for(SPEAKERS).stages,
+        vec![PreprocessingStage::HighPass]
+    );
+
+    // "none" turns preprocessing off for the device only
+    let mut settings = DeviceAudioSettings::default();
+    settings.set("preprocessing", "none").unwrap();
+    options
+        .device_settings
+        .insert(SPEAKERS.to_string(), settings);
+    assert!(options.preprocessing_for(SPEAKERS).is_empty());
+}
@@ -0,0 +1,95 @@
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+use screenpipe_audio::preprocessing::{PreprocessingChain, PreprocessingStage};
+
+const RATE: u32 = 16000;
+
+fn tone(secs: f32, frequency: f32, amplitude: f32) -> Vec<f32> {
+    (0..(secs * RATE as f32) as usize)
+        .map(|i| amplitude * (2.0 * PI * frequency * i as f32 / RATE as f32).sin())
+        .collect()
+}
+
+fn white_noise(secs: f32, amplitude: f32) -> Vec<f32> {
+    let mut state = 1u32;
+    (0..(secs * RATE as f32) as usize)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((state >> 16) as f32 / 32768.0 - 1.0) * amplitude
+        })
+        .collect()
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+fn chain(stages: &[PreprocessingStage]) -> PreprocessingChain {
+    PreprocessingChain::new(stages.to_vec())
+}
+
+#[test]
+fn test_parse_chain() {
+    let parsed = PreprocessingChain::from_str("high-pass, denoise,agc").unwrap();
+    assert_eq!(
+        parsed.stages,
+        vec![
+            PreprocessingStage::HighPass,
+            PreprocessingStage::Denoise,
+            PreprocessingStage::Agc
+        ]
+    );
+    assert_eq!(parsed.to_string(), "high-pass,denoise,agc");
+    assert!(PreprocessingChain::from_str("none").unwrap().is_empty());
+    assert!(PreprocessingChain::from_str("denoise,reverb").is_err());
+}
+
+#[test]
+fn test_empty_chain_keeps_audio() {
+    let audio = tone(0.5, 440.0, 0.3);
+    assert_eq!(PreprocessingChain::default().process(&audio, RATE), audio);
+}
+
+#[test]
+fn test_high_pass_removes_hum_and_offset() {
+    let voice = tone(1.0, 1000.0, 0.3);
+    let hum = tone(1.0, 30.0, 0.3);
+    let audio: Vec<f32> = voice.iter().zip(&hum).map(|(v, h)| v + h + 0.2).collect();
+
+    let filtered = chain(&[PreprocessingStage::HighPass]).process(&audio, RATE);
+    // Past the filter settling, only the voice is left
+    let settled = RATE as usize / 2..;
+    assert!((rms(&filtered[settled.clone()]) - rms(&voice[settled])).abs() < 0.01);
+}
+
+#[test]
+fn test_denoise_reduces_background_noise() {
+    let mut audio = white_noise(2.0, 0.05);
+    let voice = tone(1.0, 440.0, 0.3);
+    let half = RATE as usize;
+    for (sample, v) in audio[half..].iter_mut().zip(&voice) {
+        *sample += v;
+    }
+
+    let denoised = chain(&[PreprocessingStage::Denoise]).process(&audio, RATE);
+    assert_eq!(denoised.len(), audio.len());
+    assert!(rms(&denoised[..half]) < rms(&audio[..half]) * 0.7);
+    assert!(rms(&denoised[half..]) > rms(&audio[half..]) * 0.9);
+}
+
+#[test]
+fn test_agc_evens_out_loudness() {
+    let agc = chain(&[PreprocessingStage::Agc]);
+    let settled = 2 * RATE as usize..;
+
+    let quiet = tone(3.0, 300.0, 0.01);
+    assert!(rms(&agc.process(&quiet, RATE)[settled.clone()]) > rms(&quiet) * 5.0);
+
+    let loud = tone(3.0, 300.0, 0.9);
+    assert!(rms(&agc.process(&loud, RATE)[settled]) < rms(&loud) * 0.5);
+
+    // Silence is not amplified into noise
+    let silence = white_noise(1.0, 0.001);
+    assert!(rms(&agc.process(&silence, RATE)) <= rms(&silence) * 1.01);
+}
@@ -0,0 +1,102 @@
+//! Word error rate of whisper on `test_data` with each preprocessing stage.
+//!
+//! Run with `cargo test -p screenpipe-audio --test preprocessing_wer_benchmark -- --ignored --nocapture`.
+
+use std::path::PathBuf;
+
+use screenpipe_audio::core::engine::AudioTranscriptionEngine;
+use screenpipe_audio::preprocessing::{PreprocessingChain, PreprocessingStage};
+use screenpipe_audio::transcription::engine::{TranscriptionEngine, WhisperEngine};
+use screenpipe_audio::transcription::stt::SAMPLE_RATE;
+use screenpipe_audio::{pcm_decode, resample};
+use screenpipe_core::Language;
+
+const TEST_CASES: [(&str, &str); 5] = [
+    (
+        "test_data/accuracy1.wav",
+        r#"yo louis, here's the tldr of that mind-blowing meeting. bob's cat walked across his keyboard 3 times. productivity increased by 200%. sarah's virtual background glitched, revealing she was actually on a beach. no one noticed. you successfully pretended to be engaged while scrolling twitter. achievement unlocked! 7 people said "you're on mute" in perfect synchronization. new world record. meeting could've been an email. shocking. key takeaway: we're all living in a simulation, and the devs are laughing. peace out, llama3.2:3b-instruct-q4_k_m"#,
+    ),
+    (
+        "test_data/accuracy2.wav",
+        r#"bro - got some good stuff from screenpipe here's the lowdown on your day, you productivity ninja: absolutely demolished that 2-hour coding sesh on the new feature. the keyboard is still smoking, bro! crushed 3 client calls like a boss. they're probably writing love letters to you as we speak, make sure to close john tomorrow 8.00 am according to our notes, let the cash flow in! spent 45 mins on slack. 90% memes, 10% actual work. perfectly balanced, as all things should bewatched a rust tutorial. way to flex those brain muscles, you nerd! overall, you're killing it! 80% of your time on high-value tasks. the other 20%? probably spent admiring your own reflection, you handsome devil. ps: seriously, quit tiktok. your fbi agent is getting bored watching you scroll endlessly. what's the plan for tomorrow? more coding? more memes? world domination? generated by your screenpipe ai assistant (who's definitely not planning to take over the world... yet)"#,
+    ),
+    (
+        "test_data/accuracy3.wav",
+        "again, screenpipe allows you to get meeting summaries, locally, without leaking data to openai, with any apps, like whatsapp, meet, zoom, etc. and it's open source at github.com/screenpipe/screenpipe",
+    ),
+    (
+        "test_data/accuracy4.wav",
+        "eventually but, i mean, i feel like but, i mean, first, i mean, you think your your vision smart will be interesting because, yeah, you install once. you pay us, you install once. that that yours. so, basically, all the time microsoft explained, you know, ms office, long time ago, you just buy the the the software that you can using there forever unless you wanna you wanna update upgrade is the better version. right? so it's a little bit, you know",
+    ),
+    (
+        "test_data/accuracy5.wav",
+        "thank you. yeah. so i cannot they they took it, refresh because of my one set top top time. and, also, second thing is, your byte was stolen. by the time?",
+    ),
+];
+
+/// Lowercase words without punctuation
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '\'')
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+fn word_error_rate(expected: &str, actual: &str) -> f64 {
+    let expected = words(expected);
+    let actual = words(actual);
+    let distance = strsim::generic_levenshtein(&expected, &actual);
+    distance as f64 / expected.len().max(1) as f64
+}
+
+#[tokio::test]
+#[ignore]
+async fn benchmark_preprocessing_wer() {
+    let engine = WhisperEngine::load(AudioTranscriptionEngine::WhisperTinyQuantized)
+        .expect("failed to load whisper");
+    let project_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let mut configs = vec![("none".to_string(), PreprocessingChain::default())];
+    for stage in PreprocessingStage::ALL {
+        configs.push((stage.to_string(), PreprocessingChain::new(vec![stage])));
+    }
+    let all = PreprocessingChain::new(PreprocessingStage::ALL.to_vec());
+    configs.push((all.to_string(), all));
+
+    let mut samples = Vec::new();
+    for (file, expected) in TEST_CASES {
+        let (audio, sample_rate) = pcm_decode(project_dir.join(file)).expect("failed to decode");
+        let audio = resample(&audio, sample_rate, SAMPLE_RATE).expect("failed to resample");
+        samples.push((file, expected, audio));
+    }
+
+    println!("{:<36} {:>8}", "preprocessing", "WER");
+    for (name, chain) in &configs {
+        let mut total = 0.0;
+        for (file, expected, audio) in &samples {
+            let processed = chain.process(audio, SAMPLE_RATE);
+            let mut transcription = String::new();
+            for window in processed.chunks(30 * SAMPLE_RATE as usize) {
+                let transcript = engine
+                    .transcribe(window, SAMPLE_RATE, "benchmark", &[Language::English])
+                    .await
+                    .expect("transcription failed");
+                transcription.push_str(&transcript.text);
+                transcription.push(' ');
+            }
+            let wer = word_error_rate(expected, &transcription);
+            println!("  {:<34} {:>7.1}%", file, wer * 100.0);
+            total += wer;
+        }
+        println!(
+            "{:<36} {:>7.1}%",
+            name,
+            total / samples.len() as f64 * 100.0
+        );
+    }
+}
@@ -0,0 +1,244 @@
+/// Transcription Engine Tests
+///
+/// Tests fallback chains and the OpenAI-compatible backend against a local stub server.
+///
+/// Run with: cargo test --package screenpipe-audio --test transcription_engine_test -- --nocapture
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use screenpipe_audio::core::engine::AudioTranscriptionEngine;
+    use screenpipe_audio::transcription::engine::{
+        transcription_chain, FallbackEngine, OpenAiCompatibleConfig, OpenAiCompatibleEngine,
+        Transcript, TranscriptWord, TranscriptionEngine,
+    };
+    use screenpipe_core::Language;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    struct StubEngine {
+        name: &'static str,
+        fail: bool,
+        calls: AtomicUsize,
+    }
+
+    impl StubEngine {
+        fn new(name: &'static str, fail: bool) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                fail,
+                calls: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl TranscriptionEngine for StubEngine {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+
+        async fn transcribe(
+            &self,
+            _audio: &[f32],
+            _sample_rate: u32,
+            _device: &str,
+            _languages: &[Language],
+        ) -> Result<Transcript> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.fail {
+                anyhow::bail!("{} is down", self.name);
+            }
+            Ok(Transcript {
+                text: format!("hello from {}", self.name),
+                engine: self.name(),
+                words: Vec::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_transcription_chain() {
+        // Deepgram keeps falling back to the local model by default
+        assert_eq!(
+            transcription_chain(&AudioTranscriptionEngine::Deepgram, None),
+            vec![
+                AudioTranscriptionEngine::Deepgram,
+                AudioTranscriptionEngine::WhisperLargeV3TurboQuantized
+            ]
+        );
+        assert_eq!(
+            transcription_chain(&AudioTranscriptionEngine::WhisperTiny, None),
+            vec![AudioTranscriptionEngine::WhisperTiny]
+        );
+
+        // Configured fallbacks replace the default ones, without repeating engines
+        assert_eq!(
+            transcription_chain(
+                &AudioTranscriptionEngine::OpenAiCompatible,
+                Some(&[
+                    AudioTranscriptionEngine::Deepgram,
+                    AudioTranscriptionEngine::OpenAiCompatible,
+                    AudioTranscriptionEngine::WhisperTiny,
+                ])
+            ),
+            vec![
+                AudioTranscriptionEngine::OpenAiCompatible,
+                AudioTranscriptionEngine::Deepgram,
+                AudioTranscriptionEngine::WhisperTiny
+            ]
+        );
+        assert_eq!(
+            transcription_chain(&AudioTranscriptionEngine::Deepgram, Some(&[])),
+            vec![AudioTranscriptionEngine::Deepgram]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fallback_engine() {
+        let first = StubEngine::new("first", true);
+        let second = StubEngine::new("second", false);
+        let third = StubEngine::new("third", false);
+        let engine =
+            FallbackEngine::new(vec![first.clone(), second.clone(), third.clone()]).unwrap();
+
+        assert_eq!(engine.name(), "first");
+        let transcript = engine
+            .transcribe(&[0.0; 16], 16000, "mic", &[])
+            .await
+            .unwrap();
+        assert_eq!(transcript.text, "hello from second");
+        // The stored engine is the one that produced the text
+        assert_eq!(transcript.engine, "second");
+        assert_eq!(first.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(second.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(third.calls.load(Ordering::SeqCst), 0);
+
+        let failing = FallbackEngine::new(vec![
+            StubEngine::new("first", true),
+            StubEngine::new("second", true),
+        ])
+        .unwrap();
+        let error = failing
+            .transcribe(&[0.0; 16], 16000, "mic", &[])
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "second is down");
+
+        assert!(FallbackEngine::new(vec![]).is_err());
+    }
+
+    /// Answer a single request with `body`, returning the raw request.
+    async fn serve_once(listener: TcpListener, status: &'static str, body: &'static str) -> String {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some(header_end) = text.find("\r\n\r\n") {
+                let content_length = text[..header_end]
+                    .lines()
+                    .find_map(|l| l.strip_prefix("content-length: "))
+                    .and_then(|l| l.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                if request.len() >= header_end + 4 + content_length {
+                    break;
+                }
+            }
+            if n == 0 {
+                break;
+            }
+        }
+        let response = format!(
+            "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8_lossy(&request).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_openai_compatible_engine() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(serve_once(
+            listener,
+            "200 OK",
+            r#"{"text": " hello world ", "words": [
+                {"word": " hello", "start": 0.1, "end": 0.4},
+                {"word": " world", "start": 0.5, "end": 0.9, "probability": 0.8}
+            ]}"#,
+        ));
+
+        let engine = OpenAiCompatibleEngine::new(OpenAiCompatibleConfig {
+            url: format!("http://127.0.0.1:{}/v1/", port),
+            model: "large-v3".to_string(),
+            api_key: Some("secret".to_string()),
+        })
+        .unwrap();
+        let transcript = engine
+            .transcribe(&[0.0; 1600], 16000, "mic", &[Language::English])
+            .await
+            .unwrap();
+        assert_eq!(transcript.text, "hello world");
+        assert_eq!(transcript.engine, "OpenAiCompatible");
+        assert_eq!(
+            transcript.words,
+            vec![
+                TranscriptWord {
+                    text: "hello".to_string(),
+                    start: 0.1,
+                    end: 0.4,
+                    confidence: None
+                },
+                TranscriptWord {
+                    text: "world".to_string(),
+                    start: 0.5,
+                    end: 0.9,
+                    confidence: Some(0.8)
+                }
+            ]
+        );
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /v1/audio/transcriptions "));
+        assert!(request.contains("authorization: Bearer secret"));
+        assert!(request.contains("large-v3"));
+        assert!(request.contains("verbose_json"));
+        assert!(request.contains("name=\"language\"\r\n\r\nen"));
+        assert!(request.contains("RIFF"));
+    }
+
+    #[tokio::test]
+    async fn test_openai_compatible_engine_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(serve_once(
+            listener,
+            "503 Service Unavailable",
+            r#"{"error": "model loading"}"#,
+        ));
+
+        let engine = OpenAiCompatibleEngine::new(OpenAiCompatibleConfig {
+            url: format!("http://127.0.0.1:{}/v1", port),
+            ..Default::default()
+        })
+        .unwrap();
+        let error = engine
+            .transcribe(&[0.0; 1600], 16000, "mic", &[])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("503"));
+        assert!(error.to_string().contains("model loading"));
+
+        let request = server.await.unwrap();
+        assert!(!request.contains("authorization"));
+        assert!(!request.contains("name=\"language\""));
+    }
+}
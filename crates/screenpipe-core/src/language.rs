@@ -1,8 +1,8 @@
 use clap::ValueEnum;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(ValueEnum, Clone, Debug, Serialize, Hash, Eq, PartialEq)]
+#[derive(ValueEnum, Clone, Debug, Serialize, Deserialize, Hash, Eq, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 #[repr(usize)]
 pub enum Language {
@@ -22,6 +22,12 @@ pub mod llama;
 pub use llama::*;
 pub mod pipes;
 pub use pipes::*;
+pub mod pipe_sandbox;
+pub use pipe_sandbox::*;
+pub mod pipe_runs;
+pub use pipe_runs::*;
+pub mod pipe_cron;
+pub use pipe_cron::*;
 mod language;
 #[cfg(feature = "security")]
 pub mod pii_removal;
@@ -0,0 +1,176 @@
+//! Scheduling policies of pipe crons.
+//!
+//! Each entry of `crons` in `pipe.json` can tune how the scheduler runs it:
+//!
+//! ```json
+//! "crons": [{
+//!   "path": "/api/sync",
+//!   "schedule": "0 */5 * * * *",
+//!   "catch_up": "once",
+//!   "max_concurrency": 1,
+//!   "timeout": 300,
+//!   "jitter": 30
+//! }]
+//! ```
+//!
+//! - `catch_up`: what to do with runs missed while screenpipe was stopped or the
+//!   machine asleep. `none` skips them (default), `once` runs a single one for all of
+//!   them, `all` replays each of them, up to [`MAX_CATCH_UP_RUNS`].
+//! - `max_concurrency`: runs allowed in flight at once (default 1). A tick finding
+//!   them all busy is skipped, missed runs being replayed wait for their turn.
+//! - `timeout`: seconds before a run is abandoned and recorded as failed.
+//! - `jitter`: up to this many seconds of random delay before each run.
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use once_cell::sync::Lazy;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// A slot that passed longer ago than this was missed rather than just reached.
+pub const MISSED_RUN_GRACE: Duration = Duration::from_secs(60);
+/// Most missed runs replayed with `catch_up: all`.
+pub const MAX_CATCH_UP_RUNS: usize = 100;
+/// Longest the scheduler sleeps before re-reading the wall clock. Timers don't
+/// advance while the machine is suspended, so a long sleep would fire late.
+pub const CRON_CLOCK_CHECK: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUp {
+    #[default]
+    None,
+    Once,
+    All,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CronPolicy {
+    pub catch_up: CatchUp,
+    pub max_concurrency: usize,
+    /// Seconds
+    pub timeout: Option<u64>,
+    /// Seconds
+    pub jitter: u64,
+}
+
+impl Default for CronPolicy {
+    fn default() -> Self {
+        CronPolicy {
+            catch_up: CatchUp::None,
+            max_concurrency: 1,
+            timeout: None,
+            jitter: 0,
+        }
+    }
+}
+
+/// Runs of a cron that are due, see [`CronPolicy::due_runs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DueRuns {
+    /// Slots that passed without running
+    pub missed: usize,
+    /// Missed runs to replay, according to `catch_up`
+    pub catch_up: usize,
+    /// Whether a slot was just reached
+    pub on_time: bool,
+}
+
+impl DueRuns {
+    pub fn is_empty(&self) -> bool {
+        self.missed == 0 && !self.on_time
+    }
+}
+
+impl CronPolicy {
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.filter(|t| *t > 0).map(Duration::from_secs)
+    }
+
+    /// Random delay to wait before a run.
+    pub fn jitter_delay(&self) -> Duration {
+        if self.jitter == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(thread_rng().gen_range(0..=self.jitter * 1000))
+    }
+
+    /// Sort the slots since the last run (`schedule.after(&last_run)`) into the one
+    /// reached just now and the missed ones, and decide how many of those to replay.
+    pub fn due_runs(
+        &self,
+        slots: impl IntoIterator<Item = DateTime<Local>>,
+        now: DateTime<Local>,
+    ) -> DueRuns {
+        let mut passed = 0;
+        let mut latest = None;
+        for slot in slots.into_iter().take_while(|slot| *slot <= now) {
+            passed += 1;
+            latest = Some(slot);
+            // Slots of a fine-grained schedule after a long sleep, no need to walk them all
+            if passed > MAX_CATCH_UP_RUNS * 10 {
+                latest = None;
+                break;
+            }
+        }
+
+        let on_time = latest.is_some_and(|slot| {
+            (now - slot)
+                .to_std()
+                .is_ok_and(|late| late <= MISSED_RUN_GRACE)
+        });
+        let missed = passed - usize::from(on_time);
+        let catch_up = match self.catch_up {
+            CatchUp::None => 0,
+            // The run happening now stands for the missed ones
+            CatchUp::Once if on_time => 0,
+            CatchUp::Once => missed.min(1),
+            CatchUp::All => missed.min(MAX_CATCH_UP_RUNS),
+        };
+
+        DueRuns {
+            missed,
+            catch_up,
+            on_time,
+        }
+    }
+}
+
+/// A cron entry of `pipe.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CronJob {
+    pub path: String,
+    pub schedule: String,
+    #[serde(flatten)]
+    pub policy: CronPolicy,
+}
+
+impl CronJob {
+    pub fn from_config(cron: &Value) -> Result<Self> {
+        let job: CronJob = serde_json::from_value(cron.clone())
+            .map_err(|e| anyhow::anyhow!("invalid cron in pipe.json: {}", e))?;
+        if job.policy.max_concurrency == 0 {
+            anyhow::bail!(
+                "invalid cron {} in pipe.json: max_concurrency must be at least 1",
+                job.path
+            );
+        }
+        Ok(job)
+    }
+}
+
+static SYSTEM_WAKE: Lazy<watch::Sender<()>> = Lazy::new(|| watch::channel(()).0);
+
+/// Tell cron schedulers the machine just woke up, so they catch up on missed runs
+/// right away instead of when their timers fire.
+pub fn notify_system_wake() {
+    SYSTEM_WAKE.send_replace(());
+}
+
+pub(crate) fn subscribe_system_wake() -> watch::Receiver<()> {
+    SYSTEM_WAKE.subscribe()
+}
@@ -0,0 +1,382 @@
+//! Run history of pipes.
+//!
+//! Every pipe process start and every cron tick is a [`PipeRun`], stored as one
+//! JSON file per run in `<pipe_dir>/.runs/`. The output of pipe processes is
+//! also appended to a log file next to it, rolled over to `<run_id>.log.1` when
+//! it grows past [`MAX_LOG_BYTES`]. Only the last [`MAX_RUNS`] runs of a pipe are
+//! kept, older ones are removed with their logs when a new run starts.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+pub const RUNS_DIR: &str = ".runs";
+/// Runs kept per pipe
+pub const MAX_RUNS: usize = 200;
+/// Size at which a run's log file is rolled over
+pub const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+/// Lines of stdout/stderr kept in the run record
+const TAIL_LINES: usize = 50;
+/// Longer lines are cut in the run record, not in the log file
+const MAX_TAIL_LINE_CHARS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipeRunKind {
+    /// The pipe's bun process
+    Process,
+    /// A cron route of a next.js pipe
+    Cron,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipeRunStatus {
+    Running,
+    Succeeded,
+    Failed,
+    /// Stopped by the user or screenpipe
+    Stopped,
+    /// Screenpipe exited while the run was in progress
+    Interrupted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipeRun {
+    pub id: String,
+    pub pipe_id: String,
+    pub kind: PipeRunKind,
+    /// Route called, for cron runs
+    pub cron_path: Option<String>,
+    pub status: PipeRunStatus,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Exit code of the process, `None` if it was killed by a signal
+    pub exit_code: Option<i32>,
+    /// Response status, for cron runs
+    pub http_status: Option<u16>,
+    pub error: Option<String>,
+    /// Last lines of stdout, or of the response body for cron runs
+    pub stdout_tail: Vec<String>,
+    pub stderr_tail: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipeOutput {
+    Stdout,
+    Stderr,
+}
+
+struct RecorderState {
+    run: PipeRun,
+    record_path: PathBuf,
+    log: Option<RollingLog>,
+    stdout_tail: VecDeque<String>,
+    stderr_tail: VecDeque<String>,
+}
+
+/// Records one run while it is in progress. Cheap to clone, clones share the run.
+#[derive(Clone)]
+pub struct PipeRunRecorder {
+    state: Arc<Mutex<RecorderState>>,
+}
+
+impl PipeRunRecorder {
+    /// Records the start of a run. Starting a process run marks runs left
+    /// `running` by a previous screenpipe session as interrupted.
+    pub async fn start(
+        pipe_dir: &Path,
+        pipe_id: &str,
+        kind: PipeRunKind,
+        cron_path: Option<&str>,
+    ) -> Result<Self> {
+        let runs_dir = pipe_dir.join(RUNS_DIR);
+        tokio::fs::create_dir_all(&runs_dir).await?;
+
+        if kind == PipeRunKind::Process {
+            mark_interrupted_runs(&runs_dir).await?;
+        }
+        prune_runs(&runs_dir).await?;
+
+        let started_at = Utc::now();
+        let suffix: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(6)
+            .map(|c| (c as char).to_ascii_lowercase())
+            .collect();
+        // Zero-padded millis first, so file names sort by start time
+        let id = format!("{:013}-{}", started_at.timestamp_millis(), suffix);
+
+        let log = match kind {
+            PipeRunKind::Process => {
+                Some(RollingLog::open(runs_dir.join(format!("{}.log", id))).await?)
+            }
+            PipeRunKind::Cron => None,
+        };
+
+        let recorder = PipeRunRecorder {
+            state: Arc::new(Mutex::new(RecorderState {
+                run: PipeRun {
+                    id: id.clone(),
+                    pipe_id: pipe_id.to_string(),
+                    kind,
+                    cron_path: cron_path.map(str::to_string),
+                    status: PipeRunStatus::Running,
+                    started_at,
+                    ended_at: None,
+                    exit_code: None,
+                    http_status: None,
+                    error: None,
+                    stdout_tail: Vec::new(),
+                    stderr_tail: Vec::new(),
+                },
+                record_path: runs_dir.join(format!("{}.json", id)),
+                log,
+                stdout_tail: VecDeque::with_capacity(TAIL_LINES),
+                stderr_tail: VecDeque::with_capacity(TAIL_LINES),
+            })),
+        };
+        recorder.state.lock().await.save().await?;
+        Ok(recorder)
+    }
+
+    /// Appends a line of output to the log file and the tail kept in the record.
+    pub async fn line(&self, output: PipeOutput, line: &str) {
+        let mut state = self.state.lock().await;
+        let tail = match output {
+            PipeOutput::Stdout => &mut state.stdout_tail,
+            PipeOutput::Stderr => &mut state.stderr_tail,
+        };
+        if tail.len() == TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line.chars().take(MAX_TAIL_LINE_CHARS).collect());
+
+        if let Some(log) = state.log.as_mut() {
+            let stream = match output {
+                PipeOutput::Stdout => "stdout",
+                PipeOutput::Stderr => "stderr",
+            };
+            let entry = format!("{} {} | {}\n", Utc::now().to_rfc3339(), stream, line);
+            if let Err(e) = log.write(entry.as_bytes()).await {
+                debug!("failed to write pipe log: {}", e);
+            }
+        }
+    }
+
+    /// Records the end of the run.
+    pub async fn finish(
+        &self,
+        status: PipeRunStatus,
+        exit_code: Option<i32>,
+        http_status: Option<u16>,
+        error: Option<String>,
+    ) -> Result<PipeRun> {
+        let mut state = self.state.lock().await;
+        if state.run.status != PipeRunStatus::Running {
+            // Already finished, e.g. stopped while exiting
+            return Ok(state.run.clone());
+        }
+        state.run.status = status;
+        state.run.ended_at = Some(Utc::now());
+        state.run.exit_code = exit_code;
+        state.run.http_status = http_status;
+        state.run.error = error;
+        state.run.stdout_tail = state.stdout_tail.iter().cloned().collect();
+        state.run.stderr_tail = state.stderr_tail.iter().cloned().collect();
+        if let Some(log) = state.log.as_mut() {
+            let _ = log.file.flush().await;
+        }
+        state.save().await?;
+        Ok(state.run.clone())
+    }
+}
+
+impl RecorderState {
+    async fn save(&self) -> Result<()> {
+        tokio::fs::write(&self.record_path, serde_json::to_vec_pretty(&self.run)?).await?;
+        Ok(())
+    }
+}
+
+struct RollingLog {
+    path: PathBuf,
+    file: File,
+    len: u64,
+}
+
+impl RollingLog {
+    async fn open(path: PathBuf) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        let len = file.metadata().await?.len();
+        Ok(RollingLog { path, file, len })
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        if self.len + bytes.len() as u64 > MAX_LOG_BYTES {
+            self.file.flush().await?;
+            tokio::fs::rename(&self.path, rolled_log_path(&self.path)).await?;
+            self.file = File::create(&self.path).await?;
+            self.len = 0;
+        }
+        self.file.write_all(bytes).await?;
+        self.len += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+fn rolled_log_path(log_path: &Path) -> PathBuf {
+    let mut path = log_path.as_os_str().to_owned();
+    path.push(".1");
+    PathBuf::from(path)
+}
+
+/// Run records of a directory, newest first.
+async fn record_paths(runs_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let mut entries = match tokio::fs::read_dir(runs_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(paths),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort_by(|a, b| b.file_name().cmp(&a.file_name()));
+    Ok(paths)
+}
+
+async fn read_record(path: &Path) -> Result<PipeRun> {
+    Ok(serde_json::from_slice(&tokio::fs::read(path).await?)?)
+}
+
+async fn mark_interrupted_runs(runs_dir: &Path) -> Result<()> {
+    for path in record_paths(runs_dir).await? {
+        let Ok(mut run) = read_record(&path).await else {
+            continue;
+        };
+        if run.status == PipeRunStatus::Running {
+            run.status = PipeRunStatus::Interrupted;
+            tokio::fs::write(&path, serde_json::to_vec_pretty(&run)?).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the oldest runs so that a new one fits in [`MAX_RUNS`]. Runs still in
+/// progress are never removed.
+async fn prune_runs(runs_dir: &Path) -> Result<()> {
+    let paths = record_paths(runs_dir).await?;
+    for path in paths.iter().skip(MAX_RUNS.saturating_sub(1)) {
+        if let Ok(run) = read_record(path).await {
+            if run.status == PipeRunStatus::Running {
+                continue;
+            }
+        }
+        let log_path = path.with_extension("log");
+        for path in [path.clone(), rolled_log_path(&log_path), log_path] {
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("failed to remove old pipe run file {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs of a pipe, newest first.
+pub async fn list_pipe_runs(pipe_dir: &Path, limit: usize, offset: usize) -> Result<Vec<PipeRun>> {
+    let mut runs = Vec::new();
+    for path in record_paths(&pipe_dir.join(RUNS_DIR))
+        .await?
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+    {
+        match read_record(&path).await {
+            Ok(run) => runs.push(run),
+            Err(e) => debug!("skipping unreadable pipe run {:?}: {}", path, e),
+        }
+    }
+    Ok(runs)
+}
+
+/// Log of a process run, limited to the last `tail_lines` lines if set. `None`
+/// if there is no such run or it has no log.
+pub async fn read_pipe_run_log(
+    pipe_dir: &Path,
+    run_id: &str,
+    tail_lines: Option<usize>,
+) -> Result<Option<String>> {
+    // Run ids are generated, anything else could be a path outside the runs dir
+    if run_id.is_empty()
+        || !run_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Ok(None);
+    }
+    let log_path = pipe_dir.join(RUNS_DIR).join(format!("{}.log", run_id));
+    if !log_path.exists() {
+        return Ok(None);
+    }
+
+    let mut log = tokio::fs::read_to_string(rolled_log_path(&log_path))
+        .await
+        .unwrap_or_default();
+    log.push_str(&tokio::fs::read_to_string(&log_path).await?);
+
+    Ok(Some(match tail_lines {
+        Some(n) => {
+            let lines: Vec<&str> = log.lines().collect();
+            let mut tail = lines[lines.len().saturating_sub(n)..].join("\n");
+            if !tail.is_empty() {
+                tail.push('\n');
+            }
+            tail
+        }
+        None => log,
+    }))
+}
+
+static PROCESS_RUNS: Lazy<Mutex<HashMap<String, PipeRunRecorder>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Tracks the process run of a pipe until [`finish_pipe_run`] is called.
+pub(crate) async fn register_process_run(pipe: &str, recorder: PipeRunRecorder) {
+    PROCESS_RUNS.lock().await.insert(pipe.to_string(), recorder);
+}
+
+/// Records the end of the current process run of a pipe, if one is in progress.
+pub async fn finish_pipe_run(
+    pipe: &str,
+    status: PipeRunStatus,
+    exit_code: Option<i32>,
+    error: Option<String>,
+) {
+    let Some(recorder) = PROCESS_RUNS.lock().await.remove(pipe) else {
+        return;
+    };
+    if let Err(e) = recorder.finish(status, exit_code, None, error).await {
+        warn!("[{}] failed to record end of pipe run: {}", pipe, e);
+    }
+}
@@ -0,0 +1,516 @@
+//! Permissions declared by pipes and their enforcement.
+//!
+//! A pipe lists what it needs in the `permissions` block of its `pipe.json`:
+//!
+//! ```json
+//! "permissions": {
+//!     "api_scopes": ["read-search"],
+//!     "hosts": ["api.openai.com", "*.notion.so"],
+//!     "fs_paths": ["~/Documents/notes"],
+//!     "env": ["OPENAI_API_KEY"]
+//! }
+//! ```
+//!
+//! The pipe process only inherits a small set of system variables plus the ones
+//! listed in `env`, and all its HTTP(S) traffic goes through a loopback
+//! [`EgressProxy`] that refuses hosts not listed in `hosts`. Requests to the
+//! screenpipe API through the proxy carry the pipe's scoped token, so calls
+//! outside `api_scopes` are rejected by the server. bun has no filesystem
+//! permission model, `fs_paths` are shown at install and passed to the pipe as
+//! `PIPE_FS_PATHS` but not enforced.
+//!
+//! Pipes without a `permissions` block keep unrestricted network access and the
+//! default API scopes, but still get the filtered environment.
+//!
+//! This is best-effort enforcement, not a sandbox: the pipe runs as the user,
+//! and a pipe that ignores the proxy variables or opens raw sockets isn't
+//! stopped. It keeps well-behaved pipes within what the user approved.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use url::Url;
+
+/// Variables every pipe gets from the parent environment, needed by bun and node
+/// to find binaries, locales and temp/cache directories.
+const BASE_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "USERNAME",
+    "LOGNAME",
+    "SHELL",
+    "LANG",
+    "LC_ALL",
+    "LC_CTYPE",
+    "TZ",
+    "TERM",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "XDG_RUNTIME_DIR",
+    "XDG_CACHE_HOME",
+    "XDG_CONFIG_HOME",
+    "BUN_INSTALL",
+    "SYSTEMROOT",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "PROGRAMDATA",
+];
+
+/// Requests with bigger headers are refused by the proxy.
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipePermissions {
+    /// Screenpipe API scopes for the pipe's token, e.g. `read-search`.
+    /// Empty means the default pipe scopes.
+    pub api_scopes: Vec<String>,
+    /// Hosts the pipe may reach: `example.com`, `*.example.com`, `example.com:8080`
+    /// or `*` for any host
+    pub hosts: Vec<String>,
+    /// Paths outside the pipe directory the pipe reads or writes
+    pub fs_paths: Vec<String>,
+    /// Variables passed through from screenpipe's environment, `PREFIX_*` allowed
+    pub env: Vec<String>,
+}
+
+impl PipePermissions {
+    /// Permissions of a pipe that doesn't declare any.
+    pub fn undeclared() -> Self {
+        PipePermissions {
+            hosts: vec!["*".to_string()],
+            ..Default::default()
+        }
+    }
+
+    /// Reads the `permissions` block of a `pipe.json`, `None` if there is none.
+    pub fn from_pipe_config(config: &Value) -> Result<Option<Self>> {
+        let Some(value) = config.get("permissions") else {
+            return Ok(None);
+        };
+        let permissions: PipePermissions = serde_json::from_value(value.clone())
+            .map_err(|e| anyhow::anyhow!("invalid permissions in pipe.json: {}", e))?;
+        for host in &permissions.hosts {
+            HostRule::parse(host)?;
+        }
+        Ok(Some(permissions))
+    }
+
+    /// Permissions of an installed pipe, [`PipePermissions::undeclared`] if its
+    /// `pipe.json` has no `permissions` block.
+    pub async fn load(pipe_dir: &std::path::Path) -> Result<Self> {
+        let config_path = pipe_dir.join("pipe.json");
+        if !config_path.exists() {
+            return Ok(Self::undeclared());
+        }
+        let config: Value = serde_json::from_str(&tokio::fs::read_to_string(config_path).await?)?;
+        Ok(Self::from_pipe_config(&config)?.unwrap_or_else(Self::undeclared))
+    }
+
+    pub fn allows_any_host(&self) -> bool {
+        self.hosts.iter().any(|h| h.trim() == "*")
+    }
+
+    /// Human readable summary, one line per permission.
+    pub fn describe(&self) -> Vec<String> {
+        fn list(items: &[String], empty: &str) -> String {
+            if items.is_empty() {
+                empty.to_string()
+            } else {
+                items.join(", ")
+            }
+        }
+
+        vec![
+            format!("api scopes: {}", list(&self.api_scopes, "default")),
+            format!(
+                "network: {}",
+                if self.allows_any_host() {
+                    "any host".to_string()
+                } else {
+                    list(&self.hosts, "none")
+                }
+            ),
+            format!(
+                "filesystem: {}",
+                list(&self.fs_paths, "pipe directory only")
+            ),
+            format!("env: {}", list(&self.env, "none")),
+        ]
+    }
+
+    /// Lines of [`PipePermissions::describe`] that differ in `new`, as `name: old -> new`.
+    pub fn describe_changes(&self, new: &PipePermissions) -> Vec<String> {
+        self.describe()
+            .into_iter()
+            .zip(new.describe())
+            .filter(|(old, new)| old != new)
+            .map(|(old, new)| {
+                let (name, old) = old.split_once(": ").unwrap_or(("", old.as_str()));
+                let new = new.split_once(": ").map_or(new.as_str(), |(_, new)| new);
+                format!("{}: {} -> {}", name, old, new)
+            })
+            .collect()
+    }
+
+    /// Keeps the [`BASE_ENV`] variables and the ones listed in `env`.
+    pub fn filter_env(
+        &self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Vec<(String, String)> {
+        vars.into_iter()
+            .filter(|(key, _)| {
+                BASE_ENV.iter().any(|name| name.eq_ignore_ascii_case(key))
+                    || self.env.iter().any(|pattern| env_matches(pattern, key))
+            })
+            .collect()
+    }
+}
+
+fn env_matches(pattern: &str, key: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => key.starts_with(prefix),
+        None => pattern == key,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HostPattern {
+    Any,
+    Exact(String),
+    /// `*.example.com`, subdomains only
+    Subdomain(String),
+}
+
+#[derive(Debug, Clone)]
+struct HostRule {
+    pattern: HostPattern,
+    port: Option<u16>,
+}
+
+impl HostRule {
+    fn parse(rule: &str) -> Result<Self> {
+        let rule = rule.trim().to_ascii_lowercase();
+        if rule == "*" {
+            return Ok(HostRule {
+                pattern: HostPattern::Any,
+                port: None,
+            });
+        }
+        if rule.contains("://") || rule.contains('/') || rule.is_empty() {
+            anyhow::bail!(
+                "invalid host '{}', expected a host name like api.example.com",
+                rule
+            );
+        }
+        let (host, port) = match rule.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') => {
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| anyhow::anyhow!("invalid port in host '{}'", rule))?;
+                (host.to_string(), Some(port))
+            }
+            _ => (rule.clone(), None),
+        };
+        let pattern = match host.strip_prefix("*.") {
+            Some(domain) => HostPattern::Subdomain(domain.to_string()),
+            None => HostPattern::Exact(host),
+        };
+        Ok(HostRule { pattern, port })
+    }
+
+    fn matches(&self, host: &str, port: u16) -> bool {
+        if self.port.is_some_and(|p| p != port) {
+            return false;
+        }
+        match &self.pattern {
+            HostPattern::Any => true,
+            HostPattern::Exact(h) => h == host,
+            HostPattern::Subdomain(domain) => host
+                .strip_suffix(domain.as_str())
+                .is_some_and(|rest| rest.ends_with('.')),
+        }
+    }
+}
+
+/// Whether `host` names this machine: `localhost`, any loopback or unspecified
+/// address (`127.0.0.0/8`, `0.0.0.0`, `::1`, `::`, their IPv4-mapped forms) or
+/// an address of one of the local interfaces. Other host names aren't resolved.
+fn is_loopback(host: &str) -> bool {
+    if host == "localhost" || host.ends_with(".localhost") {
+        return true;
+    }
+    match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) => is_local_ip(ip.to_canonical()),
+        Err(_) => false,
+    }
+}
+
+fn is_local_ip(ip: IpAddr) -> bool {
+    // Binding only succeeds for addresses assigned to a local interface
+    ip.is_loopback() || ip.is_unspecified() || std::net::UdpSocket::bind((ip, 0)).is_ok()
+}
+
+/// What a pipe's egress proxy lets through.
+#[derive(Debug, Clone)]
+pub struct EgressPolicy {
+    hosts: Vec<HostRule>,
+    /// Loopback ports the pipe may always reach, e.g. its own next.js server
+    loopback_ports: Vec<u16>,
+    /// Screenpipe API port and the pipe's token, injected on every API request
+    api: Option<(u16, String)>,
+}
+
+impl EgressPolicy {
+    pub fn new(permissions: &PipePermissions) -> Result<Self> {
+        Ok(EgressPolicy {
+            hosts: permissions
+                .hosts
+                .iter()
+                .map(|h| HostRule::parse(h))
+                .collect::<Result<_>>()?,
+            loopback_ports: Vec::new(),
+            api: None,
+        })
+    }
+
+    pub fn with_loopback_port(mut self, port: u16) -> Self {
+        self.loopback_ports.push(port);
+        self
+    }
+
+    /// Route requests to the screenpipe API on `port` with `token` as bearer token.
+    pub fn with_api(mut self, port: u16, token: String) -> Self {
+        self.api = Some((port, token));
+        self
+    }
+
+    fn is_api(&self, host: &str, port: u16) -> bool {
+        is_loopback(host) && self.api.as_ref().is_some_and(|(p, _)| *p == port)
+    }
+
+    fn allows(&self, host: &str, port: u16) -> bool {
+        let host = host.to_ascii_lowercase();
+        if is_loopback(&host) && (self.loopback_ports.contains(&port) || self.is_api(&host, port)) {
+            return true;
+        }
+        self.hosts.iter().any(|rule| rule.matches(&host, port))
+    }
+}
+
+/// A loopback HTTP proxy a single pipe is pointed at with `HTTP_PROXY` and
+/// `HTTPS_PROXY`. Plain HTTP requests are forwarded one per connection so each
+/// one is checked, HTTPS goes through `CONNECT` and is checked by host.
+pub struct EgressProxy {
+    pub port: u16,
+    task: JoinHandle<()>,
+}
+
+impl EgressProxy {
+    pub async fn start(pipe: &str, policy: EgressPolicy) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let port = listener.local_addr()?.port();
+        let policy = Arc::new(policy);
+        let pipe: Arc<str> = Arc::from(pipe);
+        debug!("[{}] egress proxy listening on 127.0.0.1:{}", pipe, port);
+
+        let task = tokio::spawn(async move {
+            loop {
+                let (client, _) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        warn!("[{}] egress proxy accept failed: {}", pipe, e);
+                        continue;
+                    }
+                };
+                let policy = policy.clone();
+                let pipe = pipe.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(client, &policy, &pipe).await {
+                        debug!("[{}] egress proxy connection failed: {}", pipe, e);
+                    }
+                });
+            }
+        });
+
+        Ok(EgressProxy { port, task })
+    }
+
+    /// Proxy variables for the pipe process. Nothing bypasses the proxy, the
+    /// screenpipe API included.
+    pub fn env(&self) -> Vec<(String, String)> {
+        let url = format!("http://127.0.0.1:{}", self.port);
+        ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"]
+            .into_iter()
+            .map(|key| (key.to_string(), url.clone()))
+            .chain([
+                ("NO_PROXY".to_string(), String::new()),
+                ("no_proxy".to_string(), String::new()),
+            ])
+            .collect()
+    }
+}
+
+impl Drop for EgressProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+static EGRESS_PROXIES: Lazy<tokio::sync::Mutex<HashMap<String, EgressProxy>>> =
+    Lazy::new(|| tokio::sync::Mutex::new(HashMap::new()));
+
+/// Keeps the proxy running until [`cleanup_pipe_sandbox`] is called for the pipe.
+pub(crate) async fn register_egress_proxy(pipe: &str, proxy: EgressProxy) {
+    EGRESS_PROXIES.lock().await.insert(pipe.to_string(), proxy);
+}
+
+/// Stops the egress proxy of a pipe, if it has one.
+pub async fn cleanup_pipe_sandbox(pipe: &str) {
+    if let Some(proxy) = EGRESS_PROXIES.lock().await.remove(pipe) {
+        info!("[{}] stopped egress proxy on port {}", pipe, proxy.port);
+    }
+}
+
+async fn handle_connection(mut client: TcpStream, policy: &EgressPolicy, pipe: &str) -> Result<()> {
+    let (head, body_start) = read_head(&mut client).await?;
+    let head = String::from_utf8(head)?;
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return deny(&mut client, 400, "malformed request").await;
+    };
+
+    if method.eq_ignore_ascii_case("CONNECT") {
+        let Some((host, port)) = split_host_port(target) else {
+            return deny(&mut client, 400, "malformed CONNECT target").await;
+        };
+        // The API token is only injected into plain HTTP requests
+        if !policy.allows(&host, port) || policy.is_api(&host, port) {
+            warn!("[{}] blocked connection to {}:{}", pipe, host, port);
+            return deny(&mut client, 403, "host not allowed by pipe permissions").await;
+        }
+        let mut upstream = TcpStream::connect((host.as_str(), port)).await?;
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+        upstream.write_all(&body_start).await?;
+        tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+        return Ok(());
+    }
+
+    let url = match Url::parse(target) {
+        Ok(url) if url.scheme() == "http" => url,
+        _ => {
+            return deny(
+                &mut client,
+                400,
+                "only absolute http:// urls can be proxied",
+            )
+            .await
+        }
+    };
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return deny(&mut client, 400, "missing host").await;
+    };
+    let host = host.to_ascii_lowercase();
+    if !policy.allows(&host, port) {
+        warn!("[{}] blocked request to {}:{}", pipe, host, port);
+        return deny(&mut client, 403, "host not allowed by pipe permissions").await;
+    }
+    let api_token = policy
+        .api
+        .as_ref()
+        .filter(|_| policy.is_api(&host, port))
+        .map(|(_, token)| token.as_str());
+
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let mut forwarded = format!("{} {} {}\r\n", method, path, version);
+    for line in lines.filter(|l| !l.is_empty()) {
+        let name = line.split(':').next().unwrap_or_default().trim();
+        let hop_by_hop = name.eq_ignore_ascii_case("connection")
+            || name.eq_ignore_ascii_case("keep-alive")
+            || name.to_ascii_lowercase().starts_with("proxy-");
+        // Pipes can't use another token than their own against the API
+        let replaced_auth = api_token.is_some() && name.eq_ignore_ascii_case("authorization");
+        if !hop_by_hop && !replaced_auth {
+            forwarded.push_str(line);
+            forwarded.push_str("\r\n");
+        }
+    }
+    if let Some(token) = api_token {
+        forwarded.push_str(&format!("Authorization: Bearer {}\r\n", token));
+    }
+    // One request per connection, so a kept-alive connection can't skip the checks
+    forwarded.push_str("Connection: close\r\n\r\n");
+
+    let mut upstream = TcpStream::connect((host.trim_matches(['[', ']']), port)).await?;
+    upstream.write_all(forwarded.as_bytes()).await?;
+    upstream.write_all(&body_start).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+/// Reads up to the end of the request head. Returns the head and whatever body
+/// bytes were read past it.
+async fn read_head(stream: &mut TcpStream) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut buf = Vec::with_capacity(4096);
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            anyhow::bail!("connection closed before end of request head");
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buf.split_off(pos + 4);
+            buf.truncate(pos);
+            return Ok((buf, rest));
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            anyhow::bail!("request head too large");
+        }
+    }
+}
+
+fn split_host_port(target: &str) -> Option<(String, u16)> {
+    let (host, port) = target.rsplit_once(':')?;
+    let host = host.trim_matches(['[', ']']).to_ascii_lowercase();
+    Some((host, port.parse().ok()?))
+}
+
+async fn deny(client: &mut TcpStream, status: u16, message: &str) -> Result<()> {
+    let reason = match status {
+        403 => "Forbidden",
+        _ => "Bad Request",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        message.len(),
+        message
+    );
+    client.write_all(response.as_bytes()).await?;
+    Ok(())
+}
@@ -6,9 +6,10 @@ use std::collections::HashMap;
 use std::future::Future;
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::process::Command;
-use tokio::sync::watch;
+use tokio::sync::{watch, Semaphore};
 
 use anyhow::Result;
 use std::fs;
@@ -24,6 +25,11 @@ use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 
 use crate::pick_unused_port;
+use crate::pipe_cron::{subscribe_system_wake, CronJob, CronPolicy, DueRuns, CRON_CLOCK_CHECK};
+use crate::pipe_runs::{
+    register_process_run, PipeOutput, PipeRunKind, PipeRunRecorder, PipeRunStatus,
+};
+use crate::pipe_sandbox::{register_egress_proxy, EgressPolicy, EgressProxy, PipePermissions};
 use once_cell::sync::Lazy;
 
 // Add near other imports
@@ -370,6 +376,24 @@ async fn spawn_watchdog(parent_pid: u32, child_pid: u32) -> Result<tokio::proces
 pub async fn run_pipe(
     pipe: &str,
     screenpipe_dir: PathBuf,
+) -> Result<(tokio::process::Child, PipeState)> {
+    run_pipe_sandboxed(pipe, screenpipe_dir, None).await
+}
+
+/// How a pipe reaches the screenpipe API.
+#[derive(Debug, Clone)]
+pub struct PipeApiAccess {
+    pub port: u16,
+    /// Scoped token, injected by the egress proxy and exposed as `SCREENPIPE_API_TOKEN`
+    pub token: String,
+}
+
+/// Same as [`run_pipe`], with the API reachable through the pipe's egress proxy
+/// using a scoped token. See [`crate::pipe_sandbox`] for what the pipe is allowed.
+pub async fn run_pipe_sandboxed(
+    pipe: &str,
+    screenpipe_dir: PathBuf,
+    api: Option<PipeApiAccess>,
 ) -> Result<(tokio::process::Child, PipeState)> {
     let bun_path = find_bun_path().ok_or_else(|| {
         let err = anyhow::anyhow!("bun not found");
@@ -414,9 +438,28 @@ pub async fn run_pipe(
         debug!("pipe {} is enabled, continuing", pipe);
     }
 
-    // Prepare environment variables
+    // Ended by the caller with `finish_pipe_run`, whether the pipe starts or not
+    let recorder = match PipeRunRecorder::start(&pipe_dir, pipe, PipeRunKind::Process, None).await {
+        Ok(recorder) => {
+            register_process_run(pipe, recorder.clone()).await;
+            Some(recorder)
+        }
+        Err(e) => {
+            warn!("[{}] failed to record pipe run: {}", pipe, e);
+            None
+        }
+    };
+
+    let permissions = PipePermissions::load(&pipe_dir).await?;
+    debug!(
+        "[{}] permissions: {}",
+        pipe,
+        permissions.describe().join("; ")
+    );
+
+    // Prepare environment variables, only what the pipe declared is inherited
     debug!("preparing environment variables for pipe: {}", pipe);
-    let mut env_vars = std::env::vars().collect::<Vec<(String, String)>>();
+    let mut env_vars = permissions.filter_env(std::env::vars());
     env_vars.push((
         "SCREENPIPE_DIR".to_string(),
         screenpipe_dir.to_str().unwrap().to_string(),
@@ -426,6 +469,17 @@ pub async fn run_pipe(
         "PIPE_DIR".to_string(),
         pipe_dir.to_str().unwrap().to_string(),
     ));
+    if let Ok(fs_paths) = std::env::join_paths(&permissions.fs_paths) {
+        env_vars.push((
+            "PIPE_FS_PATHS".to_string(),
+            fs_paths.to_string_lossy().into_owned(),
+        ));
+    }
+    let mut egress_policy = EgressPolicy::new(&permissions)?;
+    if let Some(api) = api {
+        env_vars.push(("SCREENPIPE_API_TOKEN".to_string(), api.token.clone()));
+        egress_policy = egress_policy.with_api(api.port, api.token);
+    }
 
     if is_nextjs {
         debug!(
@@ -490,14 +544,7 @@ pub async fn run_pipe(
                 let mut handles = Vec::new();
 
                 for cron in crons {
-                    let path = cron["path"]
-                        .as_str()
-                        .ok_or_else(|| anyhow::anyhow!("missing path"))?
-                        .to_string();
-                    let schedule = cron["schedule"]
-                        .as_str()
-                        .ok_or_else(|| anyhow::anyhow!("missing schedule"))?
-                        .to_string();
+                    let job = CronJob::from_config(cron)?;
 
                     let (tx, rx) = watch::channel(false);
                     let handle = CronHandle { shutdown: tx };
@@ -512,9 +559,8 @@ pub async fn run_pipe(
                         run_cron_schedule(
                             &pipe_clone,
                             &base_url,
-                            &path,
+                            job,
                             &secret_clone,
-                            &schedule,
                             &screenpipe_dir,
                             rx,
                         )
@@ -522,8 +568,13 @@ pub async fn run_pipe(
                     });
                 }
 
-                // Store handles for later cleanup
-                CRON_HANDLES.lock().await.insert(pipe.to_string(), handles);
+                // Store handles for later cleanup, stopping crons of a previous run
+                if let Some(previous) = CRON_HANDLES.lock().await.insert(pipe.to_string(), handles)
+                {
+                    for handle in previous {
+                        handle.stop();
+                    }
+                }
             }
 
             // Install dependencies using bun
@@ -595,6 +646,11 @@ pub async fn run_pipe(
             .parse::<u16>()
             .expect("Invalid port number");
 
+        // The pipe may call its own routes through the proxy
+        let proxy = EgressProxy::start(pipe, egress_policy.with_loopback_port(port)).await?;
+        env_vars.extend(proxy.env());
+        register_egress_proxy(pipe, proxy).await;
+
         // Run the Next.js project
         info!(
             "starting next.js project in {} mode",
@@ -619,6 +675,7 @@ pub async fn run_pipe(
             .arg("--port")
             .arg(port.to_string())
             .current_dir(&pipe_dir)
+            .env_clear()
             .envs(env_vars)
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped());
@@ -632,7 +689,7 @@ pub async fn run_pipe(
         let mut child = command.spawn()?;
 
         debug!("[{}] streaming logs for next.js pipe", pipe);
-        stream_logs(pipe, &mut child).await?;
+        stream_logs(pipe, &mut child, recorder).await?;
 
         let child_pid = child.id().expect("Failed to get child PID") as u32;
         let parent_pid = std::process::id();
@@ -664,10 +721,15 @@ pub async fn run_pipe(
         main_module.to_str().unwrap().to_string(),
     ));
 
+    let proxy = EgressProxy::start(pipe, egress_policy).await?;
+    env_vars.extend(proxy.env());
+    register_egress_proxy(pipe, proxy).await;
+
     let mut cmd = Command::new(&bun_path);
     cmd.arg("run")
         .arg("--bun")
         .arg(&main_module)
+        .env_clear()
         .envs(env_vars)
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped());
@@ -679,23 +741,31 @@ pub async fn run_pipe(
     let mut child = cmd.spawn()?;
 
     // Stream logs
-    stream_logs(pipe, &mut child).await?;
+    stream_logs(pipe, &mut child, recorder).await?;
 
     let child_id = child.id().unwrap();
     Ok((child, PipeState::Pid(child_id as i32))) // Return 0 or handle port differently for non-Next.js projects
 }
 
-async fn stream_logs(pipe: &str, child: &mut tokio::process::Child) -> Result<()> {
+async fn stream_logs(
+    pipe: &str,
+    child: &mut tokio::process::Child,
+    recorder: Option<PipeRunRecorder>,
+) -> Result<()> {
     let stdout = child.stdout.take().expect("failed to get stdout");
     let stderr = child.stderr.take().expect("failed to get stderr");
 
     let pipe_clone = pipe.to_string();
+    let stdout_recorder = recorder.clone();
 
     // Spawn tasks to handle stdout and stderr
     let _stdout_handle = tokio::spawn(async move {
         let reader = BufReader::new(stdout);
         let mut lines = reader.lines();
         while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(recorder) = &stdout_recorder {
+                recorder.line(PipeOutput::Stdout, &line).await;
+            }
             info!("[{}] {}", pipe_clone, line);
         }
     });
@@ -756,6 +826,9 @@ async fn stream_logs(pipe: &str, child: &mut tokio::process::Child) -> Result<()
         let mut lines = reader.lines();
 
         while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(recorder) = &recorder {
+                recorder.line(PipeOutput::Stderr, &line).await;
+            }
             let line_lower = line.to_lowercase(); // Convert once for case-insensitive matching
 
             // Quick checks first
@@ -840,6 +913,16 @@ async fn retry_install(bun_path: &Path, dest_dir: &Path, max_retries: u32) -> Re
 }
 
 pub async fn download_pipe(source: &str, screenpipe_dir: PathBuf) -> anyhow::Result<PathBuf> {
+    download_pipe_checked(source, screenpipe_dir, |_| Ok(())).await
+}
+
+/// Same as [`download_pipe`], refusing the pipe if its permissions are invalid or
+/// `check` fails. Nothing is installed or replaced then.
+pub async fn download_pipe_checked(
+    source: &str,
+    screenpipe_dir: PathBuf,
+    check: impl FnOnce(&PipePermissions) -> anyhow::Result<()>,
+) -> anyhow::Result<PathBuf> {
     info!("Processing pipe from source: {}", source);
 
     let pipe_name = sanitize_pipe_name(Path::new(source).to_str().unwrap());
@@ -863,35 +946,45 @@ pub async fn download_pipe(source: &str, screenpipe_dir: PathBuf) -> anyhow::Res
     let temp_dir = dest_dir.with_extension("_temp");
     tokio::fs::create_dir_all(&temp_dir).await?;
 
-    // Download to temp directory first
-    let download_result = if let Ok(parsed_url) = Url::parse(source) {
-        debug!("Source is a URL: {}", parsed_url);
-        if parsed_url.host_str() == Some("github.com") {
-            download_github_folder(&parsed_url, &temp_dir).await
-        } else if cfg!(windows) && parsed_url.scheme().len() == 1 {
-            // This is likely a Windows path with drive letter being interpreted as URL scheme
-            debug!("Detected Windows path with drive letter, treating as local path");
+    // Download to temp directory first and check the permissions there, so a
+    // refused pipe never replaces the installed one
+    let download_result = async {
+        if let Ok(parsed_url) = Url::parse(source) {
+            debug!("Source is a URL: {}", parsed_url);
+            if parsed_url.host_str() == Some("github.com") {
+                download_github_folder(&parsed_url, &temp_dir).await?;
+            } else if cfg!(windows) && parsed_url.scheme().len() == 1 {
+                // This is likely a Windows path with drive letter being interpreted as URL scheme
+                debug!("Detected Windows path with drive letter, treating as local path");
+                let source_path = Path::new(source);
+                if !source_path.exists() || !source_path.is_dir() {
+                    anyhow::bail!("Invalid local source path");
+                }
+                copy_dir_all(source_path, &temp_dir).await?;
+            } else {
+                anyhow::bail!("Unsupported URL format");
+            }
+        } else {
+            debug!("Source is a local path");
             let source_path = Path::new(source);
             if !source_path.exists() || !source_path.is_dir() {
                 anyhow::bail!("Invalid local source path");
             }
-            copy_dir_all(source_path, &temp_dir).await
-        } else {
-            anyhow::bail!("Unsupported URL format");
+            copy_dir_all(source_path, &temp_dir).await?;
         }
-    } else {
-        debug!("Source is a local path");
-        let source_path = Path::new(source);
-        if !source_path.exists() || !source_path.is_dir() {
-            anyhow::bail!("Invalid local source path");
-        }
-        copy_dir_all(source_path, &temp_dir).await
-    };
+
+        let permissions = PipePermissions::load(&temp_dir).await?;
+        check(&permissions)
+    }
+    .await;
 
     // remove temp dir if download failed
     if let Err(e) = download_result {
-        tokio::fs::remove_dir_all(&temp_dir).await?;
         error!("Failed to download pipe: {}", e);
+        if let Err(remove_err) = tokio::fs::remove_dir_all(&temp_dir).await {
+            warn!("failed to remove {:?}: {}", temp_dir, remove_err);
+        }
+        return Err(e);
     }
 
     // If download successful, move temp dir to final location
@@ -1394,17 +1487,15 @@ pub async fn save_cron_execution(pipe_dir: &Path, path: &str) -> Result<()> {
     Ok(())
 }
 
-// Update the run_cron_schedule function
 async fn run_cron_schedule(
     pipe: &str,
     base_url: &str,
-    path: &str,
+    job: CronJob,
     secret: &str,
-    schedule: &str,
     screenpipe_dir: &Path,
     mut shutdown: watch::Receiver<bool>,
 ) {
-    let schedule = match cron::Schedule::from_str(schedule) {
+    let schedule = match cron::Schedule::from_str(&job.schedule) {
         Ok(s) => s,
         Err(e) => {
             let err_msg = format!("invalid cron schedule: {}", e);
@@ -1414,7 +1505,6 @@ async fn run_cron_schedule(
         }
     };
 
-    let client = Client::new();
     let mut headers = HeaderMap::new();
     headers.insert(
         AUTHORIZATION,
@@ -1424,93 +1514,219 @@ async fn run_cron_schedule(
     // Get pipe directory for state persistence
     let pipe_dir = screenpipe_dir.join("pipes").join(pipe);
 
+    let runner = Arc::new(CronRunner {
+        pipe: pipe.to_string(),
+        path: job.path.clone(),
+        url: format!("{}{}", base_url, job.path),
+        client: Client::new(),
+        headers,
+        pipe_dir: pipe_dir.clone(),
+        slots: Arc::new(Semaphore::new(job.policy.max_concurrency)),
+        policy: job.policy.clone(),
+    });
+    let mut wake = subscribe_system_wake();
+
+    // Slots before the last execution were handled, a cron that never ran has nothing to catch up
+    let mut last_run = match get_last_cron_execution(&pipe_dir, &job.path).await {
+        Ok(Some(time)) => chrono::DateTime::<chrono::Local>::from(time),
+        Ok(None) => chrono::Local::now(),
+        Err(e) => {
+            error!("[{}] failed to get last cron execution: {}", pipe, e);
+            chrono::Local::now()
+        }
+    };
+    let mut announced = None;
+
     loop {
-        // Get last execution time at the start of each loop
-        let last_run = match get_last_cron_execution(&pipe_dir, path).await {
-            Ok(time) => time,
-            Err(e) => {
-                error!(
-                    "[{}] failed to get last cron execution: {}",
-                    pipe_dir.file_name().unwrap_or_default().to_string_lossy(),
-                    e
+        let now = chrono::Local::now();
+        let due = job.policy.due_runs(schedule.after(&last_run), now);
+        if !due.is_empty() {
+            if due.missed > 0 {
+                info!(
+                    "[{}] cron at path {} missed {} runs, catching up {} (catch_up: {:?})",
+                    pipe, job.path, due.missed, due.catch_up, job.policy.catch_up
                 );
-                None
             }
-        };
+            runner.dispatch(due, shutdown.clone());
 
-        let now = chrono::Local::now();
-        let next = if let Some(last) = last_run {
-            // Get next occurrence after the last execution
-            let last_chrono = chrono::DateTime::<chrono::Local>::from(last);
-            schedule.after(&last_chrono).next()
-        } else {
-            schedule.after(&now).next()
-        };
+            last_run = now;
+            if let Err(e) = save_cron_execution(&pipe_dir, &job.path).await {
+                warn!("[{}] failed to save cron execution: {}", pipe, e);
+            }
+        }
 
-        let next = match next {
+        let next = match schedule.after(&now).next() {
             Some(next) => next,
             None => {
                 error!("no next execution time found for cron schedule");
                 break;
             }
         };
+        if announced != Some(next) {
+            info!(
+                "[{}] next cron execution for pipe at path {} in {} seconds",
+                pipe,
+                job.path,
+                (next - now).num_seconds()
+            );
+            announced = Some(next);
+        }
+        let duration = (next - now)
+            .to_std()
+            .unwrap_or_default()
+            .min(CRON_CLOCK_CHECK);
 
-        if next <= now {
-            info!("next execution time is before or equal to the current time, recalculating...");
-            continue;
+        // Wait for either the next execution time, a wake from sleep or shutdown signal
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {}
+            Ok(()) = wake.changed() => {
+                info!("[{}] system woke up, checking missed runs of cron at path {}", pipe, job.path);
+            }
+            Ok(()) = shutdown.changed() => {
+                if *shutdown.borrow() {
+                    info!("shutting down cron job for pipe at path: {}", job.path);
+                    break;
+                }
+            }
         }
-        let duration = match (next - now).to_std() {
-            Ok(duration) => duration,
-            Err(e) => {
-                error!("invalid duration: {}", e);
-                continue; // falling back to minute is messing with cron schedule
+    }
+}
+
+/// Executes the runs of one cron, at most `max_concurrency` at a time.
+struct CronRunner {
+    pipe: String,
+    path: String,
+    url: String,
+    client: Client,
+    headers: HeaderMap,
+    pipe_dir: PathBuf,
+    policy: CronPolicy,
+    slots: Arc<Semaphore>,
+}
+
+impl CronRunner {
+    /// Start due runs in the background, they stop when the cron shuts down.
+    fn dispatch(self: &Arc<Self>, due: DueRuns, mut shutdown: watch::Receiver<bool>) {
+        let runner = self.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = runner.run_due(due) => {}
+                _ = shutdown.wait_for(|stop| *stop) => {}
             }
-        };
+        });
+    }
+
+    async fn run_due(&self, due: DueRuns) {
+        // Missed runs wait for their turn
+        for _ in 0..due.catch_up {
+            let Ok(_permit) = self.slots.acquire().await else {
+                return;
+            };
+            self.execute().await;
+        }
+
+        // The current tick is dropped rather than piling up behind a slow endpoint
+        if due.on_time {
+            match self.slots.try_acquire() {
+                Ok(_permit) => self.execute().await,
+                Err(_) => warn!(
+                    "[{}] skipping cron at path {}, {} previous run(s) still in progress",
+                    self.pipe, self.path, self.policy.max_concurrency
+                ),
+            }
+        }
+    }
+
+    async fn execute(&self) {
+        let jitter = self.policy.jitter_delay();
+        if !jitter.is_zero() {
+            debug!(
+                "[{}] delaying cron at path {} by {:?}",
+                self.pipe, self.path, jitter
+            );
+            tokio::time::sleep(jitter).await;
+        }
 
         info!(
-            "[{}] next cron execution for pipe at path {} in {} seconds",
-            pipe,
-            path,
-            duration.as_secs()
+            "executing cron job for pipe {} at path {}",
+            self.pipe, self.path
         );
+        let recorder = match PipeRunRecorder::start(
+            &self.pipe_dir,
+            &self.pipe,
+            PipeRunKind::Cron,
+            Some(&self.path),
+        )
+        .await
+        {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                warn!("[{}] failed to record cron run: {}", self.pipe, e);
+                None
+            }
+        };
 
-        // Wait for either the next execution time or shutdown signal
-        tokio::select! {
-            _ = tokio::time::sleep(duration) => {
-                info!("executing cron job for pipe {} at path {}", pipe, path);
-                match client
-                    .get(format!("{}{}", base_url, path))
-                    .headers(headers.clone())
-                    .send()
-                    .await
-                {
-                    Ok(res) => {
-                        if !res.status().is_success() {
-                            let err_msg = format!("cron job failed with status: {}", res.status());
-                            error!("{}", err_msg);
-                            if let Ok(text) = res.text().await {
-                                error!("error response: {}", text);
-                                sentry::capture_message(
-                                    &format!("{}: {}", err_msg, text),
-                                    sentry::Level::Error
-                                );
-                            } else {
-                                sentry::capture_message(&err_msg, sentry::Level::Error);
-                            }
-                        }
+        let request = async {
+            let res = self
+                .client
+                .get(&self.url)
+                .headers(self.headers.clone())
+                .send()
+                .await?;
+            let http_status = res.status();
+            Ok::<_, reqwest_middleware::reqwest::Error>((http_status, res.text().await.ok()))
+        };
+        let response = match self.policy.timeout() {
+            Some(timeout) => tokio::time::timeout(timeout, request).await.ok(),
+            None => Some(request.await),
+        };
+
+        let (status, http_status, error) = match response {
+            Some(Ok((http_status, text))) => {
+                if let (Some(recorder), Some(text)) = (&recorder, &text) {
+                    for line in text.lines() {
+                        recorder.line(PipeOutput::Stdout, line).await;
                     }
-                    Err(e) => {
-                        let err_msg = format!("failed to execute cron job: {}", e);
-                        error!("{}", err_msg);
-                        sentry::capture_error(&e);
+                }
+                if !http_status.is_success() {
+                    let err_msg = format!("cron job failed with status: {}", http_status);
+                    error!("{}", err_msg);
+                    if let Some(text) = text {
+                        error!("error response: {}", text);
+                        sentry::capture_message(
+                            &format!("{}: {}", err_msg, text),
+                            sentry::Level::Error,
+                        );
+                    } else {
+                        sentry::capture_message(&err_msg, sentry::Level::Error);
                     }
+                    (
+                        PipeRunStatus::Failed,
+                        Some(http_status.as_u16()),
+                        Some(err_msg),
+                    )
+                } else {
+                    (PipeRunStatus::Succeeded, Some(http_status.as_u16()), None)
                 }
             }
-            Ok(()) = shutdown.changed() => {
-                if *shutdown.borrow() {
-                    info!("shutting down cron job for pipe at path: {}", path);
-                    break;
-                }
+            Some(Err(e)) => {
+                let err_msg = format!("failed to execute cron job: {}", e);
+                error!("{}", err_msg);
+                sentry::capture_error(&e);
+                (PipeRunStatus::Failed, None, Some(err_msg))
+            }
+            None => {
+                let err_msg = format!(
+                    "cron job timed out after {}s",
+                    self.policy.timeout.unwrap_or_default()
+                );
+                error!("[{}] {} at path {}", self.pipe, err_msg, self.path);
+                (PipeRunStatus::Failed, None, Some(err_msg))
+            }
+        };
+        if let Some(recorder) = recorder {
+            if let Err(e) = recorder.finish(status, None, http_status, error).await {
+                warn!("[{}] failed to record end of cron run: {}", self.pipe, e);
             }
         }
     }
@@ -1652,6 +1868,17 @@ pub async fn download_pipe_private(
     pipe_name: &str,
     source: &str,
     screenpipe_dir: PathBuf,
+) -> anyhow::Result<PathBuf> {
+    download_pipe_private_checked(pipe_name, source, screenpipe_dir, |_| Ok(())).await
+}
+
+/// Same as [`download_pipe_private`], refusing the pipe if its permissions are invalid or
+/// `check` fails. Nothing is installed or replaced then.
+pub async fn download_pipe_private_checked(
+    pipe_name: &str,
+    source: &str,
+    screenpipe_dir: PathBuf,
+    check: impl FnOnce(&PipePermissions) -> anyhow::Result<()>,
 ) -> anyhow::Result<PathBuf> {
     info!("processing private pipe from zip: {}", source);
 
@@ -1814,6 +2041,22 @@ pub async fn download_pipe_private(
         warn!("Failed to remove temporary zip file: {}", e);
     }
 
+    // Check the permissions before the pipe replaces the installed one
+    let permissions = PipePermissions::load(&temp_dir).await;
+    if let Err(e) = permissions.and_then(check) {
+        let err_msg = format!("Refused pipe permissions: {:#}", e);
+        error!("{}", err_msg);
+        update_build_status(
+            &temp_pipe_json,
+            "error",
+            "checking permissions",
+            Some(&err_msg),
+        )
+        .await?;
+        cleanup_temp(&temp_dir, &temp_zip).await?;
+        return Err(anyhow::anyhow!(err_msg));
+    }
+
     // Move temp dir to final location
     if dest_dir.exists() {
         if let Err(e) = tokio::fs::remove_dir_all(&dest_dir).await {
@@ -0,0 +1,112 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Local, TimeZone};
+    use screenpipe_core::{CatchUp, CronJob, CronPolicy, DueRuns, MAX_CATCH_UP_RUNS};
+    use serde_json::json;
+    use std::time::Duration;
+
+    /// Slots of a schedule firing every 5 minutes, after `last_run`.
+    fn every_five_minutes(last_run: DateTime<Local>) -> impl Iterator<Item = DateTime<Local>> {
+        (1..).map(move |i| last_run + chrono::Duration::minutes(5 * i))
+    }
+
+    fn policy(catch_up: CatchUp) -> CronPolicy {
+        CronPolicy {
+            catch_up,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cron_job_from_config() {
+        let job = CronJob::from_config(&json!({
+            "path": "/api/sync",
+            "schedule": "0 */5 * * * *",
+            "catch_up": "all",
+            "max_concurrency": 2,
+            "timeout": 300,
+            "jitter": 30
+        }))
+        .unwrap();
+        assert_eq!(job.path, "/api/sync");
+        assert_eq!(job.policy.catch_up, CatchUp::All);
+        assert_eq!(job.policy.max_concurrency, 2);
+        assert_eq!(job.policy.timeout(), Some(Duration::from_secs(300)));
+        assert!(job.policy.jitter_delay() <= Duration::from_secs(30));
+
+        // Crons without policies keep the previous behavior
+        let job = CronJob::from_config(&json!({
+            "path": "/api/log",
+            "schedule": "0 * * * * *"
+        }))
+        .unwrap();
+        assert_eq!(job.policy, CronPolicy::default());
+        assert_eq!(job.policy.timeout(), None);
+        assert_eq!(job.policy.jitter_delay(), Duration::ZERO);
+
+        assert!(CronJob::from_config(&json!({"path": "/api/log"})).is_err());
+        assert!(CronJob::from_config(&json!({
+            "path": "/api/log",
+            "schedule": "0 * * * * *",
+            "max_concurrency": 0
+        }))
+        .is_err());
+        assert!(CronJob::from_config(&json!({
+            "path": "/api/log",
+            "schedule": "0 * * * * *",
+            "catch_up": "sometimes"
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn test_due_runs_on_time_and_missed() {
+        let last_run = Local.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        // Nothing due before the first slot
+        let now = last_run + chrono::Duration::minutes(3);
+        let due = policy(CatchUp::All).due_runs(every_five_minutes(last_run), now);
+        assert_eq!(due, DueRuns::default());
+        assert!(due.is_empty());
+
+        // Timer fired on the slot
+        let now = last_run + chrono::Duration::minutes(5) + chrono::Duration::seconds(1);
+        let due = policy(CatchUp::None).due_runs(every_five_minutes(last_run), now);
+        assert_eq!(
+            due,
+            DueRuns {
+                missed: 0,
+                catch_up: 0,
+                on_time: true
+            }
+        );
+
+        // Woke up an hour later, in between two slots
+        let now = last_run + chrono::Duration::minutes(62);
+        let slots = || every_five_minutes(last_run);
+        let none = policy(CatchUp::None).due_runs(slots(), now);
+        assert_eq!((none.missed, none.catch_up, none.on_time), (12, 0, false));
+        let once = policy(CatchUp::Once).due_runs(slots(), now);
+        assert_eq!((once.missed, once.catch_up, once.on_time), (12, 1, false));
+        let all = policy(CatchUp::All).due_runs(slots(), now);
+        assert_eq!((all.missed, all.catch_up, all.on_time), (12, 12, false));
+
+        // Woke up right on a slot, that run covers the missed ones for `once`
+        let now = last_run + chrono::Duration::minutes(60);
+        let once = policy(CatchUp::Once).due_runs(slots(), now);
+        assert_eq!((once.missed, once.catch_up, once.on_time), (11, 0, true));
+        let all = policy(CatchUp::All).due_runs(slots(), now);
+        assert_eq!((all.missed, all.catch_up, all.on_time), (11, 11, true));
+    }
+
+    #[test]
+    fn test_catch_up_is_bounded() {
+        let last_run = Local.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let now = last_run + chrono::Duration::days(30);
+
+        let due = policy(CatchUp::All).due_runs(every_five_minutes(last_run), now);
+        assert!(due.missed > MAX_CATCH_UP_RUNS);
+        assert_eq!(due.catch_up, MAX_CATCH_UP_RUNS);
+        assert!(!due.on_time);
+    }
+}
@@ -0,0 +1,136 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_core::{
+        list_pipe_runs, read_pipe_run_log, PipeOutput, PipeRunKind, PipeRunRecorder, PipeRunStatus,
+        MAX_RUNS,
+    };
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_process_run_is_recorded_with_output() {
+        let temp_dir = TempDir::new().unwrap();
+        let pipe_dir = temp_dir.path().join("my-pipe");
+
+        let recorder = PipeRunRecorder::start(&pipe_dir, "my-pipe", PipeRunKind::Process, None)
+            .await
+            .unwrap();
+        let runs = list_pipe_runs(&pipe_dir, 10, 0).await.unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, PipeRunStatus::Running);
+
+        for i in 0..60 {
+            recorder
+                .line(PipeOutput::Stdout, &format!("line {}", i))
+                .await;
+        }
+        recorder.line(PipeOutput::Stderr, "boom").await;
+        let run = recorder
+            .finish(
+                PipeRunStatus::Failed,
+                Some(1),
+                None,
+                Some("exited".to_string()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(run.exit_code, Some(1));
+        assert_eq!(run.stdout_tail.len(), 50);
+        assert_eq!(run.stdout_tail.last().unwrap(), "line 59");
+        assert_eq!(run.stderr_tail, vec!["boom"]);
+
+        // Finishing twice keeps the first result
+        let again = recorder
+            .finish(PipeRunStatus::Stopped, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(again.status, PipeRunStatus::Failed);
+
+        let log = read_pipe_run_log(&pipe_dir, &run.id, Some(2))
+            .await
+            .unwrap()
+            .unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("stdout | line 59"));
+        assert!(lines[1].ends_with("stderr | boom"));
+
+        assert!(read_pipe_run_log(&pipe_dir, "../../etc/passwd", None)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_runs_left_running_are_marked_interrupted() {
+        let temp_dir = TempDir::new().unwrap();
+        let pipe_dir = temp_dir.path().join("my-pipe");
+
+        let cron =
+            PipeRunRecorder::start(&pipe_dir, "my-pipe", PipeRunKind::Cron, Some("/api/log"))
+                .await
+                .unwrap();
+        cron.finish(PipeRunStatus::Succeeded, None, Some(200), None)
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        // Never finished, as if screenpipe was killed
+        PipeRunRecorder::start(&pipe_dir, "my-pipe", PipeRunKind::Process, None)
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        PipeRunRecorder::start(&pipe_dir, "my-pipe", PipeRunKind::Process, None)
+            .await
+            .unwrap();
+
+        let runs = list_pipe_runs(&pipe_dir, 10, 0).await.unwrap();
+        let statuses: Vec<PipeRunStatus> = runs.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                PipeRunStatus::Running,
+                PipeRunStatus::Interrupted,
+                PipeRunStatus::Succeeded
+            ]
+        );
+        assert_eq!(runs[2].http_status, Some(200));
+        assert_eq!(runs[2].cron_path.as_deref(), Some("/api/log"));
+    }
+
+    #[tokio::test]
+    async fn test_old_runs_are_pruned() {
+        let temp_dir = TempDir::new().unwrap();
+        let pipe_dir = temp_dir.path().join("my-pipe");
+
+        let first = PipeRunRecorder::start(&pipe_dir, "my-pipe", PipeRunKind::Process, None)
+            .await
+            .unwrap();
+        first
+            .finish(PipeRunStatus::Succeeded, Some(0), None, None)
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        for _ in 0..MAX_RUNS {
+            PipeRunRecorder::start(&pipe_dir, "my-pipe", PipeRunKind::Cron, Some("/api/log"))
+                .await
+                .unwrap()
+                .finish(PipeRunStatus::Succeeded, None, Some(200), None)
+                .await
+                .unwrap();
+        }
+
+        let runs = list_pipe_runs(&pipe_dir, MAX_RUNS * 2, 0).await.unwrap();
+        assert_eq!(runs.len(), MAX_RUNS);
+        assert!(runs.iter().all(|r| r.kind == PipeRunKind::Cron));
+        let leftover_logs = std::fs::read_dir(pipe_dir.join(".runs"))
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .path()
+                    .extension()
+                    .is_some_and(|ext| ext == "log")
+            })
+            .count();
+        assert_eq!(leftover_logs, 0);
+    }
+}
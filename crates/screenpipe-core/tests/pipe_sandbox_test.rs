@@ -0,0 +1,172 @@
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+    use screenpipe_core::{EgressPolicy, EgressProxy, PipePermissions};
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers every request with its own head, so tests can see what was forwarded.
+    async fn start_echo_server() -> u16 {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 8192];
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    let head = String::from_utf8_lossy(&buf[..n]).to_string();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        head.len(),
+                        head
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn test_permissions_from_pipe_config() {
+        let config = json!({
+            "enabled": true,
+            "permissions": {
+                "api_scopes": ["read-search"],
+                "hosts": ["api.openai.com", "*.notion.so"],
+                "env": ["OPENAI_API_KEY", "MY_PIPE_*"]
+            }
+        });
+        let permissions = PipePermissions::from_pipe_config(&config).unwrap().unwrap();
+        assert_eq!(permissions.api_scopes, vec!["read-search"]);
+        assert!(permissions.fs_paths.is_empty());
+        assert!(!permissions.allows_any_host());
+
+        let env = permissions.filter_env(vec![
+            ("PATH".to_string(), "/usr/bin".to_string()),
+            ("OPENAI_API_KEY".to_string(), "sk".to_string()),
+            ("MY_PIPE_MODE".to_string(), "fast".to_string()),
+            ("AWS_SECRET_ACCESS_KEY".to_string(), "secret".to_string()),
+        ]);
+        let keys: Vec<&str> = env.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["PATH", "OPENAI_API_KEY", "MY_PIPE_MODE"]);
+
+        assert!(PipePermissions::from_pipe_config(&json!({}))
+            .unwrap()
+            .is_none());
+        assert!(PipePermissions::undeclared().allows_any_host());
+        assert!(PipePermissions::from_pipe_config(
+            &json!({"permissions": {"hosts": ["https://api.openai.com/v1"]}})
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_describe_permission_changes() {
+        let old = PipePermissions {
+            hosts: vec!["api.openai.com".to_string()],
+            ..Default::default()
+        };
+        let new = PipePermissions {
+            hosts: vec!["*".to_string()],
+            fs_paths: vec!["~/Documents".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            old.describe_changes(&new),
+            vec![
+                "network: api.openai.com -> any host",
+                "filesystem: pipe directory only -> ~/Documents",
+            ]
+        );
+        assert!(old.describe_changes(&old).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_egress_proxy_enforces_hosts_and_api_token() {
+        let api_port = start_echo_server().await;
+        let other_port = start_echo_server().await;
+
+        let permissions = PipePermissions {
+            hosts: vec!["api.example.com".to_string()],
+            ..Default::default()
+        };
+        let policy = EgressPolicy::new(&permissions)
+            .unwrap()
+            .with_api(api_port, "sp_pipe_token".to_string());
+        let proxy = EgressProxy::start("test-pipe", policy).await.unwrap();
+
+        let client = reqwest::Client::builder()
+            .proxy(reqwest::Proxy::all(format!("http://127.0.0.1:{}", proxy.port)).unwrap())
+            .build()
+            .unwrap();
+
+        // API requests get the pipe's own token, whatever the pipe sent
+        let response = client
+            .get(format!("http://localhost:{}/search?q=test", api_port))
+            .bearer_auth("sp_admin_token")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let forwarded = response.text().await.unwrap();
+        assert!(forwarded.starts_with("GET /search?q=test HTTP/1.1\r\n"));
+        assert!(forwarded.contains("Authorization: Bearer sp_pipe_token\r\n"));
+        assert!(!forwarded.contains("sp_admin_token"));
+
+        // Other local services aren't in the declared hosts
+        let response = client
+            .get(format!("http://localhost:{}/", other_port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = client
+            .get("http://blocked.example.com/")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_egress_proxy_recognizes_api_behind_loopback_aliases() {
+        let permissions = PipePermissions {
+            hosts: vec!["*".to_string()],
+            ..Default::default()
+        };
+        let policy = EgressPolicy::new(&permissions)
+            .unwrap()
+            .with_api(3030, "sp_pipe_token".to_string());
+        let proxy = EgressProxy::start("test-pipe", policy).await.unwrap();
+
+        // Tunnels to the API are refused however the loopback address is spelled,
+        // even though the pipe may reach any host
+        for target in [
+            "127.0.0.2:3030",
+            "0.0.0.0:3030",
+            "[::ffff:127.0.0.1]:3030",
+            "[::]:3030",
+        ] {
+            let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", proxy.port))
+                .await
+                .unwrap();
+            stream
+                .write_all(
+                    format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n\r\n", target, target).as_bytes(),
+                )
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert!(
+                response.starts_with("HTTP/1.1 403 "),
+                "{} was not refused: {}",
+                target,
+                response
+            );
+        }
+    }
+}
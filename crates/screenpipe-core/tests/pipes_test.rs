@@ -2,8 +2,8 @@
 mod tests {
     use chrono::{TimeZone, Utc};
     use screenpipe_core::{
-        download_pipe, download_pipe_private, get_last_cron_execution, run_pipe,
-        sanitize_pipe_name, save_cron_execution, PipeState,
+        download_pipe, download_pipe_checked, download_pipe_private, get_last_cron_execution,
+        run_pipe, sanitize_pipe_name, save_cron_execution, PipeState,
     };
 
     use serde_json::json;
@@ -301,6 +301,68 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_refused_pipe_is_not_installed() {
+        init();
+        let temp_dir = TempDir::new().unwrap();
+        let screenpipe_dir = temp_dir.path().to_path_buf();
+
+        let source_dir = temp_dir.path().join("source_pipe");
+        tokio::fs::create_dir_all(&source_dir).await.unwrap();
+        tokio::fs::write(source_dir.join("pipe.js"), "console.log('v1');")
+            .await
+            .unwrap();
+        let installed = download_pipe(source_dir.to_str().unwrap(), screenpipe_dir.clone())
+            .await
+            .unwrap();
+
+        // An update with a malformed permissions block is refused
+        tokio::fs::write(source_dir.join("pipe.js"), "console.log('v2');")
+            .await
+            .unwrap();
+        tokio::fs::write(
+            source_dir.join("pipe.json"),
+            json!({"permissions": {"hosts": ["https://api.openai.com/v1"]}}).to_string(),
+        )
+        .await
+        .unwrap();
+        let result = download_pipe(source_dir.to_str().unwrap(), screenpipe_dir.clone()).await;
+        assert!(result.is_err());
+
+        // So is one the caller's check rejects
+        tokio::fs::write(
+            source_dir.join("pipe.json"),
+            json!({"permissions": {"api_scopes": ["admin"]}}).to_string(),
+        )
+        .await
+        .unwrap();
+        let result = download_pipe_checked(
+            source_dir.to_str().unwrap(),
+            screenpipe_dir.clone(),
+            |permissions| {
+                anyhow::ensure!(permissions.api_scopes.is_empty(), "scopes not allowed");
+                Ok(())
+            },
+        )
+        .await;
+        assert!(result.is_err());
+
+        // The installed version is untouched and no temp directory is left behind
+        let content = tokio::fs::read_to_string(installed.join("pipe.js"))
+            .await
+            .unwrap();
+        assert_eq!(content, "console.log('v1');");
+        assert!(!installed.join("pipe.json").exists());
+        let mut entries = tokio::fs::read_dir(screenpipe_dir.join("pipes"))
+            .await
+            .unwrap();
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+        assert_eq!(names, vec!["source_pipe"]);
+    }
+
     #[tokio::test]
     #[ignore = "requires next.js build which may fail in CI"]
     async fn test_downloading_and_running_sideloaded_pipe() {
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::DatabaseManager;
+
+/// An API token as stored in the database. The plaintext token is never stored,
+/// only its hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(FromRow)]
+struct ApiTokenRow {
+    id: i64,
+    name: String,
+    scopes: String,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiTokenRow> for ApiToken {
+    fn from(row: ApiTokenRow) -> Self {
+        ApiToken {
+            id: row.id,
+            name: row.name,
+            scopes: row
+                .scopes
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect(),
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+            revoked_at: row.revoked_at,
+        }
+    }
+}
+
+impl DatabaseManager {
+    pub async fn insert_api_token(
+        &self,
+        name: &str,
+        token_hash: &str,
+        scopes: &[String],
+    ) -> Result<i64, sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        let id = sqlx::query(
+            "INSERT INTO api_tokens (name, token_hash, scopes, created_at) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(name)
+        .bind(token_hash)
+        .bind(scopes.join(","))
+        .bind(Utc::now())
+        .execute(&mut **tx.conn())
+        .await?
+        .last_insert_rowid();
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Lists all tokens, including revoked ones, newest first.
+    pub async fn list_api_tokens(&self) -> Result<Vec<ApiToken>, sqlx::Error> {
+        let rows: Vec<ApiTokenRow> = sqlx::query_as(
+            "SELECT id, name, scopes, created_at, last_used_at, revoked_at FROM api_tokens ORDER BY id DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(ApiToken::from).collect())
+    }
+
+    /// Looks up a non-revoked token by the hash of its plaintext.
+    pub async fn get_api_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<ApiToken>, sqlx::Error> {
+        let row: Option<ApiTokenRow> = sqlx::query_as(
+            "SELECT id, name, scopes, created_at, last_used_at, revoked_at FROM api_tokens WHERE token_hash = ?1 AND revoked_at IS NULL",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(ApiToken::from))
+    }
+
+    /// Revokes a token. Returns false if it doesn't exist or was already revoked.
+    pub async fn revoke_api_token(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        let revoked = sqlx::query(
+            "UPDATE api_tokens SET revoked_at = ?1 WHERE id = ?2 AND revoked_at IS NULL",
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&mut **tx.conn())
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+        Ok(revoked > 0)
+    }
+
+    pub async fn touch_api_token(&self, id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        sqlx::query("UPDATE api_tokens SET last_used_at = ?1 WHERE id = ?2")
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&mut **tx.conn())
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
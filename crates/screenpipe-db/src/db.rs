@@ -5,28 +5,27 @@ use sqlite_vec::sqlite3_vec_init;
 use sqlx::migrate::MigrateDatabase;
 use sqlx::pool::PoolConnection;
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
-use sqlx::Column;
 use sqlx::Error as SqlxError;
 use sqlx::Row;
 use sqlx::Sqlite;
-use sqlx::TypeInfo;
-use sqlx::ValueRef;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{debug, error, warn};
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 
 use zerocopy::AsBytes;
 
 use futures::future::try_join_all;
 
+use crate::ocr_layout::insert_layout_blocks;
 use crate::{
-    text_similarity::is_similar_transcription, AudioChunksResponse, AudioDevice, AudioEntry,
-    AudioResult, AudioResultRaw, ContentType, DeviceType, FrameData, FrameRow, FrameWindowData,
-    InsertUiEvent, OCREntry, OCRResult, OCRResultRaw, OcrEngine, OcrTextBlock, Order, SearchMatch,
-    SearchResult, Speaker, TagContentType, TextBounds, TextPosition, TimeSeriesChunk, UiContent,
-    UiEventRecord, UiEventRow, VideoMetadata,
+    text_similarity::is_similar_transcription, AudioChunkToRetranscribe, AudioChunksResponse,
+    AudioDevice, AudioEntry, AudioResult, AudioResultRaw, AudioTranscriptionWord, ContentType,
+    DeviceType, EnrolledSpeaker, FrameData, FrameRow, FrameWindowData, InsertUiEvent,
+    NewAudioTranscription, OCREntry, OCRResult, OCRResultRaw, OcrEngine, OcrTextBlock, Order,
+    SearchMatch, SearchResult, Speaker, TagContentType, TextBounds, TextPosition, TimeSeriesChunk,
+    UiContent, UiEventRecord, UiEventRow, VideoMetadata,
 };
 
 /// Time window (in seconds) to check for similar transcriptions across devices.
@@ -259,6 +258,17 @@ impl DatabaseManager {
         Ok(id.unwrap_or(0))
     }
 
+    /// Path of the file of an audio chunk, `None` if there is no such chunk.
+    pub async fn get_audio_chunk_path(
+        &self,
+        audio_chunk_id: i64,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar::<_, String>("SELECT file_path FROM audio_chunks WHERE id = ?1")
+            .bind(audio_chunk_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
     pub async fn get_or_insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error> {
         let mut id = self.get_audio_chunk_id(file_path).await?;
         if id == 0 {
@@ -395,11 +405,294 @@ impl DatabaseManager {
         .await?
         .rows_affected();
 
+        // The transcription is only ever cut short, drop the timings of the words cut off
+        sqlx::query(
+            "DELETE FROM audio_transcription_words WHERE word_index >= ?1 AND audio_transcription_id IN (SELECT id FROM audio_transcriptions WHERE audio_chunk_id = ?2)",
+        )
+        .bind(transcription.split_whitespace().count() as i64)
+        .bind(audio_chunk_id)
+        .execute(&mut **tx.conn())
+        .await?;
+
         // Commit the transaction for the full transcription
         tx.commit().await?;
         Ok(affected as i64)
     }
 
+    /// Store the word timings of a transcription, in the order of its text.
+    pub async fn insert_audio_transcription_words(
+        &self,
+        audio_transcription_id: i64,
+        words: &[AudioTranscriptionWord],
+    ) -> Result<(), sqlx::Error> {
+        if words.is_empty() {
+            return Ok(());
+        }
+        let mut tx = self.begin_immediate_with_retry().await?;
+        for (index, word) in words.iter().enumerate() {
+            sqlx::query(
+                "INSERT OR REPLACE INTO audio_transcription_words (audio_transcription_id, word_index, word, start_time, end_time, confidence) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )
+            .bind(audio_transcription_id)
+            .bind(index as i64)
+            .bind(&word.word)
+            .bind(word.start_time)
+            .bind(word.end_time)
+            .bind(word.confidence)
+            .execute(&mut **tx.conn())
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_audio_transcription_words(
+        &self,
+        audio_transcription_id: i64,
+    ) -> Result<Vec<AudioTranscriptionWord>, sqlx::Error> {
+        sqlx::query_as::<_, AudioTranscriptionWord>(
+            "SELECT word, start_time, end_time, confidence FROM audio_transcription_words WHERE audio_transcription_id = ?1 ORDER BY word_index",
+        )
+        .bind(audio_transcription_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Where the first word matching `query` starts in the audio chunk of each transcription,
+    /// keyed by transcription id. Transcriptions without word timings or a match are left out.
+    pub(crate) async fn get_audio_seek_times(
+        &self,
+        audio_transcription_ids: &[i64],
+        query: &str,
+    ) -> Result<HashMap<i64, f64>, sqlx::Error> {
+        let terms = seek_terms(query);
+        if terms.is_empty() || audio_transcription_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let words = sqlx::query_as::<_, (i64, String, f64)>(
+            "SELECT audio_transcription_id, word, start_time FROM audio_transcription_words
+            WHERE audio_transcription_id IN (SELECT value FROM json_each(?1))
+            ORDER BY audio_transcription_id, word_index",
+        )
+        .bind(serde_json::to_string(audio_transcription_ids).unwrap_or_default())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut seek_times = HashMap::new();
+        for (id, word, start_time) in words {
+            if seek_times.contains_key(&id) {
+                continue;
+            }
+            let word = normalize_word(&word);
+            if terms.iter().any(|term| word.starts_with(term.as_str())) {
+                seek_times.insert(id, start_time);
+            }
+        }
+        Ok(seek_times)
+    }
+
+    /// Number of audio chunks with transcriptions between `start_time` and `end_time` that
+    /// weren't all made by `transcription_engine`.
+    pub async fn count_audio_chunks_to_retranscribe(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: Option<DateTime<Utc>>,
+        transcription_engine: &str,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM (
+                SELECT audio_chunk_id FROM audio_transcriptions
+                WHERE timestamp >= ?1 AND (?2 IS NULL OR timestamp <= ?2)
+                GROUP BY audio_chunk_id
+                HAVING SUM(transcription_engine != ?3) > 0
+            )",
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(transcription_engine)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Next audio chunks, by id, with transcriptions between `start_time` and `end_time` that
+    /// weren't all made by `transcription_engine`.
+    pub async fn get_audio_chunks_to_retranscribe(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: Option<DateTime<Utc>>,
+        transcription_engine: &str,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<AudioChunkToRetranscribe>, sqlx::Error> {
+        // The bare columns come from the row of MIN(timestamp)
+        sqlx::query_as::<_, AudioChunkToRetranscribe>(
+            "SELECT audio_chunks.id, audio_chunks.file_path, MIN(audio_transcriptions.timestamp) as timestamp,
+                audio_transcriptions.device, audio_transcriptions.is_input_device
+            FROM audio_chunks
+            JOIN audio_transcriptions ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            WHERE audio_chunks.id > ?1
+                AND audio_transcriptions.timestamp >= ?2
+                AND (?3 IS NULL OR audio_transcriptions.timestamp <= ?3)
+            GROUP BY audio_chunks.id
+            HAVING SUM(audio_transcriptions.transcription_engine != ?4) > 0
+            ORDER BY audio_chunks.id
+            LIMIT ?5",
+        )
+        .bind(after_id)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(transcription_engine)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Replace the transcriptions of an audio chunk, e.g. with the ones of a better model.
+    ///
+    /// The new transcriptions keep the timestamp and device of the previous ones. Their word
+    /// timings, embeddings and full text search entries are dropped with them. Returns the ids
+    /// of the new transcriptions.
+    pub async fn replace_audio_transcriptions(
+        &self,
+        audio_chunk_id: i64,
+        transcription_engine: &str,
+        transcriptions: &[NewAudioTranscription],
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+
+        let (timestamp, device, is_input_device): (DateTime<Utc>, String, bool) = sqlx::query_as(
+            "SELECT timestamp, device, is_input_device FROM audio_transcriptions WHERE audio_chunk_id = ?1 ORDER BY timestamp LIMIT 1",
+        )
+        .bind(audio_chunk_id)
+        .fetch_one(&mut **tx.conn())
+        .await?;
+
+        sqlx::query("DELETE FROM audio_transcriptions WHERE audio_chunk_id = ?1")
+            .bind(audio_chunk_id)
+            .execute(&mut **tx.conn())
+            .await?;
+
+        let ids = Self::insert_chunk_transcriptions(
+            &mut tx,
+            audio_chunk_id,
+            transcription_engine,
+            &device,
+            is_input_device,
+            timestamp,
+            false,
+            transcriptions,
+        )
+        .await?;
+
+        tx.commit().await?;
+        Ok(ids)
+    }
+
+    /// Store an audio file recorded outside of screenpipe, e.g. by a meeting tool.
+    ///
+    /// Each transcription is timestamped `timestamp` plus its start in the file. Unlike
+    /// [`insert_audio_transcription`](Self::insert_audio_transcription), they are not
+    /// deduplicated against what was recorded around that time. Returns the id of the audio
+    /// chunk and the ids of the transcriptions.
+    pub async fn insert_imported_audio(
+        &self,
+        file_path: &str,
+        timestamp: DateTime<Utc>,
+        transcription_engine: &str,
+        device: &AudioDevice,
+        transcriptions: &[NewAudioTranscription],
+    ) -> Result<(i64, Vec<i64>), sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+
+        let audio_chunk_id =
+            sqlx::query("INSERT INTO audio_chunks (file_path, timestamp) VALUES (?1, ?2)")
+                .bind(file_path)
+                .bind(timestamp)
+                .execute(&mut **tx.conn())
+                .await?
+                .last_insert_rowid();
+
+        let ids = Self::insert_chunk_transcriptions(
+            &mut tx,
+            audio_chunk_id,
+            transcription_engine,
+            &device.name,
+            device.device_type == DeviceType::Input,
+            timestamp,
+            true,
+            transcriptions,
+        )
+        .await?;
+
+        tx.commit().await?;
+        Ok((audio_chunk_id, ids))
+    }
+
+    /// Insert the transcriptions of an audio chunk and their words, skipping empty ones and
+    /// repeated texts. With `offset_timestamps`, each one is timestamped `timestamp` plus its
+    /// start in the chunk.
+    #[allow(clippy::too_many_arguments)]
+    async fn insert_chunk_transcriptions(
+        tx: &mut ImmediateTx,
+        audio_chunk_id: i64,
+        transcription_engine: &str,
+        device: &str,
+        is_input_device: bool,
+        timestamp: DateTime<Utc>,
+        offset_timestamps: bool,
+        transcriptions: &[NewAudioTranscription],
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        let mut ids = Vec::with_capacity(transcriptions.len());
+        for transcription in transcriptions {
+            if transcription.transcription.trim().is_empty() {
+                continue;
+            }
+            let timestamp = match transcription.start_time {
+                Some(start_time) if offset_timestamps => {
+                    timestamp + chrono::Duration::milliseconds((start_time * 1000.0) as i64)
+                }
+                _ => timestamp,
+            };
+            let result = sqlx::query(
+                "INSERT OR IGNORE INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine, device, is_input_device, speaker_id, start_time, end_time, text_length) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            )
+            .bind(audio_chunk_id)
+            .bind(&transcription.transcription)
+            .bind(0)
+            .bind(timestamp)
+            .bind(transcription_engine)
+            .bind(device)
+            .bind(is_input_device)
+            .bind(transcription.speaker_id)
+            .bind(transcription.start_time)
+            .bind(transcription.end_time)
+            .bind(transcription.transcription.len() as i64)
+            .execute(&mut **tx.conn())
+            .await?;
+            // Same text as an earlier segment of the chunk
+            if result.rows_affected() == 0 {
+                continue;
+            }
+
+            let id = result.last_insert_rowid();
+            for (index, word) in transcription.words.iter().enumerate() {
+                sqlx::query(
+                    "INSERT INTO audio_transcription_words (audio_transcription_id, word_index, word, start_time, end_time, confidence) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )
+                .bind(id)
+                .bind(index as i64)
+                .bind(&word.word)
+                .bind(word.start_time)
+                .bind(word.end_time)
+                .bind(word.confidence)
+                .execute(&mut **tx.conn())
+                .await?;
+            }
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
     pub async fn insert_speaker(&self, embedding: &[f32]) -> Result<Speaker, SqlxError> {
         let mut tx = self.begin_immediate_with_retry().await?;
 
@@ -486,6 +779,134 @@ impl DatabaseManager {
         Ok(speaker_id)
     }
 
+    /// Enroll voice embeddings for a named speaker, creating the speaker if `speaker_id` is not
+    /// set. `name` renames an existing speaker.
+    pub async fn enroll_speaker(
+        &self,
+        speaker_id: Option<i64>,
+        name: Option<&str>,
+        embeddings: &[Vec<f32>],
+    ) -> Result<EnrolledSpeaker, SqlxError> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+
+        let speaker_id = match speaker_id {
+            Some(speaker_id) => {
+                // Fail on unknown speakers rather than enroll orphan embeddings
+                sqlx::query_scalar::<_, i64>("SELECT id FROM speakers WHERE id = ?1")
+                    .bind(speaker_id)
+                    .fetch_one(&mut **tx.conn())
+                    .await?;
+                if let Some(name) = name {
+                    sqlx::query("UPDATE speakers SET name = ?1 WHERE id = ?2")
+                        .bind(name)
+                        .bind(speaker_id)
+                        .execute(&mut **tx.conn())
+                        .await?;
+                }
+                speaker_id
+            }
+            None => sqlx::query("INSERT INTO speakers (name) VALUES (?1)")
+                .bind(name)
+                .execute(&mut **tx.conn())
+                .await?
+                .last_insert_rowid(),
+        };
+
+        for embedding in embeddings {
+            let bytes: &[u8] = embedding.as_bytes();
+            sqlx::query(
+                "INSERT INTO speaker_embeddings (embedding, speaker_id, enrolled) VALUES (vec_f32(?1), ?2, TRUE)",
+            )
+            .bind(bytes)
+            .bind(speaker_id)
+            .execute(&mut **tx.conn())
+            .await?;
+        }
+        tx.commit().await?;
+
+        self.get_enrolled_speakers()
+            .await?
+            .into_iter()
+            .find(|speaker| speaker.id == speaker_id)
+            .ok_or(SqlxError::RowNotFound)
+    }
+
+    /// Speakers with enrolled voice embeddings, see [`enroll_speaker`](Self::enroll_speaker).
+    pub async fn get_enrolled_speakers(&self) -> Result<Vec<EnrolledSpeaker>, SqlxError> {
+        let rows: Vec<(i64, Option<String>, Vec<u8>)> = sqlx::query_as(
+            "SELECT speakers.id, speakers.name, speaker_embeddings.embedding
+             FROM speaker_embeddings
+             JOIN speakers ON speakers.id = speaker_embeddings.speaker_id
+             WHERE speaker_embeddings.enrolled = TRUE
+             ORDER BY speakers.id, speaker_embeddings.id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut speakers: Vec<EnrolledSpeaker> = Vec::new();
+        let mut current: Option<(i64, String, Vec<Vec<f32>>)> = None;
+        for (id, name, bytes) in rows {
+            let embedding = embedding_from_bytes(&bytes);
+            match current {
+                Some((current_id, _, ref mut embeddings)) if current_id == id => {
+                    embeddings.push(embedding)
+                }
+                _ => {
+                    if let Some((id, name, embeddings)) = current.take() {
+                        speakers.push(EnrolledSpeaker::new(id, name, embeddings));
+                    }
+                    current = Some((id, name.unwrap_or_default(), vec![embedding]));
+                }
+            }
+        }
+        if let Some((id, name, embeddings)) = current {
+            speakers.push(EnrolledSpeaker::new(id, name, embeddings));
+        }
+        Ok(speakers)
+    }
+
+    /// Settings of audio devices, as device name and JSON, see
+    /// [`set_audio_device_settings`](Self::set_audio_device_settings).
+    pub async fn get_audio_device_settings(&self) -> Result<Vec<(String, String)>, SqlxError> {
+        sqlx::query_as(
+            "SELECT device_name, settings FROM audio_device_settings ORDER BY device_name",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Store the settings (JSON) of the audio device `device_name`, replacing its previous ones.
+    pub async fn set_audio_device_settings(
+        &self,
+        device_name: &str,
+        settings: &str,
+    ) -> Result<(), SqlxError> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        sqlx::query(
+            "INSERT INTO audio_device_settings (device_name, settings, updated_at)
+             VALUES (?1, ?2, CURRENT_TIMESTAMP)
+             ON CONFLICT(device_name) DO UPDATE SET
+                settings = excluded.settings,
+                updated_at = excluded.updated_at",
+        )
+        .bind(device_name)
+        .bind(settings)
+        .execute(&mut **tx.conn())
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn delete_audio_device_settings(&self, device_name: &str) -> Result<(), SqlxError> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        sqlx::query("DELETE FROM audio_device_settings WHERE device_name = ?1")
+            .bind(device_name)
+            .execute(&mut **tx.conn())
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     pub async fn insert_video_chunk(
         &self,
         file_path: &str,
@@ -708,6 +1129,8 @@ impl DatabaseManager {
             .execute(&mut **tx.conn())
             .await?;
 
+            insert_layout_blocks(&mut **tx.conn(), frame_id, &window.layout).await?;
+
             results.push((frame_id, idx));
         }
 
@@ -1363,7 +1786,8 @@ impl DatabaseManager {
                 audio_transcriptions.is_input_device,
                 audio_transcriptions.speaker_id,
                 audio_transcriptions.start_time,
-                audio_transcriptions.end_time
+                audio_transcriptions.end_time,
+                audio_transcriptions.id as transcription_id
              FROM audio_transcriptions
              JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
              LEFT JOIN speakers ON audio_transcriptions.speaker_id = speakers.id
@@ -1447,6 +1871,9 @@ impl DatabaseManager {
         query_builder = query_builder.bind(limit as i64).bind(offset as i64);
 
         let results_raw: Vec<AudioResultRaw> = query_builder.fetch_all(&self.pool).await?;
+        let transcription_ids: Vec<i64> = results_raw.iter().map(|r| r.transcription_id).collect();
+        let seek_times = self.get_audio_seek_times(&transcription_ids, query).await?;
+        let seek_times = &seek_times;
 
         // map raw results into audio result type
         let futures: Vec<_> = results_raw
@@ -1456,6 +1883,7 @@ impl DatabaseManager {
                     Some(id) => (self.get_speaker_by_id(id).await).ok(),
                     None => None,
                 };
+                let seek_time = seek_times.get(&raw.transcription_id).copied();
 
                 Ok::<AudioResult, sqlx::Error>(AudioResult {
                     audio_chunk_id: raw.audio_chunk_id,
@@ -1477,6 +1905,8 @@ impl DatabaseManager {
                     speaker,
                     start_time: raw.start_time,
                     end_time: raw.end_time,
+                    transcription_id: raw.transcription_id,
+                    seek_time,
                 })
             })
             .collect();
@@ -2058,43 +2488,6 @@ impl DatabaseManager {
         tx.commit().await?;
         Ok(())
     }
-    pub async fn execute_raw_sql(&self, query: &str) -> Result<serde_json::Value, sqlx::Error> {
-        let rows = sqlx::query(query).fetch_all(&self.pool).await?;
-
-        let result: Vec<serde_json::Map<String, serde_json::Value>> = rows
-            .iter()
-            .map(|row| {
-                let mut map = serde_json::Map::new();
-                for (i, column) in row.columns().iter().enumerate() {
-                    if let Ok(value) = row.try_get_raw(i) {
-                        let json_value = match value.type_info().name() {
-                            "TEXT" => {
-                                let s: String = row.try_get(i).unwrap_or_default();
-                                serde_json::Value::String(s)
-                            }
-                            "INTEGER" => {
-                                let i: i64 = row.try_get(i).unwrap_or_default();
-                                serde_json::Value::Number(i.into())
-                            }
-                            "REAL" => {
-                                let f: f64 = row.try_get(i).unwrap_or_default();
-                                serde_json::Value::Number(
-                                    serde_json::Number::from_f64(f).unwrap_or(0.into()),
-                                )
-                            }
-                            _ => serde_json::Value::Null,
-                        };
-                        map.insert(column.name().to_string(), json_value);
-                    }
-                }
-                map
-            })
-            .collect();
-
-        Ok(serde_json::Value::Array(
-            result.into_iter().map(serde_json::Value::Object).collect(),
-        ))
-    }
 
     pub async fn find_video_chunks(
         &self,
@@ -2554,7 +2947,18 @@ impl DatabaseManager {
         speaker_to_merge_id: i64,
     ) -> Result<Speaker, sqlx::Error> {
         let mut tx = self.begin_immediate_with_retry().await?;
+        Self::merge_speakers_in_tx(&mut tx, speaker_to_keep_id, speaker_to_merge_id).await?;
+        tx.commit().await?;
+
+        self.get_speaker_by_id(speaker_to_keep_id).await
+    }
 
+    /// Move the transcriptions and embeddings of a speaker to another and delete it.
+    pub(crate) async fn merge_speakers_in_tx(
+        tx: &mut ImmediateTx,
+        speaker_to_keep_id: i64,
+        speaker_to_merge_id: i64,
+    ) -> Result<(), sqlx::Error> {
         // for each audio transcription of the speaker to merge, update the speaker_id to the speaker to keep
         sqlx::query("UPDATE audio_transcriptions SET speaker_id = ? WHERE speaker_id = ?")
             .bind(speaker_to_keep_id)
@@ -2574,10 +2978,7 @@ impl DatabaseManager {
             .bind(speaker_to_merge_id)
             .execute(&mut **tx.conn())
             .await?;
-
-        tx.commit().await?;
-
-        self.get_speaker_by_id(speaker_to_keep_id).await
+        Ok(())
     }
 
     pub async fn search_speakers(&self, name_prefix: &str) -> Result<Vec<Speaker>, sqlx::Error> {
@@ -2770,18 +3171,22 @@ impl DatabaseManager {
         &self,
         frame_id: i64,
         embedding: String,
+        embedding_model: &str,
     ) -> Result<(), sqlx::Error> {
-        sqlx::query("INSERT INTO ocr_text_embeddings (frame_id, embedding) VALUES (?1, ?2)")
+        sqlx::query("INSERT INTO ocr_text_embeddings (frame_id, embedding, embedding_model) VALUES (?1, ?2, ?3)")
             .bind(frame_id)
             .bind(embedding)
+            .bind(embedding_model)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
+    /// Frames whose OCR embedding made with `embedding_model` is closest to `embedding`.
     pub async fn search_similar_embeddings(
         &self,
         embedding: Vec<f32>,
+        embedding_model: &str,
         limit: u32,
         threshold: f32,
     ) -> Result<Vec<OCRResult>, sqlx::Error> {
@@ -2793,7 +3198,8 @@ impl DatabaseManager {
                     frame_id,
                     vec_distance_cosine(embedding, vec_f32(?1)) as similarity
                 FROM ocr_text_embeddings
-                WHERE vec_distance_cosine(embedding, vec_f32(?1)) < ?2
+                WHERE embedding_model = ?4
+                  AND vec_distance_cosine(embedding, vec_f32(?1)) < ?2
                 ORDER BY similarity ASC
                 LIMIT ?3
             )
@@ -2826,6 +3232,7 @@ impl DatabaseManager {
             .bind(bytes)
             .bind(threshold)
             .bind(limit)
+            .bind(embedding_model)
             .fetch_all(&self.pool)
             .await?;
 
@@ -3666,6 +4073,32 @@ fn calculate_confidence(positions: &[TextPosition]) -> f32 {
     positions.iter().map(|pos| pos.confidence).sum::<f32>() / positions.len() as f32
 }
 
+/// Lowercase letters and digits of a word, without its punctuation.
+fn normalize_word(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Speaker embedding stored by `vec_f32`, as little-endian floats.
+pub(crate) fn embedding_from_bytes(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// Terms of an FTS query to look for in transcription words, operators left out.
+fn seek_terms(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .filter(|term| !matches!(*term, "AND" | "OR" | "NOT" | "NEAR"))
+        .map(normalize_word)
+        .filter(|term| !term.is_empty())
+        .collect()
+}
+
 /// Parse all OCR text blocks into TextPosition objects with bounding boxes.
 /// Unlike `find_matching_positions`, this returns ALL text positions without filtering.
 ///
@@ -3889,4 +4322,15 @@ mod tests {
         // Should match both "Hello" and "World" due to word-by-word matching
         assert_eq!(positions.len(), 2);
     }
+
+    #[test]
+    fn test_seek_terms() {
+        assert_eq!(seek_terms("Budget*"), vec!["budget"]);
+        assert_eq!(
+            seek_terms("\"quarterly report\" OR budget"),
+            vec!["quarterly", "report", "budget"]
+        );
+        assert!(seek_terms("").is_empty());
+        assert_eq!(normalize_word("Budget,"), "budget");
+    }
 }
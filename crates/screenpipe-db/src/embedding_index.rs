@@ -0,0 +1,325 @@
+//! Storage for the background embedding indexer.
+//!
+//! Each modality has two watermarks in `embedding_index_progress`: rows above
+//! `high_water_id` arrived after indexing started and are embedded first, rows
+//! below `low_water_id` are history that is backfilled at a throttled rate.
+//! OCR progress follows `ocr_text` rowids rather than frame ids, since the text of
+//! a frame can be inserted after later frames; audio follows `audio_transcriptions.id`.
+//!
+//! Vectors are stored with the name of the model that made them. A row is a
+//! candidate until it has a vector of the indexer's model, so rows embedded with
+//! another model are embedded again rather than dropped.
+
+use chrono::{DateTime, Utc};
+use oasgen::OaSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use zerocopy::AsBytes;
+
+use crate::DatabaseManager;
+
+#[derive(OaSchema, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingModality {
+    Ocr,
+    Audio,
+}
+
+impl EmbeddingModality {
+    pub const ALL: [EmbeddingModality; 2] = [EmbeddingModality::Ocr, EmbeddingModality::Audio];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmbeddingModality::Ocr => "ocr",
+            EmbeddingModality::Audio => "audio",
+        }
+    }
+
+    /// Non-empty text without an embedding of model `?3` yet, with row ids above
+    /// (`newer`) or below `?1`.
+    fn candidates_sql(&self, newer: bool) -> String {
+        let (cmp, order) = if newer { (">", "ASC") } else { ("<", "DESC") };
+        match self {
+            EmbeddingModality::Ocr => format!(
+                r#"
+                SELECT MIN(rowid) AS row_id, frame_id AS id, MAX(text) AS text
+                FROM ocr_text
+                WHERE rowid {cmp} ?1 AND text != ''
+                  AND NOT EXISTS (
+                      SELECT 1 FROM ocr_text_embeddings e
+                      WHERE e.frame_id = ocr_text.frame_id AND e.embedding_model = ?3
+                  )
+                GROUP BY frame_id
+                ORDER BY row_id {order}
+                LIMIT ?2
+                "#,
+                cmp = cmp,
+                order = order
+            ),
+            EmbeddingModality::Audio => format!(
+                r#"
+                SELECT id AS row_id, id, transcription AS text
+                FROM audio_transcriptions
+                WHERE id {cmp} ?1 AND TRIM(transcription) != ''
+                  AND NOT EXISTS (
+                      SELECT 1 FROM audio_transcription_embeddings e
+                      WHERE e.audio_transcription_id = audio_transcriptions.id
+                        AND e.embedding_model = ?3
+                  )
+                ORDER BY id {order}
+                LIMIT ?2
+                "#,
+                cmp = cmp,
+                order = order
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct EmbeddingCandidate {
+    /// Position in the indexing order, what the watermarks are compared with
+    pub row_id: i64,
+    /// Frame id for OCR, `audio_transcriptions.id` for audio
+    pub id: i64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, FromRow)]
+pub struct EmbeddingWatermarks {
+    pub high_water_id: i64,
+    pub low_water_id: i64,
+    pub backfill_complete: bool,
+}
+
+#[derive(OaSchema, Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingIndexProgress {
+    pub modality: EmbeddingModality,
+    /// Rows with text to embed
+    pub total: i64,
+    pub indexed: i64,
+    pub high_water_id: i64,
+    pub low_water_id: i64,
+    pub backfill_completed_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(FromRow)]
+struct ProgressRow {
+    high_water_id: i64,
+    low_water_id: i64,
+    backfill_completed_at: Option<DateTime<Utc>>,
+    updated_at: DateTime<Utc>,
+}
+
+impl DatabaseManager {
+    /// Watermarks for a modality, initialized on first use so everything already
+    /// recorded is treated as backfill.
+    pub async fn get_embedding_watermarks(
+        &self,
+        modality: EmbeddingModality,
+    ) -> Result<EmbeddingWatermarks, sqlx::Error> {
+        let max_id_sql = match modality {
+            EmbeddingModality::Ocr => "SELECT COALESCE(MAX(rowid), 0) FROM ocr_text",
+            EmbeddingModality::Audio => "SELECT COALESCE(MAX(id), 0) FROM audio_transcriptions",
+        };
+
+        let mut tx = self.begin_immediate_with_retry().await?;
+        let max_id: i64 = sqlx::query_scalar(max_id_sql)
+            .fetch_one(&mut **tx.conn())
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO embedding_index_progress (modality, high_water_id, low_water_id, updated_at)
+            VALUES (?1, ?2, ?2 + 1, ?3)
+            "#,
+        )
+        .bind(modality.as_str())
+        .bind(max_id)
+        .bind(Utc::now())
+        .execute(&mut **tx.conn())
+        .await?;
+        let watermarks: EmbeddingWatermarks = sqlx::query_as(
+            r#"
+            SELECT high_water_id, low_water_id, backfill_completed_at IS NOT NULL AS backfill_complete
+            FROM embedding_index_progress
+            WHERE modality = ?1
+            "#,
+        )
+        .bind(modality.as_str())
+        .fetch_one(&mut **tx.conn())
+        .await?;
+        tx.commit().await?;
+        Ok(watermarks)
+    }
+
+    /// Rows recorded after row `after_id` that still need an embedding of `model`,
+    /// oldest first.
+    pub async fn get_new_embedding_candidates(
+        &self,
+        modality: EmbeddingModality,
+        model: &str,
+        after_id: i64,
+        limit: u32,
+    ) -> Result<Vec<EmbeddingCandidate>, sqlx::Error> {
+        sqlx::query_as(&modality.candidates_sql(true))
+            .bind(after_id)
+            .bind(limit)
+            .bind(model)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Rows recorded before row `before_id` that still need an embedding of `model`,
+    /// newest first.
+    pub async fn get_backfill_embedding_candidates(
+        &self,
+        modality: EmbeddingModality,
+        model: &str,
+        before_id: i64,
+        limit: u32,
+    ) -> Result<Vec<EmbeddingCandidate>, sqlx::Error> {
+        sqlx::query_as(&modality.candidates_sql(false))
+            .bind(before_id)
+            .bind(limit)
+            .bind(model)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Stores a batch of embeddings made with `model`, keyed by candidate id, and moves
+    /// the watermarks past the rows it covered.
+    pub async fn insert_indexed_embeddings(
+        &self,
+        modality: EmbeddingModality,
+        model: &str,
+        embeddings: &[(i64, Vec<f32>)],
+        high_water_id: Option<i64>,
+        low_water_id: Option<i64>,
+    ) -> Result<(), sqlx::Error> {
+        let insert_sql = match modality {
+            // ocr_text_embeddings has no unique key, rows may also come from `screenpipe add`
+            EmbeddingModality::Ocr => {
+                r#"
+                INSERT INTO ocr_text_embeddings (frame_id, embedding, embedding_model)
+                SELECT ?1, ?2, ?3
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM ocr_text_embeddings WHERE frame_id = ?1 AND embedding_model = ?3
+                )
+                "#
+            }
+            EmbeddingModality::Audio => {
+                "INSERT OR IGNORE INTO audio_transcription_embeddings (audio_transcription_id, embedding, embedding_model) VALUES (?1, ?2, ?3)"
+            }
+        };
+
+        let mut tx = self.begin_immediate_with_retry().await?;
+        for (id, embedding) in embeddings {
+            sqlx::query(insert_sql)
+                .bind(id)
+                .bind(embedding.as_bytes())
+                .bind(model)
+                .execute(&mut **tx.conn())
+                .await?;
+        }
+        sqlx::query(
+            r#"
+            UPDATE embedding_index_progress
+            SET high_water_id = MAX(high_water_id, COALESCE(?2, high_water_id)),
+                low_water_id = MIN(low_water_id, COALESCE(?3, low_water_id)),
+                updated_at = ?4
+            WHERE modality = ?1
+            "#,
+        )
+        .bind(modality.as_str())
+        .bind(high_water_id)
+        .bind(low_water_id)
+        .bind(Utc::now())
+        .execute(&mut **tx.conn())
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Records that nothing is left to backfill for a modality.
+    pub async fn mark_embedding_backfill_complete(
+        &self,
+        modality: EmbeddingModality,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        sqlx::query(
+            r#"
+            UPDATE embedding_index_progress
+            SET backfill_completed_at = COALESCE(backfill_completed_at, ?2), updated_at = ?2
+            WHERE modality = ?1
+            "#,
+        )
+        .bind(modality.as_str())
+        .bind(Utc::now())
+        .execute(&mut **tx.conn())
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Indexing progress of each modality started, counting vectors of `model`.
+    pub async fn get_embedding_index_progress(
+        &self,
+        model: &str,
+    ) -> Result<Vec<EmbeddingIndexProgress>, sqlx::Error> {
+        let mut progress = Vec::new();
+        for modality in EmbeddingModality::ALL {
+            let Some(row) = sqlx::query_as::<_, ProgressRow>(
+                r#"
+                SELECT high_water_id, low_water_id, backfill_completed_at, updated_at
+                FROM embedding_index_progress
+                WHERE modality = ?1
+                "#,
+            )
+            .bind(modality.as_str())
+            .fetch_optional(&self.pool)
+            .await?
+            else {
+                continue;
+            };
+
+            let (total, indexed): (i64, i64) = match modality {
+                EmbeddingModality::Ocr => {
+                    sqlx::query_as(
+                        r#"
+                        SELECT
+                            (SELECT COUNT(DISTINCT frame_id) FROM ocr_text WHERE text != ''),
+                            (SELECT COUNT(DISTINCT frame_id) FROM ocr_text_embeddings WHERE embedding_model = ?1)
+                        "#,
+                    )
+                    .bind(model)
+                    .fetch_one(&self.pool)
+                    .await?
+                }
+                EmbeddingModality::Audio => {
+                    sqlx::query_as(
+                        r#"
+                        SELECT
+                            (SELECT COUNT(*) FROM audio_transcriptions WHERE TRIM(transcription) != ''),
+                            (SELECT COUNT(*) FROM audio_transcription_embeddings WHERE embedding_model = ?1)
+                        "#,
+                    )
+                    .bind(model)
+                    .fetch_one(&self.pool)
+                    .await?
+                }
+            };
+
+            progress.push(EmbeddingIndexProgress {
+                modality,
+                total,
+                indexed,
+                high_water_id: row.high_water_id,
+                low_water_id: row.low_water_id,
+                backfill_completed_at: row.backfill_completed_at,
+                updated_at: row.updated_at,
+            });
+        }
+        Ok(progress)
+    }
+}
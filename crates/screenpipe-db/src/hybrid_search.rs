@@ -0,0 +1,721 @@
+//! Hybrid search: FTS5 bm25 rankings of OCR, audio and UI text fused with
+//! vector similarity rankings using reciprocal rank fusion (RRF).
+//!
+//! Each modality contributes ranked candidate lists; a hit's fused score is
+//! `sum(1 / (RRF_K + rank))` over the lists it appears in, so a result found by
+//! both keyword and semantic search outranks one found by either alone, and
+//! scores are comparable across modalities without normalizing bm25.
+//!
+//! Input events have no free text to rank, so `input` content types only
+//! contribute their vision and audio parts.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use oasgen::OaSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use zerocopy::AsBytes;
+
+use crate::{
+    AudioResult, AudioResultRaw, ContentType, DatabaseManager, DeviceType, OCRResult, OCRResultRaw,
+    SearchResult, Speaker, UiContent,
+};
+
+/// Standard RRF constant, dampens the weight of top ranks.
+pub const RRF_K: f64 = 60.0;
+
+/// Upper bound on candidates fetched per ranked list.
+const MAX_CANDIDATES: u32 = 1000;
+
+#[derive(Debug, Clone, Default)]
+pub struct HybridSearchQuery<'a> {
+    /// FTS5 query; empty to rank by vector similarity only
+    pub query: &'a str,
+    /// Embedding of the query text; `None` to rank by bm25 only
+    pub embedding: Option<Vec<f32>>,
+    /// Model of `embedding`, only vectors of the same model are compared. `None`
+    /// compares all stored vectors
+    pub embedding_model: Option<&'a str>,
+    /// Max cosine distance for a vector match to count
+    pub max_distance: f32,
+    pub content_type: ContentType,
+    pub limit: u32,
+    pub offset: u32,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub app_name: Option<&'a str>,
+    pub window_name: Option<&'a str>,
+    /// Hide results from apps whose name contains this, e.g. screenpipe itself.
+    /// Applied before paging, unlike filtering the returned hits
+    pub exclude_app_name: Option<&'a str>,
+    pub speaker_ids: Option<Vec<i64>>,
+}
+
+/// How a hit was ranked. Ranks are 1-based positions in each candidate list.
+#[derive(OaSchema, Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HybridScore {
+    /// Fused RRF score, higher is better
+    pub score: f64,
+    pub fts_rank: Option<u32>,
+    /// Raw FTS5 bm25, lower is better
+    pub bm25: Option<f64>,
+    pub vector_rank: Option<u32>,
+    pub cosine_distance: Option<f64>,
+}
+
+#[derive(OaSchema, Debug, Serialize, Deserialize)]
+pub struct HybridSearchHit {
+    pub result: SearchResult,
+    pub score: HybridScore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum HitKey {
+    /// frames.id
+    Ocr(i64),
+    /// audio_transcriptions.id
+    Audio(i64),
+    /// ui_monitoring.id
+    Ui(i64),
+}
+
+#[derive(FromRow)]
+struct RankedId {
+    id: i64,
+    score: f64,
+}
+
+/// A candidate list and the modality its ids belong to.
+type RankedList = (fn(i64) -> HitKey, Vec<RankedId>);
+
+#[derive(FromRow)]
+struct AudioResultWithId {
+    id: i64,
+    #[sqlx(flatten)]
+    raw: AudioResultRaw,
+}
+
+fn includes_ocr(content_type: &ContentType) -> bool {
+    matches!(
+        content_type,
+        ContentType::All
+            | ContentType::OCR
+            | ContentType::Vision
+            | ContentType::OcrAndUi
+            | ContentType::AudioAndOcr
+            | ContentType::VisionAudioInput
+            | ContentType::VisionAndInput
+    )
+}
+
+fn includes_audio(content_type: &ContentType) -> bool {
+    matches!(
+        content_type,
+        ContentType::All
+            | ContentType::Audio
+            | ContentType::AudioAndUi
+            | ContentType::AudioAndOcr
+            | ContentType::VisionAudioInput
+            | ContentType::AudioAndInput
+    )
+}
+
+fn includes_ui(content_type: &ContentType) -> bool {
+    matches!(
+        content_type,
+        ContentType::All
+            | ContentType::UI
+            | ContentType::Vision
+            | ContentType::OcrAndUi
+            | ContentType::AudioAndUi
+            | ContentType::VisionAudioInput
+            | ContentType::VisionAndInput
+    )
+}
+
+fn speaker_ids_json(params: &HybridSearchQuery<'_>) -> Option<String> {
+    params
+        .speaker_ids
+        .as_ref()
+        .map(|ids| serde_json::to_string(ids).unwrap_or_else(|_| "[]".to_string()))
+}
+
+/// Fuses ranked lists of ids. Returns keys ordered by descending fused score.
+fn fuse(fts_lists: Vec<RankedList>, vector_lists: Vec<RankedList>) -> Vec<(HitKey, HybridScore)> {
+    let mut scores: HashMap<HitKey, HybridScore> = HashMap::new();
+
+    for (to_key, list) in fts_lists {
+        for (i, hit) in list.into_iter().enumerate() {
+            let rank = i as u32 + 1;
+            let entry = scores.entry(to_key(hit.id)).or_default();
+            entry.score += 1.0 / (RRF_K + rank as f64);
+            entry.fts_rank = Some(rank);
+            entry.bm25 = Some(hit.score);
+        }
+    }
+    for (to_key, list) in vector_lists {
+        for (i, hit) in list.into_iter().enumerate() {
+            let rank = i as u32 + 1;
+            let entry = scores.entry(to_key(hit.id)).or_default();
+            entry.score += 1.0 / (RRF_K + rank as f64);
+            entry.vector_rank = Some(rank);
+            entry.cosine_distance = Some(hit.score);
+        }
+    }
+
+    let mut fused: Vec<(HitKey, HybridScore)> = scores.into_iter().collect();
+    fused.sort_by(|a, b| {
+        b.1.score
+            .partial_cmp(&a.1.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            // Stable order for ties: better keyword rank first, then by content, so
+            // pages don't overlap or skip hits
+            .then_with(|| {
+                a.1.fts_rank
+                    .unwrap_or(u32::MAX)
+                    .cmp(&b.1.fts_rank.unwrap_or(u32::MAX))
+            })
+            .then_with(|| a.0.cmp(&b.0))
+    });
+    fused
+}
+
+impl DatabaseManager {
+    /// Searches OCR, audio and UI text with keyword and vector rankings fused
+    /// into one relevance-ordered list.
+    pub async fn hybrid_search(
+        &self,
+        params: &HybridSearchQuery<'_>,
+    ) -> Result<Vec<HybridSearchHit>, sqlx::Error> {
+        let candidates = params
+            .offset
+            .saturating_add(params.limit)
+            .saturating_mul(2)
+            .clamp(50, MAX_CANDIDATES);
+        let has_query = !params.query.trim().is_empty();
+        let search_ocr = includes_ocr(&params.content_type);
+        // Audio has no app or window, same as the regular search
+        let search_audio = includes_audio(&params.content_type)
+            && params.app_name.is_none()
+            && params.window_name.is_none();
+        let search_ui = includes_ui(&params.content_type);
+
+        let mut fts_lists: Vec<RankedList> = Vec::new();
+        let mut vector_lists: Vec<RankedList> = Vec::new();
+
+        if has_query {
+            if search_ocr {
+                fts_lists.push((HitKey::Ocr, self.ocr_fts_ranking(params, candidates).await?));
+            }
+            if search_audio {
+                fts_lists.push((
+                    HitKey::Audio,
+                    self.audio_fts_ranking(params, candidates).await?,
+                ));
+            }
+            if search_ui {
+                fts_lists.push((HitKey::Ui, self.ui_fts_ranking(params, candidates).await?));
+            }
+        }
+        if let Some(embedding) = &params.embedding {
+            if search_ocr {
+                vector_lists.push((
+                    HitKey::Ocr,
+                    self.ocr_vector_ranking(params, embedding, candidates)
+                        .await?,
+                ));
+            }
+            if search_audio {
+                vector_lists.push((
+                    HitKey::Audio,
+                    self.audio_vector_ranking(params, embedding, candidates)
+                        .await?,
+                ));
+            }
+        }
+
+        let page: Vec<(HitKey, HybridScore)> = fuse(fts_lists, vector_lists)
+            .into_iter()
+            .skip(params.offset as usize)
+            .take(params.limit as usize)
+            .collect();
+
+        let mut ocr_ids = Vec::new();
+        let mut audio_ids = Vec::new();
+        let mut ui_ids = Vec::new();
+        for (key, _) in &page {
+            match key {
+                HitKey::Ocr(id) => ocr_ids.push(*id),
+                HitKey::Audio(id) => audio_ids.push(*id),
+                HitKey::Ui(id) => ui_ids.push(*id),
+            }
+        }
+
+        let (mut ocr, mut audio, mut ui) = tokio::try_join!(
+            self.get_ocr_results_by_frame_ids(&ocr_ids),
+            self.get_audio_results_by_ids(&audio_ids, params.query),
+            self.get_ui_results_by_ids(&ui_ids),
+        )?;
+
+        Ok(page
+            .into_iter()
+            .filter_map(|(key, score)| {
+                let result = match key {
+                    HitKey::Ocr(id) => SearchResult::OCR(ocr.remove(&id)?),
+                    HitKey::Audio(id) => SearchResult::Audio(audio.remove(&id)?),
+                    HitKey::Ui(id) => SearchResult::UI(ui.remove(&id)?),
+                };
+                Some(HybridSearchHit { result, score })
+            })
+            .collect())
+    }
+
+    async fn ocr_fts_ranking(
+        &self,
+        params: &HybridSearchQuery<'_>,
+        candidates: u32,
+    ) -> Result<Vec<RankedId>, sqlx::Error> {
+        // FTS5 `rank` is bm25() by default, and unlike bm25() can be aggregated
+        sqlx::query_as(
+            r#"
+            SELECT matches.frame_id AS id, MIN(matches.score) AS score
+            FROM (
+                SELECT frame_id, rank AS score
+                FROM ocr_text_fts
+                WHERE ocr_text_fts MATCH ?1
+            ) AS matches
+            JOIN frames ON frames.id = matches.frame_id
+            WHERE (?2 IS NULL OR frames.timestamp >= ?2)
+              AND (?3 IS NULL OR frames.timestamp <= ?3)
+              AND (?4 IS NULL OR frames.app_name LIKE '%' || ?4 || '%')
+              AND (?5 IS NULL OR frames.window_name LIKE '%' || ?5 || '%')
+              AND (?6 IS NULL OR COALESCE(frames.app_name, '') NOT LIKE '%' || ?6 || '%')
+            GROUP BY matches.frame_id
+            ORDER BY score
+            LIMIT ?7
+            "#,
+        )
+        .bind(params.query)
+        .bind(params.start_time)
+        .bind(params.end_time)
+        .bind(params.app_name)
+        .bind(params.window_name)
+        .bind(params.exclude_app_name)
+        .bind(candidates)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn audio_fts_ranking(
+        &self,
+        params: &HybridSearchQuery<'_>,
+        candidates: u32,
+    ) -> Result<Vec<RankedId>, sqlx::Error> {
+        let speaker_ids_json = speaker_ids_json(params);
+
+        // FTS rows are keyed by chunk, (chunk, text) is unique in audio_transcriptions
+        sqlx::query_as(
+            r#"
+            SELECT audio_transcriptions.id AS id, MIN(matches.score) AS score
+            FROM (
+                SELECT audio_chunk_id, transcription, rank AS score
+                FROM audio_transcriptions_fts
+                WHERE audio_transcriptions_fts MATCH ?1
+            ) AS matches
+            JOIN audio_transcriptions
+                ON audio_transcriptions.audio_chunk_id = matches.audio_chunk_id
+                AND audio_transcriptions.transcription = matches.transcription
+            LEFT JOIN speakers ON audio_transcriptions.speaker_id = speakers.id
+            WHERE (?2 IS NULL OR audio_transcriptions.timestamp >= ?2)
+              AND (?3 IS NULL OR audio_transcriptions.timestamp <= ?3)
+              AND (speakers.id IS NULL OR speakers.hallucination = 0)
+              AND (?4 IS NULL OR json_array_length(?4) = 0
+                   OR audio_transcriptions.speaker_id IN (SELECT value FROM json_each(?4)))
+            GROUP BY audio_transcriptions.id
+            ORDER BY score
+            LIMIT ?5
+            "#,
+        )
+        .bind(params.query)
+        .bind(params.start_time)
+        .bind(params.end_time)
+        .bind(speaker_ids_json)
+        .bind(candidates)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn ui_fts_ranking(
+        &self,
+        params: &HybridSearchQuery<'_>,
+        candidates: u32,
+    ) -> Result<Vec<RankedId>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT ui_monitoring.id AS id, ui_monitoring_fts.rank AS score
+            FROM ui_monitoring_fts
+            JOIN ui_monitoring ON ui_monitoring_fts.ui_id = ui_monitoring.id
+            WHERE ui_monitoring_fts MATCH ?1
+              AND (?2 IS NULL OR ui_monitoring.timestamp >= ?2)
+              AND (?3 IS NULL OR ui_monitoring.timestamp <= ?3)
+              AND (?4 IS NULL OR ui_monitoring.app LIKE '%' || ?4 || '%')
+              AND (?5 IS NULL OR ui_monitoring.window LIKE '%' || ?5 || '%')
+              AND (?6 IS NULL OR COALESCE(ui_monitoring.app, '') NOT LIKE '%' || ?6 || '%')
+            ORDER BY score
+            LIMIT ?7
+            "#,
+        )
+        .bind(params.query)
+        .bind(params.start_time)
+        .bind(params.end_time)
+        .bind(params.app_name)
+        .bind(params.window_name)
+        .bind(params.exclude_app_name)
+        .bind(candidates)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn ocr_vector_ranking(
+        &self,
+        params: &HybridSearchQuery<'_>,
+        embedding: &[f32],
+        candidates: u32,
+    ) -> Result<Vec<RankedId>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT frame_id AS id, score
+            FROM (
+                SELECT
+                    ocr_text_embeddings.frame_id,
+                    MIN(vec_distance_cosine(ocr_text_embeddings.embedding, vec_f32(?1))) AS score
+                FROM ocr_text_embeddings
+                JOIN frames ON frames.id = ocr_text_embeddings.frame_id
+                WHERE (?2 IS NULL OR frames.timestamp >= ?2)
+                  AND (?3 IS NULL OR frames.timestamp <= ?3)
+                  AND (?4 IS NULL OR frames.app_name LIKE '%' || ?4 || '%')
+                  AND (?5 IS NULL OR frames.window_name LIKE '%' || ?5 || '%')
+                  AND (?6 IS NULL OR COALESCE(frames.app_name, '') NOT LIKE '%' || ?6 || '%')
+                  AND (?9 IS NULL OR ocr_text_embeddings.embedding_model = ?9)
+                GROUP BY ocr_text_embeddings.frame_id
+            )
+            WHERE score < ?7
+            ORDER BY score
+            LIMIT ?8
+            "#,
+        )
+        .bind(embedding.as_bytes())
+        .bind(params.start_time)
+        .bind(params.end_time)
+        .bind(params.app_name)
+        .bind(params.window_name)
+        .bind(params.exclude_app_name)
+        .bind(params.max_distance)
+        .bind(candidates)
+        .bind(params.embedding_model)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn audio_vector_ranking(
+        &self,
+        params: &HybridSearchQuery<'_>,
+        embedding: &[f32],
+        candidates: u32,
+    ) -> Result<Vec<RankedId>, sqlx::Error> {
+        let speaker_ids_json = speaker_ids_json(params);
+
+        sqlx::query_as(
+            r#"
+            SELECT id, score
+            FROM (
+                SELECT
+                    audio_transcriptions.id,
+                    vec_distance_cosine(audio_transcription_embeddings.embedding, vec_f32(?1)) AS score
+                FROM audio_transcription_embeddings
+                JOIN audio_transcriptions
+                    ON audio_transcriptions.id = audio_transcription_embeddings.audio_transcription_id
+                LEFT JOIN speakers ON audio_transcriptions.speaker_id = speakers.id
+                WHERE (?2 IS NULL OR audio_transcriptions.timestamp >= ?2)
+                  AND (?3 IS NULL OR audio_transcriptions.timestamp <= ?3)
+                  AND (speakers.id IS NULL OR speakers.hallucination = 0)
+                  AND (?4 IS NULL OR json_array_length(?4) = 0
+                       OR audio_transcriptions.speaker_id IN (SELECT value FROM json_each(?4)))
+                  AND (?7 IS NULL OR audio_transcription_embeddings.embedding_model = ?7)
+            )
+            WHERE score < ?5
+            ORDER BY score
+            LIMIT ?6
+            "#,
+        )
+        .bind(embedding.as_bytes())
+        .bind(params.start_time)
+        .bind(params.end_time)
+        .bind(speaker_ids_json)
+        .bind(params.max_distance)
+        .bind(candidates)
+        .bind(params.embedding_model)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn get_ocr_results_by_frame_ids(
+        &self,
+        frame_ids: &[i64],
+    ) -> Result<HashMap<i64, OCRResult>, sqlx::Error> {
+        if frame_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let raw_results: Vec<OCRResultRaw> = sqlx::query_as(
+            r#"
+            SELECT
+                ocr_text.frame_id,
+                ocr_text.text as ocr_text,
+                ocr_text.text_json,
+                frames.timestamp,
+                frames.name as frame_name,
+                video_chunks.file_path,
+                frames.offset_index,
+                frames.app_name,
+                ocr_text.ocr_engine,
+                frames.window_name,
+                video_chunks.device_name,
+                GROUP_CONCAT(tags.name, ',') as tags,
+                frames.browser_url,
+                frames.focused
+            FROM frames
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            JOIN ocr_text ON frames.id = ocr_text.frame_id
+            LEFT JOIN vision_tags ON frames.id = vision_tags.vision_id
+            LEFT JOIN tags ON vision_tags.tag_id = tags.id
+            WHERE frames.id IN (SELECT value FROM json_each(?1))
+            GROUP BY frames.id
+            "#,
+        )
+        .bind(serde_json::to_string(frame_ids).unwrap_or_default())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(raw_results
+            .into_iter()
+            .map(|raw| {
+                (
+                    raw.frame_id,
+                    OCRResult {
+                        frame_id: raw.frame_id,
+                        ocr_text: raw.ocr_text,
+                        text_json: raw.text_json,
+                        timestamp: raw.timestamp,
+                        frame_name: raw.frame_name,
+                        file_path: raw.file_path,
+                        offset_index: raw.offset_index,
+                        app_name: raw.app_name,
+                        ocr_engine: raw.ocr_engine,
+                        window_name: raw.window_name,
+                        device_name: raw.device_name,
+                        tags: raw
+                            .tags
+                            .map(|t| t.split(',').map(String::from).collect())
+                            .unwrap_or_default(),
+                        browser_url: raw.browser_url,
+                        focused: raw.focused,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    async fn get_audio_results_by_ids(
+        &self,
+        ids: &[i64],
+        query: &str,
+    ) -> Result<HashMap<i64, AudioResult>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let rows: Vec<AudioResultWithId> = sqlx::query_as(
+            r#"
+            SELECT
+                audio_transcriptions.id,
+                audio_transcriptions.audio_chunk_id,
+                audio_transcriptions.transcription,
+                audio_transcriptions.timestamp,
+                audio_chunks.file_path,
+                audio_transcriptions.offset_index,
+                audio_transcriptions.transcription_engine,
+                GROUP_CONCAT(tags.name, ',') as tags,
+                audio_transcriptions.device as device_name,
+                audio_transcriptions.is_input_device,
+                audio_transcriptions.speaker_id,
+                audio_transcriptions.start_time,
+                audio_transcriptions.end_time,
+                audio_transcriptions.id as transcription_id
+            FROM audio_transcriptions
+            JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            LEFT JOIN audio_tags ON audio_chunks.id = audio_tags.audio_chunk_id
+            LEFT JOIN tags ON audio_tags.tag_id = tags.id
+            WHERE audio_transcriptions.id IN (SELECT value FROM json_each(?1))
+            GROUP BY audio_transcriptions.id
+            "#,
+        )
+        .bind(serde_json::to_string(ids).unwrap_or_default())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let speaker_ids: Vec<i64> = rows.iter().filter_map(|row| row.raw.speaker_id).collect();
+        let (seek_times, speakers) = tokio::try_join!(
+            self.get_audio_seek_times(ids, query),
+            self.get_speakers_by_ids(&speaker_ids),
+        )?;
+        let mut results = HashMap::with_capacity(rows.len());
+        for AudioResultWithId { id, raw } in rows {
+            let speaker = raw
+                .speaker_id
+                .and_then(|speaker_id| speakers.get(&speaker_id).cloned());
+            let seek_time = seek_times.get(&id).copied();
+            results.insert(
+                id,
+                AudioResult {
+                    audio_chunk_id: raw.audio_chunk_id,
+                    transcription: raw.transcription,
+                    timestamp: raw.timestamp,
+                    file_path: raw.file_path,
+                    offset_index: raw.offset_index,
+                    transcription_engine: raw.transcription_engine,
+                    tags: raw
+                        .tags
+                        .map(|s| s.split(',').map(|s| s.to_owned()).collect())
+                        .unwrap_or_default(),
+                    device_name: raw.device_name,
+                    device_type: if raw.is_input_device {
+                        DeviceType::Input
+                    } else {
+                        DeviceType::Output
+                    },
+                    speaker,
+                    start_time: raw.start_time,
+                    end_time: raw.end_time,
+                    transcription_id: id,
+                    seek_time,
+                },
+            );
+        }
+        Ok(results)
+    }
+
+    async fn get_speakers_by_ids(&self, ids: &[i64]) -> Result<HashMap<i64, Speaker>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let speakers: Vec<Speaker> = sqlx::query_as(
+            "SELECT id, name, metadata FROM speakers WHERE id IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(serde_json::to_string(ids).unwrap_or_default())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(speakers
+            .into_iter()
+            .map(|speaker| (speaker.id, speaker))
+            .collect())
+    }
+
+    async fn get_ui_results_by_ids(
+        &self,
+        ids: &[i64],
+    ) -> Result<HashMap<i64, UiContent>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let rows: Vec<UiContent> = sqlx::query_as(
+            r#"
+            SELECT
+                ui_monitoring.id,
+                ui_monitoring.text_output,
+                ui_monitoring.timestamp,
+                ui_monitoring.app as app_name,
+                ui_monitoring.window as window_name,
+                ui_monitoring.initial_traversal_at,
+                video_chunks.file_path,
+                frames.offset_index,
+                frames.name as frame_name,
+                frames.browser_url
+            FROM ui_monitoring
+            LEFT JOIN frames ON
+                frames.timestamp BETWEEN
+                    datetime(ui_monitoring.timestamp, '-1 seconds')
+                    AND datetime(ui_monitoring.timestamp, '+1 seconds')
+            LEFT JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            WHERE ui_monitoring.id IN (SELECT value FROM json_each(?1))
+            GROUP BY ui_monitoring.id
+            "#,
+        )
+        .bind(serde_json::to_string(ids).unwrap_or_default())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(|ui| (ui.id, ui)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked(ids: &[i64]) -> Vec<RankedId> {
+        ids.iter()
+            .map(|&id| RankedId {
+                id,
+                score: id as f64,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fuse_prefers_hits_in_both_lists() {
+        let fused = fuse(
+            vec![
+                (HitKey::Ocr, ranked(&[1, 2, 3])),
+                (HitKey::Audio, ranked(&[1])),
+            ],
+            vec![(HitKey::Ocr, ranked(&[3, 4]))],
+        );
+        let keys: Vec<HitKey> = fused.iter().map(|(k, _)| *k).collect();
+        // ocr 3 is in both lists, ocr 1 and audio 1 tie on rank 1 of one list
+        assert_eq!(keys[0], HitKey::Ocr(3));
+        assert_eq!(keys[1..3], [HitKey::Ocr(1), HitKey::Audio(1)]);
+        assert_eq!(keys.len(), 5);
+
+        let (_, score) = &fused[0];
+        assert_eq!(score.fts_rank, Some(3));
+        assert_eq!(score.vector_rank, Some(1));
+        assert_eq!(score.cosine_distance, Some(3.0));
+        let expected = 1.0 / (RRF_K + 3.0) + 1.0 / (RRF_K + 1.0);
+        assert!((score.score - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fuse_orders_ties_the_same_way_every_time() {
+        let lists = || {
+            vec![
+                (HitKey::Ui, ranked(&[7])),
+                (HitKey::Audio, ranked(&[4])),
+                (HitKey::Ocr, ranked(&[9])),
+                (HitKey::Ocr, ranked(&[2])),
+            ]
+        };
+        let keys: Vec<HitKey> = fuse(lists(), vec![]).into_iter().map(|(k, _)| k).collect();
+        assert_eq!(
+            keys,
+            vec![
+                HitKey::Ocr(2),
+                HitKey::Ocr(9),
+                HitKey::Audio(4),
+                HitKey::Ui(7)
+            ]
+        );
+        for _ in 0..10 {
+            let again: Vec<HitKey> = fuse(lists(), vec![]).into_iter().map(|(k, _)| k).collect();
+            assert_eq!(again, keys);
+        }
+    }
+}
@@ -1,14 +1,41 @@
+mod api_tokens;
 mod db;
+mod embedding_index;
+mod hybrid_search;
+mod meetings;
 mod migration_worker;
+mod ocr_layout;
+mod raw_sql;
+mod retention;
+mod speaker_clustering;
 pub mod text_normalizer;
 pub mod text_similarity;
 mod types;
 mod video_db;
 
+pub use api_tokens::ApiToken;
 pub use db::{parse_all_text_positions, DatabaseManager, ImmediateTx};
+pub use embedding_index::{
+    EmbeddingCandidate, EmbeddingIndexProgress, EmbeddingModality, EmbeddingWatermarks,
+};
+pub use hybrid_search::{HybridScore, HybridSearchHit, HybridSearchQuery, RRF_K};
+pub use meetings::{Meeting, MeetingDetails, MeetingFrame, MeetingParticipant, MeetingTranscript};
 pub use migration_worker::{
     create_migration_worker, MigrationCommand, MigrationConfig, MigrationResponse, MigrationStatus,
     MigrationWorker,
 };
+pub use ocr_layout::{
+    is_fts_query_error, layout_to_markdown, LayoutBlockKind, OcrLayoutBlock, OcrLayoutSearchResult,
+};
+pub use raw_sql::{
+    RawSqlColumn, RawSqlError, RawSqlOptions, RawSqlPage, DEFAULT_RAW_SQL_ROWS,
+    DEFAULT_RAW_SQL_TIMEOUT, MAX_RAW_SQL_ROWS, MAX_RAW_SQL_TIMEOUT,
+};
+pub use retention::{AppRetentionOverride, RetentionPolicy, RetentionReport};
+pub use speaker_clustering::{
+    SpeakerClusteringConfig, SpeakerClusteringError, SpeakerClusteringReport, SpeakerMergeProposal,
+    SpeakerMergeProposalStatus, SpeakerMergeResolution, DEFAULT_SPEAKER_CLUSTERING_THRESHOLD,
+    MAX_CLUSTERED_SPEAKERS,
+};
 pub use text_normalizer::expand_search_query;
 pub use types::*;
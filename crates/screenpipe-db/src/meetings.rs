@@ -0,0 +1,250 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::DatabaseManager;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Meeting {
+    pub id: i64,
+    /// App the meeting was detected in, `None` when detected from audio only
+    pub app: Option<String>,
+    pub start_time: DateTime<Utc>,
+    /// `None` while the meeting is in progress
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+/// A speaker heard during a meeting.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MeetingParticipant {
+    pub speaker_id: i64,
+    pub name: Option<String>,
+    pub transcription_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MeetingFrame {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MeetingTranscript {
+    pub id: i64,
+    pub audio_chunk_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub transcription: String,
+    pub device: String,
+    pub is_input_device: bool,
+    pub speaker_id: Option<i64>,
+    pub speaker_name: Option<String>,
+    /// Offsets in seconds within the audio chunk
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeetingDetails {
+    #[serde(flatten)]
+    pub meeting: Meeting,
+    pub participants: Vec<MeetingParticipant>,
+    pub frames: Vec<MeetingFrame>,
+    pub transcripts: Vec<MeetingTranscript>,
+}
+
+impl DatabaseManager {
+    /// Records the start of a meeting. Any meeting still open is ended first,
+    /// since the detector only tracks one meeting at a time.
+    pub async fn start_meeting(
+        &self,
+        app: Option<&str>,
+        start_time: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        sqlx::query("UPDATE meetings SET end_time = ?1 WHERE end_time IS NULL")
+            .bind(start_time)
+            .execute(&mut **tx.conn())
+            .await?;
+        let id = sqlx::query("INSERT INTO meetings (app, start_time) VALUES (?1, ?2)")
+            .bind(app)
+            .bind(start_time)
+            .execute(&mut **tx.conn())
+            .await?
+            .last_insert_rowid();
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Ends the meeting in progress. Returns its id, or `None` if no meeting was open.
+    pub async fn end_meeting(&self, end_time: DateTime<Utc>) -> Result<Option<i64>, sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        let id: Option<i64> = sqlx::query_scalar(
+            "UPDATE meetings SET end_time = ?1 WHERE end_time IS NULL RETURNING id",
+        )
+        .bind(end_time)
+        .fetch_optional(&mut **tx.conn())
+        .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Ends meetings left open by a previous run at their own last activity: the last
+    /// frame of the meeting app, or the last transcription for audio-only meetings,
+    /// before the next meeting started.
+    pub async fn close_stale_meetings(&self) -> Result<u64, sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        let closed = sqlx::query(
+            r#"
+            UPDATE meetings
+            SET end_time = MAX(
+                start_time,
+                COALESCE(
+                    CASE WHEN app IS NOT NULL THEN (
+                        SELECT MAX(f.timestamp) FROM frames f
+                        WHERE f.timestamp >= meetings.start_time
+                          AND f.app_name = meetings.app COLLATE NOCASE
+                          AND NOT EXISTS (
+                              SELECT 1 FROM meetings m
+                              WHERE m.start_time > meetings.start_time AND m.start_time <= f.timestamp
+                          )
+                    ) ELSE (
+                        SELECT MAX(t.timestamp) FROM audio_transcriptions t
+                        WHERE t.timestamp >= meetings.start_time
+                          AND NOT EXISTS (
+                              SELECT 1 FROM meetings m
+                              WHERE m.start_time > meetings.start_time AND m.start_time <= t.timestamp
+                          )
+                    ) END,
+                    start_time
+                )
+            )
+            WHERE end_time IS NULL
+            "#,
+        )
+        .execute(&mut **tx.conn())
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+        Ok(closed)
+    }
+
+    /// Lists meetings overlapping the given range, newest first.
+    pub async fn list_meetings(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app: Option<&str>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Meeting>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, app, start_time, end_time
+            FROM meetings
+            WHERE (?1 IS NULL OR end_time IS NULL OR end_time >= ?1)
+              AND (?2 IS NULL OR start_time <= ?2)
+              AND (?3 IS NULL OR app LIKE '%' || ?3 || '%')
+            ORDER BY start_time DESC
+            LIMIT ?4 OFFSET ?5
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(app)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// The meeting alone, without its participants, frames and transcripts.
+    pub async fn get_meeting_by_id(&self, id: i64) -> Result<Option<Meeting>, sqlx::Error> {
+        sqlx::query_as("SELECT id, app, start_time, end_time FROM meetings WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn get_meeting(&self, id: i64) -> Result<Option<MeetingDetails>, sqlx::Error> {
+        let Some(meeting) = self.get_meeting_by_id(id).await? else {
+            return Ok(None);
+        };
+
+        let end_time = meeting.end_time.unwrap_or_else(Utc::now);
+
+        let participants = sqlx::query_as(
+            r#"
+            SELECT at.speaker_id AS speaker_id, s.name AS name, COUNT(*) AS transcription_count
+            FROM audio_transcriptions at
+            LEFT JOIN speakers s ON s.id = at.speaker_id
+            WHERE at.timestamp BETWEEN ?1 AND ?2 AND at.speaker_id IS NOT NULL
+            GROUP BY at.speaker_id
+            ORDER BY transcription_count DESC
+            "#,
+        )
+        .bind(meeting.start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Audio-only meetings link every frame in the window.
+        let frames = sqlx::query_as(
+            r#"
+            SELECT id, timestamp, app_name, window_name
+            FROM frames
+            WHERE timestamp BETWEEN ?1 AND ?2
+              AND (?3 IS NULL OR app_name = ?3 COLLATE NOCASE)
+            ORDER BY timestamp
+            "#,
+        )
+        .bind(meeting.start_time)
+        .bind(end_time)
+        .bind(&meeting.app)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let transcripts = self
+            .get_meeting_transcripts(meeting.start_time, end_time)
+            .await?;
+
+        Ok(Some(MeetingDetails {
+            meeting,
+            participants,
+            frames,
+            transcripts,
+        }))
+    }
+
+    /// Transcriptions from all audio devices in the window, merged chronologically.
+    pub async fn get_meeting_transcripts(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MeetingTranscript>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                at.id,
+                at.audio_chunk_id,
+                at.timestamp,
+                at.transcription,
+                at.device,
+                at.is_input_device,
+                at.speaker_id,
+                s.name AS speaker_name,
+                at.start_time,
+                at.end_time
+            FROM audio_transcriptions at
+            LEFT JOIN speakers s ON s.id = at.speaker_id
+            WHERE at.timestamp BETWEEN ?1 AND ?2
+            ORDER BY at.timestamp, at.start_time
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
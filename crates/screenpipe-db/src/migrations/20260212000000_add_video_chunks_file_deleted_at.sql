@@ -0,0 +1,6 @@
+-- Track video chunks whose mp4 was removed by the retention task.
+-- The video_chunks/frames rows may outlive the file when OCR is kept longer than video,
+-- so we need to remember that the file is gone to avoid re-reporting it on every run.
+ALTER TABLE video_chunks ADD COLUMN file_deleted_at DATETIME;
+
+CREATE INDEX IF NOT EXISTS idx_video_chunks_file_deleted_at ON video_chunks(file_deleted_at);
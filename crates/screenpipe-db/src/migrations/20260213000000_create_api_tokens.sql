@@ -0,0 +1,11 @@
+-- Bearer tokens for the HTTP API. Only the SHA-256 of the token is stored;
+-- the plaintext is shown once when the token is created.
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT NOT NULL,  -- comma separated, e.g. "read-search,read-media"
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at DATETIME,
+    revoked_at DATETIME
+);
@@ -0,0 +1,11 @@
+-- Meetings detected by the meeting detector (screenpipe-events). Participants,
+-- frames and transcripts are not copied: they are looked up by time window so
+-- speaker merges and retention stay reflected.
+CREATE TABLE IF NOT EXISTS meetings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    app TEXT,  -- NULL when the meeting was detected from audio only
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP  -- NULL while the meeting is in progress
+);
+
+CREATE INDEX IF NOT EXISTS idx_meetings_start_time ON meetings(start_time);
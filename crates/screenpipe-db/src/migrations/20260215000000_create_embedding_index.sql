@@ -0,0 +1,30 @@
+-- Vectors for the built-in embedding indexer. OCR keeps using ocr_text_embeddings,
+-- audio transcriptions get their own table.
+CREATE TABLE IF NOT EXISTS audio_transcription_embeddings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    audio_transcription_id INTEGER NOT NULL,
+    embedding BLOB NOT NULL,
+    embedding_model TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (audio_transcription_id, embedding_model),
+    FOREIGN KEY (audio_transcription_id) REFERENCES audio_transcriptions(id) ON DELETE CASCADE
+);
+
+-- Vectors of different models aren't comparable, so each row records the model it
+-- was made with and searches only compare vectors of the query's model. Rows written
+-- before the indexer came from Ollama's nomic-embed-text through `screenpipe add`;
+-- they stay searchable with that model, the indexer adds its own vectors next to them.
+ALTER TABLE ocr_text_embeddings ADD COLUMN embedding_model TEXT;
+UPDATE ocr_text_embeddings SET embedding_model = 'nomic-embed-text';
+
+CREATE INDEX IF NOT EXISTS idx_ocr_text_embeddings_frame_id ON ocr_text_embeddings(frame_id, embedding_model);
+
+-- Indexer progress per modality. Rows with ids above high_water_id are new and
+-- indexed first, rows below low_water_id are history still to be backfilled.
+CREATE TABLE IF NOT EXISTS embedding_index_progress (
+    modality TEXT PRIMARY KEY,  -- 'ocr' (ocr_text rowids) or 'audio' (audio_transcriptions ids)
+    high_water_id INTEGER NOT NULL,
+    low_water_id INTEGER NOT NULL,
+    backfill_completed_at TIMESTAMP,  -- set once nothing is left below low_water_id
+    updated_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,13 @@
+-- Word timings of audio transcriptions, for seeking to a search hit and rendering
+-- transcripts word by word. Times are in seconds from the start of the audio chunk.
+CREATE TABLE IF NOT EXISTS audio_transcription_words (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    audio_transcription_id INTEGER NOT NULL,
+    word_index INTEGER NOT NULL,
+    word TEXT NOT NULL,
+    start_time REAL NOT NULL,
+    end_time REAL NOT NULL,
+    confidence REAL,
+    FOREIGN KEY (audio_transcription_id) REFERENCES audio_transcriptions(id) ON DELETE CASCADE,
+    UNIQUE (audio_transcription_id, word_index)
+);
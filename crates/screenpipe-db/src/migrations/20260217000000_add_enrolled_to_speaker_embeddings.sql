@@ -0,0 +1,5 @@
+-- Embeddings of a known person's voice, enrolled explicitly for a named speaker rather
+-- than created by live diarization. Live diarization matches enrolled speakers first.
+ALTER TABLE speaker_embeddings ADD COLUMN enrolled BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_speaker_embeddings_enrolled ON speaker_embeddings(enrolled, speaker_id);
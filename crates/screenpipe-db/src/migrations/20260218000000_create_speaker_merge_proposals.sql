@@ -0,0 +1,16 @@
+-- Proposals to merge speakers found by offline clustering of speaker embeddings.
+-- Rejected proposals are kept so the same pair is not proposed again.
+CREATE TABLE IF NOT EXISTS speaker_merge_proposals (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- Speaker kept by the merge
+    speaker_id INTEGER NOT NULL,
+    -- Speaker merged into speaker_id
+    merge_speaker_id INTEGER NOT NULL,
+    confidence REAL NOT NULL,
+    -- pending, accepted or rejected
+    status TEXT NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    resolved_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_speaker_merge_proposals_status ON speaker_merge_proposals(status);
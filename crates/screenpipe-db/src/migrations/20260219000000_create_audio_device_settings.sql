@@ -0,0 +1,7 @@
+-- Settings of audio devices overriding the recorder ones, kept across restarts.
+-- settings is JSON written by screenpipe-audio.
+CREATE TABLE IF NOT EXISTS audio_device_settings (
+    device_name TEXT PRIMARY KEY,
+    settings TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
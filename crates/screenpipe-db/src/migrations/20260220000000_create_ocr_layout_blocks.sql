@@ -0,0 +1,34 @@
+-- Layout of the text read on a frame: paragraphs, tables and code blocks, so searches
+-- can return a whole block and exports can render it. Bounds are fractions of the
+-- window image, from its top left corner.
+CREATE TABLE IF NOT EXISTS ocr_layout_blocks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    frame_id INTEGER NOT NULL,
+    block_index INTEGER NOT NULL,
+    kind TEXT NOT NULL,  -- 'paragraph', 'table' or 'code'
+    column_index INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    rows_json TEXT,  -- cells of each row, tables only
+    x REAL NOT NULL,
+    y REAL NOT NULL,
+    width REAL NOT NULL,
+    height REAL NOT NULL,
+    FOREIGN KEY (frame_id) REFERENCES frames(id) ON DELETE CASCADE,
+    UNIQUE (frame_id, block_index)
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS ocr_layout_blocks_fts USING fts5(
+    text,
+    content='ocr_layout_blocks',
+    content_rowid='id',
+    tokenize='unicode61'
+);
+
+CREATE TRIGGER IF NOT EXISTS ocr_layout_blocks_ai AFTER INSERT ON ocr_layout_blocks BEGIN
+    INSERT INTO ocr_layout_blocks_fts(rowid, text) VALUES (NEW.id, NEW.text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS ocr_layout_blocks_ad AFTER DELETE ON ocr_layout_blocks BEGIN
+    INSERT INTO ocr_layout_blocks_fts(ocr_layout_blocks_fts, rowid, text)
+    VALUES ('delete', OLD.id, OLD.text);
+END;
@@ -0,0 +1,300 @@
+//! Layout of the text read on frames.
+//!
+//! screenpipe-vision groups the words read in a window into paragraphs per column, tables
+//! and code blocks. Blocks are stored per frame in `ocr_layout_blocks` and indexed for
+//! search, so a search can return a whole paragraph or table, and exports can render the
+//! text of a frame as markdown.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteConnection};
+
+use crate::DatabaseManager;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum LayoutBlockKind {
+    Paragraph,
+    Table,
+    Code,
+}
+
+/// A block of text read in a window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcrLayoutBlock {
+    pub kind: LayoutBlockKind,
+    /// Column of the window holding the block, from the left
+    pub column: u32,
+    /// Lines of the block. Code keeps its indentation, the cells of table rows are
+    /// separated by tabs
+    pub text: String,
+    /// Cells of each row of a table, the first row being its header
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rows: Vec<Vec<String>>,
+    /// Bounds as fractions of the window image, from its top left corner
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl OcrLayoutBlock {
+    /// The block with `f` applied to its text and cells, e.g. to redact PII.
+    pub fn map_text(&self, f: impl Fn(&str) -> String) -> Self {
+        Self {
+            text: f(&self.text),
+            rows: self
+                .rows
+                .iter()
+                .map(|row| row.iter().map(|cell| f(cell)).collect())
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        match self.kind {
+            LayoutBlockKind::Paragraph => self.text.clone(),
+            LayoutBlockKind::Code => {
+                // The fence has to be longer than any run of backticks in the code
+                let longest_run = self
+                    .text
+                    .split(|c| c != '`')
+                    .map(str::len)
+                    .max()
+                    .unwrap_or(0);
+                let fence = "`".repeat((longest_run + 1).max(3));
+                format!("{}\n{}\n{}", fence, self.text, fence)
+            }
+            LayoutBlockKind::Table => {
+                let columns = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+                let row_markdown = |row: &[String]| {
+                    let cells: Vec<String> = (0..columns)
+                        .map(|i| {
+                            row.get(i)
+                                .map(|cell| cell.replace('|', "\\|"))
+                                .unwrap_or_default()
+                        })
+                        .collect();
+                    format!("| {} |", cells.join(" | "))
+                };
+                let mut lines = Vec::with_capacity(self.rows.len() + 1);
+                for (i, row) in self.rows.iter().enumerate() {
+                    lines.push(row_markdown(row));
+                    if i == 0 {
+                        lines.push(format!("|{}", " --- |".repeat(columns)));
+                    }
+                }
+                lines.join("\n")
+            }
+        }
+    }
+}
+
+/// Markdown of the blocks of a frame, in reading order.
+pub fn layout_to_markdown(blocks: &[OcrLayoutBlock]) -> String {
+    blocks
+        .iter()
+        .map(OcrLayoutBlock::to_markdown)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// A block matching a search, with the frame it was last read on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrLayoutSearchResult {
+    pub frame_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    pub browser_url: Option<String>,
+    #[serde(flatten)]
+    pub block: OcrLayoutBlock,
+}
+
+#[derive(FromRow)]
+struct LayoutBlockRow {
+    kind: LayoutBlockKind,
+    column_index: i64,
+    text: String,
+    rows_json: Option<String>,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl From<LayoutBlockRow> for OcrLayoutBlock {
+    fn from(row: LayoutBlockRow) -> Self {
+        Self {
+            kind: row.kind,
+            column: row.column_index as u32,
+            text: row.text,
+            rows: row
+                .rows_json
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            left: row.x,
+            top: row.y,
+            width: row.width,
+            height: row.height,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct LayoutSearchRow {
+    frame_id: i64,
+    timestamp: DateTime<Utc>,
+    app_name: Option<String>,
+    window_name: Option<String>,
+    browser_url: Option<String>,
+    #[sqlx(flatten)]
+    block: LayoutBlockRow,
+}
+
+/// Insert the blocks of a frame in reading order.
+pub(crate) async fn insert_layout_blocks(
+    conn: &mut SqliteConnection,
+    frame_id: i64,
+    blocks: &[OcrLayoutBlock],
+) -> Result<(), sqlx::Error> {
+    for (index, block) in blocks.iter().enumerate() {
+        let rows_json = (!block.rows.is_empty())
+            .then(|| serde_json::to_string(&block.rows).unwrap_or_default());
+        sqlx::query(
+            "INSERT INTO ocr_layout_blocks (frame_id, block_index, kind, column_index, text, rows_json, x, y, width, height) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        )
+        .bind(frame_id)
+        .bind(index as i64)
+        .bind(block.kind)
+        .bind(block.column as i64)
+        .bind(&block.text)
+        .bind(rows_json)
+        .bind(block.left)
+        .bind(block.top)
+        .bind(block.width)
+        .bind(block.height)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+impl DatabaseManager {
+    pub async fn insert_ocr_layout(
+        &self,
+        frame_id: i64,
+        blocks: &[OcrLayoutBlock],
+    ) -> Result<(), sqlx::Error> {
+        if blocks.is_empty() {
+            return Ok(());
+        }
+        let mut tx = self.begin_immediate_with_retry().await?;
+        insert_layout_blocks(&mut **tx.conn(), frame_id, blocks).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Blocks of a frame in reading order, empty for frames read before layouts were stored.
+    pub async fn get_frame_layout(
+        &self,
+        frame_id: i64,
+    ) -> Result<Vec<OcrLayoutBlock>, sqlx::Error> {
+        let rows: Vec<LayoutBlockRow> = sqlx::query_as(
+            r#"
+            SELECT kind, column_index, text, rows_json, x, y, width, height
+            FROM ocr_layout_blocks
+            WHERE frame_id = ?1
+            ORDER BY block_index
+            "#,
+        )
+        .bind(frame_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Blocks matching an FTS5 query, newest first. A block read on many frames is
+    /// returned once, with the last frame it was read on.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_ocr_layout(
+        &self,
+        query: &str,
+        kind: Option<LayoutBlockKind>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<OcrLayoutSearchResult>, sqlx::Error> {
+        // The bare columns of an aggregate query come from the row holding MAX()
+        let rows: Vec<LayoutSearchRow> = sqlx::query_as(
+            r#"
+            SELECT
+                l.frame_id,
+                MAX(f.timestamp) AS timestamp,
+                f.app_name,
+                f.window_name,
+                f.browser_url,
+                l.kind,
+                l.column_index,
+                l.text,
+                l.rows_json,
+                l.x,
+                l.y,
+                l.width,
+                l.height
+            FROM ocr_layout_blocks_fts
+            JOIN ocr_layout_blocks l ON l.id = ocr_layout_blocks_fts.rowid
+            JOIN frames f ON f.id = l.frame_id
+            WHERE ocr_layout_blocks_fts MATCH ?1
+              AND (?2 IS NULL OR l.kind = ?2)
+              AND (?3 IS NULL OR f.timestamp >= ?3)
+              AND (?4 IS NULL OR f.timestamp <= ?4)
+              AND (?5 IS NULL OR f.app_name LIKE '%' || ?5 || '%')
+            GROUP BY l.kind, l.text
+            ORDER BY timestamp DESC
+            LIMIT ?6 OFFSET ?7
+            "#,
+        )
+        .bind(query)
+        .bind(kind)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(app_name)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| OcrLayoutSearchResult {
+                frame_id: row.frame_id,
+                timestamp: row.timestamp,
+                app_name: row.app_name,
+                window_name: row.window_name,
+                browser_url: row.browser_url,
+                block: row.block.into(),
+            })
+            .collect())
+    }
+}
+
+/// Whether `e` is SQLite rejecting the FTS5 query given to
+/// [`DatabaseManager::search_ocr_layout`], a mistake of the caller rather than of the database.
+pub fn is_fts_query_error(e: &sqlx::Error) -> bool {
+    e.as_database_error().is_some_and(|e| {
+        let message = e.message();
+        [
+            "fts5:",
+            "unterminated string",
+            "no such column",
+            "unknown special query",
+        ]
+        .iter()
+        .any(|prefix| message.starts_with(prefix))
+    })
+}
@@ -0,0 +1,287 @@
+//! Sandboxed execution of user-supplied SQL.
+//!
+//! Queries run on a dedicated connection opened read-only (`SQLITE_OPEN_READONLY`
+//! plus `PRAGMA query_only`), so nothing sent to `/raw_sql` can modify the
+//! database, even `PRAGMA query_only = OFF` (in-memory databases ignore the
+//! read-only flag, so only `query_only` applies there). Each query has a deadline enforced
+//! inside SQLite via a progress handler and returns at most one page of rows.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use base64::{engine::general_purpose, Engine as _};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use sqlx::sqlite::{SqliteColumn, SqliteConnection, SqliteRow};
+use sqlx::{Column, ConnectOptions, Connection, Executor, Row, Statement, TypeInfo, ValueRef};
+use tracing::warn;
+
+use crate::DatabaseManager;
+
+/// Rows returned per page when the caller doesn't ask for a limit.
+pub const DEFAULT_RAW_SQL_ROWS: usize = 1000;
+/// Hard cap on rows per page, whatever the caller asks for.
+pub const MAX_RAW_SQL_ROWS: usize = 10_000;
+pub const DEFAULT_RAW_SQL_TIMEOUT: Duration = Duration::from_secs(10);
+pub const MAX_RAW_SQL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Number of SQLite VM instructions between deadline checks.
+const PROGRESS_HANDLER_OPS: i32 = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct RawSqlOptions {
+    /// Maximum rows in the returned page, capped at [`MAX_RAW_SQL_ROWS`]
+    pub max_rows: usize,
+    /// Statement timeout, capped at [`MAX_RAW_SQL_TIMEOUT`]
+    pub timeout: Duration,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+impl Default for RawSqlOptions {
+    fn default() -> Self {
+        Self {
+            max_rows: DEFAULT_RAW_SQL_ROWS,
+            timeout: DEFAULT_RAW_SQL_TIMEOUT,
+            cursor: None,
+        }
+    }
+}
+
+/// A result column. `declared_type` comes from the schema and is `None` for
+/// expressions; `value_type` is the storage class (`INTEGER`, `REAL`, `TEXT`,
+/// `BLOB`) of the first non-null value in the page. BLOB values are returned as
+/// base64 strings.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RawSqlColumn {
+    pub name: String,
+    pub declared_type: Option<String>,
+    pub value_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawSqlPage {
+    pub columns: Vec<RawSqlColumn>,
+    pub rows: Vec<Map<String, Value>>,
+    /// Pass back as `cursor` to fetch the next page, `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum RawSqlError {
+    /// The query failed, including attempts to write
+    Sql(sqlx::Error),
+    Timeout(Duration),
+    InvalidCursor,
+}
+
+impl RawSqlError {
+    /// Whether the error was caused by the query itself rather than the database.
+    pub fn is_client_error(&self) -> bool {
+        match self {
+            RawSqlError::Sql(sqlx::Error::Database(_)) => true,
+            RawSqlError::Sql(_) => false,
+            RawSqlError::Timeout(_) | RawSqlError::InvalidCursor => true,
+        }
+    }
+}
+
+impl fmt::Display for RawSqlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RawSqlError::Sql(e) => write!(f, "{}", e),
+            RawSqlError::Timeout(timeout) => {
+                write!(f, "query exceeded the {}ms timeout", timeout.as_millis())
+            }
+            RawSqlError::InvalidCursor => write!(f, "invalid cursor"),
+        }
+    }
+}
+
+impl std::error::Error for RawSqlError {}
+
+impl From<sqlx::Error> for RawSqlError {
+    fn from(e: sqlx::Error) -> Self {
+        RawSqlError::Sql(e)
+    }
+}
+
+fn encode_cursor(offset: usize) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(format!("offset:{}", offset))
+}
+
+fn decode_cursor(cursor: &str) -> Result<usize, RawSqlError> {
+    general_purpose::URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|s| s.strip_prefix("offset:")?.parse().ok())
+        .ok_or(RawSqlError::InvalidCursor)
+}
+
+fn value_to_json(row: &SqliteRow, i: usize) -> (Value, Option<&'static str>) {
+    let Ok(raw) = row.try_get_raw(i) else {
+        return (Value::Null, None);
+    };
+    if raw.is_null() {
+        return (Value::Null, None);
+    }
+    match raw.type_info().name() {
+        "INTEGER" => match row.try_get_unchecked::<i64, _>(i) {
+            Ok(v) => (Value::Number(v.into()), Some("INTEGER")),
+            Err(_) => (Value::Null, None),
+        },
+        "REAL" => match row.try_get_unchecked::<f64, _>(i) {
+            // NaN and infinities have no JSON representation
+            Ok(v) => (
+                Number::from_f64(v).map_or(Value::Null, Value::Number),
+                Some("REAL"),
+            ),
+            Err(_) => (Value::Null, None),
+        },
+        "BLOB" => match row.try_get_unchecked::<Vec<u8>, _>(i) {
+            Ok(v) => (
+                Value::String(general_purpose::STANDARD.encode(v)),
+                Some("BLOB"),
+            ),
+            Err(_) => (Value::Null, None),
+        },
+        _ => match row.try_get_unchecked::<String, _>(i) {
+            Ok(v) => (Value::String(v), Some("TEXT")),
+            Err(_) => (Value::Null, None),
+        },
+    }
+}
+
+impl DatabaseManager {
+    /// Opens a connection to the same database that cannot write to it.
+    async fn connect_read_only(&self) -> Result<SqliteConnection, sqlx::Error> {
+        (*self.pool.connect_options())
+            .clone()
+            .read_only(true)
+            .create_if_missing(false)
+            .pragma("query_only", "ON")
+            .connect()
+            .await
+    }
+
+    /// Runs user-supplied SQL on a read-only connection and returns one page of rows.
+    pub async fn query_raw_sql(
+        &self,
+        query: &str,
+        options: &RawSqlOptions,
+    ) -> Result<RawSqlPage, RawSqlError> {
+        let offset = match &options.cursor {
+            Some(cursor) => decode_cursor(cursor)?,
+            None => 0,
+        };
+        let max_rows = options.max_rows.clamp(1, MAX_RAW_SQL_ROWS);
+        let timeout = options.timeout.min(MAX_RAW_SQL_TIMEOUT);
+
+        let mut conn = self.connect_read_only().await?;
+        let deadline = Instant::now() + timeout;
+        conn.lock_handle()
+            .await?
+            .set_progress_handler(PROGRESS_HANDLER_OPS, move || Instant::now() < deadline);
+
+        let result =
+            tokio::time::timeout(timeout, fetch_page(&mut conn, query, offset, max_rows)).await;
+        // A timed out connection may still be busy, so it is just dropped.
+        match result {
+            Ok(Ok(page)) => {
+                let _ = conn.close().await;
+                Ok(page)
+            }
+            // The progress handler interrupted the statement
+            _ if Instant::now() >= deadline => Err(RawSqlError::Timeout(timeout)),
+            Ok(Err(e)) => Err(e.into()),
+            Err(_) => Err(RawSqlError::Timeout(timeout)),
+        }
+    }
+
+    /// Runs user-supplied SQL read-only and returns up to [`MAX_RAW_SQL_ROWS`] rows as a
+    /// JSON array of objects, logging a warning when there were more. See
+    /// [`DatabaseManager::query_raw_sql`] for paging and column metadata.
+    pub async fn execute_raw_sql(&self, query: &str) -> Result<Value, RawSqlError> {
+        let options = RawSqlOptions {
+            max_rows: MAX_RAW_SQL_ROWS,
+            ..Default::default()
+        };
+        let page = self.query_raw_sql(query, &options).await?;
+        if page.next_cursor.is_some() {
+            warn!(
+                "raw sql result truncated to {} rows, use query_raw_sql to page through it",
+                MAX_RAW_SQL_ROWS
+            );
+        }
+        Ok(Value::Array(
+            page.rows.into_iter().map(Value::Object).collect(),
+        ))
+    }
+}
+
+async fn fetch_page(
+    conn: &mut SqliteConnection,
+    query: &str,
+    offset: usize,
+    max_rows: usize,
+) -> Result<RawSqlPage, sqlx::Error> {
+    let mut columns: Vec<RawSqlColumn> = Vec::new();
+    let mut rows = Vec::new();
+    let mut seen = 0;
+    let mut has_more = false;
+
+    let mut stream = sqlx::query(query).fetch(&mut *conn);
+    while let Some(row) = stream.try_next().await? {
+        if columns.is_empty() {
+            columns = to_raw_sql_columns(row.columns());
+        }
+        seen += 1;
+        if seen <= offset {
+            continue;
+        }
+        if rows.len() == max_rows {
+            has_more = true;
+            break;
+        }
+
+        let mut map = Map::new();
+        for (i, column) in columns.iter_mut().enumerate() {
+            let (value, value_type) = value_to_json(&row, i);
+            if column.value_type.is_none() {
+                column.value_type = value_type.map(String::from);
+            }
+            map.insert(column.name.clone(), value);
+        }
+        rows.push(map);
+    }
+
+    drop(stream);
+
+    // Still report the columns of queries that returned no rows.
+    if columns.is_empty() {
+        if let Ok(statement) = conn.prepare(query).await {
+            columns = to_raw_sql_columns(statement.columns());
+        }
+    }
+
+    Ok(RawSqlPage {
+        columns,
+        rows,
+        next_cursor: has_more.then(|| encode_cursor(offset + rows.len())),
+    })
+}
+
+fn to_raw_sql_columns(columns: &[SqliteColumn]) -> Vec<RawSqlColumn> {
+    columns
+        .iter()
+        .map(|c| RawSqlColumn {
+            name: c.name().to_string(),
+            declared_type: Some(c.type_info().name())
+                .filter(|t| *t != "NULL")
+                .map(String::from),
+            value_type: None,
+        })
+        .collect()
+}
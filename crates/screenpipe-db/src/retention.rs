@@ -0,0 +1,523 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Row, Sqlite};
+use tracing::debug;
+
+use crate::DatabaseManager;
+
+/// Number of rows deleted per write transaction, so pruning never holds the
+/// write lock long enough to stall frame/audio inserts.
+const RETENTION_BATCH_SIZE: i64 = 500;
+
+/// Time of an audio chunk `c`. Chunks recorded before `audio_chunks.timestamp` existed have
+/// none, they fall back to their first transcription.
+const AUDIO_CHUNK_TIME: &str = "COALESCE(c.timestamp, \
+     (SELECT MIN(t.timestamp) FROM audio_transcriptions t WHERE t.audio_chunk_id = c.id))";
+
+/// How long each kind of data is kept, in days. `None` keeps data forever.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Video files (mp4). Frame rows are kept while their OCR text is retained.
+    #[serde(default)]
+    pub video_days: Option<u32>,
+    /// OCR text, OCR embeddings and accessibility text.
+    #[serde(default)]
+    pub ocr_days: Option<u32>,
+    /// Audio files, transcriptions and audio tags.
+    #[serde(default)]
+    pub audio_days: Option<u32>,
+    /// UI events (clicks, keystrokes, clipboard, ...).
+    #[serde(default)]
+    pub ui_events_days: Option<u32>,
+    /// Per-app overrides keyed by app name (matched case-insensitively).
+    #[serde(default)]
+    pub app_overrides: BTreeMap<String, AppRetentionOverride>,
+}
+
+/// Per-app retention windows. `None` falls back to the global policy.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AppRetentionOverride {
+    #[serde(default)]
+    pub video_days: Option<u32>,
+    #[serde(default)]
+    pub ocr_days: Option<u32>,
+    #[serde(default)]
+    pub ui_events_days: Option<u32>,
+}
+
+impl RetentionPolicy {
+    /// Returns true when the policy would never delete anything.
+    pub fn is_empty(&self) -> bool {
+        self.video_days.is_none()
+            && self.ocr_days.is_none()
+            && self.audio_days.is_none()
+            && self.ui_events_days.is_none()
+            && self.app_overrides.values().all(|o| {
+                o.video_days.is_none() && o.ocr_days.is_none() && o.ui_events_days.is_none()
+            })
+    }
+
+    fn cutoffs(
+        &self,
+        now: DateTime<Utc>,
+        global: Option<u32>,
+        per_app: impl Fn(&AppRetentionOverride) -> Option<u32>,
+    ) -> Cutoffs {
+        let to_cutoff = |days: u32| now - Duration::days(days as i64);
+        Cutoffs {
+            default: global.map(to_cutoff),
+            overrides: self
+                .app_overrides
+                .iter()
+                .filter_map(|(app, o)| per_app(o).map(|d| (app.to_lowercase(), to_cutoff(d))))
+                .collect(),
+        }
+    }
+}
+
+/// Outcome of a retention pass. In dry-run mode the counts are what *would* be deleted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub ocr_text: u64,
+    pub ocr_embeddings: u64,
+    pub ocr_layout_blocks: u64,
+    pub frames: u64,
+    pub video_chunks: u64,
+    pub audio_transcriptions: u64,
+    pub audio_chunks: u64,
+    pub ui_events: u64,
+    pub accessibility: u64,
+    /// Video files whose frames are all past `video_days`. The caller removes them from disk.
+    pub video_files: Vec<String>,
+    /// Audio files of deleted audio chunks. The caller removes them from disk.
+    pub audio_files: Vec<String>,
+}
+
+/// Resolved cutoff timestamps for one kind of data.
+struct Cutoffs {
+    default: Option<DateTime<Utc>>,
+    overrides: Vec<(String, DateTime<Utc>)>,
+}
+
+impl Cutoffs {
+    fn is_none(&self) -> bool {
+        self.default.is_none() && self.overrides.is_empty()
+    }
+
+    /// Pushes a predicate that is 1 when `ts_col` is past the cutoff for the row's app, 0 otherwise
+    /// (never NULL, so it is safe to negate).
+    fn push_expired(&self, qb: &mut QueryBuilder<'_, Sqlite>, ts_col: &str, app_col: &str) {
+        qb.push("COALESCE(").push(ts_col).push(" < ");
+        if self.overrides.is_empty() {
+            qb.push_bind(self.default);
+        } else {
+            qb.push("(CASE lower(COALESCE(")
+                .push(app_col)
+                .push(", ''))");
+            for (app, cutoff) in &self.overrides {
+                qb.push(" WHEN ")
+                    .push_bind(app.clone())
+                    .push(" THEN ")
+                    .push_bind(*cutoff);
+            }
+            qb.push(" ELSE ").push_bind(self.default).push(" END)");
+        }
+        qb.push(", 0)");
+    }
+}
+
+fn ids_json(ids: &[i64]) -> String {
+    serde_json::to_string(ids).unwrap_or_else(|_| "[]".to_string())
+}
+
+impl DatabaseManager {
+    /// Deletes everything older than the retention policy allows.
+    ///
+    /// Rows are removed in small batches; FTS entries follow via the existing delete triggers.
+    /// Files are NOT touched here: the paths of removed video/audio files are returned in the
+    /// report so the caller can delete them once the rows are gone. With `dry_run` nothing is
+    /// modified and the report describes what a real run would delete.
+    pub async fn apply_retention_policy(
+        &self,
+        policy: &RetentionPolicy,
+        now: DateTime<Utc>,
+        dry_run: bool,
+    ) -> Result<RetentionReport, sqlx::Error> {
+        let video = policy.cutoffs(now, policy.video_days, |o| o.video_days);
+        let ocr = policy.cutoffs(now, policy.ocr_days, |o| o.ocr_days);
+        let ui = policy.cutoffs(now, policy.ui_events_days, |o| o.ui_events_days);
+        let audio_cutoff = policy.audio_days.map(|d| now - Duration::days(d as i64));
+
+        let mut report = RetentionReport {
+            dry_run,
+            ..Default::default()
+        };
+
+        if !ocr.is_none() {
+            self.prune_ocr(&ocr, dry_run, &mut report).await?;
+            self.prune_accessibility(&ocr, dry_run, &mut report).await?;
+        }
+        if !video.is_none() {
+            // Files first: the "all frames expired" check needs the frame rows.
+            self.prune_video_files(&video, now, dry_run, &mut report)
+                .await?;
+            if !ocr.is_none() {
+                self.prune_frames(&video, &ocr, dry_run, &mut report)
+                    .await?;
+            }
+        }
+        if let Some(cutoff) = audio_cutoff {
+            self.prune_audio(cutoff, dry_run, &mut report).await?;
+        }
+        if !ui.is_none() {
+            self.prune_ui_events(&ui, dry_run, &mut report).await?;
+        }
+
+        debug!("retention pass finished: {:?}", report);
+        Ok(report)
+    }
+
+    async fn prune_ocr(
+        &self,
+        ocr: &Cutoffs,
+        dry_run: bool,
+        report: &mut RetentionReport,
+    ) -> Result<(), sqlx::Error> {
+        if dry_run {
+            for (table, counter) in [
+                ("ocr_text", &mut report.ocr_text),
+                ("ocr_text_embeddings", &mut report.ocr_embeddings),
+                ("ocr_layout_blocks", &mut report.ocr_layout_blocks),
+            ] {
+                let mut qb = QueryBuilder::<Sqlite>::new(format!(
+                    "SELECT COUNT(*) FROM {} t JOIN frames f ON f.id = t.frame_id WHERE ",
+                    table
+                ));
+                ocr.push_expired(&mut qb, "f.timestamp", "f.app_name");
+                let count: i64 = qb.build_query_scalar().fetch_one(&self.pool).await?;
+                *counter += count as u64;
+            }
+            return Ok(());
+        }
+
+        loop {
+            let mut qb = QueryBuilder::<Sqlite>::new("SELECT f.id FROM frames f WHERE ");
+            ocr.push_expired(&mut qb, "f.timestamp", "f.app_name");
+            qb.push(
+                " AND (EXISTS (SELECT 1 FROM ocr_text o WHERE o.frame_id = f.id) \
+                 OR EXISTS (SELECT 1 FROM ocr_text_embeddings e WHERE e.frame_id = f.id) \
+                 OR EXISTS (SELECT 1 FROM ocr_layout_blocks l WHERE l.frame_id = f.id)) LIMIT ",
+            )
+            .push_bind(RETENTION_BATCH_SIZE);
+            let ids: Vec<i64> = qb.build_query_scalar().fetch_all(&self.pool).await?;
+            if ids.is_empty() {
+                return Ok(());
+            }
+
+            let ids = ids_json(&ids);
+            let mut tx = self.begin_immediate_with_retry().await?;
+            report.ocr_text += sqlx::query(
+                "DELETE FROM ocr_text WHERE frame_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids)
+            .execute(&mut **tx.conn())
+            .await?
+            .rows_affected();
+            report.ocr_embeddings += sqlx::query(
+                "DELETE FROM ocr_text_embeddings WHERE frame_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids)
+            .execute(&mut **tx.conn())
+            .await?
+            .rows_affected();
+            report.ocr_layout_blocks += sqlx::query(
+                "DELETE FROM ocr_layout_blocks WHERE frame_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids)
+            .execute(&mut **tx.conn())
+            .await?
+            .rows_affected();
+            tx.commit().await?;
+        }
+    }
+
+    async fn prune_video_files(
+        &self,
+        video: &Cutoffs,
+        now: DateTime<Utc>,
+        dry_run: bool,
+        report: &mut RetentionReport,
+    ) -> Result<(), sqlx::Error> {
+        loop {
+            // A chunk's file can go once every frame in it is past its app's video cutoff.
+            // Chunks without frames are skipped: that's the chunk currently being recorded.
+            let mut qb = QueryBuilder::<Sqlite>::new(
+                "SELECT vc.id, vc.file_path FROM video_chunks vc \
+                 WHERE vc.file_deleted_at IS NULL \
+                 AND EXISTS (SELECT 1 FROM frames f WHERE f.video_chunk_id = vc.id) \
+                 AND NOT EXISTS (SELECT 1 FROM frames f WHERE f.video_chunk_id = vc.id AND NOT ",
+            );
+            video.push_expired(&mut qb, "f.timestamp", "f.app_name");
+            qb.push(")");
+            if !dry_run {
+                qb.push(" LIMIT ").push_bind(RETENTION_BATCH_SIZE);
+            }
+            let rows = qb.build().fetch_all(&self.pool).await?;
+            if rows.is_empty() {
+                return Ok(());
+            }
+
+            let mut ids = Vec::with_capacity(rows.len());
+            for row in rows {
+                ids.push(row.try_get::<i64, _>("id")?);
+                report.video_files.push(row.try_get("file_path")?);
+            }
+            if dry_run {
+                return Ok(());
+            }
+
+            let mut tx = self.begin_immediate_with_retry().await?;
+            sqlx::query(
+                "UPDATE video_chunks SET file_deleted_at = ?1 WHERE id IN (SELECT value FROM json_each(?2))",
+            )
+            .bind(now)
+            .bind(ids_json(&ids))
+            .execute(&mut **tx.conn())
+            .await?;
+            tx.commit().await?;
+        }
+    }
+
+    /// Deletes frame rows once both their video and their OCR text have expired,
+    /// then drops video chunks that no longer have frames.
+    async fn prune_frames(
+        &self,
+        video: &Cutoffs,
+        ocr: &Cutoffs,
+        dry_run: bool,
+        report: &mut RetentionReport,
+    ) -> Result<(), sqlx::Error> {
+        let push_expired_frames = |qb: &mut QueryBuilder<'_, Sqlite>| {
+            video.push_expired(qb, "f.timestamp", "f.app_name");
+            qb.push(" AND ");
+            ocr.push_expired(qb, "f.timestamp", "f.app_name");
+        };
+
+        if dry_run {
+            let mut qb = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM frames f WHERE ");
+            push_expired_frames(&mut qb);
+            let count: i64 = qb.build_query_scalar().fetch_one(&self.pool).await?;
+            report.frames += count as u64;
+
+            let mut qb = QueryBuilder::<Sqlite>::new(
+                "SELECT COUNT(*) FROM video_chunks vc WHERE NOT EXISTS \
+                 (SELECT 1 FROM frames f WHERE f.video_chunk_id = vc.id AND NOT (",
+            );
+            push_expired_frames(&mut qb);
+            qb.push(
+                ")) AND (vc.file_deleted_at IS NOT NULL \
+                 OR EXISTS (SELECT 1 FROM frames f WHERE f.video_chunk_id = vc.id))",
+            );
+            let count: i64 = qb.build_query_scalar().fetch_one(&self.pool).await?;
+            report.video_chunks += count as u64;
+            return Ok(());
+        }
+
+        loop {
+            let mut qb = QueryBuilder::<Sqlite>::new("SELECT f.id FROM frames f WHERE ");
+            push_expired_frames(&mut qb);
+            qb.push(" LIMIT ").push_bind(RETENTION_BATCH_SIZE);
+            let ids: Vec<i64> = qb.build_query_scalar().fetch_all(&self.pool).await?;
+            if ids.is_empty() {
+                break;
+            }
+
+            let ids = ids_json(&ids);
+            let mut tx = self.begin_immediate_with_retry().await?;
+            for query in [
+                "DELETE FROM ocr_text WHERE frame_id IN (SELECT value FROM json_each(?1))",
+                "DELETE FROM ocr_text_embeddings WHERE frame_id IN (SELECT value FROM json_each(?1))",
+                "DELETE FROM ocr_layout_blocks WHERE frame_id IN (SELECT value FROM json_each(?1))",
+                "DELETE FROM vision_tags WHERE vision_id IN (SELECT value FROM json_each(?1))",
+                "DELETE FROM chunked_text_entries WHERE frame_id IN (SELECT value FROM json_each(?1))",
+                "UPDATE ui_events SET frame_id = NULL WHERE frame_id IN (SELECT value FROM json_each(?1))",
+            ] {
+                sqlx::query(query)
+                    .bind(&ids)
+                    .execute(&mut **tx.conn())
+                    .await?;
+            }
+            report.frames +=
+                sqlx::query("DELETE FROM frames WHERE id IN (SELECT value FROM json_each(?1))")
+                    .bind(&ids)
+                    .execute(&mut **tx.conn())
+                    .await?
+                    .rows_affected();
+            tx.commit().await?;
+        }
+
+        let mut tx = self.begin_immediate_with_retry().await?;
+        report.video_chunks += sqlx::query(
+            "DELETE FROM video_chunks WHERE file_deleted_at IS NOT NULL \
+             AND NOT EXISTS (SELECT 1 FROM frames WHERE frames.video_chunk_id = video_chunks.id)",
+        )
+        .execute(&mut **tx.conn())
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn prune_audio(
+        &self,
+        cutoff: DateTime<Utc>,
+        dry_run: bool,
+        report: &mut RetentionReport,
+    ) -> Result<(), sqlx::Error> {
+        if dry_run {
+            report.audio_transcriptions += sqlx::query_scalar::<_, i64>(&format!(
+                "SELECT COUNT(*) FROM audio_transcriptions a JOIN audio_chunks c ON c.id = a.audio_chunk_id WHERE {} < ?1",
+                AUDIO_CHUNK_TIME
+            ))
+            .bind(cutoff)
+            .fetch_one(&self.pool)
+            .await? as u64;
+            let files: Vec<String> = sqlx::query_scalar(&format!(
+                "SELECT c.file_path FROM audio_chunks c WHERE {} < ?1",
+                AUDIO_CHUNK_TIME
+            ))
+            .bind(cutoff)
+            .fetch_all(&self.pool)
+            .await?;
+            report.audio_chunks += files.len() as u64;
+            report.audio_files.extend(files);
+            return Ok(());
+        }
+
+        loop {
+            let rows = sqlx::query(&format!(
+                "SELECT c.id, c.file_path FROM audio_chunks c WHERE {} < ?1 LIMIT ?2",
+                AUDIO_CHUNK_TIME
+            ))
+            .bind(cutoff)
+            .bind(RETENTION_BATCH_SIZE)
+            .fetch_all(&self.pool)
+            .await?;
+            if rows.is_empty() {
+                return Ok(());
+            }
+
+            let mut ids = Vec::with_capacity(rows.len());
+            let mut files = Vec::with_capacity(rows.len());
+            for row in rows {
+                ids.push(row.try_get::<i64, _>("id")?);
+                files.push(row.try_get::<String, _>("file_path")?);
+            }
+
+            let ids = ids_json(&ids);
+            let mut tx = self.begin_immediate_with_retry().await?;
+            report.audio_transcriptions += sqlx::query(
+                "DELETE FROM audio_transcriptions WHERE audio_chunk_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids)
+            .execute(&mut **tx.conn())
+            .await?
+            .rows_affected();
+            for query in [
+                "DELETE FROM audio_tags WHERE audio_chunk_id IN (SELECT value FROM json_each(?1))",
+                "DELETE FROM chunked_text_entries WHERE audio_chunk_id IN (SELECT value FROM json_each(?1))",
+            ] {
+                sqlx::query(query)
+                    .bind(&ids)
+                    .execute(&mut **tx.conn())
+                    .await?;
+            }
+            report.audio_chunks += sqlx::query(
+                "DELETE FROM audio_chunks WHERE id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids)
+            .execute(&mut **tx.conn())
+            .await?
+            .rows_affected();
+            tx.commit().await?;
+            report.audio_files.extend(files);
+        }
+    }
+
+    async fn prune_ui_events(
+        &self,
+        ui: &Cutoffs,
+        dry_run: bool,
+        report: &mut RetentionReport,
+    ) -> Result<(), sqlx::Error> {
+        if dry_run {
+            let mut qb = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM ui_events WHERE ");
+            ui.push_expired(&mut qb, "timestamp", "app_name");
+            let count: i64 = qb.build_query_scalar().fetch_one(&self.pool).await?;
+            report.ui_events += count as u64;
+            return Ok(());
+        }
+
+        loop {
+            let mut qb = QueryBuilder::<Sqlite>::new(
+                "DELETE FROM ui_events WHERE id IN (SELECT id FROM ui_events WHERE ",
+            );
+            ui.push_expired(&mut qb, "timestamp", "app_name");
+            qb.push(" LIMIT ").push_bind(RETENTION_BATCH_SIZE).push(")");
+
+            let mut tx = self.begin_immediate_with_retry().await?;
+            let deleted = qb.build().execute(&mut **tx.conn()).await?.rows_affected();
+            tx.commit().await?;
+            report.ui_events += deleted;
+            if deleted == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Accessibility text is screen content like OCR text, so it follows the OCR cutoffs.
+    async fn prune_accessibility(
+        &self,
+        ocr: &Cutoffs,
+        dry_run: bool,
+        report: &mut RetentionReport,
+    ) -> Result<(), sqlx::Error> {
+        if dry_run {
+            let mut qb = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM accessibility WHERE ");
+            ocr.push_expired(&mut qb, "timestamp", "app_name");
+            let count: i64 = qb.build_query_scalar().fetch_one(&self.pool).await?;
+            report.accessibility += count as u64;
+            return Ok(());
+        }
+
+        loop {
+            let mut qb = QueryBuilder::<Sqlite>::new("SELECT id FROM accessibility WHERE ");
+            ocr.push_expired(&mut qb, "timestamp", "app_name");
+            qb.push(" LIMIT ").push_bind(RETENTION_BATCH_SIZE);
+            let ids: Vec<i64> = qb.build_query_scalar().fetch_all(&self.pool).await?;
+            if ids.is_empty() {
+                return Ok(());
+            }
+
+            let ids = ids_json(&ids);
+            let mut tx = self.begin_immediate_with_retry().await?;
+            sqlx::query(
+                "DELETE FROM accessibility_tags WHERE accessibility_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids)
+            .execute(&mut **tx.conn())
+            .await?;
+            report.accessibility += sqlx::query(
+                "DELETE FROM accessibility WHERE id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids)
+            .execute(&mut **tx.conn())
+            .await?
+            .rows_affected();
+            tx.commit().await?;
+        }
+    }
+}
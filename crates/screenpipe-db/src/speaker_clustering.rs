@@ -0,0 +1,555 @@
+//! Offline re-clustering of speakers.
+//!
+//! Live diarization creates a speaker whenever a voice is not close enough to a known one,
+//! so the same person ends up split over many speakers. Clustering compares all speakers at
+//! once by the centroid of their embeddings, with average-linkage agglomerative clustering,
+//! and proposes to merge the speakers of each cluster into one. Proposals are accepted or
+//! rejected in bulk, or merged right away above a strict threshold.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use tracing::{debug, info};
+
+use crate::db::embedding_from_bytes;
+use crate::DatabaseManager;
+
+/// Default minimum similarity of two clusters of speakers to be merged
+pub const DEFAULT_SPEAKER_CLUSTERING_THRESHOLD: f32 = 0.75;
+
+/// Most speakers clustered at once. Clustering takes cubic time and quadratic memory in the
+/// number of speakers, this keeps a run within seconds and a few megabytes.
+pub const MAX_CLUSTERED_SPEAKERS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum SpeakerMergeProposalStatus {
+    Pending,
+    Accepted,
+    Rejected,
+}
+
+/// A proposal to merge a speaker into another, found by [`DatabaseManager::cluster_speakers`].
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SpeakerMergeProposal {
+    pub id: i64,
+    /// Speaker kept by the merge
+    pub speaker_id: i64,
+    pub speaker_name: Option<String>,
+    /// Speaker merged into `speaker_id`
+    pub merge_speaker_id: i64,
+    pub merge_speaker_name: Option<String>,
+    /// Mean cosine similarity of `merge_speaker_id` to the other speakers of its cluster
+    pub confidence: f64,
+    pub status: SpeakerMergeProposalStatus,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpeakerClusteringConfig {
+    /// Minimum average similarity of two clusters to be merged
+    pub threshold: f32,
+    /// Merge right away the speakers proposed with at least this confidence
+    pub auto_merge_threshold: Option<f32>,
+}
+
+impl Default for SpeakerClusteringConfig {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_SPEAKER_CLUSTERING_THRESHOLD,
+            auto_merge_threshold: None,
+        }
+    }
+}
+
+/// Outcome of a clustering run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpeakerClusteringReport {
+    /// Speakers with embeddings that were clustered
+    pub speakers: usize,
+    /// Clusters of more than one speaker
+    pub clusters: usize,
+    /// Proposals left pending
+    pub proposals: usize,
+    /// Proposals merged right away
+    pub auto_merged: usize,
+}
+
+#[derive(Debug)]
+pub enum SpeakerClusteringError {
+    Sql(sqlx::Error),
+    /// More speakers with embeddings than [`MAX_CLUSTERED_SPEAKERS`]
+    TooManySpeakers(usize),
+}
+
+impl fmt::Display for SpeakerClusteringError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpeakerClusteringError::Sql(e) => write!(f, "{}", e),
+            SpeakerClusteringError::TooManySpeakers(speakers) => write!(
+                f,
+                "too many speakers to cluster: {} (at most {})",
+                speakers, MAX_CLUSTERED_SPEAKERS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpeakerClusteringError {}
+
+impl From<sqlx::Error> for SpeakerClusteringError {
+    fn from(e: sqlx::Error) -> Self {
+        SpeakerClusteringError::Sql(e)
+    }
+}
+
+/// Outcome of accepting and rejecting merge proposals.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpeakerMergeResolution {
+    pub accepted: Vec<i64>,
+    pub rejected: Vec<i64>,
+    /// Proposals not pending anymore, or whose speakers were deleted
+    pub skipped: Vec<i64>,
+}
+
+/// A speaker as seen by clustering.
+#[derive(Debug, Clone)]
+pub(crate) struct ClusteringSpeaker {
+    pub id: i64,
+    pub name: Option<String>,
+    pub enrolled: bool,
+    pub transcription_count: i64,
+    /// Normalized mean of the speaker's normalized embeddings
+    pub centroid: Vec<f32>,
+}
+
+/// A merge found by [`cluster_speakers`]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ProposedMerge {
+    pub speaker_id: i64,
+    pub merge_speaker_id: i64,
+    pub confidence: f32,
+}
+
+/// Normalized mean of the normalized `embeddings`.
+pub(crate) fn embedding_centroid(embeddings: &[Vec<f32>]) -> Vec<f32> {
+    let dimensions = embeddings.first().map_or(0, Vec::len);
+    let mut centroid = vec![0.0; dimensions];
+    for embedding in embeddings {
+        let norm = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            for (sum, v) in centroid.iter_mut().zip(embedding) {
+                *sum += v / norm;
+            }
+        }
+    }
+    let norm = centroid.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        centroid.iter_mut().for_each(|v| *v /= norm);
+    }
+    centroid
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    // Centroids are normalized
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Group `speakers` by average-linkage agglomerative clustering, merging the most similar
+/// clusters while they are at least `threshold` similar.
+///
+/// Speakers with different names, and the pairs in `cannot_link`, never end up in the same
+/// cluster. Each cluster is merged into its named, else enrolled, else most transcribed
+/// speaker.
+pub(crate) fn cluster_speakers(
+    speakers: &[ClusteringSpeaker],
+    threshold: f32,
+    cannot_link: &HashSet<(i64, i64)>,
+) -> Vec<ProposedMerge> {
+    let n = speakers.len();
+    let similarities: Vec<Vec<f32>> = speakers
+        .iter()
+        .map(|a| {
+            speakers
+                .iter()
+                .map(|b| cosine_similarity(&a.centroid, &b.centroid))
+                .collect()
+        })
+        .collect();
+
+    // Similarity between clusters, by the index of their first speaker. Pairs that must
+    // stay apart are at -inf, which average linkage keeps for the clusters they end up in.
+    let mut linkage = similarities.clone();
+    for (i, a) in speakers.iter().enumerate() {
+        for (j, b) in speakers.iter().enumerate() {
+            let different_names = matches!(
+                (&a.name, &b.name),
+                (Some(x), Some(y)) if !x.is_empty() && !y.is_empty() && x != y
+            );
+            if i == j || different_names || cannot_link.contains(&(a.id, b.id)) {
+                linkage[i][j] = f32::NEG_INFINITY;
+            }
+        }
+    }
+
+    let mut clusters: Vec<Option<Vec<usize>>> = (0..n).map(|i| Some(vec![i])).collect();
+    loop {
+        let active: Vec<usize> = (0..n).filter(|&i| clusters[i].is_some()).collect();
+        let mut best: Option<(usize, usize, f32)> = None;
+        for (index, &i) in active.iter().enumerate() {
+            for &j in &active[index + 1..] {
+                if linkage[i][j] >= threshold && best.is_none_or(|(_, _, s)| linkage[i][j] > s) {
+                    best = Some((i, j, linkage[i][j]));
+                }
+            }
+        }
+        let Some((i, j, _)) = best else { break };
+
+        let merged = clusters[j].take().unwrap_or_default();
+        let size_i = clusters[i].as_ref().map_or(0, Vec::len) as f32;
+        let size_j = merged.len() as f32;
+        for &k in &active {
+            if k == i || k == j {
+                continue;
+            }
+            let average = (size_i * linkage[i][k] + size_j * linkage[j][k]) / (size_i + size_j);
+            linkage[i][k] = average;
+            linkage[k][i] = average;
+        }
+        if let Some(cluster) = clusters[i].as_mut() {
+            cluster.extend(merged);
+        }
+    }
+
+    let mut merges = Vec::new();
+    for cluster in clusters.into_iter().flatten().filter(|c| c.len() > 1) {
+        let Some(&keep) = cluster.iter().max_by_key(|&&i| {
+            let speaker = &speakers[i];
+            let named = speaker.name.as_ref().is_some_and(|name| !name.is_empty());
+            (
+                named,
+                speaker.enrolled,
+                speaker.transcription_count,
+                -speaker.id,
+            )
+        }) else {
+            continue;
+        };
+        for &member in cluster.iter().filter(|&&i| i != keep) {
+            let others = cluster.iter().filter(|&&i| i != member);
+            let confidence = others
+                .clone()
+                .map(|&i| similarities[member][i])
+                .sum::<f32>()
+                / others.count() as f32;
+            merges.push(ProposedMerge {
+                speaker_id: speakers[keep].id,
+                merge_speaker_id: speakers[member].id,
+                confidence: confidence.clamp(0.0, 1.0),
+            });
+        }
+    }
+    merges
+}
+
+impl DatabaseManager {
+    /// Speakers with embeddings, hallucinations left out.
+    async fn get_speakers_for_clustering(&self) -> Result<Vec<ClusteringSpeaker>, sqlx::Error> {
+        let rows: Vec<(i64, Option<String>, Vec<u8>, bool)> = sqlx::query_as(
+            "SELECT s.id, s.name, e.embedding, e.enrolled
+             FROM speakers s
+             JOIN speaker_embeddings e ON e.speaker_id = s.id
+             WHERE COALESCE(s.hallucination, 0) = 0
+             ORDER BY s.id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let counts: HashMap<i64, i64> = sqlx::query_as(
+            "SELECT speaker_id, COUNT(*) FROM audio_transcriptions
+             WHERE speaker_id IS NOT NULL GROUP BY speaker_id",
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .collect();
+
+        let mut speakers: Vec<(ClusteringSpeaker, Vec<Vec<f32>>)> = Vec::new();
+        for (id, name, bytes, enrolled) in rows {
+            let embedding = embedding_from_bytes(&bytes);
+            match speakers.last_mut() {
+                Some((speaker, embeddings)) if speaker.id == id => {
+                    speaker.enrolled |= enrolled;
+                    embeddings.push(embedding);
+                }
+                _ => speakers.push((
+                    ClusteringSpeaker {
+                        id,
+                        name,
+                        enrolled,
+                        transcription_count: counts.get(&id).copied().unwrap_or(0),
+                        centroid: Vec::new(),
+                    },
+                    vec![embedding],
+                )),
+            }
+        }
+        Ok(speakers
+            .into_iter()
+            .map(|(mut speaker, embeddings)| {
+                speaker.centroid = embedding_centroid(&embeddings);
+                speaker
+            })
+            .collect())
+    }
+
+    /// Cluster all speakers and replace the pending merge proposals with the merges found.
+    ///
+    /// Pairs of speakers rejected before are not proposed again. Proposals with at least
+    /// `auto_merge_threshold` confidence are accepted right away. Fails without clustering
+    /// anything when there are more than [`MAX_CLUSTERED_SPEAKERS`] speakers.
+    pub async fn cluster_speakers(
+        &self,
+        config: &SpeakerClusteringConfig,
+    ) -> Result<SpeakerClusteringReport, SpeakerClusteringError> {
+        let speakers = self.get_speakers_for_clustering().await?;
+        if speakers.len() > MAX_CLUSTERED_SPEAKERS {
+            return Err(SpeakerClusteringError::TooManySpeakers(speakers.len()));
+        }
+        let rejected: Vec<(i64, i64)> = sqlx::query_as(
+            "SELECT speaker_id, merge_speaker_id FROM speaker_merge_proposals WHERE status = 'rejected'",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let cannot_link: HashSet<(i64, i64)> = rejected
+            .into_iter()
+            .flat_map(|(a, b)| [(a, b), (b, a)])
+            .collect();
+
+        let speaker_count = speakers.len();
+        let threshold = config.threshold;
+        // Cubic in the number of speakers, keep it off the async workers
+        let merges = tokio::task::spawn_blocking(move || {
+            cluster_speakers(&speakers, threshold, &cannot_link)
+        })
+        .await
+        .map_err(|e| {
+            SpeakerClusteringError::Sql(sqlx::Error::Protocol(format!(
+                "speaker clustering failed: {}",
+                e
+            )))
+        })?;
+        let clusters = merges
+            .iter()
+            .map(|merge| merge.speaker_id)
+            .collect::<HashSet<_>>()
+            .len();
+
+        let mut tx = self.begin_immediate_with_retry().await?;
+        sqlx::query("DELETE FROM speaker_merge_proposals WHERE status = 'pending'")
+            .execute(&mut **tx.conn())
+            .await?;
+        let mut auto_merge = Vec::new();
+        for merge in &merges {
+            let id = sqlx::query(
+                "INSERT INTO speaker_merge_proposals
+                 (speaker_id, merge_speaker_id, confidence, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )
+            .bind(merge.speaker_id)
+            .bind(merge.merge_speaker_id)
+            .bind(merge.confidence as f64)
+            .bind(Utc::now())
+            .execute(&mut **tx.conn())
+            .await?
+            .last_insert_rowid();
+            if config
+                .auto_merge_threshold
+                .is_some_and(|auto_merge_threshold| merge.confidence >= auto_merge_threshold)
+            {
+                auto_merge.push(id);
+            }
+        }
+        tx.commit().await?;
+
+        let auto_merged = if auto_merge.is_empty() {
+            0
+        } else {
+            self.resolve_speaker_merge_proposals(&auto_merge, &[])
+                .await?
+                .accepted
+                .len()
+        };
+        info!(
+            "clustered {} speakers into {} clusters, {} merge proposals, {} merged",
+            speaker_count,
+            clusters,
+            merges.len(),
+            auto_merged
+        );
+
+        Ok(SpeakerClusteringReport {
+            speakers: speaker_count,
+            clusters,
+            proposals: merges.len() - auto_merged,
+            auto_merged,
+        })
+    }
+
+    /// Merge proposals, most confident first, optionally with the given status.
+    pub async fn list_speaker_merge_proposals(
+        &self,
+        status: Option<SpeakerMergeProposalStatus>,
+    ) -> Result<Vec<SpeakerMergeProposal>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT p.id, p.speaker_id, kept.name AS speaker_name, p.merge_speaker_id,
+                    merged.name AS merge_speaker_name, p.confidence, p.status, p.created_at,
+                    p.resolved_at
+             FROM speaker_merge_proposals p
+             LEFT JOIN speakers kept ON kept.id = p.speaker_id
+             LEFT JOIN speakers merged ON merged.id = p.merge_speaker_id
+             WHERE ?1 IS NULL OR p.status = ?1
+             ORDER BY p.confidence DESC, p.id",
+        )
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Merge the speakers of the `accept` proposals and remember the `reject` ones so they
+    /// are not proposed again.
+    ///
+    /// Pending proposals involving a merged speaker are moved to the speaker it was merged
+    /// into.
+    pub async fn resolve_speaker_merge_proposals(
+        &self,
+        accept: &[i64],
+        reject: &[i64],
+    ) -> Result<SpeakerMergeResolution, sqlx::Error> {
+        let mut resolution = SpeakerMergeResolution::default();
+
+        for &id in accept {
+            let mut tx = self.begin_immediate_with_retry().await?;
+            let proposal: Option<(i64, i64)> = sqlx::query_as(
+                "SELECT p.speaker_id, p.merge_speaker_id FROM speaker_merge_proposals p
+                 WHERE p.id = ?1 AND p.status = 'pending'
+                 AND EXISTS (SELECT 1 FROM speakers WHERE id = p.speaker_id)
+                 AND EXISTS (SELECT 1 FROM speakers WHERE id = p.merge_speaker_id)",
+            )
+            .bind(id)
+            .fetch_optional(&mut **tx.conn())
+            .await?;
+            let Some((keep, merge)) = proposal else {
+                debug!("skipping speaker merge proposal {}", id);
+                resolution.skipped.push(id);
+                continue;
+            };
+
+            Self::merge_speakers_in_tx(&mut tx, keep, merge).await?;
+            sqlx::query(
+                "UPDATE speaker_merge_proposals SET status = 'accepted', resolved_at = ?2
+                 WHERE id = ?1",
+            )
+            .bind(id)
+            .bind(Utc::now())
+            .execute(&mut **tx.conn())
+            .await?;
+            for column in ["speaker_id", "merge_speaker_id"] {
+                sqlx::query(&format!(
+                    "UPDATE speaker_merge_proposals SET {column} = ?1
+                     WHERE {column} = ?2 AND status = 'pending'"
+                ))
+                .bind(keep)
+                .bind(merge)
+                .execute(&mut **tx.conn())
+                .await?;
+            }
+            sqlx::query(
+                "DELETE FROM speaker_merge_proposals
+                 WHERE speaker_id = merge_speaker_id AND status = 'pending'",
+            )
+            .execute(&mut **tx.conn())
+            .await?;
+            tx.commit().await?;
+            resolution.accepted.push(id);
+        }
+
+        for &id in reject {
+            let mut tx = self.begin_immediate_with_retry().await?;
+            let rejected = sqlx::query(
+                "UPDATE speaker_merge_proposals SET status = 'rejected', resolved_at = ?2
+                 WHERE id = ?1 AND status = 'pending'",
+            )
+            .bind(id)
+            .bind(Utc::now())
+            .execute(&mut **tx.conn())
+            .await?
+            .rows_affected();
+            tx.commit().await?;
+            if rejected > 0 {
+                resolution.rejected.push(id);
+            } else {
+                resolution.skipped.push(id);
+            }
+        }
+
+        Ok(resolution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speaker(id: i64, name: Option<&str>, centroid: Vec<f32>) -> ClusteringSpeaker {
+        ClusteringSpeaker {
+            id,
+            name: name.map(str::to_string),
+            enrolled: false,
+            transcription_count: 0,
+            centroid: embedding_centroid(&[centroid]),
+        }
+    }
+
+    #[test]
+    fn test_cluster_speakers_merges_close_speakers() {
+        let speakers = vec![
+            speaker(1, None, vec![1.0, 0.0, 0.0]),
+            speaker(2, Some("alice"), vec![0.95, 0.05, 0.0]),
+            speaker(3, None, vec![0.9, 0.1, 0.0]),
+            speaker(4, None, vec![0.0, 0.0, 1.0]),
+        ];
+        let merges = cluster_speakers(&speakers, 0.9, &HashSet::new());
+
+        // Merged into the named speaker, the distant one is left alone
+        assert_eq!(merges.len(), 2);
+        assert!(merges.iter().all(|m| m.speaker_id == 2));
+        let merged: HashSet<i64> = merges.iter().map(|m| m.merge_speaker_id).collect();
+        assert_eq!(merged, HashSet::from([1, 3]));
+        assert!(merges.iter().all(|m| m.confidence > 0.9));
+    }
+
+    #[test]
+    fn test_cluster_speakers_keeps_apart_different_names_and_rejected_pairs() {
+        let speakers = vec![
+            speaker(1, Some("alice"), vec![1.0, 0.0]),
+            speaker(2, Some("bob"), vec![1.0, 0.01]),
+            speaker(3, None, vec![1.0, 0.02]),
+        ];
+        let merges = cluster_speakers(&speakers, 0.9, &HashSet::from([(3, 1), (1, 3)]));
+
+        // 3 can only join bob, which then can't join alice
+        assert_eq!(
+            merges,
+            vec![ProposedMerge {
+                speaker_id: 2,
+                merge_speaker_id: 3,
+                confidence: merges[0].confidence,
+            }]
+        );
+    }
+}
@@ -5,6 +5,9 @@ use sqlx::FromRow;
 use std::error::Error as StdError;
 use std::fmt::{self, Display};
 
+use crate::ocr_layout::OcrLayoutBlock;
+use crate::speaker_clustering::embedding_centroid;
+
 /// Data for a single window result to be batch-inserted with its frame.
 /// Used by `insert_frames_with_ocr_batch` to reduce write lock contention.
 #[derive(Debug, Clone)]
@@ -15,6 +18,8 @@ pub struct FrameWindowData {
     pub focused: bool,
     pub text: String,
     pub text_json: String,
+    /// Paragraphs, tables and code blocks of the text, see [`OcrLayoutBlock`]
+    pub layout: Vec<OcrLayoutBlock>,
 }
 
 #[derive(OaSchema, Debug)]
@@ -156,6 +161,7 @@ pub struct AudioResultRaw {
     pub speaker_id: Option<i64>,
     pub start_time: Option<f64>,
     pub end_time: Option<f64>,
+    pub transcription_id: i64,
 }
 
 #[derive(OaSchema, Debug, Serialize, Deserialize, FromRow, Clone)]
@@ -165,6 +171,27 @@ pub struct Speaker {
     pub metadata: String,
 }
 
+/// A named speaker with voice embeddings enrolled explicitly.
+#[derive(Debug, Clone)]
+pub struct EnrolledSpeaker {
+    pub id: i64,
+    pub name: String,
+    /// Normalized mean of `embeddings`
+    pub centroid: Vec<f32>,
+    pub embeddings: Vec<Vec<f32>>,
+}
+
+impl EnrolledSpeaker {
+    pub(crate) fn new(id: i64, name: String, embeddings: Vec<Vec<f32>>) -> Self {
+        Self {
+            id,
+            name,
+            centroid: embedding_centroid(&embeddings),
+            embeddings,
+        }
+    }
+}
+
 #[derive(OaSchema, Clone, Eq, PartialEq, Hash, Serialize, Debug, Deserialize)]
 pub enum DeviceType {
     Input,
@@ -185,6 +212,42 @@ pub struct AudioResult {
     pub speaker: Option<Speaker>,
     pub start_time: Option<f64>,
     pub end_time: Option<f64>,
+    pub transcription_id: i64,
+    /// Seconds into the audio chunk of the first word matching the query, when word
+    /// timings were recorded
+    pub seek_time: Option<f64>,
+}
+
+/// A word of an audio transcription, times in seconds from the start of the audio chunk.
+#[derive(OaSchema, Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct AudioTranscriptionWord {
+    pub word: String,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub confidence: Option<f64>,
+}
+
+/// An audio chunk transcribed by another engine than the one a re-transcription uses.
+#[derive(Debug, Clone, FromRow)]
+pub struct AudioChunkToRetranscribe {
+    pub id: i64,
+    pub file_path: String,
+    /// Timestamp of its first transcription
+    pub timestamp: DateTime<Utc>,
+    pub device: String,
+    pub is_input_device: bool,
+}
+
+/// A transcription of a segment of an audio chunk, see
+/// [`DatabaseManager::replace_audio_transcriptions`](crate::DatabaseManager::replace_audio_transcriptions)
+/// and [`DatabaseManager::insert_imported_audio`](crate::DatabaseManager::insert_imported_audio).
+#[derive(Debug, Clone, Default)]
+pub struct NewAudioTranscription {
+    pub transcription: String,
+    pub speaker_id: Option<i64>,
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+    pub words: Vec<AudioTranscriptionWord>,
 }
 
 #[derive(OaSchema, Debug, Deserialize, PartialEq)]
@@ -368,6 +431,7 @@ pub enum OcrEngine {
     WindowsNative,
     AppleNative,
     Custom(CustomOcrConfig),
+    Onnx,
 }
 
 #[derive(OaSchema, Debug, Clone, Serialize, Deserialize)]
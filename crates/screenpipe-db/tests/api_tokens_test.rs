@@ -0,0 +1,50 @@
+//! API token storage tests
+//!
+//! Run with: cargo test --package screenpipe-db --test api_tokens_test -- --nocapture
+
+#[cfg(test)]
+mod tests {
+    use screenpipe_db::DatabaseManager;
+
+    async fn setup_test_db() -> DatabaseManager {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+
+        match sqlx::migrate!("./src/migrations").run(&db.pool).await {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Migration error: {:?}", e);
+                panic!("Database migration failed: {}", e);
+            }
+        }
+
+        db
+    }
+
+    #[tokio::test]
+    async fn test_insert_lookup_and_revoke() {
+        let db = setup_test_db().await;
+        let scopes = vec!["read-search".to_string(), "read-media".to_string()];
+        let id = db
+            .insert_api_token("laptop", "hash-1", &scopes)
+            .await
+            .unwrap();
+
+        let token = db.get_api_token_by_hash("hash-1").await.unwrap().unwrap();
+        assert_eq!(token.id, id);
+        assert_eq!(token.name, "laptop");
+        assert_eq!(token.scopes, scopes);
+        assert!(token.last_used_at.is_none());
+
+        db.touch_api_token(id).await.unwrap();
+        let tokens = db.list_api_tokens().await.unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert!(tokens[0].last_used_at.is_some());
+
+        assert!(db.get_api_token_by_hash("other").await.unwrap().is_none());
+
+        assert!(db.revoke_api_token(id).await.unwrap());
+        assert!(!db.revoke_api_token(id).await.unwrap());
+        assert!(db.get_api_token_by_hash("hash-1").await.unwrap().is_none());
+        assert!(db.list_api_tokens().await.unwrap()[0].revoked_at.is_some());
+    }
+}
@@ -4,7 +4,8 @@ mod tests {
 
     use chrono::Utc;
     use screenpipe_db::{
-        AudioDevice, ContentType, DatabaseManager, DeviceType, Frame, OcrEngine, SearchResult,
+        AudioDevice, AudioTranscriptionWord, ContentType, DatabaseManager, DeviceType, Frame,
+        NewAudioTranscription, OcrEngine, SearchResult,
     };
 
     async fn setup_test_db() -> DatabaseManager {
@@ -235,6 +236,335 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_audio_transcription_words() {
+        let db = setup_test_db().await;
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        let transcription_id = db
+            .insert_audio_transcription(
+                audio_chunk_id,
+                "Let's review the budget, then lunch",
+                0,
+                "",
+                &AudioDevice {
+                    name: "test".to_string(),
+                    device_type: DeviceType::Input,
+                },
+                None,
+                Some(10.0),
+                Some(13.0),
+            )
+            .await
+            .unwrap();
+        let words: Vec<AudioTranscriptionWord> = [
+            ("Let's", 10.0),
+            ("review", 10.4),
+            ("the", 10.9),
+            ("budget,", 11.1),
+            ("then", 11.8),
+            ("lunch", 12.2),
+        ]
+        .into_iter()
+        .map(|(word, start_time)| AudioTranscriptionWord {
+            word: word.to_string(),
+            start_time,
+            end_time: start_time + 0.3,
+            confidence: Some(0.9),
+        })
+        .collect();
+        db.insert_audio_transcription_words(transcription_id, &words)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_audio_transcription_words(transcription_id)
+                .await
+                .unwrap(),
+            words
+        );
+
+        // Search hits seek to the matching word
+        let results = db
+            .search_audio("budget*", 10, 0, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].transcription_id, transcription_id);
+        assert_eq!(results[0].seek_time, Some(11.1));
+
+        let results = db
+            .search_audio("", 10, 0, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(results[0].seek_time, None);
+
+        // Cutting the transcription short drops the timings of the words cut off
+        db.update_audio_transcription(audio_chunk_id, "Let's review the budget,")
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_audio_transcription_words(transcription_id)
+                .await
+                .unwrap(),
+            words[..4]
+        );
+
+        // Each hit seeks within its own transcription
+        let other_chunk_id = db.insert_audio_chunk("other_audio.mp4").await.unwrap();
+        let other_id = db
+            .insert_audio_transcription(
+                other_chunk_id,
+                "budget approved",
+                0,
+                "",
+                &AudioDevice {
+                    name: "test".to_string(),
+                    device_type: DeviceType::Input,
+                },
+                None,
+                Some(40.0),
+                Some(41.0),
+            )
+            .await
+            .unwrap();
+        db.insert_audio_transcription_words(
+            other_id,
+            &[AudioTranscriptionWord {
+                word: "budget".to_string(),
+                start_time: 40.2,
+                end_time: 40.6,
+                confidence: None,
+            }],
+        )
+        .await
+        .unwrap();
+        let results = db
+            .search_audio("budget", 10, 0, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        let mut seek_times: Vec<(i64, Option<f64>)> = results
+            .iter()
+            .map(|r| (r.transcription_id, r.seek_time))
+            .collect();
+        seek_times.sort_by_key(|(id, _)| *id);
+        assert_eq!(
+            seek_times,
+            vec![(transcription_id, Some(11.1)), (other_id, Some(40.2))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replace_audio_transcriptions() {
+        let db = setup_test_db().await;
+        let device = AudioDevice {
+            name: "test".to_string(),
+            device_type: DeviceType::Output,
+        };
+        let since = Utc::now() - chrono::Duration::minutes(1);
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        let transcription_id = db
+            .insert_audio_transcription(
+                audio_chunk_id,
+                "the quick brown fax",
+                0,
+                "WhisperTiny",
+                &device,
+                None,
+                Some(0.0),
+                Some(2.0),
+            )
+            .await
+            .unwrap();
+        db.insert_audio_transcription_words(
+            transcription_id,
+            &[AudioTranscriptionWord {
+                word: "the".to_string(),
+                start_time: 0.0,
+                end_time: 0.2,
+                confidence: None,
+            }],
+        )
+        .await
+        .unwrap();
+        let timestamp = db
+            .search_audio("", 10, 0, None, None, None, None, None, None)
+            .await
+            .unwrap()[0]
+            .timestamp;
+
+        let engine = "WhisperLargeV3Turbo";
+        assert_eq!(
+            db.count_audio_chunks_to_retranscribe(since, None, engine)
+                .await
+                .unwrap(),
+            1
+        );
+        let chunks = db
+            .get_audio_chunks_to_retranscribe(since, None, engine, 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].id, audio_chunk_id);
+        assert_eq!(chunks[0].file_path, "test_audio.mp4");
+        assert_eq!(chunks[0].device, "test");
+        assert!(!chunks[0].is_input_device);
+        assert!(db
+            .get_audio_chunks_to_retranscribe(since, None, engine, audio_chunk_id, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.count_audio_chunks_to_retranscribe(since, Some(since), engine)
+                .await
+                .unwrap(),
+            0
+        );
+
+        let ids = db
+            .replace_audio_transcriptions(
+                audio_chunk_id,
+                engine,
+                &[
+                    NewAudioTranscription {
+                        transcription: "the quick brown fox".to_string(),
+                        start_time: Some(0.0),
+                        end_time: Some(2.0),
+                        words: vec![AudioTranscriptionWord {
+                            word: "fox".to_string(),
+                            start_time: 1.5,
+                            end_time: 1.9,
+                            confidence: Some(0.9),
+                        }],
+                        ..Default::default()
+                    },
+                    NewAudioTranscription {
+                        transcription: " ".to_string(),
+                        ..Default::default()
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(ids.len(), 1);
+        assert!(db
+            .get_audio_transcription_words(transcription_id)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.get_audio_transcription_words(ids[0])
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Full text search only finds the new text, at the time of the old one
+        assert!(db
+            .search_audio("fax", 10, 0, None, None, None, None, None, None)
+            .await
+            .unwrap()
+            .is_empty());
+        let results = db
+            .search_audio("fox", 10, 0, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].transcription_id, ids[0]);
+        assert_eq!(results[0].transcription_engine, engine);
+        assert_eq!(results[0].device_name, "test");
+        assert_eq!(results[0].timestamp, timestamp);
+        assert_eq!(results[0].seek_time, Some(1.5));
+
+        // Nothing left to re-transcribe with that engine
+        assert_eq!(
+            db.count_audio_chunks_to_retranscribe(since, None, engine)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_imported_audio() {
+        let db = setup_test_db().await;
+        let device = AudioDevice {
+            name: "imported_files".to_string(),
+            device_type: DeviceType::Output,
+        };
+        let recorded_at = Utc::now() - chrono::Duration::days(30);
+
+        let (audio_chunk_id, ids) = db
+            .insert_imported_audio(
+                "meeting.m4a",
+                recorded_at,
+                "WhisperLargeV3Turbo",
+                &device,
+                &[
+                    NewAudioTranscription {
+                        transcription: "welcome everyone".to_string(),
+                        start_time: Some(0.0),
+                        end_time: Some(2.0),
+                        ..Default::default()
+                    },
+                    NewAudioTranscription {
+                        transcription: "next quarter roadmap".to_string(),
+                        start_time: Some(90.0),
+                        end_time: Some(93.0),
+                        words: vec![AudioTranscriptionWord {
+                            word: "roadmap".to_string(),
+                            start_time: 91.5,
+                            end_time: 92.0,
+                            confidence: None,
+                        }],
+                        ..Default::default()
+                    },
+                    // Not deduplicated against the previous one across devices, but within
+                    // the chunk
+                    NewAudioTranscription {
+                        transcription: "welcome everyone".to_string(),
+                        start_time: Some(120.0),
+                        end_time: Some(122.0),
+                        ..Default::default()
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+        assert!(audio_chunk_id > 0);
+        assert_eq!(ids.len(), 2);
+
+        let results = db
+            .search_audio("roadmap", 10, 0, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].audio_chunk_id, audio_chunk_id);
+        assert_eq!(results[0].file_path, "meeting.m4a");
+        assert_eq!(results[0].device_name, "imported_files");
+        assert_eq!(results[0].device_type, DeviceType::Output);
+        assert_eq!(results[0].seek_time, Some(91.5));
+        // Timestamped at the time of the segment in the recording
+        assert_eq!((results[0].timestamp - recorded_at).num_seconds(), 90);
+
+        // Found when searching the time of the recording
+        let results = db
+            .search_audio(
+                "",
+                10,
+                0,
+                Some(recorded_at - chrono::Duration::minutes(1)),
+                Some(recorded_at + chrono::Duration::minutes(1)),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].transcription, "welcome everyone");
+    }
+
     #[tokio::test]
     async fn test_search_all() {
         let db = setup_test_db().await;
@@ -834,6 +1164,51 @@ mod tests {
         assert_eq!(speaker.id, 1);
     }
 
+    #[tokio::test]
+    async fn test_enroll_speaker() {
+        let db = setup_test_db().await;
+
+        // Implicit speakers have no enrolled embeddings
+        let implicit = db.insert_speaker(&vec![0.1; 512]).await.unwrap();
+        assert!(db.get_enrolled_speakers().await.unwrap().is_empty());
+
+        let mut first = vec![0.0; 512];
+        first[0] = 1.0;
+        let mut second = vec![0.0; 512];
+        second[1] = 2.0;
+        let speaker = db
+            .enroll_speaker(None, Some("alice"), &[first, second])
+            .await
+            .unwrap();
+        assert_ne!(speaker.id, implicit.id);
+        assert_eq!(speaker.name, "alice");
+        assert_eq!(speaker.embeddings.len(), 2);
+        // Mean of the normalized embeddings, normalized
+        let expected = 1.0 / 2f32.sqrt();
+        assert!((speaker.centroid[0] - expected).abs() < 1e-6);
+        assert!((speaker.centroid[1] - expected).abs() < 1e-6);
+
+        // Extending and renaming an existing speaker
+        let speaker = db
+            .enroll_speaker(Some(implicit.id), Some("bob"), &[vec![0.3; 512]])
+            .await
+            .unwrap();
+        assert_eq!(speaker.id, implicit.id);
+        assert_eq!(speaker.name, "bob");
+        assert_eq!(speaker.embeddings.len(), 1);
+
+        let enrolled = db.get_enrolled_speakers().await.unwrap();
+        assert_eq!(enrolled.len(), 2);
+        assert_eq!(enrolled[0].name, "bob");
+        assert_eq!(enrolled[1].name, "alice");
+
+        // Unknown speakers are not created
+        assert!(db
+            .enroll_speaker(Some(9999), None, &[vec![0.3; 512]])
+            .await
+            .is_err());
+    }
+
     #[tokio::test]
     async fn test_update_speaker_name() {
         let db = setup_test_db().await;
@@ -1542,4 +1917,41 @@ mod tests {
             .unwrap();
         assert_eq!(count, 0, "Should count zero results for non-matching query");
     }
+
+    #[tokio::test]
+    async fn test_audio_device_settings() {
+        let db = setup_test_db().await;
+        assert!(db.get_audio_device_settings().await.unwrap().is_empty());
+
+        db.set_audio_device_settings("Mic (input)", r#"{"languages":["english"]}"#)
+            .await
+            .unwrap();
+        db.set_audio_device_settings("Speakers (output)", r#"{"enable_diarization":true}"#)
+            .await
+            .unwrap();
+        // Settings are replaced, not merged
+        db.set_audio_device_settings("Mic (input)", r#"{"vad_sensitivity":"low"}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_audio_device_settings().await.unwrap(),
+            vec![
+                (
+                    "Mic (input)".to_string(),
+                    r#"{"vad_sensitivity":"low"}"#.to_string()
+                ),
+                (
+                    "Speakers (output)".to_string(),
+                    r#"{"enable_diarization":true}"#.to_string()
+                ),
+            ]
+        );
+
+        db.delete_audio_device_settings("Mic (input)")
+            .await
+            .unwrap();
+        let settings = db.get_audio_device_settings().await.unwrap();
+        assert_eq!(settings.len(), 1);
+        assert_eq!(settings[0].0, "Speakers (output)");
+    }
 }
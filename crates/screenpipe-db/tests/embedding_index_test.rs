@@ -0,0 +1,287 @@
+//! Embedding indexer storage tests
+//!
+//! Run with: cargo test --package screenpipe-db --test embedding_index_test -- --nocapture
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use screenpipe_db::{
+        AudioDevice, ContentType, DatabaseManager, DeviceType, EmbeddingModality,
+        HybridSearchQuery, OcrEngine, SearchResult,
+    };
+
+    const MODEL: &str = "local-model";
+
+    async fn setup_test_db() -> DatabaseManager {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+
+        match sqlx::migrate!("./src/migrations").run(&db.pool).await {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Migration error: {:?}", e);
+                panic!("Database migration failed: {}", e);
+            }
+        }
+
+        db
+    }
+
+    async fn insert_ocr(db: &DatabaseManager, text: &str) -> i64 {
+        let frame_id = db
+            .insert_frame(
+                "monitor_1",
+                None,
+                None,
+                Some("Code"),
+                Some("window"),
+                true,
+                None,
+            )
+            .await
+            .unwrap();
+        db.insert_ocr_text(frame_id, text, "", Arc::new(OcrEngine::Tesseract))
+            .await
+            .unwrap();
+        frame_id
+    }
+
+    async fn insert_transcription(db: &DatabaseManager, text: &str) -> i64 {
+        let chunk_id = db
+            .insert_audio_chunk(&format!("{}.mp4", text))
+            .await
+            .unwrap();
+        db.insert_audio_transcription(
+            chunk_id,
+            text,
+            0,
+            "whisper",
+            &AudioDevice {
+                name: "mic".to_string(),
+                device_type: DeviceType::Input,
+            },
+            None,
+            Some(0.0),
+            Some(1.0),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_watermarks_split_new_rows_from_backfill() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "monitor_1")
+            .await
+            .unwrap();
+        let old_a = insert_ocr(&db, "old frame a").await;
+        insert_ocr(&db, "").await;
+        let old_b = insert_ocr(&db, "old frame b").await;
+
+        let watermarks = db
+            .get_embedding_watermarks(EmbeddingModality::Ocr)
+            .await
+            .unwrap();
+        assert_eq!(watermarks.high_water_id, old_b);
+        assert_eq!(watermarks.low_water_id, old_b + 1);
+        assert!(!watermarks.backfill_complete);
+
+        let new = insert_ocr(&db, "new frame").await;
+        let candidates = db
+            .get_new_embedding_candidates(
+                EmbeddingModality::Ocr,
+                MODEL,
+                watermarks.high_water_id,
+                10,
+            )
+            .await
+            .unwrap();
+        let ids: Vec<i64> = candidates.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![new]);
+
+        // Empty OCR text is never a candidate
+        let backfill = db
+            .get_backfill_embedding_candidates(
+                EmbeddingModality::Ocr,
+                MODEL,
+                watermarks.low_water_id,
+                10,
+            )
+            .await
+            .unwrap();
+        let ids: Vec<i64> = backfill.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![old_b, old_a]);
+
+        db.insert_indexed_embeddings(
+            EmbeddingModality::Ocr,
+            MODEL,
+            &[(new, vec![1.0, 0.0]), (old_b, vec![0.0, 1.0])],
+            Some(new),
+            Some(old_b),
+        )
+        .await
+        .unwrap();
+
+        let watermarks = db
+            .get_embedding_watermarks(EmbeddingModality::Ocr)
+            .await
+            .unwrap();
+        assert_eq!(watermarks.high_water_id, new);
+        assert_eq!(watermarks.low_water_id, old_b);
+        let backfill = db
+            .get_backfill_embedding_candidates(
+                EmbeddingModality::Ocr,
+                MODEL,
+                watermarks.low_water_id,
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(backfill.len(), 1);
+        assert_eq!(backfill[0].id, old_a);
+        assert_eq!(backfill[0].text, "old frame a");
+
+        db.mark_embedding_backfill_complete(EmbeddingModality::Ocr)
+            .await
+            .unwrap();
+        let progress = db.get_embedding_index_progress(MODEL).await.unwrap();
+        assert_eq!(progress.len(), 1);
+        assert_eq!(progress[0].modality, EmbeddingModality::Ocr);
+        assert_eq!(progress[0].total, 3);
+        assert_eq!(progress[0].indexed, 2);
+        assert!(progress[0].backfill_completed_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_late_ocr_text_and_other_models_are_indexed() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "monitor_1")
+            .await
+            .unwrap();
+        // The frame is recorded first, its text lands after a later frame's
+        let late = db
+            .insert_frame(
+                "monitor_1",
+                None,
+                None,
+                Some("Code"),
+                Some("window"),
+                true,
+                None,
+            )
+            .await
+            .unwrap();
+        let embedded_elsewhere = insert_ocr(&db, "embedded with ollama").await;
+        db.insert_embeddings(
+            embedded_elsewhere,
+            "[1.0, 0.0]".to_string(),
+            "nomic-embed-text",
+        )
+        .await
+        .unwrap();
+
+        let watermarks = db
+            .get_embedding_watermarks(EmbeddingModality::Ocr)
+            .await
+            .unwrap();
+        db.insert_ocr_text(late, "late text", "", Arc::new(OcrEngine::Tesseract))
+            .await
+            .unwrap();
+
+        let new = db
+            .get_new_embedding_candidates(
+                EmbeddingModality::Ocr,
+                MODEL,
+                watermarks.high_water_id,
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].id, late);
+        assert!(new[0].row_id > watermarks.high_water_id);
+
+        // A vector of another model doesn't count as indexed
+        let backfill = db
+            .get_backfill_embedding_candidates(
+                EmbeddingModality::Ocr,
+                MODEL,
+                watermarks.low_water_id,
+                10,
+            )
+            .await
+            .unwrap();
+        let ids: Vec<i64> = backfill.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![embedded_elsewhere]);
+    }
+
+    #[tokio::test]
+    async fn test_audio_embeddings_rank_in_hybrid_search() {
+        let db = setup_test_db().await;
+        let watermarks = db
+            .get_embedding_watermarks(EmbeddingModality::Audio)
+            .await
+            .unwrap();
+        assert_eq!(watermarks.high_water_id, 0);
+
+        let close = insert_transcription(&db, "we should ship the release on friday").await;
+        let far = insert_transcription(&db, "lunch was great").await;
+        let candidates = db
+            .get_new_embedding_candidates(
+                EmbeddingModality::Audio,
+                MODEL,
+                watermarks.high_water_id,
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(candidates.len(), 2);
+
+        db.insert_indexed_embeddings(
+            EmbeddingModality::Audio,
+            MODEL,
+            &[(close, vec![1.0, 0.0, 0.0]), (far, vec![0.0, 1.0, 0.0])],
+            Some(far),
+            None,
+        )
+        .await
+        .unwrap();
+        // Re-inserting is a no-op
+        db.insert_indexed_embeddings(
+            EmbeddingModality::Audio,
+            MODEL,
+            &[(close, vec![0.0, 0.0, 1.0])],
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(db
+            .get_new_embedding_candidates(EmbeddingModality::Audio, MODEL, 0, 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // No keyword overlap, only the vector ranking matches
+        let hits = db
+            .hybrid_search(&HybridSearchQuery {
+                query: "deploy",
+                embedding: Some(vec![0.9, 0.1, 0.0]),
+                max_distance: 0.3,
+                content_type: ContentType::Audio,
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].score.vector_rank, Some(1));
+        assert!(hits[0].score.fts_rank.is_none());
+        match &hits[0].result {
+            SearchResult::Audio(audio) => {
+                assert_eq!(audio.transcription, "we should ship the release on friday")
+            }
+            _ => panic!("expected an audio result"),
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Hybrid (bm25 + vector) search tests
+//!
+//! Run with: cargo test --package screenpipe-db --test hybrid_search_test -- --nocapture
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::Utc;
+    use screenpipe_db::{
+        is_fts_query_error, AudioDevice, ContentType, DatabaseManager, DeviceType,
+        HybridSearchQuery, OcrEngine, SearchResult,
+    };
+
+    async fn setup_test_db() -> DatabaseManager {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+
+        match sqlx::migrate!("./src/migrations").run(&db.pool).await {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Migration error: {:?}", e);
+                panic!("Database migration failed: {}", e);
+            }
+        }
+
+        db
+    }
+
+    async fn insert_ocr(db: &DatabaseManager, app: &str, text: &str) -> i64 {
+        let frame_id = db
+            .insert_frame(
+                "monitor_1",
+                None,
+                None,
+                Some(app),
+                Some("window"),
+                true,
+                None,
+            )
+            .await
+            .unwrap();
+        db.insert_ocr_text(frame_id, text, "", Arc::new(OcrEngine::Tesseract))
+            .await
+            .unwrap();
+        frame_id
+    }
+
+    async fn insert_transcription(db: &DatabaseManager, text: &str) {
+        let chunk_id = db
+            .insert_audio_chunk(&format!("{}.mp4", text))
+            .await
+            .unwrap();
+        db.insert_audio_transcription(
+            chunk_id,
+            text,
+            0,
+            "whisper",
+            &AudioDevice {
+                name: "mic".to_string(),
+                device_type: DeviceType::Input,
+            },
+            None,
+            Some(0.0),
+            Some(1.0),
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn insert_ui(db: &DatabaseManager, text: &str) {
+        sqlx::query(
+            "INSERT INTO ui_monitoring (text_output, timestamp, app, window, initial_traversal_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(text)
+        .bind(Utc::now())
+        .bind("Finder")
+        .bind("Documents")
+        .bind(Utc::now())
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    }
+
+    async fn setup_video_chunk(db: &DatabaseManager) {
+        db.insert_video_chunk("test_video.mp4", "monitor_1")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search_merges_modalities() {
+        let db = setup_test_db().await;
+        setup_video_chunk(&db).await;
+        insert_ocr(&db, "Code", "quarterly budget review spreadsheet").await;
+        insert_transcription(&db, "let's go over the quarterly budget").await;
+        insert_ui(&db, "budget.xlsx").await;
+        insert_ocr(&db, "Code", "unrelated text").await;
+
+        let hits = db
+            .hybrid_search(&HybridSearchQuery {
+                query: "budget",
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(hits.len(), 3);
+        assert!(hits
+            .iter()
+            .any(|h| matches!(h.result, SearchResult::OCR(_))));
+        assert!(hits
+            .iter()
+            .any(|h| matches!(h.result, SearchResult::Audio(_))));
+        assert!(hits.iter().any(|h| matches!(h.result, SearchResult::UI(_))));
+        for hit in &hits {
+            assert_eq!(hit.score.fts_rank, Some(1));
+            assert!(hit.score.bm25.is_some());
+            assert!(hit.score.vector_rank.is_none());
+        }
+
+        let audio_only = db
+            .hybrid_search(&HybridSearchQuery {
+                query: "budget",
+                content_type: ContentType::Audio,
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(audio_only.len(), 1);
+        match &audio_only[0].result {
+            SearchResult::Audio(audio) => {
+                assert_eq!(audio.transcription, "let's go over the quarterly budget")
+            }
+            _ => panic!("expected an audio result"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search_fuses_vector_ranks() {
+        let db = setup_test_db().await;
+        setup_video_chunk(&db).await;
+        let keyword_only = insert_ocr(&db, "Mail", "invoice from acme").await;
+        let both = insert_ocr(&db, "Mail", "invoice payment overdue").await;
+        let semantic_only = insert_ocr(&db, "Mail", "your bill is late").await;
+        let far = insert_ocr(&db, "Mail", "weather forecast").await;
+
+        db.insert_embeddings(both, "[1.0, 0.0, 0.0]".to_string(), "test")
+            .await
+            .unwrap();
+        db.insert_embeddings(semantic_only, "[0.9, 0.1, 0.0]".to_string(), "test")
+            .await
+            .unwrap();
+        db.insert_embeddings(far, "[0.0, 0.0, 1.0]".to_string(), "test")
+            .await
+            .unwrap();
+
+        let hits = db
+            .hybrid_search(&HybridSearchQuery {
+                query: "invoice",
+                embedding: Some(vec![1.0, 0.0, 0.0]),
+                max_distance: 0.3,
+                content_type: ContentType::OCR,
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let frame_ids: Vec<i64> = hits
+            .iter()
+            .map(|h| match &h.result {
+                SearchResult::OCR(ocr) => ocr.frame_id,
+                _ => panic!("expected OCR results only"),
+            })
+            .collect();
+        assert_eq!(frame_ids.len(), 3);
+        assert_eq!(frame_ids[0], both);
+        assert!(frame_ids.contains(&keyword_only));
+        assert!(frame_ids.contains(&semantic_only));
+        assert!(!frame_ids.contains(&far));
+
+        let top = &hits[0].score;
+        assert!(top.fts_rank.is_some());
+        assert_eq!(top.vector_rank, Some(1));
+        assert!(top.cosine_distance.unwrap() < 1e-6);
+        assert!(hits
+            .windows(2)
+            .all(|w| w[0].score.score >= w[1].score.score));
+
+        let paged = db
+            .hybrid_search(&HybridSearchQuery {
+                query: "invoice",
+                embedding: Some(vec![1.0, 0.0, 0.0]),
+                max_distance: 0.3,
+                content_type: ContentType::OCR,
+                limit: 1,
+                offset: 1,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(paged.len(), 1);
+        assert_eq!(paged[0].score, hits[1].score);
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search_excludes_apps_before_paging() {
+        let db = setup_test_db().await;
+        setup_video_chunk(&db).await;
+        insert_ocr(&db, "screenpipe", "meeting notes").await;
+        insert_ocr(&db, "Notes", "meeting notes").await;
+        insert_ocr(&db, "Screenpipe Helper", "meeting notes").await;
+        insert_ocr(&db, "Mail", "meeting notes").await;
+
+        let mut apps = Vec::new();
+        for offset in 0..3 {
+            let page = db
+                .hybrid_search(&HybridSearchQuery {
+                    query: "meeting",
+                    content_type: ContentType::OCR,
+                    exclude_app_name: Some("screenpipe"),
+                    limit: 1,
+                    offset,
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            for hit in page {
+                match hit.result {
+                    SearchResult::OCR(ocr) => apps.push(ocr.app_name),
+                    _ => panic!("expected an OCR result"),
+                }
+            }
+        }
+        apps.sort();
+        assert_eq!(apps, vec!["Mail".to_string(), "Notes".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search_invalid_query_is_a_query_error() {
+        let db = setup_test_db().await;
+        setup_video_chunk(&db).await;
+        insert_ocr(&db, "Notes", "meeting notes").await;
+
+        for query in ["meeting AND", "\"meeting"] {
+            let e = db
+                .hybrid_search(&HybridSearchQuery {
+                    query,
+                    limit: 10,
+                    ..Default::default()
+                })
+                .await
+                .unwrap_err();
+            assert!(is_fts_query_error(&e), "{}: {}", query, e);
+        }
+    }
+}
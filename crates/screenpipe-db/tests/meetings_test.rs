@@ -0,0 +1,206 @@
+//! Meeting persistence tests
+//!
+//! Run with: cargo test --package screenpipe-db --test meetings_test -- --nocapture
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use screenpipe_db::{AudioDevice, DatabaseManager, DeviceType};
+
+    async fn setup_test_db() -> DatabaseManager {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+
+        match sqlx::migrate!("./src/migrations").run(&db.pool).await {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Migration error: {:?}", e);
+                panic!("Database migration failed: {}", e);
+            }
+        }
+
+        db
+    }
+
+    async fn insert_transcription(
+        db: &DatabaseManager,
+        text: &str,
+        device: &str,
+        device_type: DeviceType,
+        speaker_id: Option<i64>,
+    ) {
+        let chunk_id = db
+            .insert_audio_chunk(&format!("{}.mp4", text))
+            .await
+            .unwrap();
+        db.insert_audio_transcription(
+            chunk_id,
+            text,
+            0,
+            "whisper",
+            &AudioDevice {
+                name: device.to_string(),
+                device_type,
+            },
+            speaker_id,
+            Some(0.0),
+            Some(1.0),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_meeting_lifecycle() {
+        let db = setup_test_db().await;
+        let start = Utc::now() - Duration::seconds(5);
+        let id = db.start_meeting(Some("zoom.us"), start).await.unwrap();
+
+        let speaker = db.insert_speaker(&[0.1; 512]).await.unwrap();
+        db.update_speaker_name(speaker.id, "alice").await.unwrap();
+        insert_transcription(
+            &db,
+            "good morning everyone",
+            "mic",
+            DeviceType::Input,
+            Some(speaker.id),
+        )
+        .await;
+        insert_transcription(
+            &db,
+            "morning, let's start",
+            "speakers",
+            DeviceType::Output,
+            None,
+        )
+        .await;
+        db.insert_video_chunk("meeting.mp4", "monitor_1")
+            .await
+            .unwrap();
+        db.insert_frame(
+            "monitor_1",
+            None,
+            None,
+            Some("zoom.us"),
+            Some("Zoom Meeting"),
+            true,
+            None,
+        )
+        .await
+        .unwrap();
+        db.insert_frame(
+            "monitor_1",
+            None,
+            None,
+            Some("Slack"),
+            Some("general"),
+            false,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(db.end_meeting(Utc::now()).await.unwrap(), Some(id));
+        assert_eq!(db.end_meeting(Utc::now()).await.unwrap(), None);
+
+        let meeting = db.get_meeting(id).await.unwrap().unwrap();
+        assert_eq!(meeting.meeting.app.as_deref(), Some("zoom.us"));
+        assert!(meeting.meeting.end_time.is_some());
+        assert_eq!(meeting.participants.len(), 1);
+        assert_eq!(meeting.participants[0].name.as_deref(), Some("alice"));
+        assert_eq!(meeting.frames.len(), 1);
+        assert_eq!(
+            meeting.frames[0].window_name.as_deref(),
+            Some("Zoom Meeting")
+        );
+        let texts: Vec<&str> = meeting
+            .transcripts
+            .iter()
+            .map(|t| t.transcription.as_str())
+            .collect();
+        assert_eq!(texts, vec!["good morning everyone", "morning, let's start"]);
+
+        let alone = db.get_meeting_by_id(id).await.unwrap().unwrap();
+        assert_eq!(alone.start_time, meeting.meeting.start_time);
+        assert_eq!(alone.end_time, meeting.meeting.end_time);
+        assert!(db.get_meeting_by_id(id + 1).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_meetings() {
+        let db = setup_test_db().await;
+        let now = Utc::now();
+        let yesterday = db
+            .start_meeting(Some("Microsoft Teams"), now - Duration::days(1))
+            .await
+            .unwrap();
+        db.end_meeting(now - Duration::days(1) + Duration::minutes(15))
+            .await
+            .unwrap();
+        // Starting a meeting closes the previous one if it was left open.
+        let audio_only = db
+            .start_meeting(None, now - Duration::hours(1))
+            .await
+            .unwrap();
+        let today = db.start_meeting(Some("zoom.us"), now).await.unwrap();
+
+        let all = db.list_meetings(None, None, None, 10, 0).await.unwrap();
+        let ids: Vec<i64> = all.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![today, audio_only, yesterday]);
+        assert!(all[1].end_time.is_some());
+        assert!(all[0].end_time.is_none());
+
+        let teams = db
+            .list_meetings(None, None, Some("teams"), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(teams.len(), 1);
+        assert_eq!(teams[0].id, yesterday);
+
+        let recent = db
+            .list_meetings(Some(now - Duration::hours(2)), None, None, 10, 0)
+            .await
+            .unwrap();
+        let ids: Vec<i64> = recent.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![today, audio_only]);
+    }
+
+    #[tokio::test]
+    async fn test_close_stale_meetings() {
+        let db = setup_test_db().await;
+        let start = Utc::now() - Duration::minutes(30);
+        let id = db.start_meeting(Some("zoom.us"), start).await.unwrap();
+        db.insert_video_chunk("meeting.mp4", "monitor_1")
+            .await
+            .unwrap();
+        let last_zoom_frame = start + Duration::minutes(10);
+        for (minutes, app) in [(5, "zoom.us"), (10, "zoom.us"), (20, "Slack")] {
+            db.insert_frame(
+                "monitor_1",
+                Some(start + Duration::minutes(minutes)),
+                None,
+                Some(app),
+                Some("window"),
+                true,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        // Heard after the meeting, it does not extend a meeting detected in an app
+        insert_transcription(&db, "hello", "mic", DeviceType::Input, None).await;
+
+        assert_eq!(db.close_stale_meetings().await.unwrap(), 1);
+        let meeting = db.get_meeting(id).await.unwrap().unwrap();
+        assert_eq!(meeting.meeting.end_time, Some(last_zoom_frame));
+        assert_eq!(meeting.frames.len(), 2);
+        assert!(meeting.transcripts.is_empty());
+
+        // Audio-only meetings end at their last transcription
+        let id = db.start_meeting(None, Utc::now()).await.unwrap();
+        insert_transcription(&db, "still talking", "mic", DeviceType::Input, None).await;
+        assert_eq!(db.close_stale_meetings().await.unwrap(), 1);
+        let meeting = db.get_meeting(id).await.unwrap().unwrap();
+        assert!(meeting.meeting.end_time.unwrap() > meeting.meeting.start_time);
+        assert_eq!(meeting.transcripts.len(), 1);
+    }
+}
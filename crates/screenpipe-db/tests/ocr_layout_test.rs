@@ -0,0 +1,206 @@
+//! OCR layout persistence tests
+//!
+//! Run with: cargo test --package screenpipe-db --test ocr_layout_test -- --nocapture
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{Duration, Utc};
+    use screenpipe_db::{
+        is_fts_query_error, layout_to_markdown, DatabaseManager, FrameWindowData, LayoutBlockKind,
+        OcrEngine, OcrLayoutBlock, RetentionPolicy,
+    };
+
+    async fn setup_test_db() -> DatabaseManager {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+
+        match sqlx::migrate!("./src/migrations").run(&db.pool).await {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Migration error: {:?}", e);
+                panic!("Database migration failed: {}", e);
+            }
+        }
+
+        db
+    }
+
+    fn block(kind: LayoutBlockKind, text: &str, rows: Vec<Vec<&str>>) -> OcrLayoutBlock {
+        OcrLayoutBlock {
+            kind,
+            column: 0,
+            text: text.to_string(),
+            rows: rows
+                .into_iter()
+                .map(|row| row.into_iter().map(str::to_string).collect())
+                .collect(),
+            left: 0.1,
+            top: 0.2,
+            width: 0.5,
+            height: 0.1,
+        }
+    }
+
+    fn layout() -> Vec<OcrLayoutBlock> {
+        vec![
+            block(
+                LayoutBlockKind::Paragraph,
+                "Quarterly planning notes\nfor the team",
+                vec![],
+            ),
+            block(
+                LayoutBlockKind::Table,
+                "Name\tRole\nAlice\tEngineer",
+                vec![vec!["Name", "Role"], vec!["Alice", "Engineer"]],
+            ),
+            block(LayoutBlockKind::Code, "fn main() {\n    run();\n}", vec![]),
+        ]
+    }
+
+    async fn insert_frame(db: &DatabaseManager, minutes_ago: i64) -> i64 {
+        let windows = vec![FrameWindowData {
+            app_name: Some("Notes".to_string()),
+            window_name: Some("planning".to_string()),
+            browser_url: None,
+            focused: true,
+            text: "Quarterly planning notes".to_string(),
+            text_json: "[]".to_string(),
+            layout: layout(),
+        }];
+        let results = db
+            .insert_frames_with_ocr_batch(
+                "monitor_1",
+                Some(Utc::now() - Duration::minutes(minutes_ago)),
+                0,
+                &windows,
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        results[0].0
+    }
+
+    #[tokio::test]
+    async fn test_layout_is_stored_with_the_frame() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("video.mp4", "monitor_1")
+            .await
+            .unwrap();
+        let frame_id = insert_frame(&db, 0).await;
+
+        assert_eq!(db.get_frame_layout(frame_id).await.unwrap(), layout());
+        assert!(db.get_frame_layout(frame_id + 1).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_returns_whole_blocks_once() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("video.mp4", "monitor_1")
+            .await
+            .unwrap();
+        insert_frame(&db, 10).await;
+        let latest = insert_frame(&db, 1).await;
+
+        let results = db
+            .search_ocr_layout("alice", None, None, None, None, 20, 0)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].frame_id, latest);
+        assert_eq!(results[0].block.kind, LayoutBlockKind::Table);
+        assert_eq!(results[0].block.rows[1], vec!["Alice", "Engineer"]);
+
+        let results = db
+            .search_ocr_layout("planning", None, None, None, Some("notes"), 20, 0)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].block.text,
+            "Quarterly planning notes\nfor the team"
+        );
+
+        let code = db
+            .search_ocr_layout("main", Some(LayoutBlockKind::Code), None, None, None, 20, 0)
+            .await
+            .unwrap();
+        assert_eq!(code.len(), 1);
+        assert!(db
+            .search_ocr_layout(
+                "main",
+                Some(LayoutBlockKind::Table),
+                None,
+                None,
+                None,
+                20,
+                0
+            )
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_search_query_is_a_query_error() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("video.mp4", "monitor_1")
+            .await
+            .unwrap();
+        insert_frame(&db, 1).await;
+
+        for query in ["alice AND", "\"alice", "nope:alice"] {
+            let e = db
+                .search_ocr_layout(query, None, None, None, None, 20, 0)
+                .await
+                .unwrap_err();
+            assert!(is_fts_query_error(&e), "{}: {}", query, e);
+        }
+        assert!(!is_fts_query_error(&sqlx::Error::RowNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_layout_is_pruned_with_the_ocr_text() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("video.mp4", "monitor_1")
+            .await
+            .unwrap();
+        insert_frame(&db, 60 * 24 * 30).await;
+
+        let policy = RetentionPolicy {
+            ocr_days: Some(7),
+            ..Default::default()
+        };
+        let report = db
+            .apply_retention_policy(&policy, Utc::now(), false)
+            .await
+            .unwrap();
+
+        assert_eq!(report.ocr_layout_blocks, 3);
+        assert!(db
+            .search_ocr_layout("alice", None, None, None, None, 20, 0)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_layout_markdown() {
+        let mut blocks = layout();
+        blocks[2].text = "let fence = \"```\";".to_string();
+
+        assert_eq!(
+            layout_to_markdown(&blocks),
+            "Quarterly planning notes\nfor the team\n\n\
+             | Name | Role |\n| --- | --- |\n| Alice | Engineer |\n\n\
+             ````\nlet fence = \"```\";\n````"
+        );
+
+        let pipe = block(
+            LayoutBlockKind::Table,
+            "",
+            vec![vec!["a|b"], vec!["c", "d"]],
+        );
+        assert_eq!(pipe.to_markdown(), "| a\\|b |  |\n| --- | --- |\n| c | d |");
+    }
+}
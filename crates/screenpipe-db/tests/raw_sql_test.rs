@@ -0,0 +1,185 @@
+//! Read-only raw SQL tests
+//!
+//! Run with: cargo test --package screenpipe-db --test raw_sql_test -- --nocapture
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use screenpipe_db::{DatabaseManager, RawSqlError, RawSqlOptions};
+
+    async fn setup_test_db() -> DatabaseManager {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+
+        match sqlx::migrate!("./src/migrations").run(&db.pool).await {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Migration error: {:?}", e);
+                panic!("Database migration failed: {}", e);
+            }
+        }
+
+        db
+    }
+
+    #[tokio::test]
+    async fn test_rejects_writes() {
+        // In-memory databases ignore SQLITE_OPEN_READONLY, so use a real file.
+        let path = std::env::temp_dir().join(format!("raw_sql_test_{}.sqlite", std::process::id()));
+        let db = DatabaseManager::new(path.to_str().unwrap()).await.unwrap();
+        db.insert_video_chunk("chunk.mp4", "monitor_1")
+            .await
+            .unwrap();
+
+        for query in [
+            "DELETE FROM video_chunks",
+            "DROP TABLE video_chunks",
+            "PRAGMA query_only = OFF; DELETE FROM video_chunks",
+        ] {
+            let result = db.query_raw_sql(query, &RawSqlOptions::default()).await;
+            assert!(
+                matches!(result, Err(RawSqlError::Sql(_))),
+                "{} should fail",
+                query
+            );
+        }
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM video_chunks")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        db.pool.close().await;
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blob_null_and_column_types() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("chunk.mp4", "monitor_1")
+            .await
+            .unwrap();
+
+        let page = db
+            .query_raw_sql(
+                "SELECT id, file_path, NULL AS empty, X'68656C6C6F' AS data, 1.5 AS ratio FROM video_chunks",
+                &RawSqlOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        let row = &page.rows[0];
+        assert_eq!(row["file_path"], "chunk.mp4");
+        assert!(row["empty"].is_null());
+        assert_eq!(row["data"], "aGVsbG8=");
+        assert_eq!(row["ratio"], 1.5);
+
+        let types: Vec<(&str, Option<&str>, Option<&str>)> = page
+            .columns
+            .iter()
+            .map(|c| {
+                (
+                    c.name.as_str(),
+                    c.declared_type.as_deref(),
+                    c.value_type.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("id", Some("INTEGER"), Some("INTEGER")),
+                ("file_path", Some("TEXT"), Some("TEXT")),
+                ("empty", None, None),
+                ("data", None, Some("BLOB")),
+                ("ratio", None, Some("REAL")),
+            ]
+        );
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pagination() {
+        let db = setup_test_db().await;
+        for i in 0..5 {
+            db.insert_video_chunk(&format!("{}.mp4", i), "monitor_1")
+                .await
+                .unwrap();
+        }
+
+        let query = "SELECT file_path FROM video_chunks ORDER BY id";
+        let mut options = RawSqlOptions {
+            max_rows: 2,
+            ..Default::default()
+        };
+        let mut paths = Vec::new();
+        loop {
+            let page = db.query_raw_sql(query, &options).await.unwrap();
+            assert!(page.rows.len() <= 2);
+            paths.extend(
+                page.rows
+                    .iter()
+                    .map(|r| r["file_path"].as_str().unwrap().to_string()),
+            );
+            match page.next_cursor {
+                Some(cursor) => options.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(paths, vec!["0.mp4", "1.mp4", "2.mp4", "3.mp4", "4.mp4"]);
+
+        // Limits over the hard cap are capped
+        let page = db
+            .query_raw_sql(
+                query,
+                &RawSqlOptions {
+                    max_rows: usize::MAX,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.rows.len(), 5);
+        assert!(page.next_cursor.is_none());
+
+        options.cursor = Some("not a cursor".to_string());
+        assert!(matches!(
+            db.query_raw_sql(query, &options).await,
+            Err(RawSqlError::InvalidCursor)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_columns_without_rows() {
+        let db = setup_test_db().await;
+        let page = db
+            .query_raw_sql(
+                "SELECT id, file_path FROM video_chunks",
+                &RawSqlOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert!(page.rows.is_empty());
+        let names: Vec<&str> = page.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "file_path"]);
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let db = setup_test_db().await;
+        let options = RawSqlOptions {
+            timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let result = db
+            .query_raw_sql(
+                "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n) SELECT COUNT(*) FROM n",
+                &options,
+            )
+            .await;
+        assert!(matches!(result, Err(RawSqlError::Timeout(_))));
+    }
+}
@@ -0,0 +1,301 @@
+//! Data retention tests
+//!
+//! Run with: cargo test --package screenpipe-db --test retention_test -- --nocapture
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{Duration, Utc};
+    use screenpipe_db::{
+        AppRetentionOverride, AudioDevice, DatabaseManager, DeviceType, OcrEngine, RetentionPolicy,
+    };
+
+    async fn setup_test_db() -> DatabaseManager {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+
+        match sqlx::migrate!("./src/migrations").run(&db.pool).await {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Migration error: {:?}", e);
+                panic!("Database migration failed: {}", e);
+            }
+        }
+
+        db
+    }
+
+    async fn insert_frame_with_ocr(
+        db: &DatabaseManager,
+        days_ago: i64,
+        app_name: &str,
+        text: &str,
+    ) -> i64 {
+        let frame_id = db
+            .insert_frame(
+                "monitor_1",
+                Some(Utc::now() - Duration::days(days_ago)),
+                None,
+                Some(app_name),
+                Some("window"),
+                true,
+                None,
+            )
+            .await
+            .unwrap();
+        db.insert_ocr_text(frame_id, text, "[]", Arc::new(OcrEngine::Tesseract))
+            .await
+            .unwrap();
+        frame_id
+    }
+
+    async fn count(db: &DatabaseManager, query: &str) -> i64 {
+        sqlx::query_scalar(query).fetch_one(&db.pool).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_does_not_delete() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("old.mp4", "monitor_1").await.unwrap();
+        insert_frame_with_ocr(&db, 30, "Chrome", "old text").await;
+
+        let policy = RetentionPolicy {
+            video_days: Some(7),
+            ocr_days: Some(7),
+            ..Default::default()
+        };
+        let report = db
+            .apply_retention_policy(&policy, Utc::now(), true)
+            .await
+            .unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!(report.ocr_text, 1);
+        assert_eq!(report.frames, 1);
+        assert_eq!(report.video_chunks, 1);
+        assert_eq!(report.video_files, vec!["old.mp4".to_string()]);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM frames").await, 1);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM ocr_text").await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_prunes_expired_frames_and_keeps_recent() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("old.mp4", "monitor_1").await.unwrap();
+        insert_frame_with_ocr(&db, 30, "Chrome", "old text").await;
+        db.insert_video_chunk("new.mp4", "monitor_1").await.unwrap();
+        let recent = insert_frame_with_ocr(&db, 1, "Chrome", "recent text").await;
+
+        let policy = RetentionPolicy {
+            video_days: Some(7),
+            ocr_days: Some(7),
+            ..Default::default()
+        };
+        let report = db
+            .apply_retention_policy(&policy, Utc::now(), false)
+            .await
+            .unwrap();
+
+        assert_eq!(report.ocr_text, 1);
+        assert_eq!(report.frames, 1);
+        assert_eq!(report.video_chunks, 1);
+        assert_eq!(report.video_files, vec!["old.mp4".to_string()]);
+
+        let frame_ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM frames")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(frame_ids, vec![recent]);
+        assert_eq!(
+            count(
+                &db,
+                "SELECT COUNT(*) FROM ocr_text_fts WHERE ocr_text_fts MATCH 'old'"
+            )
+            .await,
+            0
+        );
+
+        // A second pass has nothing left to do.
+        let report = db
+            .apply_retention_policy(&policy, Utc::now(), false)
+            .await
+            .unwrap();
+        assert_eq!(report.frames, 0);
+        assert!(report.video_files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_video_expiry_keeps_ocr_searchable() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("old.mp4", "monitor_1").await.unwrap();
+        insert_frame_with_ocr(&db, 30, "Chrome", "old text").await;
+
+        let policy = RetentionPolicy {
+            video_days: Some(7),
+            ocr_days: Some(90),
+            ..Default::default()
+        };
+        let report = db
+            .apply_retention_policy(&policy, Utc::now(), false)
+            .await
+            .unwrap();
+
+        assert_eq!(report.video_files, vec!["old.mp4".to_string()]);
+        assert_eq!(report.frames, 0);
+        assert_eq!(report.ocr_text, 0);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM ocr_text").await, 1);
+        assert_eq!(
+            count(
+                &db,
+                "SELECT COUNT(*) FROM video_chunks WHERE file_deleted_at IS NOT NULL"
+            )
+            .await,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_app_override() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("chunk.mp4", "monitor_1")
+            .await
+            .unwrap();
+        insert_frame_with_ocr(&db, 2, "1Password", "secret").await;
+        insert_frame_with_ocr(&db, 2, "Chrome", "public").await;
+
+        let mut policy = RetentionPolicy {
+            ocr_days: Some(30),
+            ..Default::default()
+        };
+        policy.app_overrides.insert(
+            "1password".to_string(),
+            AppRetentionOverride {
+                ocr_days: Some(0),
+                ..Default::default()
+            },
+        );
+        let report = db
+            .apply_retention_policy(&policy, Utc::now(), false)
+            .await
+            .unwrap();
+
+        assert_eq!(report.ocr_text, 1);
+        let texts: Vec<String> = sqlx::query_scalar("SELECT text FROM ocr_text")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(texts, vec!["public".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_prunes_audio() {
+        let db = setup_test_db().await;
+        let chunk_id = db.insert_audio_chunk("audio.mp4").await.unwrap();
+        db.insert_audio_transcription(
+            chunk_id,
+            "hello world",
+            0,
+            "whisper",
+            &AudioDevice {
+                name: "mic".to_string(),
+                device_type: DeviceType::Input,
+            },
+            None,
+            Some(0.0),
+            Some(1.0),
+        )
+        .await
+        .unwrap();
+
+        let policy = RetentionPolicy {
+            audio_days: Some(7),
+            ..Default::default()
+        };
+
+        // Nothing is old enough yet.
+        let report = db
+            .apply_retention_policy(&policy, Utc::now(), false)
+            .await
+            .unwrap();
+        assert_eq!(report.audio_chunks, 0);
+
+        let report = db
+            .apply_retention_policy(&policy, Utc::now() + Duration::days(8), false)
+            .await
+            .unwrap();
+        assert_eq!(report.audio_chunks, 1);
+        assert_eq!(report.audio_transcriptions, 1);
+        assert_eq!(report.audio_files, vec!["audio.mp4".to_string()]);
+        assert_eq!(
+            count(&db, "SELECT COUNT(*) FROM audio_transcriptions_fts").await,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prunes_audio_chunks_without_timestamp() {
+        let db = setup_test_db().await;
+        let chunk_id = db.insert_audio_chunk("legacy.mp4").await.unwrap();
+        db.insert_audio_transcription(
+            chunk_id,
+            "recorded before chunk timestamps",
+            0,
+            "whisper",
+            &AudioDevice {
+                name: "mic".to_string(),
+                device_type: DeviceType::Input,
+            },
+            None,
+            Some(0.0),
+            Some(1.0),
+        )
+        .await
+        .unwrap();
+        sqlx::query("UPDATE audio_chunks SET timestamp = NULL")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let policy = RetentionPolicy {
+            audio_days: Some(7),
+            ..Default::default()
+        };
+        let report = db
+            .apply_retention_policy(&policy, Utc::now() + Duration::days(8), false)
+            .await
+            .unwrap();
+        assert_eq!(report.audio_chunks, 1);
+        assert_eq!(report.audio_files, vec!["legacy.mp4".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_prunes_accessibility_with_ocr() {
+        let db = setup_test_db().await;
+        for days_ago in [30, 1] {
+            sqlx::query(
+                "INSERT INTO accessibility (timestamp, app_name, window_name, text_content) VALUES (?1, 'Chrome', 'window', 'page text')",
+            )
+            .bind(Utc::now() - Duration::days(days_ago))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        }
+
+        let policy = RetentionPolicy {
+            ocr_days: Some(7),
+            ..Default::default()
+        };
+        let report = db
+            .apply_retention_policy(&policy, Utc::now(), true)
+            .await
+            .unwrap();
+        assert_eq!(report.accessibility, 1);
+
+        let report = db
+            .apply_retention_policy(&policy, Utc::now(), false)
+            .await
+            .unwrap();
+        assert_eq!(report.accessibility, 1);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM accessibility").await, 1);
+    }
+}
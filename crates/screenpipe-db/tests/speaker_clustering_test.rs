@@ -0,0 +1,177 @@
+//! Speaker clustering and merge proposal tests
+//!
+//! Run with: cargo test --package screenpipe-db --test speaker_clustering_test -- --nocapture
+
+#[cfg(test)]
+mod tests {
+    use screenpipe_db::{
+        AudioDevice, DatabaseManager, DeviceType, SpeakerClusteringConfig, SpeakerClusteringError,
+        SpeakerMergeProposalStatus, MAX_CLUSTERED_SPEAKERS,
+    };
+
+    async fn setup_test_db() -> DatabaseManager {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+
+        match sqlx::migrate!("./src/migrations").run(&db.pool).await {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Migration error: {:?}", e);
+                panic!("Database migration failed: {}", e);
+            }
+        }
+
+        db
+    }
+
+    /// Unit vector along `axis`, slightly tilted towards the next one by `tilt`
+    fn voice(axis: usize, tilt: f32) -> Vec<f32> {
+        let mut embedding = vec![0.0; 512];
+        embedding[axis] = 1.0;
+        embedding[axis + 1] = tilt;
+        embedding
+    }
+
+    async fn insert_transcription(db: &DatabaseManager, text: &str, speaker_id: i64) {
+        let chunk_id = db
+            .insert_audio_chunk(&format!("{}.mp4", text))
+            .await
+            .unwrap();
+        db.insert_audio_transcription(
+            chunk_id,
+            text,
+            0,
+            "whisper",
+            &AudioDevice {
+                name: "mic".to_string(),
+                device_type: DeviceType::Input,
+            },
+            Some(speaker_id),
+            Some(0.0),
+            Some(1.0),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cluster_and_accept_proposals() {
+        let db = setup_test_db().await;
+        let alice = db.insert_speaker(&voice(0, 0.0)).await.unwrap();
+        db.update_speaker_name(alice.id, "alice").await.unwrap();
+        let fragment_1 = db.insert_speaker(&voice(0, 0.05)).await.unwrap();
+        let fragment_2 = db.insert_speaker(&voice(0, 0.1)).await.unwrap();
+        let bob = db.insert_speaker(&voice(10, 0.0)).await.unwrap();
+        insert_transcription(&db, "hello from a fragment", fragment_1.id).await;
+
+        let report = db
+            .cluster_speakers(&SpeakerClusteringConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(report.speakers, 4);
+        assert_eq!(report.clusters, 1);
+        assert_eq!(report.proposals, 2);
+        assert_eq!(report.auto_merged, 0);
+
+        let proposals = db
+            .list_speaker_merge_proposals(Some(SpeakerMergeProposalStatus::Pending))
+            .await
+            .unwrap();
+        assert_eq!(proposals.len(), 2);
+        assert!(proposals.iter().all(|p| p.speaker_id == alice.id));
+        assert!(proposals
+            .iter()
+            .all(|p| p.speaker_name.as_deref() == Some("alice")));
+        assert!(proposals.iter().all(|p| p.merge_speaker_id != bob.id));
+
+        // Re-clustering replaces the pending proposals
+        db.cluster_speakers(&SpeakerClusteringConfig::default())
+            .await
+            .unwrap();
+        let proposals = db.list_speaker_merge_proposals(None).await.unwrap();
+        assert_eq!(proposals.len(), 2);
+
+        let to_accept = proposals
+            .iter()
+            .find(|p| p.merge_speaker_id == fragment_1.id)
+            .unwrap()
+            .id;
+        let to_reject = proposals
+            .iter()
+            .find(|p| p.merge_speaker_id == fragment_2.id)
+            .unwrap()
+            .id;
+        let resolution = db
+            .resolve_speaker_merge_proposals(&[to_accept, 9999], &[to_reject])
+            .await
+            .unwrap();
+        assert_eq!(resolution.accepted, vec![to_accept]);
+        assert_eq!(resolution.rejected, vec![to_reject]);
+        assert_eq!(resolution.skipped, vec![9999]);
+
+        // The fragment's transcriptions moved to alice
+        assert!(db.get_speaker_by_id(fragment_1.id).await.is_err());
+        let chunks = db.get_audio_chunks_for_speaker(alice.id).await.unwrap();
+        assert_eq!(chunks.len(), 1);
+
+        // Rejected pairs are not proposed again
+        let report = db
+            .cluster_speakers(&SpeakerClusteringConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(report.proposals, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cluster_auto_merge() {
+        let db = setup_test_db().await;
+        let first = db.insert_speaker(&voice(0, 0.0)).await.unwrap();
+        let second = db.insert_speaker(&voice(0, 0.02)).await.unwrap();
+        let third = db.insert_speaker(&voice(0, 0.6)).await.unwrap();
+
+        let report = db
+            .cluster_speakers(&SpeakerClusteringConfig {
+                threshold: 0.8,
+                auto_merge_threshold: Some(0.9),
+            })
+            .await
+            .unwrap();
+        // All three are one cluster, only the closest fragment is similar enough to merge
+        assert_eq!(report.clusters, 1);
+        assert_eq!(report.auto_merged, 1);
+        assert_eq!(report.proposals, 1);
+
+        // Merged into the lowest id
+        assert!(db.get_speaker_by_id(first.id).await.is_ok());
+        assert!(db.get_speaker_by_id(second.id).await.is_err());
+        let pending = db
+            .list_speaker_merge_proposals(Some(SpeakerMergeProposalStatus::Pending))
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].speaker_id, first.id);
+        assert_eq!(pending[0].merge_speaker_id, third.id);
+    }
+
+    #[tokio::test]
+    async fn test_cluster_refuses_too_many_speakers() {
+        let db = setup_test_db().await;
+        for i in 0..=MAX_CLUSTERED_SPEAKERS {
+            db.insert_speaker(&voice(i % 500, i as f32 / 1000.0))
+                .await
+                .unwrap();
+        }
+
+        let result = db
+            .cluster_speakers(&SpeakerClusteringConfig::default())
+            .await;
+        assert!(matches!(
+            result,
+            Err(SpeakerClusteringError::TooManySpeakers(n)) if n == MAX_CLUSTERED_SPEAKERS + 1
+        ));
+        assert!(db
+            .list_speaker_merge_proposals(None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
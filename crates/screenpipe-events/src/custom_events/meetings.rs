@@ -7,6 +7,9 @@ use serde_json;
 use std::collections::{HashMap, HashSet};
 use std::time::{Duration, Instant};
 
+/// App name of meetings detected from audio only (several speakers heard).
+pub const AUDIO_DETECTED_MEETING_APP: &str = "Unknown (detected via audio)";
+
 const MEETING_APPS: &[&str] = &["zoom", "teams", "meet", "webex", "skype", "slack"];
 const MEETING_KEYWORDS: &[&str] = &[
     "meeting",
@@ -158,7 +161,7 @@ pub async fn poll_meetings_events() -> Result<()> {
                         send_event(
                             "meeting_started",
                             MeetingEvent {
-                                app: "Unknown (detected via audio)".to_string(),
+                                app: AUDIO_DETECTED_MEETING_APP.to_string(),
                                 timestamp: Utc::now(),
                             },
                         )?;
@@ -191,7 +194,7 @@ pub async fn poll_meetings_events() -> Result<()> {
                     send_event(
                         "meeting_ended",
                         MeetingEvent {
-                            app: "Unknown (detected via audio)".to_string(),
+                            app: AUDIO_DETECTED_MEETING_APP.to_string(),
                             timestamp: Utc::now(),
                         },
                     )?;
@@ -203,10 +206,11 @@ pub async fn poll_meetings_events() -> Result<()> {
     Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct MeetingEvent {
-    app: String,
-    timestamp: DateTime<Utc>,
+/// Payload of the `meeting_started` and `meeting_ended` events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeetingEvent {
+    pub app: String,
+    pub timestamp: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
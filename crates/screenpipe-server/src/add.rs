@@ -1,17 +1,12 @@
 use anyhow::Result;
 use regex::Regex;
+use screenpipe_audio::file_transcription::{
+    import_audio_file, FileTranscriber, FileTranscriptionConfig,
+};
 use screenpipe_db::DatabaseManager;
 use screenpipe_vision::frame_comparison::{FrameComparer, FrameComparisonConfig};
 use screenpipe_vision::utils::OcrEngine;
-
-#[cfg(target_os = "macos")]
-use screenpipe_vision::perform_ocr_apple;
-
-#[cfg(target_os = "windows")]
-use screenpipe_vision::perform_ocr_windows;
-
-#[allow(unused)]
-use screenpipe_vision::perform_ocr_tesseract;
+use screenpipe_vision::{create_ocr_backend, OcrBackend};
 
 use serde_json::json;
 use std::path::Path;
@@ -26,7 +21,7 @@ use walkdir::WalkDir;
 
 use crate::{
     cli::CliOcrEngine,
-    text_embeds::generate_embedding,
+    text_embeds::{embedding_model, generate_embedding},
     video_utils::{extract_frames_from_video, get_video_metadata, VideoMetadataOverrides},
 };
 
@@ -41,6 +36,7 @@ pub async fn handle_index_command(
     metadata_override: Option<PathBuf>,
     copy_videos: bool,
     use_embedding: bool,
+    audio_transcription: Option<FileTranscriptionConfig>,
 ) -> Result<()> {
     // Load metadata override if provided
     let metadata_overrides = if let Some(path) = metadata_override {
@@ -50,15 +46,15 @@ pub async fn handle_index_command(
         None
     };
 
-    // Get list of video files
-    let video_files = find_video_files(&path, pattern.as_deref())?;
-    info!("found {} video files to process", video_files.len());
+    // Get list of video files, and audio files if they are transcribed
+    let media_files = find_media_files(&path, pattern.as_deref(), audio_transcription.is_some())?;
+    info!("found {} files to process", media_files.len());
 
     // Validate that we have metadata for all files if overrides are provided
     if let Some(ref overrides) = metadata_overrides {
         let mut unmatched_files = Vec::new();
 
-        for video_path in &video_files {
+        for video_path in &media_files {
             let file_str = video_path.to_string_lossy();
             let matched = overrides
                 .overrides
@@ -78,8 +74,15 @@ pub async fn handle_index_command(
         }
     }
 
+    // Load the transcription models once for all files
+    let transcriber = match audio_transcription {
+        Some(config) if !media_files.is_empty() => Some(FileTranscriber::new(&db, config).await?),
+        _ => None,
+    };
+
     let mut total_frames = 0;
     let mut total_text = 0;
+    let mut total_transcriptions = 0;
 
     // Setup channel for OCR results
 
@@ -88,8 +91,8 @@ pub async fn handle_index_command(
         println!("{{\"version\":1,\"stream\":["); // Start of JSON stream
     }
 
-    for video_path in video_files {
-        info!("processing video: {}", video_path.display());
+    for video_path in media_files {
+        info!("processing file: {}", video_path.display());
 
         // Get metadata override before copying file
         let mut metadata = get_video_metadata(video_path.to_str().unwrap()).await?;
@@ -130,6 +133,54 @@ pub async fn handle_index_command(
             video_path.clone()
         };
 
+        if let Some(ref transcriber) = transcriber {
+            match import_audio_file(
+                &db,
+                transcriber,
+                &video_path,
+                metadata.creation_time,
+                metadata.device_name.clone(),
+            )
+            .await
+            {
+                Ok((audio_chunk_id, transcriptions)) => {
+                    info!(
+                        "inserted {} transcriptions for audio of {}",
+                        transcriptions,
+                        video_path.display()
+                    );
+                    if output_format == crate::cli::OutputFormat::Json {
+                        if total_frames > 0 || total_transcriptions > 0 {
+                            print!(",");
+                        }
+                        print!(
+                            "{}",
+                            serde_json::to_string(&json!({
+                                "type": "audio",
+                                "data": {
+                                    "audio_chunk_id": audio_chunk_id,
+                                    "transcriptions": transcriptions,
+                                    "file_path": video_path.to_string_lossy()
+                                }
+                            }))?
+                        );
+                    }
+                    total_transcriptions += transcriptions;
+                }
+                // Videos may have no audio track
+                Err(e) if is_video_file(&video_path) => {
+                    debug!("no audio imported from {}: {}", video_path.display(), e);
+                }
+                Err(e) => {
+                    error!("failed to import audio of {}: {}", video_path.display(), e);
+                }
+            }
+        }
+
+        if !is_video_file(&video_path) {
+            continue;
+        }
+
         let frames = extract_frames_from_video(&video_path, None).await?;
 
         // Create video chunk and frames first
@@ -174,18 +225,17 @@ pub async fn handle_index_command(
             };
 
             // Do OCR processing directly
-            let (text, _, confidence): (String, String, Option<f64>) = match engine.clone() {
-                #[cfg(target_os = "macos")]
-                OcrEngine::AppleNative => perform_ocr_apple(frame, &[]),
-                #[cfg(target_os = "windows")]
-                OcrEngine::WindowsNative => perform_ocr_windows(&frame).await.unwrap(),
-                _ => {
-                    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-                    {
-                        perform_ocr_tesseract(&frame, Vec::new());
+            let (text, confidence) = match create_ocr_backend(&engine) {
+                Ok(backend) => match backend.recognize(frame, &[]).await {
+                    Ok(output) => (output.text, output.confidence),
+                    Err(e) => {
+                        warn!("ocr failed for frame {}: {}", frame_counter, e);
+                        ("".to_string(), None)
                     }
-                    warn!("unsupported ocr engine");
-                    ("".to_string(), "".to_string(), None)
+                },
+                Err(e) => {
+                    warn!("unsupported ocr engine: {}", e);
+                    ("".to_string(), None)
                 }
             };
 
@@ -199,7 +249,11 @@ pub async fn handle_index_command(
                     Ok(emb) => {
                         debug!("generated embedding for frame {}", frame_ids[idx]);
                         if let Err(e) = db
-                            .insert_embeddings(frame_ids[idx], serde_json::to_string(&emb)?)
+                            .insert_embeddings(
+                                frame_ids[idx],
+                                serde_json::to_string(&emb)?,
+                                embedding_model(),
+                            )
                             .await
                         {
                             error!("error batch inserting embeddings: {}", e);
@@ -268,7 +322,7 @@ pub async fn handle_index_command(
     match output_format {
         crate::cli::OutputFormat::Json => {
             // Add final summary item
-            if total_frames > 0 {
+            if total_frames > 0 || total_transcriptions > 0 {
                 print!(",");
             }
             print!(
@@ -277,7 +331,8 @@ pub async fn handle_index_command(
                     "type": "summary",
                     "data": {
                         "total_frames": total_frames,
-                        "total_text_chars": total_text
+                        "total_text_chars": total_text,
+                        "total_transcriptions": total_transcriptions
                     }
                 }))?
             );
@@ -285,8 +340,8 @@ pub async fn handle_index_command(
         }
         crate::cli::OutputFormat::Text => {
             info!(
-                "processed {} frames, extracted {} characters of text",
-                total_frames, total_text
+                "processed {} frames, extracted {} characters of text and {} transcriptions",
+                total_frames, total_text, total_transcriptions
             );
         }
     }
@@ -294,8 +349,25 @@ pub async fn handle_index_command(
     Ok(())
 }
 
-fn find_video_files(root: &str, pattern: Option<&str>) -> Result<Vec<PathBuf>> {
-    let mut video_files = Vec::new();
+const VIDEO_EXTENSIONS: [&str; 3] = ["mp4", "mov", "avi"];
+const AUDIO_EXTENSIONS: [&str; 4] = ["wav", "mp3", "m4a", "ogg"];
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_lowercase().as_str()))
+}
+
+fn is_video_file(path: &Path) -> bool {
+    has_extension(path, &VIDEO_EXTENSIONS)
+}
+
+fn find_media_files(
+    root: &str,
+    pattern: Option<&str>,
+    include_audio: bool,
+) -> Result<Vec<PathBuf>> {
+    let mut media_files = Vec::new();
     let regex = pattern.map(Regex::new).transpose()?;
 
     for entry in WalkDir::new(root)
@@ -304,20 +376,17 @@ fn find_video_files(root: &str, pattern: Option<&str>) -> Result<Vec<PathBuf>> {
         .filter_map(|e| e.ok())
     {
         let path = entry.path();
-        if path.is_file() {
-            if let Some(ext) = path.extension() {
-                if ext == "mp4" || ext == "mov" || ext == "avi" {
-                    if let Some(ref regex) = regex {
-                        if regex.is_match(&path.to_string_lossy()) {
-                            video_files.push(path.to_path_buf());
-                        }
-                    } else {
-                        video_files.push(path.to_path_buf());
-                    }
+        let is_audio_file = include_audio && has_extension(path, &AUDIO_EXTENSIONS);
+        if path.is_file() && (is_video_file(path) || is_audio_file) {
+            if let Some(ref regex) = regex {
+                if regex.is_match(&path.to_string_lossy()) {
+                    media_files.push(path.to_path_buf());
                 }
+            } else {
+                media_files.push(path.to_path_buf());
             }
         }
     }
 
-    Ok(video_files)
+    Ok(media_files)
 }
@@ -0,0 +1,372 @@
+//! Bearer-token authentication for the HTTP API.
+//!
+//! Auth is opt-in (`--enable-api-auth`). When enabled, every route except the
+//! health check requires `Authorization: Bearer <token>` with a scope matching
+//! the route (see [`required_scope`]). Tokens are created with
+//! `screenpipe auth create` and only their SHA-256 is stored in the database.
+//! Pipes get an in-memory token from the [`PipeManager`](crate::PipeManager)
+//! that dies with the pipe, scoped to the `api_scopes` in their `pipe.json`.
+//! Pipe tokens are checked even when auth is disabled.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tower_http::cors::{AllowOrigin, Any};
+use tracing::{debug, warn};
+
+use crate::server::AppState;
+
+/// Prefix of generated tokens, so they are easy to spot in configs and logs.
+const TOKEN_PREFIX: &str = "sp_";
+
+/// Don't write `last_used_at` more often than this per token.
+const TOUCH_INTERVAL_SECS: i64 = 60;
+
+/// What a token is allowed to do. `Admin` implies every other scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApiScope {
+    /// Search, speakers, ui events and other read-only queries
+    ReadSearch,
+    /// Frames, video export and streams
+    ReadMedia,
+    /// Recording control, tags, speaker edits, imports
+    Write,
+    /// Raw SQL, sync and everything else
+    Admin,
+    /// Pipe management
+    Pipes,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 5] = [
+        ApiScope::ReadSearch,
+        ApiScope::ReadMedia,
+        ApiScope::Write,
+        ApiScope::Admin,
+        ApiScope::Pipes,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::ReadSearch => "read-search",
+            ApiScope::ReadMedia => "read-media",
+            ApiScope::Write => "write",
+            ApiScope::Admin => "admin",
+            ApiScope::Pipes => "pipes",
+        }
+    }
+}
+
+impl fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ApiScope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown scope '{}' (expected one of: read-search, read-media, write, admin, pipes)",
+                    s
+                )
+            })
+    }
+}
+
+/// Scopes given to pipes started by the pipe manager.
+pub const DEFAULT_PIPE_SCOPES: [ApiScope; 2] = [ApiScope::ReadSearch, ApiScope::ReadMedia];
+
+/// HTTP server auth settings.
+#[derive(Debug, Clone, Default)]
+pub struct AuthConfig {
+    /// Require a bearer token on every non-public route
+    pub enabled: bool,
+    /// Origins allowed by CORS. Empty allows any origin.
+    pub cors_allowed_origins: Vec<String>,
+}
+
+impl AuthConfig {
+    pub fn cors_allow_origin(&self) -> AllowOrigin {
+        if self.cors_allowed_origins.is_empty() {
+            return Any.into();
+        }
+        let origins: Vec<HeaderValue> = self
+            .cors_allowed_origins
+            .iter()
+            .filter_map(|origin| match HeaderValue::from_str(origin) {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    warn!("ignoring invalid cors origin: {}", origin);
+                    None
+                }
+            })
+            .collect();
+        AllowOrigin::list(origins)
+    }
+}
+
+/// Generate a new random token. Only ever shown once to the user.
+pub fn generate_token() -> String {
+    format!(
+        "{}{}{}",
+        TOKEN_PREFIX,
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+pub fn hash_token(token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(token.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Scope needed to call a route, `None` for public routes.
+pub fn required_scope(method: &Method, path: &str) -> Option<ApiScope> {
+    let path = path.trim_end_matches('/');
+
+    if matches!(
+        path,
+        "/health" | "/ws/health" | "/openapi.yaml" | "/openapi.json"
+    ) {
+        return None;
+    }
+    if path == "/raw_sql" || path.starts_with("/sync/") {
+        return Some(ApiScope::Admin);
+    }
+    if path.starts_with("/pipes/") {
+        return Some(ApiScope::Pipes);
+    }
+    if path.starts_with("/frames/")
+        || path == "/stream/frames"
+        || path == "/experimental/validate/media"
+    {
+        // /frames/next-valid is just a lookup
+        if path == "/frames/next-valid" {
+            return Some(ApiScope::ReadSearch);
+        }
+        return Some(ApiScope::ReadMedia);
+    }
+    if path.starts_with("/audio/") && (method == Method::POST || path.starts_with("/audio/device/"))
+    {
+        return Some(ApiScope::Write);
+    }
+    if path == "/add" || path == "/experimental/frames/merge" || path.starts_with("/tags/") {
+        return Some(ApiScope::Write);
+    }
+    if path.starts_with("/speakers/") && method != Method::GET {
+        return Some(ApiScope::Write);
+    }
+    if path == "/v1/embeddings" || path == "/ai/chat/completions" {
+        return Some(ApiScope::ReadSearch);
+    }
+    if method == Method::GET || method == Method::HEAD || method == Method::OPTIONS {
+        return Some(ApiScope::ReadSearch);
+    }
+    // Anything else that mutates state and isn't listed above
+    Some(ApiScope::Admin)
+}
+
+fn has_scope(scopes: &[ApiScope], required: ApiScope) -> bool {
+    scopes.contains(&ApiScope::Admin) || scopes.contains(&required)
+}
+
+/// Browsers can't set headers on websocket upgrades, so streaming routes also
+/// accept `?token=`.
+fn accepts_query_token(path: &str) -> bool {
+    path.starts_with("/ws/") || path == "/stream/frames" || path == "/frames/export"
+}
+
+fn extract_token(req: &Request) -> Option<String> {
+    if let Some(value) = req.headers().get(AUTHORIZATION) {
+        return value
+            .to_str()
+            .ok()
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|t| t.trim().to_string());
+    }
+    if accepts_query_token(req.uri().path()) {
+        // Generated tokens are URL-safe, so no percent-decoding is needed.
+        return req.uri().query().and_then(|query| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(k, _)| *k == "token")
+                .map(|(_, v)| v.to_string())
+        });
+    }
+    None
+}
+
+fn unauthorized(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({"error": message, "success": false}))).into_response()
+}
+
+/// Axum middleware enforcing bearer-token auth when enabled.
+pub async fn require_auth(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if req.method() == Method::OPTIONS {
+        return next.run(req).await;
+    }
+    let Some(required) = required_scope(req.method(), req.uri().path()) else {
+        return next.run(req).await;
+    };
+
+    if !state.auth.enabled {
+        // Pipes are held to their declared scopes even when auth is off. Their
+        // egress proxy adds the token to every API request.
+        let pipe_scopes = match extract_token(&req) {
+            Some(token) => {
+                state
+                    .pipe_manager
+                    .pipe_token_scopes(&hash_token(&token))
+                    .await
+            }
+            None => None,
+        };
+        if let Some(scopes) = pipe_scopes {
+            if !has_scope(&scopes, required) {
+                return unauthorized(
+                    StatusCode::FORBIDDEN,
+                    &format!("pipe is missing the '{}' scope", required),
+                );
+            }
+        }
+        return next.run(req).await;
+    }
+
+    let Some(token) = extract_token(&req) else {
+        return unauthorized(StatusCode::UNAUTHORIZED, "missing bearer token");
+    };
+    let token_hash = hash_token(&token);
+
+    let scopes: Vec<ApiScope> = if let Some(scopes) =
+        state.pipe_manager.pipe_token_scopes(&token_hash).await
+    {
+        scopes
+    } else {
+        match state.db.get_api_token_by_hash(&token_hash).await {
+            Ok(Some(record)) => {
+                let stale = record.last_used_at.map_or(true, |t| {
+                    (Utc::now() - t).num_seconds() > TOUCH_INTERVAL_SECS
+                });
+                if stale {
+                    let db = state.db.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = db.touch_api_token(record.id).await {
+                            debug!("failed to update token last_used_at: {}", e);
+                        }
+                    });
+                }
+                record
+                    .scopes
+                    .iter()
+                    .filter_map(|s| s.parse().ok())
+                    .collect()
+            }
+            Ok(None) => return unauthorized(StatusCode::UNAUTHORIZED, "invalid or revoked token"),
+            Err(e) => {
+                warn!("failed to look up api token: {}", e);
+                return unauthorized(StatusCode::INTERNAL_SERVER_ERROR, "failed to verify token");
+            }
+        }
+    };
+
+    if !has_scope(&scopes, required) {
+        return unauthorized(
+            StatusCode::FORBIDDEN,
+            &format!("token is missing the '{}' scope", required),
+        );
+    }
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_scope() {
+        assert_eq!(required_scope(&Method::GET, "/health"), None);
+        assert_eq!(
+            required_scope(&Method::GET, "/search"),
+            Some(ApiScope::ReadSearch)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/raw_sql"),
+            Some(ApiScope::Admin)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/pipes/list"),
+            Some(ApiScope::Pipes)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/frames/42"),
+            Some(ApiScope::ReadMedia)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/audio/start"),
+            Some(ApiScope::Write)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/audio/list"),
+            Some(ApiScope::ReadSearch)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/speakers/merge"),
+            Some(ApiScope::Write)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/sync/trigger"),
+            Some(ApiScope::Admin)
+        );
+    }
+
+    #[test]
+    fn test_admin_implies_all_scopes() {
+        for scope in ApiScope::ALL {
+            assert!(has_scope(&[ApiScope::Admin], scope));
+        }
+        assert!(!has_scope(&[ApiScope::ReadSearch], ApiScope::Write));
+    }
+
+    #[test]
+    fn test_scope_round_trip() {
+        for scope in ApiScope::ALL {
+            assert_eq!(scope.as_str().parse::<ApiScope>().unwrap(), scope);
+        }
+        assert!("root".parse::<ApiScope>().is_err());
+    }
+
+    #[test]
+    fn test_generated_tokens_are_unique() {
+        let a = generate_token();
+        let b = generate_token();
+        assert!(a.starts_with(TOKEN_PREFIX));
+        assert_ne!(a, b);
+        assert_ne!(hash_token(&a), hash_token(&b));
+    }
+}
@@ -11,23 +11,35 @@ use screenpipe_audio::{
     core::device::{
         default_input_device, default_output_device, list_audio_devices, parse_audio_device,
     },
+    file_transcription::FileTranscriptionConfig,
+    preprocessing::PreprocessingChain,
+    retranscribe::{
+        create_retranscription_worker, RetranscriptionCommand, RetranscriptionConfig,
+        RetranscriptionStatus,
+    },
+    transcription::engine::OpenAiCompatibleConfig,
 };
-use screenpipe_core::find_ffmpeg_path;
 use screenpipe_core::sync::{
     BlobType, SyncClientConfig, SyncEvent, SyncManager, SyncService, SyncServiceConfig,
 };
+use screenpipe_core::{find_ffmpeg_path, Language, PipePermissions, PipeRun, PipeRunKind};
 use screenpipe_db::{
     create_migration_worker, DatabaseManager, MigrationCommand, MigrationConfig, MigrationStatus,
+    RetentionPolicy, SpeakerClusteringConfig,
 };
 use screenpipe_server::{
     analytics,
+    auth::{generate_token, hash_token, AuthConfig},
     cli::{
-        get_or_create_machine_id, AudioCommand, Cli, CliAudioTranscriptionEngine, CliOcrEngine,
-        Command, McpCommand, MigrationSubCommand, OutputFormat, PipeCommand, SyncCommand,
-        VisionCommand,
+        get_or_create_machine_id, AudioCommand, AuthCommand, Cli, CliAudioTranscriptionEngine,
+        CliOcrEngine, Command, McpCommand, MigrationSubCommand, OutputFormat, PipeCommand,
+        RetentionCommand, SyncCommand, VisionCommand,
     },
+    embedding::indexer::{start_embedding_indexer, EmbeddingIndexerConfig},
     handle_index_command,
     pipe_manager::PipeInfo,
+    retention::{run_retention, start_retention_task},
+    speaker_clustering::start_speaker_clustering_task,
     start_continuous_recording, start_sleep_monitor, start_ui_recording,
     sync_provider::ScreenpipeSyncProvider,
     vision_manager::{
@@ -36,6 +48,7 @@ use screenpipe_server::{
     watch_pid, PipeManager, ResourceMonitor, SCServer,
 };
 use screenpipe_vision::monitor::list_monitors;
+use screenpipe_vision::CapturePolicyFile;
 use serde::Deserialize;
 use serde_json::{json, Value};
 use std::path::Path;
@@ -151,6 +164,27 @@ fn get_base_dir(custom_path: &Option<String>) -> anyhow::Result<PathBuf> {
     Ok(base_dir)
 }
 
+/// Transcribe audio files with `engine` and the languages, API keys, VAD and PII removal
+/// of the recording flags.
+fn file_transcription_config(
+    cli: &Cli,
+    engine: CliAudioTranscriptionEngine,
+) -> anyhow::Result<FileTranscriptionConfig> {
+    Ok(FileTranscriptionConfig {
+        engine: engine.into(),
+        languages: cli.unique_languages().map_err(|e| anyhow::anyhow!(e))?,
+        deepgram_api_key: cli.deepgram_api_key.clone(),
+        openai_compatible: OpenAiCompatibleConfig {
+            url: cli.openai_compatible_stt_url.clone(),
+            model: cli.openai_compatible_stt_model.clone(),
+            api_key: cli.openai_compatible_stt_api_key.clone(),
+        },
+        vad_engine: cli.vad_engine.clone().into(),
+        use_pii_removal: cli.use_pii_removal,
+        enrolled_speaker_threshold: cli.enrolled_speaker_threshold,
+    })
+}
+
 fn setup_logging(local_data_dir: &PathBuf, cli: &Cli) -> anyhow::Result<WorkerGuard> {
     let file_appender = RollingFileAppender::builder()
         .rotation(Rotation::DAILY)
@@ -379,7 +413,7 @@ async fn main() -> anyhow::Result<()> {
     };
 
     let pipe_manager = if cli.enable_pipe_manager {
-        Arc::new(PipeManager::new(local_data_dir_clone.clone()))
+        Arc::new(PipeManager::new(local_data_dir_clone.clone()).with_api_port(cli.port))
     } else {
         Arc::new(PipeManager::new(PathBuf::from("")))
     };
@@ -415,6 +449,108 @@ async fn main() -> anyhow::Result<()> {
                     }
                     return Ok(());
                 }
+                AudioCommand::Retranscribe {
+                    since,
+                    until,
+                    engine,
+                    data_dir,
+                    output,
+                } => {
+                    let local_data_dir = get_base_dir(data_dir)?;
+                    let db = Arc::new(
+                        DatabaseManager::new(&format!(
+                            "{}/db.sqlite",
+                            local_data_dir.to_string_lossy()
+                        ))
+                        .await
+                        .map_err(|e| {
+                            error!("failed to initialize database: {:?}", e);
+                            e
+                        })?,
+                    );
+
+                    let config = RetranscriptionConfig {
+                        start_time: *since,
+                        end_time: *until,
+                        transcription: file_transcription_config(&cli, engine.clone())?,
+                        ..Default::default()
+                    };
+                    let (cmd_tx, mut status_rx, worker_handle) =
+                        create_retranscription_worker(db, config);
+                    cmd_tx.send(RetranscriptionCommand::Start).await?;
+
+                    // Report progress until the job is over, stop it on ctrl-c: the chunks
+                    // done so far are kept and skipped by the next run
+                    let mut interval = tokio::time::interval(Duration::from_secs(5));
+                    let ctrl_c = signal::ctrl_c();
+                    pin_mut!(ctrl_c);
+                    let mut stopping = false;
+                    loop {
+                        tokio::select! {
+                            _ = interval.tick() => {}
+                            _ = &mut ctrl_c, if !stopping => {
+                                info!("stopping re-transcription");
+                                stopping = true;
+                                cmd_tx.send(RetranscriptionCommand::Stop).await?;
+                            }
+                            changed = status_rx.changed() => {
+                                if changed.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+
+                        let status = status_rx.borrow_and_update().clone();
+                        match output {
+                            OutputFormat::Json => {
+                                println!("{}", serde_json::to_string_pretty(&status)?)
+                            }
+                            OutputFormat::Text => match &status {
+                                RetranscriptionStatus::Running {
+                                    total_chunks,
+                                    processed_chunks,
+                                    failed_chunks,
+                                } => info!(
+                                    "Re-transcribing audio chunks: {}/{} ({:.2}%), {} failed",
+                                    processed_chunks,
+                                    total_chunks,
+                                    if *total_chunks > 0 {
+                                        (*processed_chunks as f64 / *total_chunks as f64) * 100.0
+                                    } else {
+                                        0.0
+                                    },
+                                    failed_chunks
+                                ),
+                                RetranscriptionStatus::Completed {
+                                    total_chunks,
+                                    failed_chunks,
+                                    duration_secs,
+                                } => info!(
+                                    "Re-transcription completed: {} audio chunks processed in {} seconds, {} failed",
+                                    total_chunks, duration_secs, failed_chunks
+                                ),
+                                RetranscriptionStatus::Failed {
+                                    total_chunks,
+                                    processed_chunks,
+                                    error,
+                                } => error!(
+                                    "Re-transcription failed: {}/{} audio chunks processed. Error: {}",
+                                    processed_chunks, total_chunks, error
+                                ),
+                                _ => info!("Re-transcription status: {:?}", status),
+                            },
+                        }
+                        if status.is_finished() {
+                            break;
+                        }
+                    }
+
+                    drop(cmd_tx);
+                    if let Err(e) = worker_handle.await {
+                        error!("error waiting for re-transcription to finish: {}", e);
+                    }
+                    return Ok(());
+                }
             },
             Command::Vision { subcommand } => match subcommand {
                 VisionCommand::List { output } => {
@@ -647,6 +783,7 @@ async fn main() -> anyhow::Result<()> {
                 copy_videos,
                 debug,
                 use_embedding,
+                transcribe_audio,
             } => {
                 let local_data_dir = get_base_dir(data_dir)?;
 
@@ -685,6 +822,14 @@ async fn main() -> anyhow::Result<()> {
                     metadata_override.clone(),
                     *copy_videos,
                     *use_embedding,
+                    if *transcribe_audio {
+                        Some(file_transcription_config(
+                            &cli,
+                            cli.audio_transcription_engine.clone(),
+                        )?)
+                    } else {
+                        None
+                    },
                 )
                 .await?;
                 return Ok(());
@@ -697,6 +842,14 @@ async fn main() -> anyhow::Result<()> {
                 handle_sync_command(subcommand).await?;
                 return Ok(());
             }
+            Command::Retention { subcommand } => {
+                handle_retention_command(subcommand).await?;
+                return Ok(());
+            }
+            Command::Auth { subcommand } => {
+                handle_auth_command(subcommand).await?;
+                return Ok(());
+            }
         }
     }
 
@@ -808,6 +961,11 @@ async fn main() -> anyhow::Result<()> {
     };
 
     let db_server = db.clone();
+    let retention_policy = cli.retention.to_policy();
+    let speaker_clustering = SpeakerClusteringConfig {
+        threshold: cli.speaker_clustering_threshold,
+        auto_merge_threshold: cli.speaker_auto_merge_threshold,
+    };
 
     let warning_ocr_engine_clone = cli.ocr_engine.clone();
     let warning_audio_transcription_engine_clone = cli.audio_transcription_engine.clone();
@@ -855,11 +1013,36 @@ async fn main() -> anyhow::Result<()> {
         .languages(languages.clone())
         .transcription_engine(cli.audio_transcription_engine.into())
         .realtime(cli.enable_realtime_audio_transcription)
+        .realtime_engine(cli.realtime_audio_engine.clone().into())
         .enabled_devices(audio_devices)
         .deepgram_api_key(cli.deepgram_api_key.clone())
+        .openai_compatible(OpenAiCompatibleConfig {
+            url: cli.openai_compatible_stt_url.clone(),
+            model: cli.openai_compatible_stt_model.clone(),
+            api_key: cli.openai_compatible_stt_api_key.clone(),
+        })
         .output_path(PathBuf::from(output_path_clone.clone().to_string()))
         .use_pii_removal(cli.use_pii_removal)
-        .use_system_default_audio(cli.use_system_default_audio);
+        .use_system_default_audio(cli.use_system_default_audio)
+        .enrolled_speaker_threshold(cli.enrolled_speaker_threshold)
+        .echo_cancellation(cli.echo_cancellation.clone().into())
+        .echo_threshold(cli.echo_threshold)
+        .audio_preprocessing(PreprocessingChain::new(cli.audio_preprocessing.clone()));
+
+    for (device, settings) in &cli.device_audio_settings {
+        audio_manager_builder =
+            audio_manager_builder.device_settings(device.clone(), settings.clone());
+    }
+
+    if !cli.audio_transcription_fallback.is_empty() {
+        audio_manager_builder = audio_manager_builder.transcription_fallbacks(
+            cli.audio_transcription_fallback
+                .iter()
+                .cloned()
+                .map(Into::into)
+                .collect(),
+        );
+    }
 
     let audio_manager = match audio_manager_builder.build(db.clone()).await {
         Ok(manager) => Arc::new(manager),
@@ -869,6 +1052,23 @@ async fn main() -> anyhow::Result<()> {
         }
     };
 
+    if !cli.disable_vision && cli.ocr_engine == CliOcrEngine::Onnx {
+        if languages
+            .iter()
+            .any(|language| !matches!(language, Language::English | Language::Chinese))
+        {
+            warn!(
+                "the onnx ocr engine only reads chinese and english, other languages are ignored"
+            );
+        }
+        screenpipe_vision::preload_onnx_ocr().await?;
+    }
+
+    let capture_policies = match &cli.capture_policies {
+        Some(path) => Some(CapturePolicyFile::open(path)?),
+        None => None,
+    };
+
     // Create VisionManager for dynamic monitor detection if enabled
     let vision_manager: Option<Arc<VisionManager>> = if cli.use_all_monitors && !cli.disable_vision
     {
@@ -907,6 +1107,8 @@ async fn main() -> anyhow::Result<()> {
             ignored_windows: cli.ignored_windows.clone(),
             included_windows: cli.included_windows.clone(),
             ignored_urls: cli.ignored_urls.clone(),
+            capture_mask: Arc::new(cli.to_capture_mask()),
+            capture_policies: capture_policies.clone(),
             languages: languages_clone.clone(),
             capture_unfocused_windows: cli.capture_unfocused_windows,
             realtime_vision: cli.enable_realtime_audio_transcription,
@@ -995,6 +1197,8 @@ async fn main() -> anyhow::Result<()> {
                     &cli.ignored_windows,
                     &cli.included_windows,
                     &cli.ignored_urls,
+                    Arc::new(cli.to_capture_mask()),
+                    capture_policies.clone(),
                     languages_clone.clone(),
                     cli.capture_unfocused_windows,
                     cli.enable_realtime_audio_transcription,
@@ -1053,6 +1257,13 @@ async fn main() -> anyhow::Result<()> {
     } else {
         server
     };
+    let server = server
+        .with_retention_policy(retention_policy.clone())
+        .with_speaker_clustering(speaker_clustering.clone())
+        .with_auth(AuthConfig {
+            enabled: cli.enable_api_auth,
+            cors_allowed_origins: cli.cors_allowed_origins.clone(),
+        });
 
     // print screenpipe in gradient
     println!("\n\n{}", DISPLAY.truecolor(147, 112, 219).bold());
@@ -1145,6 +1356,30 @@ async fn main() -> anyhow::Result<()> {
             format!("{} seconds", cli.sync_interval_secs)
         );
     }
+    println!(
+        "│ data retention         │ {:<34} │",
+        if retention_policy.is_empty() {
+            "keep everything".to_string()
+        } else {
+            format_retention_policy(&retention_policy)
+        }
+    );
+    println!(
+        "│ api auth               │ {:<34} │",
+        if cli.enable_api_auth {
+            "bearer token required"
+        } else {
+            "disabled"
+        }
+    );
+    println!(
+        "│ embedding index        │ {:<34} │",
+        if cli.enable_embedding_index {
+            format!("backfill {} rows/min", cli.embedding_backfill_rate)
+        } else {
+            "disabled".to_string()
+        }
+    );
     println!(
         "│ auto-destruct pid      │ {:<34} │",
         cli.auto_destruct_pid.unwrap_or(0)
@@ -1312,6 +1547,9 @@ async fn main() -> anyhow::Result<()> {
 
     // Add warning for cloud arguments and telemetry
     if warning_audio_transcription_engine_clone == CliAudioTranscriptionEngine::Deepgram
+        || cli
+            .audio_transcription_fallback
+            .contains(&CliAudioTranscriptionEngine::Deepgram)
         || warning_ocr_engine_clone == CliOcrEngine::Unstructured
     {
         println!(
@@ -1378,6 +1616,38 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
+    // Start data retention task
+    if !retention_policy.is_empty() {
+        start_retention_task(
+            db.clone(),
+            retention_policy.clone(),
+            Duration::from_secs(cli.retention_interval_minutes.max(1) * 60),
+            shutdown_tx.subscribe(),
+        );
+    }
+
+    // Start speaker clustering task
+    if cli.speaker_clustering_interval_hours > 0 {
+        start_speaker_clustering_task(
+            db.clone(),
+            audio_manager.clone(),
+            speaker_clustering.clone(),
+            Duration::from_secs(cli.speaker_clustering_interval_hours * 3600),
+            shutdown_tx.subscribe(),
+        );
+    }
+
+    // Start background embedding indexer
+    if cli.enable_embedding_index {
+        start_embedding_indexer(
+            db.clone(),
+            EmbeddingIndexerConfig {
+                backfill_rate_per_minute: cli.embedding_backfill_rate,
+            },
+            shutdown_tx.subscribe(),
+        );
+    }
+
     // Start UI event recording
     let ui_recorder_handle = {
         if ui_recorder_config.enabled {
@@ -1532,7 +1802,18 @@ async fn handle_pipe_command(
                     for pipe in pipes {
                         let id = pipe.id;
                         let enabled = pipe.enabled;
-                        println!("  id: {}, enabled: {}", id, enabled);
+                        match pipe.supervisor {
+                            Some(supervisor) => println!(
+                                "  id: {}, enabled: {}, state: {}, restarts: {}",
+                                id,
+                                enabled,
+                                serde_json::to_value(supervisor.state)?
+                                    .as_str()
+                                    .unwrap_or_default(),
+                                supervisor.restarts
+                            ),
+                            None => println!("  id: {}, enabled: {}", id, enabled),
+                        }
                     }
                 }
             }
@@ -1551,10 +1832,17 @@ async fn handle_pipe_command(
                     let data: Value = response.json().await?;
                     match output {
                         OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&data)?),
-                        OutputFormat::Text => println!(
-                            "pipe downloaded successfully. id: {}",
-                            data["pipe_id"].as_str().unwrap_or("unknown")
-                        ),
+                        OutputFormat::Text => {
+                            println!(
+                                "pipe downloaded successfully. id: {}",
+                                data["data"]["pipe_id"].as_str().unwrap_or("unknown")
+                            );
+                            if let Ok(permissions) = serde_json::from_value::<PipePermissions>(
+                                data["data"]["permissions"].clone(),
+                            ) {
+                                print_pipe_permissions(&permissions);
+                            }
+                        }
                     }
                 }
                 _ => match pipe_manager.download_pipe(url).await {
@@ -1564,13 +1852,17 @@ async fn handle_pipe_command(
                             serde_json::to_string_pretty(&json!({
                                 "data": {
                                     "pipe_id": pipe_id,
-                                    "message": "pipe downloaded successfully"
+                                    "message": "pipe downloaded successfully",
+                                    "permissions": pipe_manager.pipe_permissions(&pipe_id).await.ok()
                                 },
                                 "success": true
                             }))?
                         ),
                         OutputFormat::Text => {
-                            println!("pipe downloaded successfully. id: {}", pipe_id)
+                            println!("pipe downloaded successfully. id: {}", pipe_id);
+                            if let Ok(permissions) = pipe_manager.pipe_permissions(&pipe_id).await {
+                                print_pipe_permissions(&permissions);
+                            }
                         }
                     },
                     Err(e) => {
@@ -1725,10 +2017,89 @@ async fn handle_pipe_command(
                 },
             }
         }
+
+        // Run history lives in the pipe directory, no need for the server
+        PipeCommand::Logs {
+            id,
+            run,
+            runs,
+            lines,
+            output,
+        } => {
+            let pipe_runs = pipe_manager.list_pipe_runs(id, *runs, 0).await?;
+            let log_run_id = run.clone().or_else(|| {
+                pipe_runs
+                    .iter()
+                    .find(|r| r.kind == PipeRunKind::Process)
+                    .map(|r| r.id.clone())
+            });
+            let log = match &log_run_id {
+                Some(run_id) => pipe_manager.pipe_run_log(id, run_id, Some(*lines)).await?,
+                None => None,
+            };
+
+            match output {
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&json!({
+                        "data": {
+                            "runs": pipe_runs,
+                            "log_run_id": log_run_id,
+                            "log": log
+                        },
+                        "success": true
+                    }))?
+                ),
+                OutputFormat::Text => {
+                    if pipe_runs.is_empty() {
+                        println!("no runs recorded for pipe {}", id);
+                    }
+                    for run in &pipe_runs {
+                        print_pipe_run(run);
+                    }
+                    match (log_run_id, log) {
+                        (Some(run_id), Some(log)) => {
+                            println!("\noutput of run {} (last {} lines):", run_id, lines);
+                            print!("{}", log);
+                        }
+                        (Some(run_id), None) => println!("\nno output recorded for run {}", run_id),
+                        _ => {}
+                    }
+                }
+            }
+        }
     }
     Ok(())
 }
 
+fn print_pipe_run(run: &PipeRun) {
+    let kind = format!("{:?}", run.kind).to_lowercase();
+    let status = format!("{:?}", run.status).to_lowercase();
+    let mut line = format!(
+        "{}  {}  {}  {}",
+        run.id,
+        run.started_at.format("%Y-%m-%d %H:%M:%S"),
+        kind,
+        status
+    );
+    if let Some(path) = &run.cron_path {
+        line.push_str(&format!("  {}", path));
+    }
+    if let Some(ended_at) = run.ended_at {
+        line.push_str(&format!("  {}s", (ended_at - run.started_at).num_seconds()));
+    }
+    if let Some(code) = run.exit_code {
+        line.push_str(&format!("  exit {}", code));
+    }
+    if let Some(status) = run.http_status {
+        line.push_str(&format!("  http {}", status));
+    }
+    println!("{}", line);
+    if let Some(error) = &run.error {
+        println!("    error: {}", error);
+    }
+}
+
 pub async fn handle_mcp_command(
     command: &McpCommand,
     local_data_dir: &PathBuf,
@@ -1925,6 +2296,13 @@ pub async fn handle_mcp_command(
     Ok(())
 }
 
+fn print_pipe_permissions(permissions: &PipePermissions) {
+    println!("permissions:");
+    for line in permissions.describe() {
+        println!("  {}", line);
+    }
+}
+
 async fn download_mcp_directory(
     client: &Client,
     api_url: &str,
@@ -2196,3 +2574,173 @@ async fn handle_sync_command(command: &SyncCommand) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn format_retention_policy(policy: &RetentionPolicy) -> String {
+    let days = |d: Option<u32>| d.map_or("∞".to_string(), |d| format!("{}d", d));
+    let mut s = format!(
+        "video {}, ocr {}, audio {}, ui {}",
+        days(policy.video_days),
+        days(policy.ocr_days),
+        days(policy.audio_days),
+        days(policy.ui_events_days)
+    );
+    if !policy.app_overrides.is_empty() {
+        s.push_str(&format!(" (+{} apps)", policy.app_overrides.len()));
+    }
+    s
+}
+
+async fn handle_retention_command(command: &RetentionCommand) -> anyhow::Result<()> {
+    let (policy, data_dir, output, dry_run) = match command {
+        RetentionCommand::Report {
+            policy,
+            data_dir,
+            output,
+        } => (policy.to_policy(), data_dir, output, true),
+        RetentionCommand::Run {
+            policy,
+            data_dir,
+            output,
+        } => (policy.to_policy(), data_dir, output, false),
+    };
+
+    if policy.is_empty() {
+        return Err(anyhow::anyhow!(
+            "no retention configured, pass at least one of --retention-video-days, --retention-ocr-days, --retention-audio-days, --retention-ui-events-days or --retention-app-override"
+        ));
+    }
+
+    let local_data_dir = get_base_dir(data_dir)?;
+    let db = DatabaseManager::new(&format!("{}/db.sqlite", local_data_dir.to_string_lossy()))
+        .await
+        .map_err(|e| {
+            error!("failed to initialize database: {:?}", e);
+            e
+        })?;
+
+    let report = run_retention(&db, &policy, dry_run).await?;
+
+    match output {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({
+                "data": report,
+                "success": true
+            }))?
+        ),
+        OutputFormat::Text => {
+            println!(
+                "{}",
+                if dry_run {
+                    "retention report (dry run, nothing deleted):"
+                } else {
+                    "retention run complete:"
+                }
+            );
+            println!(
+                "  policy:               {}",
+                format_retention_policy(&policy)
+            );
+            println!("  frames:               {}", report.frames);
+            println!("  ocr text:             {}", report.ocr_text);
+            println!("  ocr embeddings:       {}", report.ocr_embeddings);
+            println!("  ocr layout blocks:    {}", report.ocr_layout_blocks);
+            println!("  video chunks:         {}", report.video_chunks);
+            println!("  video files:          {}", report.video_files.len());
+            println!("  audio chunks:         {}", report.audio_chunks);
+            println!("  audio transcriptions: {}", report.audio_transcriptions);
+            println!("  ui events:            {}", report.ui_events);
+            println!("  accessibility:        {}", report.accessibility);
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_auth_command(command: &AuthCommand) -> anyhow::Result<()> {
+    let data_dir = match command {
+        AuthCommand::Create { data_dir, .. }
+        | AuthCommand::List { data_dir, .. }
+        | AuthCommand::Revoke { data_dir, .. } => data_dir,
+    };
+    let local_data_dir = get_base_dir(data_dir)?;
+    let db = DatabaseManager::new(&format!("{}/db.sqlite", local_data_dir.to_string_lossy()))
+        .await
+        .map_err(|e| {
+            error!("failed to initialize database: {:?}", e);
+            e
+        })?;
+
+    match command {
+        AuthCommand::Create {
+            name,
+            scopes,
+            output,
+            ..
+        } => {
+            let token = generate_token();
+            let scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+            let id = db
+                .insert_api_token(name, &hash_token(&token), &scopes)
+                .await?;
+            match output {
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&json!({
+                        "data": {"id": id, "name": name, "scopes": scopes, "token": token},
+                        "success": true
+                    }))?
+                ),
+                OutputFormat::Text => {
+                    println!(
+                        "created token {} ({}) with scopes: {}",
+                        id,
+                        name,
+                        scopes.join(", ")
+                    );
+                    println!("{}", token);
+                    println!("store it now, it will not be shown again");
+                }
+            }
+        }
+        AuthCommand::List { output, .. } => {
+            let tokens = db.list_api_tokens().await?;
+            match output {
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&json!({
+                        "data": tokens,
+                        "success": true
+                    }))?
+                ),
+                OutputFormat::Text => {
+                    if tokens.is_empty() {
+                        println!("no api tokens");
+                    }
+                    for token in tokens {
+                        println!(
+                            "{:>4}  {:<20}  {:<40}  {}",
+                            token.id,
+                            token.name,
+                            token.scopes.join(","),
+                            match (token.revoked_at, token.last_used_at) {
+                                (Some(revoked), _) => format!("revoked {}", revoked),
+                                (None, Some(used)) => format!("last used {}", used),
+                                (None, None) => "never used".to_string(),
+                            }
+                        );
+                    }
+                }
+            }
+        }
+        AuthCommand::Revoke { id, .. } => {
+            if db.revoke_api_token(*id).await? {
+                println!("revoked token {}", id);
+            } else {
+                return Err(anyhow::anyhow!("no active token with id {}", id));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,17 +1,30 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{path::PathBuf, str::FromStr, sync::Arc};
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
 
 use clap::CommandFactory;
 use clap::ValueEnum;
-use clap::{Parser, Subcommand, ValueHint};
+use clap::{Args, Parser, Subcommand, ValueHint};
 use clap_complete::{generate, Shell};
 use screenpipe_audio::{
-    core::engine::AudioTranscriptionEngine as CoreAudioTranscriptionEngine,
+    audio_manager::DeviceAudioSettings,
+    core::engine::{
+        AudioTranscriptionEngine as CoreAudioTranscriptionEngine, RealtimeTranscriptionEngine,
+    },
+    echo_cancellation::{EchoCancellationMode, DEFAULT_ECHO_THRESHOLD},
+    preprocessing::{PreprocessingChain, PreprocessingStage},
+    speaker::embedding_manager::DEFAULT_ENROLLED_SPEAKER_THRESHOLD,
     vad::{VadEngineEnum, VadSensitivity},
 };
 use screenpipe_core::Language;
 use screenpipe_db::CustomOcrConfig as DBCustomOcrConfig;
 use screenpipe_db::OcrEngine as DBOcrEngine;
-use screenpipe_vision::{custom_ocr::CustomOcrConfig, utils::OcrEngine as CoreOcrEngine};
+use screenpipe_db::{AppRetentionOverride, RetentionPolicy, DEFAULT_SPEAKER_CLUSTERING_THRESHOLD};
+use screenpipe_vision::{
+    custom_ocr::CustomOcrConfig, utils::OcrEngine as CoreOcrEngine, CaptureMask, MaskRect,
+};
+
+use crate::auth::ApiScope;
 #[derive(Clone, Debug, ValueEnum, PartialEq)]
 pub enum CliAudioTranscriptionEngine {
     #[clap(name = "deepgram")]
@@ -28,6 +41,8 @@ pub enum CliAudioTranscriptionEngine {
     WhisperLargeV3Turbo,
     #[clap(name = "whisper-large-v3-turbo-quantized")]
     WhisperLargeV3TurboQuantized,
+    #[clap(name = "openai-compatible")]
+    OpenAiCompatible,
 }
 
 impl From<CliAudioTranscriptionEngine> for CoreAudioTranscriptionEngine {
@@ -50,6 +65,9 @@ impl From<CliAudioTranscriptionEngine> for CoreAudioTranscriptionEngine {
             CliAudioTranscriptionEngine::WhisperLargeV3TurboQuantized => {
                 CoreAudioTranscriptionEngine::WhisperLargeV3TurboQuantized
             }
+            CliAudioTranscriptionEngine::OpenAiCompatible => {
+                CoreAudioTranscriptionEngine::OpenAiCompatible
+            }
         }
     }
 }
@@ -64,6 +82,7 @@ pub enum CliOcrEngine {
     #[cfg(target_os = "macos")]
     AppleNative,
     Custom,
+    Onnx,
 }
 
 impl From<CliOcrEngine> for Arc<DBOcrEngine> {
@@ -77,6 +96,7 @@ impl From<CliOcrEngine> for Arc<DBOcrEngine> {
             #[cfg(target_os = "windows")]
             CliOcrEngine::WindowsNative => Arc::new(DBOcrEngine::WindowsNative),
             CliOcrEngine::Custom => Arc::new(DBOcrEngine::Custom(DBCustomOcrConfig::default())),
+            CliOcrEngine::Onnx => Arc::new(DBOcrEngine::Onnx),
         }
     }
 }
@@ -105,6 +125,7 @@ impl From<CliOcrEngine> for CoreOcrEngine {
                     CoreOcrEngine::Custom(CustomOcrConfig::default())
                 }
             }
+            CliOcrEngine::Onnx => CoreOcrEngine::Onnx,
         }
     }
 }
@@ -132,6 +153,38 @@ pub enum CliVadSensitivity {
     High,
 }
 
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliRealtimeAudioEngine {
+    Deepgram,
+    Whisper,
+}
+
+impl From<CliRealtimeAudioEngine> for RealtimeTranscriptionEngine {
+    fn from(cli_engine: CliRealtimeAudioEngine) -> Self {
+        match cli_engine {
+            CliRealtimeAudioEngine::Deepgram => RealtimeTranscriptionEngine::Deepgram,
+            CliRealtimeAudioEngine::Whisper => RealtimeTranscriptionEngine::Whisper,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliEchoCancellation {
+    Off,
+    Tag,
+    Suppress,
+}
+
+impl From<CliEchoCancellation> for EchoCancellationMode {
+    fn from(cli_echo_cancellation: CliEchoCancellation) -> Self {
+        match cli_echo_cancellation {
+            CliEchoCancellation::Off => EchoCancellationMode::Off,
+            CliEchoCancellation::Tag => EchoCancellationMode::Tag,
+            CliEchoCancellation::Suppress => EchoCancellationMode::Suppress,
+        }
+    }
+}
+
 impl From<CliVadSensitivity> for VadSensitivity {
     fn from(cli_sensitivity: CliVadSensitivity) -> Self {
         match cli_sensitivity {
@@ -206,13 +259,37 @@ pub struct Cli {
     /// WhisperTiny is a local, lightweight transcription model, recommended for high data privacy.
     /// WhisperDistilLargeV3 is a local, lightweight transcription model (-a whisper-large), recommended for higher quality audio than tiny.
     /// WhisperLargeV3Turbo is a local, lightweight transcription model (-a whisper-large-v3-turbo), recommended for higher quality audio than tiny.
+    /// OpenAiCompatible sends audio to any server implementing /v1/audio/transcriptions (-a openai-compatible), e.g. a local faster-whisper or whisper.cpp server.
     #[arg(short = 'a', long, value_enum, default_value_t = CliAudioTranscriptionEngine::WhisperLargeV3TurboQuantized)]
     pub audio_transcription_engine: CliAudioTranscriptionEngine,
 
+    /// Engines to try in order when the transcription engine fails, example:
+    /// -a openai-compatible --audio-transcription-fallback deepgram --audio-transcription-fallback whisper-tiny
+    /// Defaults to whisper-large-v3-turbo-quantized for deepgram and no fallback otherwise
+    #[arg(long, value_enum)]
+    pub audio_transcription_fallback: Vec<CliAudioTranscriptionEngine>,
+
+    /// Base url of the OpenAI-compatible transcription server, /audio/transcriptions is appended to it
+    #[arg(long, default_value = "http://localhost:8000/v1")]
+    pub openai_compatible_stt_url: String,
+
+    /// Model requested from the OpenAI-compatible transcription server
+    #[arg(long, default_value = "whisper-1")]
+    pub openai_compatible_stt_model: String,
+
+    /// API key sent to the OpenAI-compatible transcription server
+    #[arg(long)]
+    pub openai_compatible_stt_api_key: Option<String>,
+
     /// Enable realtime audio transcription
     #[arg(long, default_value_t = false)]
     pub enable_realtime_audio_transcription: bool,
 
+    /// Engine of realtime audio transcription: Deepgram's streaming API, or a local whisper
+    /// model that works offline. Results are streamed on /ws/transcripts
+    #[arg(long, value_enum, default_value_t = CliRealtimeAudioEngine::Deepgram)]
+    pub realtime_audio_engine: CliRealtimeAudioEngine,
+
     /// Enable realtime vision
     #[arg(long, default_value_t = true)]
     pub enable_realtime_vision: bool,
@@ -222,6 +299,8 @@ pub struct Cli {
     /// WindowsNative is a local OCR engine for Windows.
     /// Unstructured is a cloud OCR engine (free of charge on us for now), recommended for high quality OCR.
     /// Tesseract is a local OCR engine (not supported on macOS)
+    /// Onnx is a faster local OCR engine running PaddleOCR models on the CPU, loaded at startup.
+    /// It reads Chinese and English only, whatever --language is
     #[cfg_attr(
         target_os = "macos",
         arg(short = 'o', long, value_enum, default_value_t = CliOcrEngine::AppleNative)
@@ -279,6 +358,35 @@ pub struct Cli {
     #[arg(long)]
     pub ignored_urls: Vec<String>,
 
+    /// Rectangles painted black on the recorded frames before they are encoded or read, in
+    /// pixels of the frames, as x,y,width,height or monitor_id:x,y,width,height for a single
+    /// monitor, example: --masked-regions "0:0,0,1920,40" hides the top bar of monitor 0
+    #[arg(long, value_parser = MaskRect::from_str)]
+    pub masked_regions: Vec<MaskRect>,
+
+    /// List of windows to black out (by app name or title) on the recorded frames, matched like
+    /// --ignored-windows. They are masked wherever they show, even behind other windows, and
+    /// never read
+    #[arg(long)]
+    pub blackout_windows: Vec<String>,
+
+    /// List of URLs whose browser windows are blacked out on the recorded frames, matched like
+    /// --ignored-urls
+    #[arg(long)]
+    pub blackout_urls: Vec<String>,
+
+    /// Paint PII (emails, credit cards, SSNs, API keys...) found by OCR black on the recorded
+    /// frames and redact it from the OCR text, before anything is written to disk
+    #[arg(long, default_value_t = false)]
+    pub mask_pii: bool,
+
+    /// JSON file of per-application capture policies, mapping app names, window title regexes
+    /// or URLs to capture, ocr, video, fps and video_quality settings, example:
+    /// [{"app": "Slack", "fps": 0.2}, {"app": "Terminal", "video": false}, {"app": "1Password", "capture": false}]
+    /// The first matching rule applies, and the file is read again when it changes
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub capture_policies: Option<PathBuf>,
+
     /// Video chunk duration in seconds
     #[arg(long, default_value_t = 60)]
     pub video_chunk_duration: u64,
@@ -295,6 +403,35 @@ pub struct Cli {
     #[arg(long, value_enum, default_value_t = CliVadSensitivity::High)]
     pub vad_sensitivity: CliVadSensitivity,
 
+    /// Minimum similarity (0 to 1) for speech to be attributed to a speaker enrolled with
+    /// /speakers/enroll, higher is stricter
+    #[arg(long, default_value_t = DEFAULT_ENROLLED_SPEAKER_THRESHOLD)]
+    pub enrolled_speaker_threshold: f32,
+
+    /// What to do with microphone speech that echoes audio played on a recorded output
+    /// device: transcribe it anyway (off), transcribe it without a speaker (tag), or drop it
+    /// before transcription (suppress)
+    #[arg(long, value_enum, default_value_t = CliEchoCancellation::Off)]
+    pub echo_cancellation: CliEchoCancellation,
+
+    /// Minimum correlation (0 to 1) of microphone speech with an output device to be an echo,
+    /// lower catches more echoes but may drop speech made while audio plays
+    #[arg(long, default_value_t = DEFAULT_ECHO_THRESHOLD)]
+    pub echo_threshold: f32,
+
+    /// Preprocessing of recorded audio before transcription, comma separated stages applied
+    /// in order: high-pass, denoise, agc, normalize. None by default
+    #[arg(long, value_delimiter = ',', value_parser = PreprocessingStage::from_str)]
+    pub audio_preprocessing: Vec<PreprocessingStage>,
+
+    /// Settings of one device overriding the global ones, e.g. --device-audio-settings
+    /// "Display 1 (output):language=english,vad=low,engine=whisper-tiny,diarization=off,chunk=15".
+    /// language can be given several times, the flag once per device. preprocessing
+    /// stages are joined by +, e.g. preprocessing=denoise+agc, or none. Settings changed
+    /// through /audio/device/start are kept across restarts and win over these
+    #[arg(long, value_parser = parse_device_audio_settings)]
+    pub device_audio_settings: Vec<(String, DeviceAudioSettings)>,
+
     /// Disable telemetry
     #[arg(long, default_value_t = false)]
     pub disable_telemetry: bool,
@@ -355,6 +492,59 @@ pub struct Cli {
     #[arg(long)]
     pub sync_machine_id: Option<String>,
 
+    // =========================================================================
+    // API Auth Options
+    // =========================================================================
+    /// Require a bearer token (see `screenpipe auth create`) on all API routes except /health
+    #[arg(long, default_value_t = false)]
+    pub enable_api_auth: bool,
+
+    /// Origins allowed to call the API from a browser (comma separated or repeated).
+    /// Defaults to any origin.
+    #[arg(long, value_delimiter = ',')]
+    pub cors_allowed_origins: Vec<String>,
+
+    // =========================================================================
+    // Data Retention Options
+    // =========================================================================
+    #[command(flatten)]
+    pub retention: RetentionArgs,
+
+    /// How often the retention task prunes expired data, in minutes (default: 60)
+    #[arg(long, default_value_t = 60)]
+    pub retention_interval_minutes: u64,
+
+    // =========================================================================
+    // Speaker Clustering Options
+    // =========================================================================
+    /// Re-cluster all speakers every N hours and propose merges of fragments of the same
+    /// voice (see /speakers/merge-proposals). 0 disables the background clustering.
+    #[arg(long, default_value_t = 0)]
+    pub speaker_clustering_interval_hours: u64,
+
+    /// Minimum similarity (0 to 1) of speakers to be proposed for a merge
+    #[arg(long, default_value_t = DEFAULT_SPEAKER_CLUSTERING_THRESHOLD)]
+    pub speaker_clustering_threshold: f32,
+
+    /// Merge without asking the speakers proposed with at least this similarity (0 to 1),
+    /// e.g. 0.9. Disabled by default.
+    #[arg(long)]
+    pub speaker_auto_merge_threshold: Option<f32>,
+
+    // =========================================================================
+    // Embedding Index Options
+    // =========================================================================
+    /// Embed OCR text and audio transcriptions in the background with the built-in
+    /// local model, for offline semantic and hybrid search. Downloads the model on first use.
+    /// Without it, semantic search embeds queries with Ollama's nomic-embed-text.
+    #[arg(long, default_value_t = false)]
+    pub enable_embedding_index: bool,
+
+    /// How many past OCR/audio rows per minute the embedding index backfills.
+    /// New data is always embedded as it arrives. 0 disables the backfill.
+    #[arg(long, default_value_t = 300)]
+    pub embedding_backfill_rate: u32,
+
     #[command(subcommand)]
     pub command: Option<Command>,
 }
@@ -369,6 +559,17 @@ impl Cli {
         }
         Ok(unique_langs.into_iter().collect())
     }
+
+    /// Capture-time masking configured by the CLI arguments
+    pub fn to_capture_mask(&self) -> CaptureMask {
+        CaptureMask {
+            rects: self.masked_regions.clone(),
+            blackout_windows: self.blackout_windows.clone(),
+            blackout_urls: self.blackout_urls.clone(),
+            mask_pii: self.mask_pii,
+        }
+    }
+
     pub fn handle_completions(&self, shell: Shell) -> anyhow::Result<()> {
         let mut cmd = Self::command();
         generate(shell, &mut cmd, "screenpipe", &mut std::io::stdout());
@@ -420,9 +621,10 @@ pub enum Command {
         #[command(subcommand)]
         subcommand: McpCommand,
     },
-    /// Add video files to existing screenpipe data (OCR only) - DOES NOT SUPPORT AUDIO
+    /// Add video files to existing screenpipe data (OCR only), and audio files (wav, mp3,
+    /// m4a, ogg) with --transcribe-audio
     Add {
-        /// Path to folder containing video files
+        /// Path to folder containing video and audio files
         path: String,
         /// Data directory. Default to $HOME/.screenpipe
         #[arg(long, value_hint = ValueHint::DirPath)]
@@ -439,7 +641,7 @@ pub enum Command {
         /// Path to JSON file containing metadata overrides
         #[arg(long, value_hint = ValueHint::FilePath)]
         metadata_override: Option<PathBuf>,
-        /// Copy videos to screenpipe data directory
+        /// Copy videos and audio files to screenpipe data directory
         #[arg(long, default_value_t = true)]
         copy_videos: bool,
         /// Enable debug logging for screenpipe modules
@@ -448,6 +650,10 @@ pub enum Command {
         /// Enable embedding generation for OCR text
         #[arg(long, default_value_t = false)]
         use_embedding: bool,
+        /// Also import audio files and the audio track of videos, transcribed with the
+        /// transcription engine, languages, VAD and PII removal of the recording flags
+        #[arg(long, default_value_t = false)]
+        transcribe_audio: bool,
     },
     /// Run data migrations in the background
     Migrate {
@@ -473,6 +679,16 @@ pub enum Command {
         #[arg(long, default_value_t = true)]
         continue_on_error: bool,
     },
+    /// API token management commands
+    Auth {
+        #[command(subcommand)]
+        subcommand: AuthCommand,
+    },
+    /// Data retention commands
+    Retention {
+        #[command(subcommand)]
+        subcommand: RetentionCommand,
+    },
     /// Generate shell completions
     Completions {
         /// The shell to generate completions for
@@ -501,6 +717,25 @@ pub enum AudioCommand {
         #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
         output: OutputFormat,
     },
+    /// Transcribe recorded audio again with another engine, replacing its transcriptions.
+    /// Languages, API keys, VAD and PII removal come from the recording flags
+    Retranscribe {
+        /// Audio recorded from then, RFC 3339 or YYYY-MM-DD (UTC)
+        #[arg(long, value_parser = parse_datetime)]
+        since: DateTime<Utc>,
+        /// Audio recorded until then, RFC 3339 or YYYY-MM-DD (UTC). Default to now
+        #[arg(long, value_parser = parse_datetime)]
+        until: Option<DateTime<Utc>>,
+        /// Engine replacing the previous transcriptions
+        #[arg(long, value_enum, default_value_t = CliAudioTranscriptionEngine::WhisperLargeV3Turbo)]
+        engine: CliAudioTranscriptionEngine,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
 }
 
 #[derive(Subcommand)]
@@ -604,6 +839,23 @@ pub enum PipeCommand {
         #[arg(short = 'p', long, default_value_t = 3030)]
         port: u16,
     },
+    /// Show recent runs of a pipe and the output of its latest process run
+    Logs {
+        /// ID of the pipe
+        id: String,
+        /// Show the output of this run instead of the latest process run
+        #[arg(long)]
+        run: Option<String>,
+        /// Number of runs to list
+        #[arg(long, default_value_t = 10)]
+        runs: usize,
+        /// Number of log lines to show
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
 }
 
 #[derive(Subcommand)]
@@ -662,6 +914,174 @@ pub enum SyncCommand {
     },
 }
 
+#[derive(Subcommand)]
+pub enum AuthCommand {
+    /// Create an API token. The token is printed once and cannot be recovered
+    Create {
+        /// Name to recognize the token by
+        #[arg(long)]
+        name: String,
+        /// Scopes granted to the token: read-search, read-media, write, admin, pipes
+        #[arg(long = "scope", required = true, value_delimiter = ',', value_parser = ApiScope::from_str)]
+        scopes: Vec<ApiScope>,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// List API tokens
+    List {
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Revoke an API token
+    Revoke {
+        /// ID of the token to revoke
+        id: i64,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RetentionCommand {
+    /// Show what the retention policy would delete, without deleting anything
+    Report {
+        #[command(flatten)]
+        policy: RetentionArgs,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Delete everything past the retention policy now
+    Run {
+        #[command(flatten)]
+        policy: RetentionArgs,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+}
+
+/// Retention windows shared by the recorder flags and the `retention` subcommand.
+#[derive(Args, Clone, Debug, Default)]
+pub struct RetentionArgs {
+    /// Delete video files older than N days. Frames stay searchable while their OCR text is retained
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub retention_video_days: Option<u32>,
+
+    /// Delete OCR text, OCR embeddings and accessibility text older than N days
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub retention_ocr_days: Option<u32>,
+
+    /// Delete audio files and transcriptions older than N days
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub retention_audio_days: Option<u32>,
+
+    /// Delete UI events (clicks, keystrokes, clipboard) older than N days
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub retention_ui_events_days: Option<u32>,
+
+    /// Per-app retention override, e.g. --retention-app-override "Slack:video=1,ocr=7,ui_events=7".
+    /// Can be repeated. Unset fields fall back to the global retention.
+    #[arg(long = "retention-app-override", value_parser = parse_app_retention_override)]
+    pub retention_app_overrides: Vec<(String, AppRetentionOverride)>,
+}
+
+impl RetentionArgs {
+    pub fn to_policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            video_days: self.retention_video_days,
+            ocr_days: self.retention_ocr_days,
+            audio_days: self.retention_audio_days,
+            ui_events_days: self.retention_ui_events_days,
+            app_overrides: self.retention_app_overrides.iter().cloned().collect(),
+        }
+    }
+}
+
+/// Parse an RFC 3339 timestamp or a YYYY-MM-DD date, taken as midnight UTC.
+pub fn parse_datetime(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| Utc.from_utc_datetime(&datetime))
+        .ok_or_else(|| {
+            format!(
+                "expected an RFC 3339 timestamp or YYYY-MM-DD date, got '{}'",
+                s
+            )
+        })
+}
+
+fn parse_app_retention_override(s: &str) -> Result<(String, AppRetentionOverride), String> {
+    let (app, rules) = s
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected APP:kind=days[,kind=days], got '{}'", s))?;
+    if app.trim().is_empty() {
+        return Err(format!("missing app name in '{}'", s));
+    }
+
+    let mut rule = AppRetentionOverride::default();
+    for part in rules.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (kind, days) = part
+            .split_once('=')
+            .ok_or_else(|| format!("expected kind=days, got '{}'", part))?;
+        let days: u32 = days
+            .trim()
+            .parse()
+            .ok()
+            .filter(|days| *days > 0)
+            .ok_or_else(|| format!("invalid number of days '{}' (at least 1)", days))?;
+        match kind.trim() {
+            "video" => rule.video_days = Some(days),
+            "ocr" => rule.ocr_days = Some(days),
+            "ui_events" => rule.ui_events_days = Some(days),
+            other => {
+                return Err(format!(
+                    "unknown retention kind '{}' (expected video, ocr or ui_events)",
+                    other
+                ))
+            }
+        }
+    }
+    Ok((app.trim().to_string(), rule))
+}
+
+fn parse_device_audio_settings(s: &str) -> Result<(String, DeviceAudioSettings), String> {
+    let (device, rules) = s
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected DEVICE:setting=value[,setting=value], got '{}'", s))?;
+    if device.trim().is_empty() {
+        return Err(format!("missing device name in '{}'", s));
+    }
+
+    let mut settings = DeviceAudioSettings::default();
+    for part in rules.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (setting, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("expected setting=value, got '{}'", part))?;
+        settings.set(setting.trim(), value.trim())?;
+    }
+    Ok((device.trim().to_string(), settings))
+}
+
 /// Get or create a persistent machine ID for sync
 pub fn get_or_create_machine_id(override_id: Option<String>) -> String {
     if let Some(id) = override_id {
@@ -1,3 +1,4 @@
+use crate::meetings::record_meetings;
 use crate::VideoCapture;
 use anyhow::Result;
 use futures::future::join_all;
@@ -6,7 +7,7 @@ use screenpipe_core::Language;
 use screenpipe_db::{DatabaseManager, FrameWindowData, Speaker};
 use screenpipe_events::{poll_meetings_events, send_event};
 use screenpipe_vision::core::WindowOcr;
-use screenpipe_vision::OcrEngine;
+use screenpipe_vision::{CaptureMask, CapturePolicyFile, OcrEngine};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::runtime::Handle;
@@ -26,6 +27,8 @@ pub async fn start_continuous_recording(
     ignored_windows: &[String],
     include_windows: &[String],
     ignored_urls: &[String],
+    capture_mask: Arc<CaptureMask>,
+    capture_policies: Option<Arc<CapturePolicyFile>>,
     languages: Vec<Language>,
     capture_unfocused_windows: bool,
     realtime_vision: bool,
@@ -43,6 +46,8 @@ pub async fn start_continuous_recording(
                 let ignored_windows_video = ignored_windows.to_vec();
                 let include_windows_video = include_windows.to_vec();
                 let ignored_urls_video = ignored_urls.to_vec();
+                let capture_mask = Arc::clone(&capture_mask);
+                let capture_policies = capture_policies.clone();
 
                 let languages = languages.clone();
                 let activity_feed = activity_feed.clone();
@@ -63,6 +68,8 @@ pub async fn start_continuous_recording(
                             &ignored_windows_video,
                             &include_windows_video,
                             &ignored_urls_video,
+                            capture_mask.clone(),
+                            capture_policies.clone(),
                             video_chunk_duration,
                             languages.clone(),
                             capture_unfocused_windows,
@@ -95,6 +102,12 @@ pub async fn start_continuous_recording(
     };
 
     if !vision_disabled {
+        let db_meetings = Arc::clone(&db);
+        vision_handle.spawn(async move {
+            if let Err(e) = record_meetings(db_meetings).await {
+                error!("Meeting recorder failed: {}", e);
+            }
+        });
         vision_handle.spawn(async move {
             info!("Starting meeting events polling");
             match poll_meetings_events().await {
@@ -130,6 +143,8 @@ pub async fn record_video(
     ignored_windows: &[String],
     include_windows: &[String],
     ignored_urls: &[String],
+    capture_mask: Arc<CaptureMask>,
+    capture_policies: Option<Arc<CapturePolicyFile>>,
     video_chunk_duration: Duration,
     languages: Vec<Language>,
     capture_unfocused_windows: bool,
@@ -181,6 +196,8 @@ pub async fn record_video(
         ignored_windows,
         include_windows,
         ignored_urls,
+        capture_mask,
+        capture_policies,
         languages,
         capture_unfocused_windows,
         activity_feed,
@@ -291,6 +308,15 @@ pub async fn record_video(
                     (window_result.text.clone(), window_result.text_json.clone())
                 };
                 let text_json = serde_json::to_string(&sanitized_text_json).unwrap_or_default();
+                let layout = if use_pii_removal {
+                    window_result
+                        .layout
+                        .iter()
+                        .map(|block| block.map_text(remove_pii))
+                        .collect()
+                } else {
+                    window_result.layout.clone()
+                };
 
                 batch_windows.push(FrameWindowData {
                     app_name: Some(window_result.app_name.clone()),
@@ -299,6 +325,7 @@ pub async fn record_video(
                     focused: window_result.focused,
                     text: text.clone(),
                     text_json: text_json.clone(),
+                    layout,
                 });
 
                 // Store metadata for realtime events (sent after DB insert)
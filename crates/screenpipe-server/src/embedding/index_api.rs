@@ -0,0 +1,29 @@
+//! Embedding indexer API endpoints.
+
+use axum::{extract::State, http::StatusCode, Json};
+use screenpipe_db::EmbeddingIndexProgress;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::server::AppState;
+use crate::text_embeds::LOCAL_EMBEDDING_MODEL;
+
+/// Indexing progress per modality. Empty until the indexer has run once
+/// (see `--enable-embedding-index`).
+pub async fn embedding_index_status(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<EmbeddingIndexProgress>>, (StatusCode, Json<Value>)> {
+    state
+        .db
+        .get_embedding_index_progress(LOCAL_EMBEDDING_MODEL)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("failed to get embedding index progress: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("failed to get embedding index progress: {}", e)})),
+            )
+        })
+}
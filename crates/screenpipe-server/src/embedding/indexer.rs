@@ -0,0 +1,179 @@
+//! Background embedding indexer.
+//!
+//! Embeds OCR text and audio transcriptions with the local [`EmbeddingModel`] so
+//! semantic and hybrid search work without external services. New rows are
+//! embedded as they arrive; rows recorded before the indexer first ran are
+//! backfilled newest first at a throttled rate. Progress is kept in the database,
+//! so restarts pick up where the last run stopped.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use screenpipe_core::embedding::model::EmbeddingModel;
+use screenpipe_db::{DatabaseManager, EmbeddingCandidate, EmbeddingModality};
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use super::embedding_endpoint::get_or_initialize_model;
+use crate::text_embeds::{use_local_embedding_model, LOCAL_EMBEDDING_MODEL};
+
+/// Delay before the first pass so model loading doesn't compete with startup.
+const INITIAL_DELAY: Duration = Duration::from_secs(30);
+const TICK: Duration = Duration::from_secs(10);
+const BATCH_SIZE: u32 = 16;
+/// Longer texts are truncated, a full screen of OCR rarely adds meaning past this.
+const MAX_TEXT_CHARS: usize = 2000;
+
+#[derive(Debug, Clone)]
+pub struct EmbeddingIndexerConfig {
+    /// Past rows embedded per minute, 0 to only embed new rows
+    pub backfill_rate_per_minute: u32,
+}
+
+/// Spawn the background indexer. Exits early if the model can't be loaded.
+pub fn start_embedding_indexer(
+    db: Arc<DatabaseManager>,
+    config: EmbeddingIndexerConfig,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> JoinHandle<()> {
+    // Search queries have to be embedded with the model the index is built with
+    use_local_embedding_model();
+
+    tokio::spawn(async move {
+        info!(
+            "starting embedding indexer (backfill: {} rows/min)",
+            config.backfill_rate_per_minute
+        );
+        tokio::time::sleep(INITIAL_DELAY).await;
+
+        let model = match get_or_initialize_model().await {
+            Ok(model) => model,
+            Err(e) => {
+                error!("embedding indexer stopped, failed to load model: {}", e);
+                return;
+            }
+        };
+
+        let backfill_per_tick = match config.backfill_rate_per_minute {
+            0 => 0,
+            rate => ((rate as u64 * TICK.as_secs() / 60) as u32).max(1),
+        };
+        let mut ticker = tokio::time::interval(TICK);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    for modality in EmbeddingModality::ALL {
+                        if let Err(e) = index_modality(&db, &model, modality, backfill_per_tick).await {
+                            warn!("embedding indexer: {} pass failed: {}", modality.as_str(), e);
+                        }
+                    }
+                }
+                _ = shutdown_rx.recv() => {
+                    info!("stopping embedding indexer");
+                    break;
+                }
+            }
+        }
+    })
+}
+
+/// Embeds every new row, then up to `backfill_limit` past rows.
+async fn index_modality(
+    db: &DatabaseManager,
+    model: &Arc<Mutex<EmbeddingModel>>,
+    modality: EmbeddingModality,
+    backfill_limit: u32,
+) -> anyhow::Result<()> {
+    let watermarks = db.get_embedding_watermarks(modality).await?;
+
+    let mut high_water_id = watermarks.high_water_id;
+    loop {
+        let batch = db
+            .get_new_embedding_candidates(
+                modality,
+                LOCAL_EMBEDDING_MODEL,
+                high_water_id,
+                BATCH_SIZE,
+            )
+            .await?;
+        let Some(last_id) = batch.last().map(|c| c.row_id) else {
+            break;
+        };
+        let is_full = batch.len() == BATCH_SIZE as usize;
+        let embeddings = embed(model, batch).await?;
+        db.insert_indexed_embeddings(
+            modality,
+            LOCAL_EMBEDDING_MODEL,
+            &embeddings,
+            Some(last_id),
+            None,
+        )
+        .await?;
+        debug!(
+            "embedding indexer: embedded {} new {} rows",
+            embeddings.len(),
+            modality.as_str()
+        );
+        high_water_id = last_id;
+        if !is_full {
+            break;
+        }
+    }
+
+    if watermarks.backfill_complete || backfill_limit == 0 {
+        return Ok(());
+    }
+
+    let mut low_water_id = watermarks.low_water_id;
+    let mut remaining = backfill_limit;
+    while remaining > 0 {
+        let batch = db
+            .get_backfill_embedding_candidates(
+                modality,
+                LOCAL_EMBEDDING_MODEL,
+                low_water_id,
+                remaining.min(BATCH_SIZE),
+            )
+            .await?;
+        let Some(last_id) = batch.last().map(|c| c.row_id) else {
+            db.mark_embedding_backfill_complete(modality).await?;
+            info!("embedding indexer: {} backfill complete", modality.as_str());
+            break;
+        };
+        remaining = remaining.saturating_sub(batch.len() as u32);
+        let embeddings = embed(model, batch).await?;
+        db.insert_indexed_embeddings(
+            modality,
+            LOCAL_EMBEDDING_MODEL,
+            &embeddings,
+            None,
+            Some(last_id),
+        )
+        .await?;
+        low_water_id = last_id;
+    }
+
+    Ok(())
+}
+
+async fn embed(
+    model: &Arc<Mutex<EmbeddingModel>>,
+    batch: Vec<EmbeddingCandidate>,
+) -> anyhow::Result<Vec<(i64, Vec<f32>)>> {
+    let (ids, texts): (Vec<i64>, Vec<String>) = batch
+        .into_iter()
+        .map(|c| (c.id, c.text.chars().take(MAX_TEXT_CHARS).collect()))
+        .unzip();
+
+    // Inference is CPU/GPU bound, keep it off the async workers
+    let model = model.clone();
+    let vectors = tokio::task::spawn_blocking(move || {
+        model.blocking_lock().generate_batch_embeddings(&texts)
+    })
+    .await??;
+
+    Ok(ids.into_iter().zip(vectors).collect())
+}
@@ -1 +1,3 @@
 pub mod embedding_endpoint;
+pub(crate) mod index_api;
+pub mod indexer;
@@ -0,0 +1,140 @@
+//! Hybrid search API endpoint.
+//!
+//! Ranks OCR, audio and UI text by FTS5 bm25 and embedding similarity fused
+//! with reciprocal rank fusion, see [`screenpipe_db::DatabaseManager::hybrid_search`].
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use screenpipe_db::{is_fts_query_error, ContentType, HybridScore, HybridSearchQuery};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::{debug, error, warn};
+
+use crate::server::{from_comma_separated_array, AppState, ContentItem};
+use crate::text_embeds::{embedding_model, generate_embedding};
+
+#[derive(Debug, Deserialize)]
+pub struct HybridSearchRequest {
+    pub q: String,
+    #[serde(default)]
+    pub content_type: ContentType,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    #[serde(default, deserialize_with = "from_comma_separated_array")]
+    pub speaker_ids: Option<Vec<i64>>,
+    /// Rank by embedding similarity as well as keywords
+    #[serde(default = "default_semantic")]
+    pub semantic: bool,
+    /// Max cosine distance for a semantic match, same default as /semantic-search
+    #[serde(default = "default_threshold")]
+    pub threshold: f32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+fn default_semantic() -> bool {
+    true
+}
+
+fn default_threshold() -> f32 {
+    0.3
+}
+
+#[derive(Debug, Serialize)]
+pub struct HybridSearchItem {
+    #[serde(flatten)]
+    pub content: ContentItem,
+    pub score: HybridScore,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HybridSearchResponse {
+    pub data: Vec<HybridSearchItem>,
+    /// Whether embedding similarity contributed to the ranking
+    pub semantic: bool,
+}
+
+/// Search all text modalities and return one relevance-ordered list.
+pub async fn hybrid_search(
+    State(state): State<Arc<AppState>>,
+    Query(request): Query<HybridSearchRequest>,
+) -> Result<Json<HybridSearchResponse>, (StatusCode, Json<Value>)> {
+    let q = request.q.trim();
+    if q.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "q must not be empty"})),
+        ));
+    }
+
+    // Keyword ranking still works without embeddings, so don't fail the search
+    let embedding = if request.semantic {
+        match generate_embedding(q, 0).await {
+            Ok(embedding) => Some(embedding),
+            Err(e) => {
+                warn!("hybrid search falling back to keywords only: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let semantic = embedding.is_some();
+
+    let hits = state
+        .db
+        .hybrid_search(&HybridSearchQuery {
+            query: q,
+            embedding,
+            embedding_model: Some(embedding_model()),
+            max_distance: request.threshold,
+            content_type: request.content_type,
+            limit: request.limit,
+            offset: request.offset,
+            start_time: request.start_time,
+            end_time: request.end_time,
+            app_name: request.app_name.as_deref(),
+            window_name: request.window_name.as_deref(),
+            // Hide screenpipe's own windows, like /search does
+            exclude_app_name: Some("screenpipe"),
+            speaker_ids: request.speaker_ids,
+        })
+        .await
+        .map_err(|e| {
+            if is_fts_query_error(&e) {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": format!("invalid search query: {}", e)})),
+                );
+            }
+            error!("hybrid search failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("hybrid search failed: {}", e)})),
+            )
+        })?;
+
+    let data: Vec<HybridSearchItem> = hits
+        .into_iter()
+        .map(|hit| HybridSearchItem {
+            content: ContentItem::from(&hit.result),
+            score: hit.score,
+        })
+        .collect();
+    debug!("hybrid search for '{}' found {} results", q, data.len());
+
+    Ok(Json(HybridSearchResponse { data, semantic }))
+}
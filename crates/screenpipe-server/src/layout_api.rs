@@ -0,0 +1,139 @@
+//! OCR layout API endpoints.
+//!
+//! Paragraphs, tables and code blocks read on frames are built by
+//! [`screenpipe_vision::layout::analyze_layout`] and stored with the OCR text.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use screenpipe_db::{
+    is_fts_query_error, layout_to_markdown, LayoutBlockKind, OcrLayoutBlock, OcrLayoutSearchResult,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::server::AppState;
+
+type ApiError = (StatusCode, Json<Value>);
+
+fn internal_error(context: &str, e: impl std::fmt::Display) -> ApiError {
+    error!("{}: {}", context, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"error": format!("{}: {}", context, e)})),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LayoutSearchQuery {
+    pub q: String,
+    /// Only blocks of this kind: `paragraph`, `table` or `code`
+    pub kind: Option<LayoutBlockKind>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub app_name: Option<String>,
+    /// Clamped to [`MAX_LIMIT`]
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+}
+
+/// Most blocks returned by one search, each carries the text of a whole block.
+const MAX_LIMIT: u32 = 1000;
+
+fn default_limit() -> u32 {
+    20
+}
+
+#[derive(Debug, Serialize)]
+pub struct LayoutSearchResponse {
+    pub data: Vec<OcrLayoutSearchResult>,
+}
+
+/// Search the paragraphs, tables and code blocks read on screen, newest first.
+pub async fn search_layout(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LayoutSearchQuery>,
+) -> Result<Json<LayoutSearchResponse>, ApiError> {
+    let q = query.q.trim();
+    if q.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "q must not be empty"})),
+        ));
+    }
+
+    state
+        .db
+        .search_ocr_layout(
+            q,
+            query.kind,
+            query.start_time,
+            query.end_time,
+            query.app_name.as_deref(),
+            query.limit.min(MAX_LIMIT),
+            query.offset,
+        )
+        .await
+        .map(|data| Json(LayoutSearchResponse { data }))
+        .map_err(|e| {
+            if is_fts_query_error(&e) {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": format!("invalid search query: {}", e)})),
+                )
+            } else {
+                internal_error("layout search failed", e)
+            }
+        })
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutFormat {
+    #[default]
+    Json,
+    Markdown,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FrameLayoutQuery {
+    #[serde(default)]
+    pub format: LayoutFormat,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FrameLayoutResponse {
+    pub frame_id: i64,
+    pub blocks: Vec<OcrLayoutBlock>,
+}
+
+/// Export the layout of the text read on a frame, as JSON blocks or markdown. Empty for
+/// frames read before layouts were stored.
+pub async fn get_frame_layout(
+    State(state): State<Arc<AppState>>,
+    Path(frame_id): Path<i64>,
+    Query(query): Query<FrameLayoutQuery>,
+) -> Result<Response, ApiError> {
+    let blocks = state
+        .db
+        .get_frame_layout(frame_id)
+        .await
+        .map_err(|e| internal_error("failed to get frame layout", e))?;
+
+    match query.format {
+        LayoutFormat::Json => Ok(Json(FrameLayoutResponse { frame_id, blocks }).into_response()),
+        LayoutFormat::Markdown => Ok((
+            [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+            layout_to_markdown(&blocks),
+        )
+            .into_response()),
+    }
+}
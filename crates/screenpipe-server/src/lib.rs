@@ -2,16 +2,29 @@ mod add;
 pub mod analytics;
 #[cfg(feature = "apple-intelligence")]
 mod apple_intelligence_api;
+pub mod auth;
 mod auto_destruct;
 pub mod chunking;
 pub mod cli;
 pub mod cloud_search;
 pub mod core;
 pub mod filtering;
+mod hybrid_search_api;
+mod layout_api;
+pub mod meetings;
+mod meetings_api;
 pub mod pipe_manager;
+mod pipe_runs_api;
+pub mod pipe_supervisor;
 mod resource_monitor;
+pub mod retention;
+mod retention_api;
+mod retranscribe_api;
 mod server;
 pub mod sleep_monitor;
+pub mod speaker_clustering;
+mod speaker_clustering_api;
+mod speaker_enrollment_api;
 mod sync_api;
 pub mod sync_provider;
 pub mod text_embeds;
@@ -37,7 +50,10 @@ pub use server::PaginatedResponse;
 pub use server::SCServer;
 pub use server::{api_list_monitors, MonitorInfo};
 pub use sleep_monitor::start_sleep_monitor;
-pub use video::{FrameWriteInfo, FrameWriteTracker, VideoCapture, video_quality_to_crf, video_quality_to_jpeg_q, video_quality_to_preset};
+pub use video::{
+    video_quality_to_crf, video_quality_to_jpeg_q, video_quality_to_preset, FrameWriteInfo,
+    FrameWriteTracker, VideoCapture,
+};
 pub mod embedding;
 pub use cloud_search::{CloudSearchClient, CloudSearchMetadata, CloudStatus};
 pub use ui_recorder::{start_ui_recording, UiRecorderConfig, UiRecorderHandle};
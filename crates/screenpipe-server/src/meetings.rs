@@ -0,0 +1,52 @@
+//! Persists the `meeting_started` / `meeting_ended` events emitted by the
+//! meeting detector in screenpipe-events.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures::StreamExt;
+use screenpipe_db::DatabaseManager;
+use screenpipe_events::{subscribe_to_all_events, MeetingEvent, AUDIO_DETECTED_MEETING_APP};
+use tracing::{debug, error, info, warn};
+
+/// Records detected meetings in the database until the event stream ends.
+pub async fn record_meetings(db: Arc<DatabaseManager>) -> Result<()> {
+    let mut subscription = subscribe_to_all_events();
+
+    match db.close_stale_meetings().await {
+        Ok(0) => {}
+        Ok(n) => info!("closed {} meeting(s) left open by a previous run", n),
+        Err(e) => warn!("failed to close stale meetings: {}", e),
+    }
+
+    while let Some(event) = subscription.next().await {
+        let started = match event.name.as_str() {
+            "meeting_started" => true,
+            "meeting_ended" => false,
+            _ => continue,
+        };
+        let meeting: MeetingEvent = match serde_json::from_value(event.data) {
+            Ok(meeting) => meeting,
+            Err(e) => {
+                warn!("invalid {} event: {}", event.name, e);
+                continue;
+            }
+        };
+
+        if started {
+            let app = (meeting.app != AUDIO_DETECTED_MEETING_APP).then_some(meeting.app.as_str());
+            match db.start_meeting(app, meeting.timestamp).await {
+                Ok(id) => info!("meeting {} started ({})", id, meeting.app),
+                Err(e) => error!("failed to record meeting start: {}", e),
+            }
+        } else {
+            match db.end_meeting(meeting.timestamp).await {
+                Ok(Some(id)) => info!("meeting {} ended", id),
+                Ok(None) => debug!("meeting_ended without a meeting in progress"),
+                Err(e) => error!("failed to record meeting end: {}", e),
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+//! Meeting API endpoints.
+//!
+//! Meetings are recorded by [`crate::meetings::record_meetings`]. Participants,
+//! frames and transcripts are resolved from the meeting's time window.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use screenpipe_db::{Meeting, MeetingDetails, MeetingTranscript};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::server::AppState;
+
+type ApiError = (StatusCode, Json<Value>);
+
+fn internal_error(context: &str, e: impl std::fmt::Display) -> ApiError {
+    error!("{}: {}", context, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"error": format!("{}: {}", context, e)})),
+    )
+}
+
+fn not_found(id: i64) -> ApiError {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({"error": format!("meeting {} not found", id)})),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListMeetingsQuery {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Case-insensitive substring of the meeting app
+    pub app: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+}
+
+fn default_limit() -> u32 {
+    20
+}
+
+/// List meetings overlapping a time range, newest first.
+pub async fn list_meetings(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListMeetingsQuery>,
+) -> Result<Json<Vec<Meeting>>, ApiError> {
+    state
+        .db
+        .list_meetings(
+            query.start_time,
+            query.end_time,
+            query.app.as_deref(),
+            query.limit,
+            query.offset,
+        )
+        .await
+        .map(Json)
+        .map_err(|e| internal_error("failed to list meetings", e))
+}
+
+/// A meeting with its participants, frames and transcripts.
+pub async fn get_meeting(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Json<MeetingDetails>, ApiError> {
+    match state.db.get_meeting(id).await {
+        Ok(Some(meeting)) => Ok(Json(meeting)),
+        Ok(None) => Err(not_found(id)),
+        Err(e) => Err(internal_error("failed to get meeting", e)),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    #[default]
+    Json,
+    Text,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TranscriptQuery {
+    #[serde(default)]
+    pub format: TranscriptFormat,
+}
+
+fn speaker_label(transcript: &MeetingTranscript) -> String {
+    match (&transcript.speaker_name, transcript.speaker_id) {
+        (Some(name), _) if !name.is_empty() => name.clone(),
+        (_, Some(id)) => format!("speaker {}", id),
+        _ => transcript.device.clone(),
+    }
+}
+
+/// Export the meeting transcript from all audio devices, merged chronologically.
+pub async fn get_meeting_transcript(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(query): Query<TranscriptQuery>,
+) -> Result<Response, ApiError> {
+    let meeting = match state.db.get_meeting_by_id(id).await {
+        Ok(Some(meeting)) => meeting,
+        Ok(None) => return Err(not_found(id)),
+        Err(e) => return Err(internal_error("failed to get meeting", e)),
+    };
+    let transcripts = state
+        .db
+        .get_meeting_transcripts(
+            meeting.start_time,
+            meeting.end_time.unwrap_or_else(Utc::now),
+        )
+        .await
+        .map_err(|e| internal_error("failed to get meeting transcript", e))?;
+
+    match query.format {
+        TranscriptFormat::Json => Ok(Json(transcripts).into_response()),
+        TranscriptFormat::Text => {
+            let mut text = format!(
+                "# {} meeting, {}\n\n",
+                meeting.app.as_deref().unwrap_or("audio"),
+                meeting.start_time.format("%Y-%m-%d %H:%M UTC")
+            );
+            for transcript in &transcripts {
+                text.push_str(&format!(
+                    "[{}] {}: {}\n",
+                    transcript.timestamp.format("%H:%M:%S"),
+                    speaker_label(transcript),
+                    transcript.transcription.trim()
+                ));
+            }
+            Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], text).into_response())
+        }
+    }
+}
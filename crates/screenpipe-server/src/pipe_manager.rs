@@ -1,17 +1,29 @@
 use anyhow::Result;
-use screenpipe_core::{download_pipe, download_pipe_private, PipeState};
+use chrono::Utc;
+use screenpipe_core::{
+    cleanup_pipe_sandbox, download_pipe_checked, download_pipe_private_checked, finish_pipe_run,
+    list_pipe_runs, read_pipe_run_log, PipeApiAccess, PipePermissions, PipeRun, PipeRunStatus,
+    PipeState,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
-use tokio::sync::mpsc::{self, Sender};
+use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
+use crate::auth::{generate_token, hash_token, ApiScope, DEFAULT_PIPE_SCOPES};
+use crate::pipe_supervisor::{
+    update_status, wait_until_unhealthy, PipeSupervisorStatus, RestartTracker, SupervisorConfig,
+    SupervisorState, SupervisorStatuses,
+};
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct PipeInfo {
     pub id: String,
@@ -22,24 +34,104 @@ pub struct PipeInfo {
     pub is_nextjs: bool,
     pub desc: String,
     pub build_status: Option<Value>,
+    #[serde(default)]
+    pub permissions: PipePermissions,
+    /// Restart and health state, `None` if the pipe wasn't started since screenpipe launched
+    #[serde(default)]
+    pub supervisor: Option<PipeSupervisorStatus>,
 }
 
 struct PipeHandle {
-    state: PipeState,
+    /// `None` while the supervisor waits to restart a crashed pipe
+    state: Option<PipeState>,
     kill_tx: Sender<()>,
 }
 
 pub struct PipeManager {
     screenpipe_dir: PathBuf,
+    /// Port of the screenpipe API, reachable by pipes through their egress proxy
+    api_port: u16,
     running_pipes: Arc<RwLock<HashMap<String, PipeHandle>>>,
+    /// API tokens handed to running pipes, keyed by token hash
+    pipe_tokens: Arc<RwLock<HashMap<String, PipeToken>>>,
+    supervisors: SupervisorStatuses,
+}
+
+struct PipeToken {
+    pipe_id: String,
+    scopes: Vec<ApiScope>,
 }
 
 impl PipeManager {
     pub fn new(screenpipe_dir: PathBuf) -> Self {
         PipeManager {
             screenpipe_dir,
+            api_port: 3030,
             running_pipes: Arc::new(RwLock::new(HashMap::new())),
+            pipe_tokens: Arc::new(RwLock::new(HashMap::new())),
+            supervisors: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn with_api_port(mut self, port: u16) -> Self {
+        self.api_port = port;
+        self
+    }
+
+    /// API scopes a pipe declared, the default pipe scopes if it declared none.
+    pub fn pipe_scopes(permissions: &PipePermissions) -> Result<Vec<ApiScope>> {
+        if permissions.api_scopes.is_empty() {
+            return Ok(DEFAULT_PIPE_SCOPES.to_vec());
         }
+        permissions
+            .api_scopes
+            .iter()
+            .map(|scope| {
+                scope
+                    .parse::<ApiScope>()
+                    .map_err(|e| anyhow::anyhow!("invalid api scope in pipe.json: {}", e))
+            })
+            .collect()
+    }
+
+    /// Permissions declared in a pipe's `pipe.json`.
+    pub async fn pipe_permissions(&self, id: &str) -> Result<PipePermissions> {
+        PipePermissions::load(&self.screenpipe_dir.join("pipes").join(id)).await
+    }
+
+    /// Scopes of the token given to a running pipe, if `token_hash` belongs to one.
+    pub async fn pipe_token_scopes(&self, token_hash: &str) -> Option<Vec<ApiScope>> {
+        self.pipe_tokens
+            .read()
+            .await
+            .get(token_hash)
+            .map(|t| t.scopes.clone())
+    }
+
+    /// Mint a fresh API token for a pipe, replacing any previous one.
+    async fn issue_pipe_token(
+        pipe_tokens: &RwLock<HashMap<String, PipeToken>>,
+        pipe_id: &str,
+        scopes: Vec<ApiScope>,
+    ) -> String {
+        let token = generate_token();
+        let mut tokens = pipe_tokens.write().await;
+        tokens.retain(|_, t| t.pipe_id != pipe_id);
+        tokens.insert(
+            hash_token(&token),
+            PipeToken {
+                pipe_id: pipe_id.to_string(),
+                scopes,
+            },
+        );
+        token
+    }
+
+    async fn revoke_pipe_token(pipe_tokens: &RwLock<HashMap<String, PipeToken>>, pipe_id: &str) {
+        pipe_tokens
+            .write()
+            .await
+            .retain(|_, t| t.pipe_id != pipe_id);
     }
 
     pub async fn update_config(&self, id: &str, new_config: Value) -> Result<()> {
@@ -176,12 +268,18 @@ impl PipeManager {
                 .unwrap_or(false),
             desc: desc_pipe,
             build_status: config.get("buildStatus").cloned(),
+            permissions: PipePermissions::from_pipe_config(&config)
+                .ok()
+                .flatten()
+                .unwrap_or_else(PipePermissions::undeclared),
+            supervisor: None,
         }
     }
 
     pub async fn list_pipes(&self) -> Vec<PipeInfo> {
         let pipe_dir = self.screenpipe_dir.join("pipes");
         let mut pipe_infos = Vec::new();
+        let supervisors = self.supervisors.read().await;
 
         if let Ok(mut entries) = tokio::fs::read_dir(pipe_dir).await {
             while let Ok(Some(entry)) = entries.next_entry().await {
@@ -196,7 +294,9 @@ impl PipeManager {
                         .map(|ft| ft.is_dir())
                         .unwrap_or(false)
                 {
-                    pipe_infos.push(Self::load_pipe_info(pipe_id.into_owned(), entry.path()).await);
+                    let mut info = Self::load_pipe_info(pipe_id.into_owned(), entry.path()).await;
+                    info.supervisor = supervisors.get(&info.id).cloned();
+                    pipe_infos.push(info);
                 }
             }
         }
@@ -208,11 +308,17 @@ impl PipeManager {
         // Remove any surrounding quotes and normalize backslashes
         let normalized_url = url.trim_matches('"').replace("\\", "/");
 
-        let pipe_dir = download_pipe(&normalized_url, self.screenpipe_dir.clone()).await?;
+        // Refuse pipes with permissions we can't enforce before installing them
+        let pipe_dir = download_pipe_checked(&normalized_url, self.screenpipe_dir.clone(), |p| {
+            Self::pipe_scopes(p).map(|_| ())
+        })
+        .await?;
+        let pipe_id = pipe_dir.file_name().unwrap().to_string_lossy().into_owned();
+        Self::show_permissions(&pipe_id, &PipePermissions::load(&pipe_dir).await?);
 
         // update the config with the source url
         self.update_config(
-            &pipe_dir.file_name().unwrap().to_string_lossy(),
+            &pipe_id,
             serde_json::json!({
                 "source": normalized_url,
                 "enabled": true, // always enable the pipe
@@ -220,12 +326,23 @@ impl PipeManager {
         )
         .await?;
 
-        info!(
-            "pipe {} downloaded",
-            pipe_dir.file_name().unwrap().to_string_lossy()
-        );
+        info!("pipe {} downloaded", pipe_id);
 
-        Ok(pipe_dir.file_name().unwrap().to_string_lossy().into_owned())
+        Ok(pipe_id)
+    }
+
+    /// Log the permissions a pipe being installed requests.
+    fn show_permissions(pipe_id: &str, permissions: &PipePermissions) {
+        if *permissions == PipePermissions::undeclared() {
+            warn!(
+                "pipe {} declares no permissions, it can reach any host with the default api scopes",
+                pipe_id
+            );
+        }
+        info!("pipe {} requests:", pipe_id);
+        for line in permissions.describe() {
+            info!("  {}", line);
+        }
     }
 
     pub async fn download_pipe_private(
@@ -234,7 +351,15 @@ impl PipeManager {
         pipe_name: &str,
         pipe_id: &str,
     ) -> Result<String> {
-        let pipe_dir = download_pipe_private(pipe_name, url, self.screenpipe_dir.clone()).await?;
+        let pipe_dir =
+            download_pipe_private_checked(pipe_name, url, self.screenpipe_dir.clone(), |p| {
+                Self::pipe_scopes(p).map(|_| ())
+            })
+            .await?;
+        Self::show_permissions(
+            &pipe_dir.file_name().unwrap().to_string_lossy(),
+            &PipePermissions::load(&pipe_dir).await?,
+        );
 
         let package_json_path = pipe_dir.join("package.json");
         let version = if package_json_path.exists() {
@@ -331,6 +456,7 @@ impl PipeManager {
     pub async fn delete_pipe(&self, id: &str) -> Result<()> {
         // First stop the pipe if running
         self.stop_pipe(id).await?;
+        self.supervisors.write().await.remove(id);
 
         // Then delete the directory
         let pipe_dir = self.screenpipe_dir.join("pipes").join(id);
@@ -357,227 +483,251 @@ impl PipeManager {
             // Wait a bit for the process to actually terminate
             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
-            #[cfg(unix)]
-            {
-                // Make grep pattern more specific to target only pipe processes
-                let command = format!(
-                    "ps axuw | grep 'pipes/{}/' | grep -v grep | awk '{{print $2}}' | xargs -I {{}} kill -TERM {{}}",
-                    &id.to_string()
-                );
-
-                let _ = tokio::process::Command::new("sh")
-                    .arg("-c")
-                    .arg(command)
-                    .output()
-                    .await;
+            if let Some(state) = handle.state {
+                Self::kill_pipe_processes(id, state).await?;
             }
 
-            #[cfg(windows)]
-            {
-                // killing by name is faster
-                const CREATE_NO_WINDOW: u32 = 0x08000000;
-                let _ = tokio::process::Command::new("powershell")
-                    .arg("-NoProfile")
-                    .arg("-WindowStyle")
-                    .arg("hidden")
-                    .arg("-Command")
-                    .arg(format!(
-                        r#"Get-WmiObject Win32_Process | Where-Object {{ $_.CommandLine -like "*.screenpipe\pipes\{}*" }} | ForEach-Object {{ taskkill.exe /T /F /PID $_.ProcessId }}"#,
-                        &id.to_string()
-                    ))
-                    .creation_flags(CREATE_NO_WINDOW)
-                    .output()
-                    .await;
-            }
-
-            match handle.state {
-                PipeState::Port(port) => {
-                    tokio::task::spawn(async move {
-                        // killport doesn't seems working
-                        #[cfg(unix)]
-                        {
-                            // soft kill
-                            let command = format!(
-                                "lsof -i :{} | grep -E 'bun|node' | awk 'NR>1 {{print $2}}' | xargs -I {{}} kill -TERM {{}}",
-                                port
-                            );
+            // Clean up cron jobs
+            screenpipe_core::pipes::cleanup_pipe_crons(id).await?;
 
-                            let output = tokio::process::Command::new("sh")
-                                .arg("-c")
-                                .arg(command)
-                                .output()
-                                .await
-                                .expect("failed to execute sh command");
-
-                            if !output.status.success() {
-                                // keep killport in fallback
-                                use killport::cli::Mode;
-                                use killport::killport::{Killport, KillportOperations};
-                                use killport::signal::KillportSignal;
-
-                                let killport = Killport;
-                                let signal: KillportSignal = "SIGKILL".parse().unwrap();
-
-                                match killport.kill_service_by_port(port, signal.clone(), Mode::Auto, false)
-                                {
-                                    Ok(killed_services) => {
-                                        if killed_services.is_empty() {
-                                            debug!("no services found using port {}", port);
-                                        } else {
-                                            for (killable_type, name) in killed_services {
-                                                debug!(
-                                                    "successfully killed {} '{}' listening on port {}",
-                                                    killable_type, name, port
-                                                );
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        warn!("error killing port {}: {}", port, e);
-                                    }
-                                }
+            Self::revoke_pipe_token(&self.pipe_tokens, id).await;
+            cleanup_pipe_sandbox(id).await;
+            update_status(&self.supervisors, id, |s| {
+                s.state = SupervisorState::Stopped;
+                s.next_restart_at = None;
+            })
+            .await;
 
-                            } else {
-                                debug!(
-                                    "successfully killed listening on port {}",
-                                    port
-                                );
-                            }
-                        }
-                        #[cfg(windows)]
-                        {
-                            const CREATE_NO_WINDOW: u32 = 0x08000000;
-                            let output = tokio::process::Command::new("netstat")
-                                .args(&["-ano"])
-                                .creation_flags(CREATE_NO_WINDOW)
-                                .output()
-                                .await
-                                .expect("failed to execute netstat");
-
-                            let output_str = std::str::from_utf8(&output.stdout)
-                                .expect("failed to convert output to string");
-
-                            for line in output_str.lines() {
-                                // parts
-                                let parts: Vec<&str> = line.split_whitespace().collect();
-                                if parts.len() >= 5 {
-                                // only kill local address
-                                    let local_address = parts[1];
-                                    if local_address.ends_with(&format!(":{}", port)) {
-                                        // extract pid
-                                        if let Ok(pid) = parts[4].parse::<u32>() {
-                                            let kill_result = tokio::process::Command::new("taskkill.exe")
-                                                .args(&["/F", "/T", "/PID", &pid.to_string()])
-                                                .creation_flags(CREATE_NO_WINDOW)
-                                                .output()
-                                                .await
-                                                .expect("failed to execute taskkill");
-                                            if kill_result.status.success() {
-                                                info!("successfully stopped pipe running on port: {}", port);
-                                            }
+            info!("stopped pipe: {}", id);
+        }
+        Ok(())
+    }
+
+    /// Kill whatever a pipe left running, its child process tree or the server on its port.
+    async fn kill_pipe_processes(id: &str, state: PipeState) -> Result<()> {
+        #[cfg(unix)]
+        {
+            // Make grep pattern more specific to target only pipe processes
+            let command = format!(
+                "ps axuw | grep 'pipes/{}/' | grep -v grep | awk '{{print $2}}' | xargs -I {{}} kill -TERM {{}}",
+                &id.to_string()
+            );
+
+            let _ = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .output()
+                .await;
+        }
+
+        #[cfg(windows)]
+        {
+            // killing by name is faster
+            const CREATE_NO_WINDOW: u32 = 0x08000000;
+            let _ = tokio::process::Command::new("powershell")
+                .arg("-NoProfile")
+                .arg("-WindowStyle")
+                .arg("hidden")
+                .arg("-Command")
+                .arg(format!(
+                    r#"Get-WmiObject Win32_Process | Where-Object {{ $_.CommandLine -like "*.screenpipe\pipes\{}*" }} | ForEach-Object {{ taskkill.exe /T /F /PID $_.ProcessId }}"#,
+                    &id.to_string()
+                ))
+                .creation_flags(CREATE_NO_WINDOW)
+                .output()
+                .await;
+        }
+
+        match state {
+            PipeState::Port(port) => {
+                tokio::task::spawn(async move {
+                    // killport doesn't seems working
+                    #[cfg(unix)]
+                    {
+                        // soft kill
+                        let command = format!(
+                            "lsof -i :{} | grep -E 'bun|node' | awk 'NR>1 {{print $2}}' | xargs -I {{}} kill -TERM {{}}",
+                            port
+                        );
+
+                        let output = tokio::process::Command::new("sh")
+                            .arg("-c")
+                            .arg(command)
+                            .output()
+                            .await
+                            .expect("failed to execute sh command");
+
+                        if !output.status.success() {
+                            // keep killport in fallback
+                            use killport::cli::Mode;
+                            use killport::killport::{Killport, KillportOperations};
+                            use killport::signal::KillportSignal;
+
+                            let killport = Killport;
+                            let signal: KillportSignal = "SIGKILL".parse().unwrap();
+
+                            match killport.kill_service_by_port(port, signal.clone(), Mode::Auto, false)
+                            {
+                                Ok(killed_services) => {
+                                    if killed_services.is_empty() {
+                                        debug!("no services found using port {}", port);
+                                    } else {
+                                        for (killable_type, name) in killed_services {
+                                            debug!(
+                                                "successfully killed {} '{}' listening on port {}",
+                                                killable_type, name, port
+                                            );
                                         }
                                     }
                                 }
+                                Err(e) => {
+                                    warn!("error killing port {}: {}", port, e);
+                                }
                             }
+
+                        } else {
+                            debug!(
+                                "successfully killed listening on port {}",
+                                port
+                            );
                         }
-                    })
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Failed to kill port: {}", e))?;
-                }
-                PipeState::Pid(pid) => {
-                    // Force kill the process if it's still running
-                    #[cfg(unix)]
-                    {
-                        use nix::sys::signal::{kill, Signal};
-                        use nix::unistd::Pid;
-                        let _ = kill(Pid::from_raw(pid), Signal::SIGKILL);
                     }
                     #[cfg(windows)]
                     {
                         const CREATE_NO_WINDOW: u32 = 0x08000000;
-                        let kill_result = tokio::process::Command::new("taskkill")
-                            .args(&["/F", "/T", "/PID", &pid.to_string()])
+                        let output = tokio::process::Command::new("netstat")
+                            .args(&["-ano"])
                             .creation_flags(CREATE_NO_WINDOW)
                             .output()
                             .await
-                            .expect("failed to execute taskkill");
-                        if kill_result.status.success() {
-                            info!("successfully stopped pipe pid: {}", pid.to_string());
+                            .expect("failed to execute netstat");
+
+                        let output_str = std::str::from_utf8(&output.stdout)
+                            .expect("failed to convert output to string");
+
+                        for line in output_str.lines() {
+                            // parts
+                            let parts: Vec<&str> = line.split_whitespace().collect();
+                            if parts.len() >= 5 {
+                            // only kill local address
+                                let local_address = parts[1];
+                                if local_address.ends_with(&format!(":{}", port)) {
+                                    // extract pid
+                                    if let Ok(pid) = parts[4].parse::<u32>() {
+                                        let kill_result = tokio::process::Command::new("taskkill.exe")
+                                            .args(&["/F", "/T", "/PID", &pid.to_string()])
+                                            .creation_flags(CREATE_NO_WINDOW)
+                                            .output()
+                                            .await
+                                            .expect("failed to execute taskkill");
+                                        if kill_result.status.success() {
+                                            info!("successfully stopped pipe running on port: {}", port);
+                                        }
+                                    }
+                                }
+                            }
                         }
                     }
+                })
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to kill port: {}", e))?;
+            }
+            PipeState::Pid(pid) => {
+                // Force kill the process if it's still running
+                #[cfg(unix)]
+                {
+                    use nix::sys::signal::{kill, Signal};
+                    use nix::unistd::Pid;
+                    let _ = kill(Pid::from_raw(pid), Signal::SIGKILL);
+                }
+                #[cfg(windows)]
+                {
+                    const CREATE_NO_WINDOW: u32 = 0x08000000;
+                    let kill_result = tokio::process::Command::new("taskkill")
+                        .args(&["/F", "/T", "/PID", &pid.to_string()])
+                        .creation_flags(CREATE_NO_WINDOW)
+                        .output()
+                        .await
+                        .expect("failed to execute taskkill");
+                    if kill_result.status.success() {
+                        info!("successfully stopped pipe pid: {}", pid.to_string());
+                    }
                 }
             }
-
-            // Clean up cron jobs
-            screenpipe_core::pipes::cleanup_pipe_crons(id).await?;
-
-            info!("stopped pipe: {}", id);
         }
+
         Ok(())
     }
 
     pub async fn start_pipe_task(&self, id: String) -> Result<impl Future<Output = Result<()>>> {
-        let screenpipe_dir = self.screenpipe_dir.clone();
+        let scopes = Self::pipe_scopes(&self.pipe_permissions(&id).await?)?;
+        let config = SupervisorConfig::load(&self.screenpipe_dir.join("pipes").join(&id)).await;
         let running_pipes = self.running_pipes.clone();
-        let id_for_map = id.clone();
+        let statuses = self.supervisors.clone();
+        let screenpipe_dir = self.screenpipe_dir.clone();
+        let pipe_tokens = self.pipe_tokens.clone();
+        let api_port = self.api_port;
 
         Ok(async move {
-            match screenpipe_core::run_pipe(&id, screenpipe_dir.clone()).await {
-                Ok((mut child, pipe_state)) => {
-                    let (kill_tx, mut kill_rx) = mpsc::channel::<()>(1);
-
-                    running_pipes.write().await.insert(
-                        id_for_map.clone(),
-                        PipeHandle {
-                            state: pipe_state,
-                            kill_tx: kill_tx.clone(),
-                        },
-                    );
-
-                    match pipe_state {
-                        PipeState::Port(port) => {
-                            info!("started pipe: {} on port {}", id, port);
-                        }
-                        PipeState::Pid(pid) => {
-                            info!("started pipe: {} on pid {}", id, pid);
-                        }
-                    }
-
-                    tokio::select! {
-                        status = child.wait() => {
-                            match status {
-                                Ok(status) if !status.success() => {
-                                    println!("pipe {} exited with status: {}", id, status);
-                                    running_pipes.write().await.remove(&id_for_map);
-                                    anyhow::bail!("pipe exited with non-zero status: {}", status);
-                                }
-                                Err(e) => {
-                                    println!("error waiting for pipe {}: {}", id, e);
-                                    running_pipes.write().await.remove(&id_for_map);
-                                    anyhow::bail!("error waiting for pipe: {}", e);
-                                }
-                                Ok(_) => Ok(())
-                            }
-                        }
-                        _ = kill_rx.recv() => {
-                            // Kill received through channel
-                            let _ = child.kill().await;
-                            running_pipes.write().await.remove(&id_for_map);
-                            Ok(())
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("[{}] failed to start pipe {}:", id, e);
-                    Err(e)
-                }
+            // One kill channel for the whole supervisor, so stop_pipe also cancels pending restarts
+            let (kill_tx, kill_rx) = mpsc::channel::<()>(1);
+            running_pipes.write().await.insert(
+                id.clone(),
+                PipeHandle {
+                    state: None,
+                    kill_tx: kill_tx.clone(),
+                },
+            );
+            statuses
+                .write()
+                .await
+                .insert(id.clone(), PipeSupervisorStatus::new(config.restart_policy));
+
+            PipeSupervisor {
+                id,
+                screenpipe_dir,
+                api_port,
+                scopes,
+                config,
+                running_pipes,
+                pipe_tokens,
+                statuses,
+                kill_tx,
             }
+            .run(kill_rx)
+            .await
         })
     }
 
+    /// Run history of a pipe, newest first.
+    pub async fn list_pipe_runs(
+        &self,
+        id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<PipeRun>> {
+        list_pipe_runs(&self.screenpipe_dir.join("pipes").join(id), limit, offset).await
+    }
+
+    /// Captured output of a pipe run, the last `tail_lines` lines if set.
+    pub async fn pipe_run_log(
+        &self,
+        id: &str,
+        run_id: &str,
+        tail_lines: Option<usize>,
+    ) -> Result<Option<String>> {
+        read_pipe_run_log(
+            &self.screenpipe_dir.join("pipes").join(id),
+            run_id,
+            tail_lines,
+        )
+        .await
+    }
+
     pub async fn update_pipe_version(&self, id: &str, source: &str) -> Result<()> {
         debug!("updating pipe: {}", id);
         let pipe_dir = self.screenpipe_dir.join("pipes").join(id);
+        let old_permissions = self.pipe_permissions(id).await?;
 
         // 1. Get source URL from existing config
         let pipe_json_path = pipe_dir.join("pipe.json");
@@ -621,8 +771,12 @@ impl PipeManager {
             tokio::fs::write(&pipe_json_path, updated_config).await?;
         }
 
-        // Download new version to temp directory
-        let tmp_pipe_dir = match download_pipe_private(id, source, tmp_dir.clone()).await {
+        // Download new version to temp directory, refusing permissions we can't enforce
+        let tmp_pipe_dir = match download_pipe_private_checked(id, source, tmp_dir.clone(), |p| {
+            Self::pipe_scopes(p).map(|_| ())
+        })
+        .await
+        {
             Ok(dir) => {
                 debug!("downloaded new version to temp dir: {:?}", dir);
                 dir
@@ -738,6 +892,29 @@ impl PipeManager {
             tokio::fs::write(&pipe_json_path, updated_config).await?;
         }
 
+        // The existing pipe.json is kept, only the permissions of the new version replace its
+        // own, after showing what they change
+        let new_permissions = PipePermissions::load(&tmp_pipe_dir).await?;
+        let changes = old_permissions.describe_changes(&new_permissions);
+        if !changes.is_empty() {
+            warn!("pipe {} update changes its permissions:", id);
+            for line in &changes {
+                warn!("  {}", line);
+            }
+        }
+        if let Some(obj) = config.as_object_mut() {
+            if new_permissions == PipePermissions::undeclared() {
+                obj.remove("permissions");
+            } else {
+                obj.insert(
+                    "permissions".to_string(),
+                    serde_json::to_value(&new_permissions)?,
+                );
+            }
+            let updated_config = serde_json::to_string_pretty(&config)?;
+            tokio::fs::write(&pipe_json_path, updated_config).await?;
+        }
+
         // Update version in existing config
         if let Some(new_version) = new_config.get("version").and_then(Value::as_str) {
             if let Some(obj) = config.as_object_mut() {
@@ -953,3 +1130,239 @@ async fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()
 
     Ok(())
 }
+
+enum PipeExit {
+    /// Stopped through `stop_pipe`
+    Killed,
+    Exited {
+        failed: bool,
+        code: Option<i32>,
+        error: Option<String>,
+    },
+}
+
+impl PipeExit {
+    fn failed(code: Option<i32>, error: String) -> Self {
+        PipeExit::Exited {
+            failed: true,
+            code,
+            error: Some(error),
+        }
+    }
+}
+
+/// Runs a pipe and restarts it according to its [`SupervisorConfig`].
+struct PipeSupervisor {
+    id: String,
+    screenpipe_dir: PathBuf,
+    api_port: u16,
+    scopes: Vec<ApiScope>,
+    config: SupervisorConfig,
+    running_pipes: Arc<RwLock<HashMap<String, PipeHandle>>>,
+    pipe_tokens: Arc<RwLock<HashMap<String, PipeToken>>>,
+    statuses: SupervisorStatuses,
+    kill_tx: Sender<()>,
+}
+
+impl PipeSupervisor {
+    async fn run(self, mut kill_rx: Receiver<()>) -> Result<()> {
+        let mut tracker = RestartTracker::new(self.config.clone());
+
+        loop {
+            self.update(|s| {
+                s.state = SupervisorState::Starting;
+                s.next_restart_at = None;
+                s.health = None;
+            })
+            .await;
+
+            let started = Instant::now();
+            let exit = self.run_once(&mut kill_rx).await;
+            PipeManager::revoke_pipe_token(&self.pipe_tokens, &self.id).await;
+            cleanup_pipe_sandbox(&self.id).await;
+
+            let (failed, code, error) = match exit {
+                PipeExit::Killed => return Ok(()),
+                PipeExit::Exited {
+                    failed,
+                    code,
+                    error,
+                } => (failed, code, error),
+            };
+            self.update(|s| {
+                s.last_exit_code = code;
+                s.last_error = error.clone();
+            })
+            .await;
+
+            if !self.config.restart_policy.should_restart(failed) || !self.is_enabled().await {
+                self.update(|s| s.state = SupervisorState::Exited).await;
+                self.release_handle().await;
+                return match error {
+                    Some(error) if failed => Err(anyhow::anyhow!("pipe {} {}", self.id, error)),
+                    _ => Ok(()),
+                };
+            }
+
+            let Some(delay) = tracker.next_restart(Instant::now(), started.elapsed()) else {
+                error!(
+                    "[{}] pipe crashed {} times within {}s, not restarting it until it is enabled again",
+                    self.id, self.config.max_restarts, self.config.restart_window_secs
+                );
+                self.update(|s| s.state = SupervisorState::CrashLoop).await;
+                self.release_handle().await;
+                anyhow::bail!("pipe {} is crash looping", self.id);
+            };
+
+            let next_restart_at = Utc::now()
+                + chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::zero());
+            self.set_handle_state(None).await;
+            self.update(|s| {
+                s.state = SupervisorState::Backoff;
+                s.restarts += 1;
+                s.next_restart_at = Some(next_restart_at);
+            })
+            .await;
+            warn!("[{}] restarting pipe in {:?}", self.id, delay);
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = kill_rx.recv() => return Ok(()),
+            }
+        }
+    }
+
+    /// Start the pipe once and wait until it exits, fails its health checks or is stopped.
+    async fn run_once(&self, kill_rx: &mut Receiver<()>) -> PipeExit {
+        let id = &self.id;
+        let api = PipeApiAccess {
+            port: self.api_port,
+            token: PipeManager::issue_pipe_token(&self.pipe_tokens, id, self.scopes.clone()).await,
+        };
+
+        let (mut child, pipe_state) =
+            match screenpipe_core::run_pipe_sandboxed(id, self.screenpipe_dir.clone(), Some(api))
+                .await
+            {
+                Ok(started) => started,
+                Err(e) => {
+                    error!("[{}] failed to start pipe {}:", id, e);
+                    finish_pipe_run(id, PipeRunStatus::Failed, None, Some(e.to_string())).await;
+                    return PipeExit::failed(None, e.to_string());
+                }
+            };
+
+        self.set_handle_state(Some(pipe_state)).await;
+        match pipe_state {
+            PipeState::Port(port) => {
+                info!("started pipe: {} on port {}", id, port);
+            }
+            PipeState::Pid(pid) => {
+                info!("started pipe: {} on pid {}", id, pid);
+            }
+        }
+        self.update(|s| {
+            s.state = SupervisorState::Running;
+            s.started_at = Some(Utc::now());
+        })
+        .await;
+
+        let unhealthy = async {
+            match pipe_state {
+                PipeState::Port(port) => {
+                    wait_until_unhealthy(id, port, &self.config, &self.statuses).await
+                }
+                PipeState::Pid(_) => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            status = child.wait() => {
+                match status {
+                    Ok(status) if !status.success() => {
+                        warn!("pipe {} exited with status: {}", id, status);
+                        let error = format!("exited with status: {}", status);
+                        finish_pipe_run(id, PipeRunStatus::Failed, status.code(), Some(error.clone()))
+                            .await;
+                        PipeExit::failed(status.code(), error)
+                    }
+                    Err(e) => {
+                        error!("error waiting for pipe {}: {}", id, e);
+                        finish_pipe_run(id, PipeRunStatus::Failed, None, Some(e.to_string())).await;
+                        PipeExit::failed(None, format!("error waiting for pipe: {}", e))
+                    }
+                    Ok(status) => {
+                        finish_pipe_run(id, PipeRunStatus::Succeeded, status.code(), None).await;
+                        PipeExit::Exited {
+                            failed: false,
+                            code: status.code(),
+                            error: None,
+                        }
+                    }
+                }
+            }
+            reason = unhealthy => {
+                warn!("[{}] pipe {}, killing it", id, reason);
+                let _ = child.kill().await;
+                // The Next.js server may outlive bun, free its port before restarting
+                if let Err(e) = PipeManager::kill_pipe_processes(id, pipe_state).await {
+                    warn!("[{}] failed to kill unhealthy pipe: {}", id, e);
+                }
+                finish_pipe_run(id, PipeRunStatus::Failed, None, Some(reason.clone())).await;
+                PipeExit::failed(None, reason)
+            }
+            _ = kill_rx.recv() => {
+                // Kill received through channel
+                let _ = child.kill().await;
+                finish_pipe_run(id, PipeRunStatus::Stopped, None, None).await;
+                PipeExit::Killed
+            }
+        }
+    }
+
+    async fn is_enabled(&self) -> bool {
+        let config_path = self
+            .screenpipe_dir
+            .join("pipes")
+            .join(&self.id)
+            .join("pipe.json");
+        tokio::fs::read_to_string(config_path)
+            .await
+            .ok()
+            .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+            .and_then(|config| config.get("enabled").and_then(Value::as_bool))
+            .unwrap_or(false)
+    }
+
+    fn owns(&self, handle: &PipeHandle) -> bool {
+        handle.kill_tx.same_channel(&self.kill_tx)
+    }
+
+    /// Update the reported status, unless the pipe was stopped or handed to a newer supervisor.
+    async fn update(&self, f: impl FnOnce(&mut PipeSupervisorStatus)) {
+        let owned = self
+            .running_pipes
+            .read()
+            .await
+            .get(&self.id)
+            .is_some_and(|h| self.owns(h));
+        if owned {
+            update_status(&self.statuses, &self.id, f).await;
+        }
+    }
+
+    async fn set_handle_state(&self, state: Option<PipeState>) {
+        if let Some(handle) = self.running_pipes.write().await.get_mut(&self.id) {
+            if self.owns(handle) {
+                handle.state = state;
+            }
+        }
+    }
+
+    async fn release_handle(&self) {
+        let mut pipes = self.running_pipes.write().await;
+        if pipes.get(&self.id).is_some_and(|h| self.owns(h)) {
+            pipes.remove(&self.id);
+        }
+    }
+}
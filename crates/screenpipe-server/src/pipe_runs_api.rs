@@ -0,0 +1,119 @@
+//! Pipe run history endpoints.
+//!
+//! Runs are recorded by screenpipe-core when a pipe process starts or a cron
+//! route is called, see [`screenpipe_core::PipeRun`].
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::server::AppState;
+
+type ApiError = (StatusCode, Json<Value>);
+
+fn pipes_disabled() -> ApiError {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({
+            "error": "pipe functionality is disabled",
+            "success": false
+        })),
+    )
+}
+
+fn not_found(message: String) -> ApiError {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "error": message,
+            "success": false
+        })),
+    )
+}
+
+fn run_error(e: anyhow::Error) -> ApiError {
+    error!("failed to read pipe runs: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({
+            "error": format!("failed to read pipe runs: {}", e),
+            "success": false
+        })),
+    )
+}
+
+fn check_pipe(state: &AppState, pipe_id: &str) -> Result<(), ApiError> {
+    if !state.enable_pipe_manager {
+        return Err(pipes_disabled());
+    }
+    if !state.screenpipe_dir.join("pipes").join(pipe_id).is_dir() {
+        return Err(not_found(format!("pipe '{}' does not exist", pipe_id)));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRunsQuery {
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+fn default_limit() -> usize {
+    20
+}
+
+/// Runs of a pipe, newest first.
+pub async fn list_pipe_runs(
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+    Query(query): Query<ListRunsQuery>,
+) -> Result<Json<Value>, ApiError> {
+    check_pipe(&state, &pipe_id)?;
+    let runs = state
+        .pipe_manager
+        .list_pipe_runs(&pipe_id, query.limit, query.offset)
+        .await
+        .map_err(run_error)?;
+    Ok(Json(json!({
+        "data": runs,
+        "success": true
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RunLogQuery {
+    /// Only return the last lines of the log
+    pub tail: Option<usize>,
+}
+
+/// Captured stdout/stderr of a pipe process run.
+pub async fn get_pipe_run_log(
+    State(state): State<Arc<AppState>>,
+    Path((pipe_id, run_id)): Path<(String, String)>,
+    Query(query): Query<RunLogQuery>,
+) -> Result<Json<Value>, ApiError> {
+    check_pipe(&state, &pipe_id)?;
+    match state
+        .pipe_manager
+        .pipe_run_log(&pipe_id, &run_id, query.tail)
+        .await
+        .map_err(run_error)?
+    {
+        Some(log) => Ok(Json(json!({
+            "data": {
+                "run_id": run_id,
+                "log": log
+            },
+            "success": true
+        }))),
+        None => Err(not_found(format!("no log for run {}", run_id))),
+    }
+}
@@ -0,0 +1,377 @@
+//! Restarting pipes that crash.
+//!
+//! Every enabled pipe runs under a supervisor loop (see
+//! [`PipeManager::start_pipe_task`](crate::PipeManager::start_pipe_task)) tuned by an
+//! optional `supervisor` block in `pipe.json`. Pipes are not restarted unless the block
+//! sets a `restart_policy`:
+//!
+//! ```json
+//! "supervisor": {
+//!   "restart_policy": "on-failure",
+//!   "max_restarts": 5,
+//!   "restart_window_secs": 300,
+//!   "health_check_path": "/"
+//! }
+//! ```
+//!
+//! Restarts back off exponentially. A pipe restarting more than `max_restarts` times
+//! within `restart_window_secs` is considered crash looping and is left stopped until
+//! it is enabled again. Next.js pipes that may be restarted are also probed over HTTP on
+//! their port, a pipe failing `health_check_failures` probes in a row is killed and counts
+//! as crashed.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// A pipe that ran at least this long is considered healthy again, its backoff resets.
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    OnFailure,
+    Always,
+}
+
+impl RestartPolicy {
+    /// Whether a pipe that exited on its own should be started again.
+    pub fn should_restart(&self, failed: bool) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => failed,
+            RestartPolicy::Always => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SupervisorConfig {
+    pub restart_policy: RestartPolicy,
+    /// Restarts allowed within `restart_window_secs` before giving up
+    pub max_restarts: u32,
+    pub restart_window_secs: u64,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_secs: u64,
+    /// Path probed on Next.js pipes, relative to their port
+    pub health_check_path: String,
+    pub health_check_interval_secs: u64,
+    pub health_check_timeout_secs: u64,
+    /// Time given to a freshly started pipe before it is probed
+    pub health_check_grace_secs: u64,
+    /// Consecutive failed probes before the pipe is restarted, 0 disables probing
+    pub health_check_failures: u32,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        SupervisorConfig {
+            restart_policy: RestartPolicy::default(),
+            max_restarts: 5,
+            restart_window_secs: 300,
+            initial_backoff_ms: 1000,
+            max_backoff_secs: 60,
+            health_check_path: "/".to_string(),
+            health_check_interval_secs: 30,
+            health_check_timeout_secs: 5,
+            health_check_grace_secs: 60,
+            health_check_failures: 3,
+        }
+    }
+}
+
+impl SupervisorConfig {
+    /// Parse the `supervisor` block of a pipe config, defaults if it is missing or invalid.
+    pub fn from_pipe_config(config: &Value) -> Self {
+        match config.get("supervisor") {
+            None | Some(Value::Null) => SupervisorConfig::default(),
+            Some(value) => serde_json::from_value(value.clone()).unwrap_or_else(|e| {
+                warn!(
+                    "invalid supervisor config in pipe.json, using defaults: {}",
+                    e
+                );
+                SupervisorConfig::default()
+            }),
+        }
+    }
+
+    pub async fn load(pipe_dir: &Path) -> Self {
+        let config = tokio::fs::read_to_string(pipe_dir.join("pipe.json"))
+            .await
+            .ok()
+            .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+            .unwrap_or(Value::Null);
+        Self::from_pipe_config(&config)
+    }
+
+    /// Delay before the `attempt`th consecutive restart, doubling from `initial_backoff_ms`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let max = Duration::from_secs(self.max_backoff_secs);
+        let delay = Duration::from_millis(self.initial_backoff_ms)
+            .saturating_mul(2u32.saturating_pow(attempt.min(31)));
+        delay.min(max)
+    }
+
+    /// Probing only pays off when the killed pipe is started again.
+    fn health_checks_enabled(&self) -> bool {
+        self.health_check_failures > 0 && self.restart_policy != RestartPolicy::Never
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SupervisorState {
+    Starting,
+    Running,
+    /// Waiting to restart after a crash
+    Backoff,
+    /// Restarted too often, left stopped until enabled again
+    CrashLoop,
+    /// Exited and not restarted by its policy
+    Exited,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipeHealth {
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// Supervisor view of a pipe, reported in [`PipeInfo`](crate::pipe_manager::PipeInfo).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipeSupervisorStatus {
+    pub state: SupervisorState,
+    pub restart_policy: RestartPolicy,
+    /// Restarts since the pipe was enabled
+    pub restarts: u32,
+    pub started_at: Option<DateTime<Utc>>,
+    pub last_exit_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_restart_at: Option<DateTime<Utc>>,
+    /// Only set for Next.js pipes
+    pub health: Option<PipeHealth>,
+}
+
+impl PipeSupervisorStatus {
+    pub fn new(restart_policy: RestartPolicy) -> Self {
+        PipeSupervisorStatus {
+            state: SupervisorState::Starting,
+            restart_policy,
+            restarts: 0,
+            started_at: None,
+            last_exit_code: None,
+            last_error: None,
+            next_restart_at: None,
+            health: None,
+        }
+    }
+}
+
+pub(crate) type SupervisorStatuses = Arc<RwLock<HashMap<String, PipeSupervisorStatus>>>;
+
+pub(crate) async fn update_status(
+    statuses: &SupervisorStatuses,
+    pipe_id: &str,
+    f: impl FnOnce(&mut PipeSupervisorStatus),
+) {
+    if let Some(status) = statuses.write().await.get_mut(pipe_id) {
+        f(status);
+    }
+}
+
+/// Restart bookkeeping of one supervised pipe.
+pub(crate) struct RestartTracker {
+    config: SupervisorConfig,
+    restarts: VecDeque<Instant>,
+    attempt: u32,
+}
+
+impl RestartTracker {
+    pub fn new(config: SupervisorConfig) -> Self {
+        RestartTracker {
+            config,
+            restarts: VecDeque::new(),
+            attempt: 0,
+        }
+    }
+
+    /// Delay before restarting a pipe that ran for `uptime`, `None` once it is crash looping.
+    pub fn next_restart(&mut self, now: Instant, uptime: Duration) -> Option<Duration> {
+        if uptime >= STABLE_RUN {
+            self.attempt = 0;
+        }
+
+        let window = Duration::from_secs(self.config.restart_window_secs);
+        while self
+            .restarts
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > window)
+        {
+            self.restarts.pop_front();
+        }
+        if self.restarts.len() >= self.config.max_restarts as usize {
+            return None;
+        }
+        self.restarts.push_back(now);
+
+        let delay = self.config.backoff(self.attempt);
+        self.attempt += 1;
+        Some(delay)
+    }
+}
+
+/// Probe a Next.js pipe until it fails enough checks in a row, returning why.
+pub(crate) async fn wait_until_unhealthy(
+    pipe_id: &str,
+    port: u16,
+    config: &SupervisorConfig,
+    statuses: &SupervisorStatuses,
+) -> String {
+    if !config.health_checks_enabled() {
+        return std::future::pending().await;
+    }
+
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.health_check_timeout_secs))
+        .no_proxy()
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("[{}] health checks disabled: {}", pipe_id, e);
+            return std::future::pending().await;
+        }
+    };
+    let path = if config.health_check_path.starts_with('/') {
+        config.health_check_path.clone()
+    } else {
+        format!("/{}", config.health_check_path)
+    };
+    let url = format!("http://localhost:{}{}", port, path);
+
+    tokio::time::sleep(Duration::from_secs(config.health_check_grace_secs)).await;
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        config.health_check_interval_secs.max(1),
+    ));
+    let mut health = PipeHealth::default();
+
+    loop {
+        interval.tick().await;
+
+        let error = match client.get(&url).send().await {
+            // Any answer below 500 means the server is up, pipes may not serve `/`
+            Ok(response) if !response.status().is_server_error() => None,
+            Ok(response) => Some(format!("{} returned {}", url, response.status())),
+            Err(e) => Some(format!("{} is unreachable: {}", url, e)),
+        };
+        health.last_checked_at = Some(Utc::now());
+        match error {
+            None => {
+                health.healthy = true;
+                health.consecutive_failures = 0;
+                health.last_error = None;
+            }
+            Some(error) => {
+                debug!("[{}] health check failed: {}", pipe_id, error);
+                health.healthy = false;
+                health.consecutive_failures += 1;
+                health.last_error = Some(error);
+            }
+        }
+        let snapshot = health.clone();
+        update_status(statuses, pipe_id, |s| s.health = Some(snapshot)).await;
+
+        if health.consecutive_failures >= config.health_check_failures {
+            return format!(
+                "failed {} health checks in a row: {}",
+                health.consecutive_failures,
+                health.last_error.unwrap_or_default()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_supervisor_config_from_pipe_config() {
+        let config = SupervisorConfig::from_pipe_config(&json!({
+            "enabled": true,
+            "supervisor": {"restart_policy": "always", "max_restarts": 2}
+        }));
+        assert_eq!(config.restart_policy, RestartPolicy::Always);
+        assert_eq!(config.max_restarts, 2);
+        assert_eq!(config.health_check_path, "/");
+
+        assert_eq!(
+            SupervisorConfig::from_pipe_config(&json!({"enabled": true})),
+            SupervisorConfig::default()
+        );
+        assert_eq!(
+            SupervisorConfig::default().restart_policy,
+            RestartPolicy::Never
+        );
+        assert_eq!(
+            SupervisorConfig::from_pipe_config(
+                &json!({"supervisor": {"restart_policy": "sometimes"}})
+            ),
+            SupervisorConfig::default()
+        );
+
+        assert!(RestartPolicy::OnFailure.should_restart(true));
+        assert!(!RestartPolicy::OnFailure.should_restart(false));
+        assert!(RestartPolicy::Always.should_restart(false));
+        assert!(!RestartPolicy::Never.should_restart(true));
+    }
+
+    #[test]
+    fn test_restart_backoff_and_crash_loop() {
+        let config = SupervisorConfig {
+            max_restarts: 3,
+            restart_window_secs: 60,
+            initial_backoff_ms: 1000,
+            max_backoff_secs: 3,
+            ..Default::default()
+        };
+        let mut tracker = RestartTracker::new(config);
+        let start = Instant::now();
+        let crash = Duration::from_secs(1);
+
+        assert_eq!(
+            tracker.next_restart(start, crash),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            tracker.next_restart(start, crash),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            tracker.next_restart(start, crash),
+            Some(Duration::from_secs(3))
+        );
+        // Fourth crash within the window trips the breaker
+        assert_eq!(tracker.next_restart(start, crash), None);
+
+        // Once the window has passed, a pipe that ran stably starts over
+        let later = start + Duration::from_secs(120);
+        assert_eq!(
+            tracker.next_restart(later, STABLE_RUN),
+            Some(Duration::from_secs(1))
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Data retention.
+//!
+//! Periodically applies the configured [`RetentionPolicy`]: expired rows are pruned in
+//! the database (FTS entries follow via the delete triggers) and the mp4/audio files
+//! they pointed to are removed from disk afterwards.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use screenpipe_db::{DatabaseManager, RetentionPolicy, RetentionReport};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+/// Delay before the first pass so pruning doesn't compete with startup.
+const INITIAL_DELAY: Duration = Duration::from_secs(60);
+
+/// Run a single retention pass. With `dry_run` nothing is deleted and the report
+/// describes what a real pass would remove.
+pub async fn run_retention(
+    db: &DatabaseManager,
+    policy: &RetentionPolicy,
+    dry_run: bool,
+) -> anyhow::Result<RetentionReport> {
+    let report = db
+        .apply_retention_policy(policy, Utc::now(), dry_run)
+        .await?;
+
+    if !dry_run {
+        // Rows are gone at this point, so a crash here leaves orphaned files
+        // rather than rows pointing at missing media.
+        for path in report.video_files.iter().chain(report.audio_files.iter()) {
+            remove_media_file(path).await;
+        }
+    }
+
+    Ok(report)
+}
+
+async fn remove_media_file(path: &str) {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => debug!("retention: removed {}", path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("retention: failed to remove {}: {}", path, e),
+    }
+}
+
+/// Spawn the background retention task. Does nothing useful for an empty policy,
+/// so callers should only start it when [`RetentionPolicy::is_empty`] is false.
+pub fn start_retention_task(
+    db: Arc<DatabaseManager>,
+    policy: RetentionPolicy,
+    interval: Duration,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        info!(
+            "starting retention task (every {:?}): {:?}",
+            interval, policy
+        );
+        let mut ticker =
+            tokio::time::interval_at(tokio::time::Instant::now() + INITIAL_DELAY, interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    match run_retention(&db, &policy, false).await {
+                        Ok(report) => info!(
+                            "retention: removed {} frames, {} ocr rows, {} video files, {} audio chunks, {} ui events",
+                            report.frames,
+                            report.ocr_text,
+                            report.video_files.len(),
+                            report.audio_chunks,
+                            report.ui_events
+                        ),
+                        Err(e) => error!("retention pass failed: {}", e),
+                    }
+                }
+                _ = shutdown_rx.recv() => {
+                    debug!("retention task shutting down");
+                    break;
+                }
+            }
+        }
+    })
+}
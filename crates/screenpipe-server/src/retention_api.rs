@@ -0,0 +1,64 @@
+//! Data retention API endpoints.
+//!
+//! Only a dry-run report is exposed: deletions are done by the background
+//! retention task or explicitly via `screenpipe retention run`.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use screenpipe_db::RetentionReport;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::retention::run_retention;
+use crate::server::AppState;
+
+/// Optional overrides of the configured global retention windows, to preview
+/// the effect of a policy before enabling it.
+#[derive(Debug, Default, Deserialize)]
+pub struct RetentionReportQuery {
+    pub video_days: Option<u32>,
+    pub ocr_days: Option<u32>,
+    pub audio_days: Option<u32>,
+    pub ui_events_days: Option<u32>,
+}
+
+/// Report what the retention policy would delete right now, without deleting anything.
+pub async fn retention_report(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RetentionReportQuery>,
+) -> Result<Json<RetentionReport>, (StatusCode, Json<Value>)> {
+    let windows = [
+        query.video_days,
+        query.ocr_days,
+        query.audio_days,
+        query.ui_events_days,
+    ];
+    if windows.contains(&Some(0)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "retention windows are at least 1 day"})),
+        ));
+    }
+
+    let mut policy = (*state.retention_policy).clone();
+    policy.video_days = query.video_days.or(policy.video_days);
+    policy.ocr_days = query.ocr_days.or(policy.ocr_days);
+    policy.audio_days = query.audio_days.or(policy.audio_days);
+    policy.ui_events_days = query.ui_events_days.or(policy.ui_events_days);
+
+    run_retention(&state.db, &policy, true)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("failed to compute retention report: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("failed to compute retention report: {}", e)})),
+            )
+        })
+}
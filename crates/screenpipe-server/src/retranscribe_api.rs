@@ -0,0 +1,200 @@
+//! Re-transcription API endpoints.
+//!
+//! One job runs at a time, with the languages, API keys, VAD and PII removal
+//! the audio is recorded with. Starting a new job replaces a finished one.
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use screenpipe_audio::retranscribe::{
+    create_retranscription_worker, RetranscriptionCommand, RetranscriptionConfig,
+    RetranscriptionStatus,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, Mutex};
+use tracing::{error, info};
+
+use crate::cli::CliAudioTranscriptionEngine;
+use crate::server::AppState;
+
+/// The re-transcription job started through the API, if any.
+pub struct RetranscriptionJob {
+    pub engine: String,
+    pub since: DateTime<Utc>,
+    pub until: Option<DateTime<Utc>>,
+    cmd_tx: mpsc::Sender<RetranscriptionCommand>,
+    status_rx: watch::Receiver<RetranscriptionStatus>,
+}
+
+/// Thread-safe container for the optional re-transcription job
+pub type RetranscriptionState = Arc<Mutex<Option<RetranscriptionJob>>>;
+
+/// Create a new empty re-transcription state container
+pub fn new_retranscription_state() -> RetranscriptionState {
+    Arc::new(Mutex::new(None))
+}
+
+/// Request to start a re-transcription job.
+#[derive(Debug, Deserialize)]
+pub struct RetranscribeRequest {
+    /// Re-transcribe the audio recorded from then
+    pub since: DateTime<Utc>,
+    /// Until then, default to when the job starts
+    pub until: Option<DateTime<Utc>>,
+    /// Engine as named by the CLI, e.g. `whisper-large-v3-turbo`
+    #[serde(default = "default_engine")]
+    pub engine: String,
+}
+
+fn default_engine() -> String {
+    "whisper-large-v3-turbo".to_string()
+}
+
+/// Status of the re-transcription job.
+#[derive(Debug, Serialize)]
+pub struct RetranscribeStatusResponse {
+    pub engine: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub status: RetranscriptionStatus,
+}
+
+impl RetranscribeStatusResponse {
+    fn from_job(job: Option<&RetranscriptionJob>) -> Self {
+        match job {
+            Some(job) => Self {
+                engine: Some(job.engine.clone()),
+                since: Some(job.since),
+                until: job.until,
+                status: job.status_rx.borrow().clone(),
+            },
+            None => Self {
+                engine: None,
+                since: None,
+                until: None,
+                status: RetranscriptionStatus::NotStarted,
+            },
+        }
+    }
+}
+
+fn error_response(status: StatusCode, message: String) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({ "error": message })))
+}
+
+/// Start re-transcribing the audio recorded in a time range.
+pub async fn start_retranscription(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<RetranscribeRequest>,
+) -> Result<Json<RetranscribeStatusResponse>, (StatusCode, Json<Value>)> {
+    let engine = CliAudioTranscriptionEngine::from_str(&request.engine, true).map_err(|_| {
+        error_response(
+            StatusCode::BAD_REQUEST,
+            format!("unknown transcription engine '{}'", request.engine),
+        )
+    })?;
+    if let Some(until) = request.until {
+        if until < request.since {
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                "until must be after since".to_string(),
+            ));
+        }
+    }
+
+    let mut job = state.retranscription.lock().await;
+    if let Some(running) = job.as_ref() {
+        if !running.status_rx.borrow().is_finished() {
+            return Err(error_response(
+                StatusCode::CONFLICT,
+                "a re-transcription is already in progress".to_string(),
+            ));
+        }
+    }
+
+    let config = RetranscriptionConfig::from_options(
+        &state.audio_manager.options().await,
+        engine.into(),
+        request.since,
+        request.until,
+    );
+    let engine_name = config.transcription.engine.to_string();
+    let (cmd_tx, status_rx, _handle) = create_retranscription_worker(state.db.clone(), config);
+    cmd_tx
+        .send(RetranscriptionCommand::Start)
+        .await
+        .map_err(|e| {
+            error!("failed to start re-transcription: {}", e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to start re-transcription: {}", e),
+            )
+        })?;
+    info!(
+        "started re-transcription from {} with {}",
+        request.since, engine_name
+    );
+
+    *job = Some(RetranscriptionJob {
+        engine: engine_name,
+        since: request.since,
+        until: request.until,
+        cmd_tx,
+        status_rx,
+    });
+    Ok(Json(RetranscribeStatusResponse::from_job(job.as_ref())))
+}
+
+/// Get the progress of the re-transcription job.
+pub async fn retranscription_status(
+    State(state): State<Arc<AppState>>,
+) -> Json<RetranscribeStatusResponse> {
+    let job = state.retranscription.lock().await;
+    Json(RetranscribeStatusResponse::from_job(job.as_ref()))
+}
+
+/// Pause the re-transcription job after the chunk being processed.
+pub async fn pause_retranscription(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<RetranscribeStatusResponse>, (StatusCode, Json<Value>)> {
+    send_command(&state, RetranscriptionCommand::Pause).await
+}
+
+/// Resume a paused re-transcription job.
+pub async fn resume_retranscription(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<RetranscribeStatusResponse>, (StatusCode, Json<Value>)> {
+    send_command(&state, RetranscriptionCommand::Start).await
+}
+
+/// Stop the re-transcription job, it cannot be resumed.
+pub async fn stop_retranscription(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<RetranscribeStatusResponse>, (StatusCode, Json<Value>)> {
+    send_command(&state, RetranscriptionCommand::Stop).await
+}
+
+async fn send_command(
+    state: &AppState,
+    cmd: RetranscriptionCommand,
+) -> Result<Json<RetranscribeStatusResponse>, (StatusCode, Json<Value>)> {
+    let job = state.retranscription.lock().await;
+    let running = job
+        .as_ref()
+        .filter(|job| !job.status_rx.borrow().is_finished())
+        .ok_or_else(|| {
+            error_response(
+                StatusCode::NOT_FOUND,
+                "no re-transcription in progress".to_string(),
+            )
+        })?;
+    running.cmd_tx.send(cmd).await.map_err(|e| {
+        error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to send command to re-transcription: {}", e),
+        )
+    })?;
+    Ok(Json(RetranscribeStatusResponse::from_job(job.as_ref())))
+}
@@ -4,7 +4,7 @@ use axum::{
         ws::{Message, WebSocket, WebSocketUpgrade},
         Json, Path, Query, State,
     },
-    http::StatusCode,
+    http::{HeaderValue, StatusCode},
     response::{IntoResponse, Json as JsonResponse, Response},
     routing::get,
     serve, Router,
@@ -13,8 +13,9 @@ use oasgen::{oasgen, OaSchema, Server};
 
 use chrono::TimeZone;
 use screenpipe_db::{
-    ContentType, DatabaseManager, FrameData, Order, SearchMatch, SearchResult, Speaker,
-    TagContentType, TextPosition,
+    AudioTranscriptionWord, ContentType, DatabaseManager, FrameData, Order, RawSqlError,
+    RawSqlOptions, RetentionPolicy, SearchMatch, SearchResult, Speaker, SpeakerClusteringConfig,
+    TagContentType, TextPosition, DEFAULT_RAW_SQL_ROWS, DEFAULT_RAW_SQL_TIMEOUT, MAX_RAW_SQL_ROWS,
 };
 
 use tokio_util::io::ReaderStream;
@@ -26,7 +27,9 @@ use futures::{
     SinkExt, StreamExt,
 };
 use image::{GenericImageView, ImageFormat};
-use screenpipe_events::{send_event, subscribe_to_all_events, Event as ScreenpipeEvent};
+use screenpipe_events::{
+    send_event, subscribe_to_all_events, subscribe_to_event, Event as ScreenpipeEvent,
+};
 
 use crate::{
     analytics,
@@ -42,15 +45,25 @@ use crate::{
 };
 use chrono::{DateTime, Utc};
 use screenpipe_audio::{
-    audio_manager::AudioManager,
+    audio_manager::{AudioManager, DeviceAudioSettings},
     core::device::{
         default_input_device, default_output_device, list_audio_devices, AudioDevice, DeviceType,
     },
+    transcription::deepgram::streaming::RealtimeTranscriptionEvent,
 };
 use screenpipe_core::pii_removal::detect_pii_regions;
 use screenpipe_core::sync::SyncServiceHandle;
 use tracing::{debug, error, info, warn};
 
+use crate::auth::{self, AuthConfig};
+use crate::hybrid_search_api;
+use crate::layout_api;
+use crate::meetings_api;
+use crate::pipe_runs_api;
+use crate::retention_api;
+use crate::retranscribe_api::{self, RetranscriptionState};
+use crate::speaker_clustering_api;
+use crate::speaker_enrollment_api;
 use crate::sync_api::{self, SyncState};
 
 use screenpipe_vision::monitor::{get_monitor_by_id, list_monitors, list_monitors_detailed, MonitorListError};
@@ -88,7 +101,7 @@ use tower_http::{cors::CorsLayer, trace::DefaultMakeSpan};
 
 use std::str::FromStr;
 
-use crate::text_embeds::generate_embedding;
+use crate::text_embeds::{embedding_model, generate_embedding};
 
 use std::collections::HashMap;
 // or sentry::protocol::Uuid depending on which you want to use
@@ -125,6 +138,14 @@ pub struct AppState {
     pub video_quality: String,
     /// API request counter for usage analytics
     pub api_request_count: Arc<AtomicUsize>,
+    /// Data retention policy (empty = keep everything)
+    pub retention_policy: Arc<RetentionPolicy>,
+    /// API token auth and CORS settings
+    pub auth: Arc<AuthConfig>,
+    /// Re-transcription job (started via /audio/retranscribe)
+    pub retranscription: RetranscriptionState,
+    /// Defaults of /speakers/cluster
+    pub speaker_clustering: SpeakerClusteringConfig,
 }
 
 // Update the SearchQuery struct
@@ -283,6 +304,9 @@ pub struct AudioContent {
     pub speaker: Option<Speaker>,
     pub start_time: Option<f64>,
     pub end_time: Option<f64>,
+    pub transcription_id: i64,
+    /// Seconds into the audio chunk where the query was said
+    pub seek_time: Option<f64>,
 }
 
 #[derive(OaSchema, Serialize, Deserialize, Debug, Clone)]
@@ -406,6 +430,87 @@ fn compute_search_cache_key(query: &SearchQuery) -> u64 {
     hasher.finish()
 }
 
+fn is_screenpipe_app(app_name: &str) -> bool {
+    app_name.to_lowercase().contains("screenpipe")
+}
+
+/// Whether a result was captured from screenpipe itself, which is hidden at display time.
+pub(crate) fn is_screenpipe_result(result: &SearchResult) -> bool {
+    match result {
+        SearchResult::OCR(ocr) => is_screenpipe_app(&ocr.app_name),
+        SearchResult::Audio(_) => false, // Audio doesn't have app_name
+        SearchResult::UI(ui) => is_screenpipe_app(&ui.app_name),
+        SearchResult::Input(input) => input
+            .app_name
+            .as_ref()
+            .is_some_and(|app| is_screenpipe_app(app)),
+    }
+}
+
+impl From<&SearchResult> for ContentItem {
+    fn from(result: &SearchResult) -> Self {
+        match result {
+            SearchResult::OCR(ocr) => ContentItem::OCR(OCRContent {
+                frame_id: ocr.frame_id,
+                text: ocr.ocr_text.clone(),
+                timestamp: ocr.timestamp,
+                file_path: ocr.file_path.clone(),
+                offset_index: ocr.offset_index,
+                app_name: ocr.app_name.clone(),
+                window_name: ocr.window_name.clone(),
+                tags: ocr.tags.clone(),
+                frame: None,
+                frame_name: Some(ocr.frame_name.clone()),
+                browser_url: ocr.browser_url.clone(),
+                focused: ocr.focused,
+                device_name: ocr.device_name.clone(),
+            }),
+            SearchResult::Audio(audio) => ContentItem::Audio(AudioContent {
+                chunk_id: audio.audio_chunk_id,
+                transcription: audio.transcription.clone(),
+                timestamp: audio.timestamp,
+                file_path: audio.file_path.clone(),
+                offset_index: audio.offset_index,
+                tags: audio.tags.clone(),
+                device_name: audio.device_name.clone(),
+                device_type: audio.device_type.clone().into(),
+                speaker: audio.speaker.clone(),
+                start_time: audio.start_time,
+                end_time: audio.end_time,
+                transcription_id: audio.transcription_id,
+                seek_time: audio.seek_time,
+            }),
+            SearchResult::UI(ui) => ContentItem::UI(UiContent {
+                id: ui.id,
+                text: ui.text.clone(),
+                timestamp: ui.timestamp,
+                app_name: ui.app_name.clone(),
+                window_name: ui.window_name.clone(),
+                initial_traversal_at: ui.initial_traversal_at,
+                file_path: ui.file_path.clone(),
+                offset_index: ui.offset_index,
+                frame_name: ui.frame_name.clone(),
+                browser_url: ui.browser_url.clone(),
+            }),
+            SearchResult::Input(input) => ContentItem::Input(InputContent {
+                id: input.id,
+                timestamp: input.timestamp,
+                event_type: input.event_type.to_string(),
+                app_name: input.app_name.clone(),
+                window_title: input.window_title.clone(),
+                browser_url: input.browser_url.clone(),
+                text_content: input.text_content.clone(),
+                x: input.x,
+                y: input.y,
+                key_code: input.key_code,
+                modifiers: input.modifiers,
+                element_role: input.element.as_ref().and_then(|e| e.role.clone()),
+                element_name: input.element.as_ref().and_then(|e| e.name.clone()),
+            }),
+        }
+    }
+}
+
 // Update the search function
 #[oasgen]
 pub(crate) async fn search(
@@ -487,79 +592,11 @@ pub(crate) async fn search(
         )
     })?;
 
-    // Helper to check if app name contains "screenpipe" (case insensitive)
-    let is_screenpipe_app =
-        |app_name: &str| -> bool { app_name.to_lowercase().contains("screenpipe") };
-
     let mut content_items: Vec<ContentItem> = results
         .iter()
         // Filter out screenpipe results at display time
-        .filter(|result| match result {
-            SearchResult::OCR(ocr) => !is_screenpipe_app(&ocr.app_name),
-            SearchResult::Audio(_) => true, // Audio doesn't have app_name
-            SearchResult::UI(ui) => !is_screenpipe_app(&ui.app_name),
-            SearchResult::Input(input) => input
-                .app_name
-                .as_ref()
-                .is_none_or(|app| !is_screenpipe_app(app)),
-        })
-        .map(|result| match result {
-            SearchResult::OCR(ocr) => ContentItem::OCR(OCRContent {
-                frame_id: ocr.frame_id,
-                text: ocr.ocr_text.clone(),
-                timestamp: ocr.timestamp,
-                file_path: ocr.file_path.clone(),
-                offset_index: ocr.offset_index,
-                app_name: ocr.app_name.clone(),
-                window_name: ocr.window_name.clone(),
-                tags: ocr.tags.clone(),
-                frame: None,
-                frame_name: Some(ocr.frame_name.clone()),
-                browser_url: ocr.browser_url.clone(),
-                focused: ocr.focused,
-                device_name: ocr.device_name.clone(),
-            }),
-            SearchResult::Audio(audio) => ContentItem::Audio(AudioContent {
-                chunk_id: audio.audio_chunk_id,
-                transcription: audio.transcription.clone(),
-                timestamp: audio.timestamp,
-                file_path: audio.file_path.clone(),
-                offset_index: audio.offset_index,
-                tags: audio.tags.clone(),
-                device_name: audio.device_name.clone(),
-                device_type: audio.device_type.clone().into(),
-                speaker: audio.speaker.clone(),
-                start_time: audio.start_time,
-                end_time: audio.end_time,
-            }),
-            SearchResult::UI(ui) => ContentItem::UI(UiContent {
-                id: ui.id,
-                text: ui.text.clone(),
-                timestamp: ui.timestamp,
-                app_name: ui.app_name.clone(),
-                window_name: ui.window_name.clone(),
-                initial_traversal_at: ui.initial_traversal_at,
-                file_path: ui.file_path.clone(),
-                offset_index: ui.offset_index,
-                frame_name: ui.frame_name.clone(),
-                browser_url: ui.browser_url.clone(),
-            }),
-            SearchResult::Input(input) => ContentItem::Input(InputContent {
-                id: input.id,
-                timestamp: input.timestamp,
-                event_type: input.event_type.to_string(),
-                app_name: input.app_name.clone(),
-                window_title: input.window_title.clone(),
-                browser_url: input.browser_url.clone(),
-                text_content: input.text_content.clone(),
-                x: input.x,
-                y: input.y,
-                key_code: input.key_code,
-                modifiers: input.modifiers,
-                element_role: input.element.as_ref().and_then(|e| e.role.clone()),
-                element_name: input.element.as_ref().and_then(|e| e.name.clone()),
-            }),
-        })
+        .filter(|result| !is_screenpipe_result(result))
+        .map(ContentItem::from)
         .collect();
 
     if query.include_frames {
@@ -1040,13 +1077,17 @@ async fn download_pipe_handler(
     }
     debug!("Downloading pipe: {}", payload.url);
     match state.pipe_manager.download_pipe(&payload.url).await {
-        Ok(pipe_dir) => Ok(JsonResponse(json!({
-            "data": {
-                "pipe_id": pipe_dir,
-                "message": "pipe downloaded successfully"
-            },
-            "success": true
-        }))),
+        Ok(pipe_id) => {
+            let permissions = state.pipe_manager.pipe_permissions(&pipe_id).await.ok();
+            Ok(JsonResponse(json!({
+                "data": {
+                    "pipe_id": pipe_id,
+                    "message": "pipe downloaded successfully",
+                    "permissions": permissions
+                },
+                "success": true
+            })))
+        }
         Err(e) => {
             error!("Failed to download pipe: {}", e);
             Err((
@@ -1320,6 +1361,9 @@ pub struct SCServer {
     use_pii_removal: bool,
     sync_handle: Option<Arc<SyncServiceHandle>>,
     video_quality: String,
+    retention_policy: RetentionPolicy,
+    auth: AuthConfig,
+    speaker_clustering: SpeakerClusteringConfig,
 }
 
 impl SCServer {
@@ -1348,6 +1392,9 @@ impl SCServer {
             use_pii_removal,
             sync_handle: None,
             video_quality,
+            retention_policy: RetentionPolicy::default(),
+            auth: AuthConfig::default(),
+            speaker_clustering: SpeakerClusteringConfig::default(),
         }
     }
 
@@ -1363,6 +1410,24 @@ impl SCServer {
         self
     }
 
+    /// Set the data retention policy used by the /retention endpoints
+    pub fn with_retention_policy(mut self, policy: RetentionPolicy) -> Self {
+        self.retention_policy = policy;
+        self
+    }
+
+    /// Set the defaults of the /speakers/cluster endpoint
+    pub fn with_speaker_clustering(mut self, config: SpeakerClusteringConfig) -> Self {
+        self.speaker_clustering = config;
+        self
+    }
+
+    /// Enable API token auth and/or restrict CORS origins
+    pub fn with_auth(mut self, auth: AuthConfig) -> Self {
+        self.auth = auth;
+        self
+    }
+
     pub async fn start(self, enable_frame_cache: bool) -> Result<(), std::io::Error> {
         // Create the OpenAPI server
         let app = self.create_router(enable_frame_cache).await;
@@ -1467,10 +1532,14 @@ impl SCServer {
             sync_state: sync_api::new_sync_state(),
             video_quality: self.video_quality.clone(),
             api_request_count: api_request_count.clone(),
+            retention_policy: Arc::new(self.retention_policy.clone()),
+            auth: Arc::new(self.auth.clone()),
+            retranscription: retranscribe_api::new_retranscription_state(),
+            speaker_clustering: self.speaker_clustering.clone(),
         });
 
         let cors = CorsLayer::new()
-            .allow_origin(Any)
+            .allow_origin(self.auth.cors_allow_origin())
             .allow_methods(Any)
             .allow_headers(Any)
             .expose_headers([
@@ -1495,6 +1564,10 @@ impl SCServer {
             .post("/pipes/purge", purge_pipe_handler)
             .get("/frames/:frame_id", get_frame_data)
             .get("/frames/:frame_id/ocr", get_frame_ocr_data)
+            .get(
+                "/audio/transcriptions/:transcription_id/words",
+                get_audio_transcription_words,
+            )
             .get("/frames/next-valid", get_next_valid_frame)
             .get("/health", health_check)
             .post("/raw_sql", execute_raw_sql)
@@ -1536,6 +1609,77 @@ impl SCServer {
                 "/sync/download",
                 axum::routing::post(sync_api::sync_download),
             )
+            // Hybrid keyword + embedding search
+            .route("/search/hybrid", get(hybrid_search_api::hybrid_search))
+            // Paragraphs, tables and code blocks read on screen
+            .route("/search/layout", get(layout_api::search_layout))
+            .route(
+                "/frames/:frame_id/layout",
+                get(layout_api::get_frame_layout),
+            )
+            // Embedding index
+            .route(
+                "/embeddings/status",
+                get(crate::embedding::index_api::embedding_index_status),
+            )
+            // Pipe run history
+            .route("/pipes/:id/runs", get(pipe_runs_api::list_pipe_runs))
+            .route(
+                "/pipes/:id/runs/:run_id/logs",
+                get(pipe_runs_api::get_pipe_run_log),
+            )
+            // Re-transcription of recorded audio
+            .route(
+                "/audio/retranscribe",
+                axum::routing::post(retranscribe_api::start_retranscription),
+            )
+            .route(
+                "/audio/retranscribe/status",
+                get(retranscribe_api::retranscription_status),
+            )
+            .route(
+                "/audio/retranscribe/pause",
+                axum::routing::post(retranscribe_api::pause_retranscription),
+            )
+            .route(
+                "/audio/retranscribe/resume",
+                axum::routing::post(retranscribe_api::resume_retranscription),
+            )
+            .route(
+                "/audio/retranscribe/stop",
+                axum::routing::post(retranscribe_api::stop_retranscription),
+            )
+            // Speaker re-clustering
+            .route(
+                "/speakers/cluster",
+                axum::routing::post(speaker_clustering_api::cluster_speakers),
+            )
+            .route(
+                "/speakers/merge-proposals",
+                get(speaker_clustering_api::list_merge_proposals),
+            )
+            .route(
+                "/speakers/merge-proposals/resolve",
+                axum::routing::post(speaker_clustering_api::resolve_merge_proposals),
+            )
+            // Speaker voice enrollment
+            .route(
+                "/speakers/enroll",
+                axum::routing::post(speaker_enrollment_api::enroll_speaker),
+            )
+            .route(
+                "/speakers/enrolled",
+                get(speaker_enrollment_api::list_enrolled_speakers),
+            )
+            // Data retention
+            .route("/retention/report", get(retention_api::retention_report))
+            // Meetings
+            .route("/meetings", get(meetings_api::list_meetings))
+            .route("/meetings/:id", get(meetings_api::get_meeting))
+            .route(
+                "/meetings/:id/transcript",
+                get(meetings_api::get_meeting_transcript),
+            )
             // Vision status endpoint (not in OpenAPI spec to avoid oasgen registration issues)
             .route("/vision/status", get(api_vision_status));
 
@@ -1553,15 +1697,22 @@ impl SCServer {
             .route("/stream/frames", get(stream_frames_handler))
             .route("/ws/events", get(ws_events_handler))
             .route("/ws/health", get(ws_health_handler))
+            .route("/ws/transcripts", get(ws_transcripts_handler))
             .route("/frames/export", get(handle_video_export_ws))
             .with_state(app_state.clone())
-            .layer(axum::middleware::from_fn(move |req: axum::extract::Request, next: axum::middleware::Next| {
-                let counter = app_state.api_request_count.clone();
-                async move {
-                    counter.fetch_add(1, Ordering::Relaxed);
-                    next.run(req).await
-                }
-            }))
+            .layer(axum::middleware::from_fn_with_state(
+                app_state.clone(),
+                auth::require_auth,
+            ))
+            .layer(axum::middleware::from_fn(
+                move |req: axum::extract::Request, next: axum::middleware::Next| {
+                    let counter = app_state.api_request_count.clone();
+                    async move {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                        next.run(req).await
+                    }
+                },
+            ))
             .layer(cors)
             .layer(TraceLayer::new_for_http().make_span_with(DefaultMakeSpan::default()))
     }
@@ -1603,21 +1754,67 @@ async fn validate_media_handler(
 #[derive(OaSchema, Deserialize)]
 struct RawSqlQuery {
     query: String,
+    /// Rows per page (max 10000). Defaults to 1000 with `include_metadata`, and to 10000
+    /// for the bare array, which has an `x-next-cursor` header when the rows were cut
+    #[serde(default)]
+    limit: Option<usize>,
+    /// `next_cursor` from a previous response
+    #[serde(default)]
+    cursor: Option<String>,
+    /// Statement timeout in milliseconds (default 10s, max 60s)
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+    /// Return `{columns, rows, next_cursor}` instead of a bare array of rows
+    #[serde(default)]
+    include_metadata: bool,
 }
 
+/// Runs SQL on a read-only connection. Writes are rejected, BLOBs are returned
+/// as base64 and results are paged.
 #[oasgen]
 async fn execute_raw_sql(
     State(state): State<Arc<AppState>>,
     JsonResponse(payload): JsonResponse<RawSqlQuery>,
-) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
-    match state.db.execute_raw_sql(&payload.query).await {
-        Ok(result) => Ok(JsonResponse(result)),
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let options = RawSqlOptions {
+        max_rows: payload.limit.unwrap_or(if payload.include_metadata {
+            DEFAULT_RAW_SQL_ROWS
+        } else {
+            MAX_RAW_SQL_ROWS
+        }),
+        timeout: payload
+            .timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_RAW_SQL_TIMEOUT),
+        cursor: payload.cursor,
+    };
+    match state.db.query_raw_sql(&payload.query, &options).await {
+        Ok(page) if payload.include_metadata => Ok(JsonResponse(json!(page)).into_response()),
+        Ok(page) => {
+            let mut response = JsonResponse(serde_json::Value::Array(
+                page.rows
+                    .into_iter()
+                    .map(serde_json::Value::Object)
+                    .collect(),
+            ))
+            .into_response();
+            // The bare array has no next_cursor, the header tells the rows were cut
+            if let Some(cursor) = page
+                .next_cursor
+                .and_then(|cursor| HeaderValue::from_str(&cursor).ok())
+            {
+                response.headers_mut().insert("x-next-cursor", cursor);
+            }
+            Ok(response)
+        }
         Err(e) => {
+            let status = match e {
+                RawSqlError::Timeout(_) => StatusCode::REQUEST_TIMEOUT,
+                _ if e.is_client_error() => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
             error!("Failed to execute raw SQL query: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                JsonResponse(json!({"error": e.to_string()})),
-            ))
+            Err((status, JsonResponse(json!({"error": e.to_string()}))))
         }
     }
 }
@@ -1991,7 +2188,7 @@ pub struct GetSimilarSpeakersRequest {
     limit: u32,
 }
 
-fn from_comma_separated_array<'de, D>(deserializer: D) -> Result<Option<Vec<i64>>, D::Error>
+pub(crate) fn from_comma_separated_array<'de, D>(deserializer: D) -> Result<Option<Vec<i64>>, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -2108,6 +2305,9 @@ async fn delete_speaker_handler(
             JsonResponse(json!({"error": e.to_string()})),
         )
     })?;
+    if let Err(e) = state.audio_manager.reload_enrolled_speakers().await {
+        warn!("failed to reload enrolled speakers: {}", e);
+    }
 
     // delete all audio chunks from the file system
     for audio_chunk in audio_chunks {
@@ -2158,6 +2358,10 @@ async fn merge_speakers_handler(
                 JsonResponse(json!({"error": e.to_string(), "speaker_to_keep_id": speaker_to_keep_id, "speaker_to_merge_id": speaker_to_merge_id})),
             )
         })?;
+    // Enrolled embeddings of the merged speaker now belong to the kept one
+    if let Err(e) = state.audio_manager.reload_enrolled_speakers().await {
+        warn!("failed to reload enrolled speakers: {}", e);
+    }
 
     Ok(JsonResponse(json!({"success": true})))
 }
@@ -2320,6 +2524,14 @@ struct EventsQuery {
     images: Option<bool>,
 }
 
+#[derive(Deserialize)]
+struct TranscriptsQuery {
+    /// Only stream the transcriptions of this device, e.g. "MacBook Pro Microphone (input)"
+    device: Option<String>,
+    /// Also stream partial hypotheses, true by default
+    partial: Option<bool>,
+}
+
 #[derive(Debug, OaSchema, Deserialize)]
 struct SemanticSearchQuery {
     text: String,
@@ -2355,7 +2567,7 @@ async fn semantic_search_handler(
     // Search database for similar embeddings
     match state
         .db
-        .search_similar_embeddings(embedding, limit, threshold)
+        .search_similar_embeddings(embedding, embedding_model(), limit, threshold)
         .await
     {
         Ok(results) => {
@@ -2581,6 +2793,68 @@ async fn handle_socket(socket: WebSocket, query: Query<EventsQuery>, _guard: WsC
     // _guard is dropped here, decrementing the connection counter
 }
 
+// websocket realtime transcriptions handler
+async fn ws_transcripts_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TranscriptsQuery>,
+) -> Response {
+    match try_acquire_ws_connection(&state.ws_connection_count) {
+        Some(guard) => ws.on_upgrade(|socket| handle_transcripts_socket(socket, query, guard)),
+        None => Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(Body::from("Too many WebSocket connections"))
+            .unwrap(),
+    }
+}
+
+/// Stream the partial and final results of realtime transcription, local or Deepgram's, with
+/// their device and speaker.
+async fn handle_transcripts_socket(
+    socket: WebSocket,
+    query: TranscriptsQuery,
+    _guard: WsConnectionGuard,
+) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut transcripts = subscribe_to_event::<RealtimeTranscriptionEvent>("transcription");
+    let include_partial = query.partial.unwrap_or(true);
+
+    loop {
+        tokio::select! {
+            transcript = transcripts.next() => {
+                let Some(transcript) = transcript else { break };
+                let transcript = transcript.data;
+                if !include_partial && !transcript.is_final {
+                    continue;
+                }
+                if query.device.as_ref().is_some_and(|device| *device != transcript.device) {
+                    continue;
+                }
+                if let Err(e) = sender
+                    .send(Message::Text(
+                        serde_json::to_string(&transcript).unwrap_or_default(),
+                    ))
+                    .await
+                {
+                    debug!("failed to send transcript: {}", e);
+                    break;
+                }
+            }
+            message = receiver.next() => {
+                // Clients only listen, until they close the connection
+                if matches!(message, None | Some(Err(_)) | Some(Ok(Message::Close(_)))) {
+                    break;
+                }
+            }
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {
+                let _ = sender.send(Message::Ping(vec![])).await;
+            }
+        }
+    }
+
+    debug!("transcripts WebSocket connection closed");
+}
+
 async fn ws_health_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> Response {
     // Check connection limit before upgrading
     match try_acquire_ws_connection(&state.ws_connection_count) {
@@ -2661,10 +2935,57 @@ struct AudioDeviceControlRequest {
     device_name: String,
 }
 
+/// Device to start, with settings overriding the ones given when any is. Settings are kept
+/// across restarts, values are those of --device-audio-settings.
+#[derive(OaSchema, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+struct StartAudioDeviceRequest {
+    device_name: String,
+    #[serde(default)]
+    languages: Option<Vec<String>>,
+    /// low, medium or high
+    #[serde(default)]
+    vad_sensitivity: Option<String>,
+    /// e.g. whisper-large-v3-turbo or deepgram
+    #[serde(default)]
+    transcription_engine: Option<String>,
+    #[serde(default)]
+    enable_diarization: Option<bool>,
+    #[serde(default)]
+    audio_chunk_duration_secs: Option<u64>,
+    /// e.g. denoise,agc or none
+    #[serde(default)]
+    preprocessing: Option<String>,
+}
+
+impl StartAudioDeviceRequest {
+    /// Settings given with the request, `None` if there are none.
+    fn device_settings(&self) -> Result<Option<DeviceAudioSettings>, String> {
+        let mut settings = DeviceAudioSettings::default();
+        for language in self.languages.iter().flatten() {
+            settings.set("language", language)?;
+        }
+        if let Some(vad_sensitivity) = &self.vad_sensitivity {
+            settings.set("vad", vad_sensitivity)?;
+        }
+        if let Some(engine) = &self.transcription_engine {
+            settings.set("engine", engine)?;
+        }
+        if let Some(secs) = self.audio_chunk_duration_secs {
+            settings.set("chunk", &secs.to_string())?;
+        }
+        if let Some(preprocessing) = &self.preprocessing {
+            settings.set("preprocessing", preprocessing)?;
+        }
+        settings.enable_diarization = self.enable_diarization;
+        Ok((!settings.is_empty()).then_some(settings))
+    }
+}
+
 #[oasgen]
 async fn start_audio_device(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<AudioDeviceControlRequest>,
+    Json(payload): Json<StartAudioDeviceRequest>,
 ) -> Result<Json<AudioDeviceControlResponse>, (StatusCode, JsonResponse<Value>)> {
     let device_name = payload.device_name.clone();
     let device: AudioDevice;
@@ -2681,6 +3002,28 @@ async fn start_audio_device(
         }
     };
 
+    let settings = payload.device_settings().map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"success": false, "message": e})),
+        )
+    })?;
+    if let Some(settings) = settings {
+        if let Err(e) = state
+            .audio_manager
+            .set_device_settings(&device, settings)
+            .await
+        {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                JsonResponse(json!({
+                    "success": false,
+                    "message": format!("Failed to apply settings of device {}: {}", device_name, e)
+                })),
+            ));
+        }
+    }
+
     if let Err(e) = state.audio_manager.start_device(&device).await {
         return Err((
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -3478,6 +3821,45 @@ pub async fn get_frame_ocr_data(
     }
 }
 
+/// Response type for audio transcription words endpoint
+#[derive(OaSchema, Serialize)]
+pub struct AudioTranscriptionWordsResponse {
+    pub transcription_id: i64,
+    pub words: Vec<AudioTranscriptionWord>,
+}
+
+/// Get the timings and confidence of each word of an audio transcription.
+/// Empty for transcriptions made before word timings were recorded.
+#[oasgen]
+pub async fn get_audio_transcription_words(
+    State(state): State<Arc<AppState>>,
+    Path(transcription_id): Path<i64>,
+) -> Result<JsonResponse<AudioTranscriptionWordsResponse>, (StatusCode, JsonResponse<Value>)> {
+    match state
+        .db
+        .get_audio_transcription_words(transcription_id)
+        .await
+    {
+        Ok(words) => Ok(JsonResponse(AudioTranscriptionWordsResponse {
+            transcription_id,
+            words,
+        })),
+        Err(e) => {
+            error!(
+                "Failed to get words for audio transcription {}: {}",
+                transcription_id, e
+            );
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({
+                    "error": format!("Failed to get words: {}", e),
+                    "transcription_id": transcription_id
+                })),
+            ))
+        }
+    }
+}
+
 /// Apply PII redaction to a frame image
 async fn apply_pii_redaction(
     state: &Arc<AppState>,
@@ -1,8 +1,9 @@
 //! macOS Sleep/Wake Monitor
 //!
 //! Listens for system sleep and wake events using NSWorkspace notifications.
-//! Currently only tracks these events with PostHog analytics to validate
-//! the hypothesis that sleep/wake events cause recording degradation.
+//! Tracks these events with PostHog analytics to validate the hypothesis
+//! that sleep/wake events cause recording degradation, and wakes pipe cron
+//! schedulers so they catch up on runs missed while asleep.
 //!
 //! Future work: Trigger component reinitialization on wake events.
 
@@ -129,6 +130,9 @@ fn on_did_wake(handle: &tokio::runtime::Handle) {
     // Mark that we recently woke
     RECENTLY_WOKE.store(true, Ordering::SeqCst);
 
+    // Crons scheduled while asleep are overdue, let their schedulers apply their catch-up policy
+    screenpipe_core::notify_system_wake();
+
     // Spawn a task on the captured tokio runtime handle to check recording
     // health after a short delay. We can't use bare tokio::spawn() here
     // because this callback runs on an NSRunLoop thread, not a tokio thread.
@@ -0,0 +1,62 @@
+//! Periodic speaker re-clustering.
+//!
+//! Clusters all speakers in the background, see
+//! [`DatabaseManager::cluster_speakers`], so merge proposals are ready for review and
+//! fragments of the same voice above the auto-merge threshold are merged without asking.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use screenpipe_audio::audio_manager::AudioManager;
+use screenpipe_db::{DatabaseManager, SpeakerClusteringConfig};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+/// Delay before the first pass so clustering doesn't compete with startup.
+const INITIAL_DELAY: Duration = Duration::from_secs(300);
+
+/// Spawn the background speaker clustering task.
+pub fn start_speaker_clustering_task(
+    db: Arc<DatabaseManager>,
+    audio_manager: Arc<AudioManager>,
+    config: SpeakerClusteringConfig,
+    interval: Duration,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        info!(
+            "starting speaker clustering task (every {:?}): {:?}",
+            interval, config
+        );
+        let mut ticker =
+            tokio::time::interval_at(tokio::time::Instant::now() + INITIAL_DELAY, interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    match db.cluster_speakers(&config).await {
+                        Ok(report) => {
+                            info!(
+                                "speaker clustering: {} merge proposals, {} speakers merged",
+                                report.proposals, report.auto_merged
+                            );
+                            // Enrolled speakers may have been merged into others
+                            if report.auto_merged > 0 {
+                                if let Err(e) = audio_manager.reload_enrolled_speakers().await {
+                                    warn!("failed to reload enrolled speakers: {}", e);
+                                }
+                            }
+                        }
+                        Err(e) => error!("speaker clustering failed: {}", e),
+                    }
+                }
+                _ = shutdown_rx.recv() => {
+                    debug!("speaker clustering task shutting down");
+                    break;
+                }
+            }
+        }
+    })
+}
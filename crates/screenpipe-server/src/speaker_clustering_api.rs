@@ -0,0 +1,150 @@
+//! Speaker re-clustering API endpoints.
+//!
+//! Clustering proposes to merge fragments of the same voice, see
+//! [`DatabaseManager::cluster_speakers`](screenpipe_db::DatabaseManager::cluster_speakers).
+//! Proposals are listed, then accepted or rejected in bulk.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use screenpipe_db::{
+    SpeakerClusteringConfig, SpeakerClusteringError, SpeakerClusteringReport, SpeakerMergeProposal,
+    SpeakerMergeProposalStatus, SpeakerMergeResolution,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::{error, warn};
+
+use crate::server::AppState;
+
+/// Request to cluster all speakers, defaults to the server settings.
+#[derive(Debug, Default, Deserialize)]
+pub struct ClusterSpeakersRequest {
+    /// Minimum average similarity (0 to 1) of two clusters to be merged
+    pub threshold: Option<f32>,
+    /// Merge right away the speakers proposed with at least this confidence
+    pub auto_merge_threshold: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListMergeProposalsQuery {
+    /// Defaults to pending proposals
+    #[serde(default = "default_status")]
+    pub status: SpeakerMergeProposalStatus,
+}
+
+fn default_status() -> SpeakerMergeProposalStatus {
+    SpeakerMergeProposalStatus::Pending
+}
+
+/// Proposals to accept and reject, by id.
+#[derive(Debug, Deserialize)]
+pub struct ResolveMergeProposalsRequest {
+    #[serde(default)]
+    pub accept: Vec<i64>,
+    #[serde(default)]
+    pub reject: Vec<i64>,
+}
+
+fn error_response(status: StatusCode, message: String) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({ "error": message })))
+}
+
+fn check_threshold(name: &str, threshold: Option<f32>) -> Result<(), (StatusCode, Json<Value>)> {
+    match threshold {
+        Some(threshold) if !(0.0..=1.0).contains(&threshold) => Err(error_response(
+            StatusCode::BAD_REQUEST,
+            format!("{} must be between 0 and 1", name),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Cluster all speakers and replace the pending merge proposals.
+pub async fn cluster_speakers(
+    State(state): State<Arc<AppState>>,
+    request: Option<Json<ClusterSpeakersRequest>>,
+) -> Result<Json<SpeakerClusteringReport>, (StatusCode, Json<Value>)> {
+    let Json(request) = request.unwrap_or_default();
+    check_threshold("threshold", request.threshold)?;
+    check_threshold("auto_merge_threshold", request.auto_merge_threshold)?;
+
+    let config = SpeakerClusteringConfig {
+        threshold: request
+            .threshold
+            .unwrap_or(state.speaker_clustering.threshold),
+        auto_merge_threshold: request
+            .auto_merge_threshold
+            .or(state.speaker_clustering.auto_merge_threshold),
+    };
+    let report = state
+        .db
+        .cluster_speakers(&config)
+        .await
+        .map_err(|e| match e {
+            SpeakerClusteringError::TooManySpeakers(_) => {
+                error_response(StatusCode::BAD_REQUEST, e.to_string())
+            }
+            SpeakerClusteringError::Sql(_) => {
+                error!("speaker clustering failed: {}", e);
+                error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("speaker clustering failed: {}", e),
+                )
+            }
+        })?;
+    if report.auto_merged > 0 {
+        reload_enrolled_speakers(&state).await;
+    }
+    Ok(Json(report))
+}
+
+/// List merge proposals, most confident first.
+pub async fn list_merge_proposals(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListMergeProposalsQuery>,
+) -> Result<Json<Vec<SpeakerMergeProposal>>, (StatusCode, Json<Value>)> {
+    let proposals = state
+        .db
+        .list_speaker_merge_proposals(Some(query.status))
+        .await
+        .map_err(|e| {
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to list merge proposals: {}", e),
+            )
+        })?;
+    Ok(Json(proposals))
+}
+
+/// Accept and reject merge proposals in bulk.
+pub async fn resolve_merge_proposals(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ResolveMergeProposalsRequest>,
+) -> Result<Json<SpeakerMergeResolution>, (StatusCode, Json<Value>)> {
+    let resolution = state
+        .db
+        .resolve_speaker_merge_proposals(&request.accept, &request.reject)
+        .await
+        .map_err(|e| {
+            error!("failed to resolve merge proposals: {}", e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to resolve merge proposals: {}", e),
+            )
+        })?;
+    if !resolution.accepted.is_empty() {
+        reload_enrolled_speakers(&state).await;
+    }
+    Ok(Json(resolution))
+}
+
+/// Enrolled speakers may have been merged into others
+async fn reload_enrolled_speakers(state: &AppState) {
+    if let Err(e) = state.audio_manager.reload_enrolled_speakers().await {
+        warn!("failed to reload enrolled speakers: {}", e);
+    }
+}
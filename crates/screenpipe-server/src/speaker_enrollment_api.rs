@@ -0,0 +1,150 @@
+//! Speaker voice enrollment API endpoints.
+//!
+//! Enrolling a few seconds of a known person's voice, uploaded or picked from recorded
+//! audio chunks, lets diarization attribute their speech to the same named speaker instead
+//! of creating new ones to merge later.
+
+use axum::{extract::State, http::StatusCode, Json};
+use base64::{engine::general_purpose, Engine as _};
+use screenpipe_audio::speaker::enrollment::EnrollmentAudio;
+use screenpipe_db::EnrolledSpeaker;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::sync::Arc;
+use tracing::error;
+
+use crate::server::AppState;
+
+/// Part of a recorded audio chunk to enroll, in seconds from its start.
+#[derive(Debug, Deserialize)]
+pub struct EnrollmentChunk {
+    pub audio_chunk_id: i64,
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+}
+
+/// Request to enroll a speaker's voice.
+#[derive(Debug, Deserialize)]
+pub struct EnrollSpeakerRequest {
+    /// Speaker to extend, a new speaker is created if not set
+    pub speaker_id: Option<i64>,
+    /// Name of the speaker, required for new speakers
+    pub name: Option<String>,
+    /// Base64-encoded audio files (wav, mp3, ...) of the speaker's voice
+    #[serde(default)]
+    pub audio: Vec<String>,
+    /// Recorded audio of the speaker's voice
+    #[serde(default)]
+    pub audio_chunks: Vec<EnrollmentChunk>,
+}
+
+/// A speaker with enrolled voice embeddings.
+#[derive(Debug, Serialize)]
+pub struct EnrolledSpeakerResponse {
+    pub id: i64,
+    pub name: String,
+    pub embedding_count: usize,
+}
+
+impl From<EnrolledSpeaker> for EnrolledSpeakerResponse {
+    fn from(speaker: EnrolledSpeaker) -> Self {
+        Self {
+            id: speaker.id,
+            name: speaker.name,
+            embedding_count: speaker.embeddings.len(),
+        }
+    }
+}
+
+fn error_response(status: StatusCode, message: String) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({ "error": message })))
+}
+
+/// Enroll a speaker's voice, creating the speaker or adding to its embeddings.
+pub async fn enroll_speaker(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<EnrollSpeakerRequest>,
+) -> Result<Json<EnrolledSpeakerResponse>, (StatusCode, Json<Value>)> {
+    if request.audio.is_empty() && request.audio_chunks.is_empty() {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "audio or audio_chunks is required".to_string(),
+        ));
+    }
+    let name = request
+        .name
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty());
+    if request.speaker_id.is_none() && name.is_none() {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "name is required to enroll a new speaker".to_string(),
+        ));
+    }
+
+    // Uploads are decoded from temporary files, removed when dropped after enrollment
+    let mut files = Vec::new();
+    for (index, audio) in request.audio.iter().enumerate() {
+        let bytes = general_purpose::STANDARD.decode(audio).map_err(|e| {
+            error_response(
+                StatusCode::BAD_REQUEST,
+                format!("audio {} is not valid base64: {}", index, e),
+            )
+        })?;
+        let file = tempfile::NamedTempFile::new()
+            .and_then(|mut file| file.write_all(&bytes).map(|_| file))
+            .map_err(|e| {
+                error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to store audio: {}", e),
+                )
+            })?;
+        files.push(file);
+    }
+
+    let audio: Vec<EnrollmentAudio> = files
+        .iter()
+        .map(|file| EnrollmentAudio::File(file.path().to_path_buf()))
+        .chain(
+            request
+                .audio_chunks
+                .iter()
+                .map(|chunk| EnrollmentAudio::AudioChunk {
+                    id: chunk.audio_chunk_id,
+                    start_time: chunk.start_time,
+                    end_time: chunk.end_time,
+                }),
+        )
+        .collect();
+
+    let speaker = state
+        .audio_manager
+        .enroll_speaker(request.speaker_id, name, &audio)
+        .await
+        .map_err(|e| {
+            error!("failed to enroll speaker: {}", e);
+            let status = match e.downcast_ref::<sqlx::Error>() {
+                Some(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
+                Some(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                None => StatusCode::BAD_REQUEST,
+            };
+            error_response(status, e.to_string())
+        })?;
+
+    Ok(Json(speaker.into()))
+}
+
+/// List the speakers with enrolled voices.
+pub async fn list_enrolled_speakers(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<EnrolledSpeakerResponse>>, (StatusCode, Json<Value>)> {
+    let speakers = state.db.get_enrolled_speakers().await.map_err(|e| {
+        error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to list enrolled speakers: {}", e),
+        )
+    })?;
+    Ok(Json(speakers.into_iter().map(Into::into).collect()))
+}
@@ -1,8 +1,34 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use anyhow::Result;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error, info};
 
+use crate::embedding::embedding_endpoint::get_or_initialize_model;
+
+/// Ollama model used for embeddings unless the embedding index is enabled
+pub const OLLAMA_EMBEDDING_MODEL: &str = "nomic-embed-text";
+/// Built-in model the embedding index stores vectors with
+pub const LOCAL_EMBEDDING_MODEL: &str = "jina-embeddings-v2-base-en";
+
+static USE_LOCAL_MODEL: AtomicBool = AtomicBool::new(false);
+
+/// Embed with the built-in model from now on, so queries are comparable with the
+/// vectors of the embedding index. Until then Ollama is used and nothing is downloaded.
+pub fn use_local_embedding_model() {
+    USE_LOCAL_MODEL.store(true, Ordering::Relaxed);
+}
+
+/// Name of the model [`generate_embedding`] currently embeds with
+pub fn embedding_model() -> &'static str {
+    if USE_LOCAL_MODEL.load(Ordering::Relaxed) {
+        LOCAL_EMBEDDING_MODEL
+    } else {
+        OLLAMA_EMBEDDING_MODEL
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct OllamaRequest {
     model: String,
@@ -14,15 +40,25 @@ struct OllamaResponse {
     embedding: Vec<f32>,
 }
 
-/// Generates embeddings for text using Ollama's nomic-embed-text model
+/// Generates embeddings for text with the model named by [`embedding_model`]: the
+/// built-in one when the embedding index is enabled, Ollama's nomic-embed-text otherwise
 pub async fn generate_embedding(text: &str, frame_id: i64) -> Result<Vec<f32>> {
-    let client = Client::new();
-
     debug!(
         "generating embedding for frame_id: {}, text: {}",
         frame_id, text
     );
 
+    if USE_LOCAL_MODEL.load(Ordering::Relaxed) {
+        let model = get_or_initialize_model().await?;
+        let text = text.to_string();
+        return tokio::task::spawn_blocking(move || {
+            model.blocking_lock().generate_embedding(&text)
+        })
+        .await?;
+    }
+
+    let client = Client::new();
+
     // Check if Ollama server is running
     if let Err(e) = client
         .get("http://localhost:11434/api/version")
@@ -34,7 +70,7 @@ pub async fn generate_embedding(text: &str, frame_id: i64) -> Result<Vec<f32>> {
     }
 
     let request = OllamaRequest {
-        model: "nomic-embed-text".to_string(),
+        model: OLLAMA_EMBEDDING_MODEL.to_string(),
         prompt: text.to_string(),
     };
 
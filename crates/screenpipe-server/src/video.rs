@@ -4,7 +4,8 @@ use dashmap::DashMap;
 use image::ImageFormat::{self};
 use screenpipe_core::{find_ffmpeg_path, Language};
 use screenpipe_vision::{
-    capture_screenshot_by_window::WindowFilters, continuous_capture, CaptureResult, OcrEngine,
+    capture_screenshot_by_window::WindowFilters, continuous_capture, CaptureMask,
+    CapturePolicyFile, CaptureResult, OcrEngine,
 };
 use std::borrow::Cow;
 use std::path::PathBuf;
@@ -122,6 +123,8 @@ impl VideoCapture {
         ignore_list: &[String],
         include_list: &[String],
         ignored_urls: &[String],
+        capture_mask: Arc<CaptureMask>,
+        capture_policies: Option<Arc<CapturePolicyFile>>,
         languages: Vec<Language>,
         capture_unfocused_windows: bool,
         activity_feed: screenpipe_vision::ActivityFeedOption,
@@ -148,7 +151,11 @@ impl VideoCapture {
         let capture_video_frame_queue = video_frame_queue.clone();
         let capture_ocr_frame_queue = ocr_frame_queue.clone();
         let (result_sender, mut result_receiver) = channel(512);
-        let window_filters = Arc::new(WindowFilters::new(ignore_list, include_list, ignored_urls));
+        let window_filters = Arc::new(
+            WindowFilters::new(ignore_list, include_list, ignored_urls)
+                .with_blackout(&capture_mask.blackout_windows, &capture_mask.blackout_urls)
+                .with_policies(capture_policies),
+        );
 
         let capture_ocr_engine = ocr_engine.clone();
         let capture_window_filters = window_filters.clone();
@@ -171,6 +178,7 @@ impl VideoCapture {
                     (*capture_ocr_engine).clone(),
                     monitor_id,
                     capture_window_filters.clone(),
+                    capture_mask.clone(),
                     capture_languages.clone(),
                     capture_unfocused,
                     capture_activity_feed.clone(),
@@ -564,7 +572,11 @@ async fn save_frames_as_video(
                 output_file, monitor_id
             );
 
-            match start_ffmpeg_process(&output_file, fps, video_quality).await {
+            // The capture policy of the first frame can change the quality of the chunk
+            let chunk_quality = first_frame
+                .video_quality
+                .map_or(video_quality, |quality| quality.as_str());
+            match start_ffmpeg_process(&output_file, fps, chunk_quality).await {
                 Ok(mut child) => {
                     let mut stdin = child.stdin.take().expect("Failed to open stdin");
                     spawn_ffmpeg_loggers(child.stderr.take(), child.stdout.take());
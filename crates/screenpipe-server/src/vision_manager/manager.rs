@@ -5,7 +5,7 @@ use dashmap::DashMap;
 use screenpipe_core::Language;
 use screenpipe_db::DatabaseManager;
 use screenpipe_vision::monitor::{get_monitor_by_id, list_monitors};
-use screenpipe_vision::OcrEngine;
+use screenpipe_vision::{CaptureMask, CapturePolicyFile, OcrEngine};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::runtime::Handle;
@@ -26,6 +26,9 @@ pub struct VisionManagerConfig {
     pub ignored_windows: Vec<String>,
     pub included_windows: Vec<String>,
     pub ignored_urls: Vec<String>,
+    pub capture_mask: Arc<CaptureMask>,
+    /// Per-application capture policies, read again when their file changes
+    pub capture_policies: Option<Arc<CapturePolicyFile>>,
     pub languages: Vec<Language>,
     pub capture_unfocused_windows: bool,
     pub realtime_vision: bool,
@@ -162,6 +165,8 @@ impl VisionManager {
         let ignored_windows = self.config.ignored_windows.clone();
         let included_windows = self.config.included_windows.clone();
         let ignored_urls = self.config.ignored_urls.clone();
+        let capture_mask = self.config.capture_mask.clone();
+        let capture_policies = self.config.capture_policies.clone();
         let languages = self.config.languages.clone();
         let capture_unfocused_windows = self.config.capture_unfocused_windows;
         let realtime_vision = self.config.realtime_vision;
@@ -181,6 +186,8 @@ impl VisionManager {
                     &ignored_windows,
                     &included_windows,
                     &ignored_urls,
+                    capture_mask.clone(),
+                    capture_policies.clone(),
                     video_chunk_duration,
                     languages.clone(),
                     capture_unfocused_windows,
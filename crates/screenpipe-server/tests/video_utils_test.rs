@@ -137,6 +137,8 @@ async fn test_extract_frames_and_ocr() -> Result<()> {
         window_y: 0,
         window_width: first_frame.width(),
         window_height: first_frame.height(),
+        blackout: None,
+        policy: Default::default(),
     };
 
     // perform ocr using apple native (macos only)
@@ -6,6 +6,8 @@ use strsim::jaro_winkler;
 #[cfg(target_os = "macos")]
 use screenpipe_vision::perform_ocr_apple;
 
+#[cfg(target_os = "linux")]
+use screenpipe_vision::onnx_ocr::{get_or_download_onnx_ocr_models, OnnxOcr};
 #[cfg(target_os = "linux")]
 use screenpipe_vision::perform_ocr_tesseract;
 
@@ -134,7 +136,7 @@ fn bench_tesseract_ocr(c: &mut Criterion) {
 
             for _ in 0..iters {
                 let start = std::time::Instant::now();
-                let (result, _, _) = perform_ocr_tesseract(black_box(&image), vec![]);
+                let (result, _, _) = perform_ocr_tesseract(black_box(&image), vec![]).unwrap();
                 total_duration += start.elapsed();
 
                 let accuracy = calculate_accuracy(&result, EXPECTED_KEYWORDS);
@@ -149,6 +151,42 @@ fn bench_tesseract_ocr(c: &mut Criterion) {
     group.finish();
 }
 
+// ONNX OCR benchmark, compared to Tesseract (Linux only)
+#[cfg(target_os = "linux")]
+fn bench_onnx_ocr(c: &mut Criterion) {
+    let image = load_test_image();
+    let models_dir = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(get_or_download_onnx_ocr_models())
+        .expect("Failed to get ONNX OCR models");
+    let ocr = OnnxOcr::load(&models_dir).expect("Failed to load ONNX OCR models");
+
+    let mut group = c.benchmark_group("ONNX OCR");
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(10));
+
+    group.bench_function(BenchmarkId::new("Performance and Accuracy", ""), |b| {
+        b.iter_custom(|iters| {
+            let mut total_duration = Duration::new(0, 0);
+            let mut total_accuracy = 0.0;
+
+            for _ in 0..iters {
+                let start = std::time::Instant::now();
+                let result = ocr.recognize(black_box(&image)).unwrap();
+                total_duration += start.elapsed();
+
+                let accuracy = calculate_accuracy(&result.text, EXPECTED_KEYWORDS);
+                total_accuracy += accuracy;
+            }
+
+            println!("Average Accuracy: {:.2}", total_accuracy / iters as f32);
+            total_duration
+        })
+    });
+
+    group.finish();
+}
+
 // TODO fix windows
 // Windows OCR benchmark (Windows only)
 #[cfg(target_os = "windows")]
@@ -194,7 +232,7 @@ criterion_group!(
 );
 
 #[cfg(target_os = "linux")]
-criterion_group!(benches, bench_tesseract_ocr);
+criterion_group!(benches, bench_tesseract_ocr, bench_onnx_ocr);
 
 #[cfg(target_os = "windows")]
 criterion_group!(benches, bench_windows_ocr);
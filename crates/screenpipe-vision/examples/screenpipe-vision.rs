@@ -2,7 +2,7 @@ use clap::Parser;
 use screenpipe_core::Language;
 use screenpipe_vision::{
     capture_screenshot_by_window::WindowFilters, continuous_capture, monitor::list_monitors,
-    OcrEngine,
+    CaptureMask, OcrEngine,
 };
 use std::{sync::Arc, time::Duration};
 use tokio::sync::mpsc::channel;
@@ -46,6 +46,7 @@ async fn main() {
         OcrEngine::AppleNative,
         monitor_id,
         window_filters,
+        Arc::new(CaptureMask::default()),
         languages.clone(),
         false,
         None, // activity_feed - None disables adaptive FPS
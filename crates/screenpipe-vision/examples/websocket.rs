@@ -5,7 +5,7 @@ use futures_util::{SinkExt, StreamExt};
 use image::ImageEncoder;
 use screenpipe_vision::capture_screenshot_by_window::WindowFilters;
 use screenpipe_vision::{
-    continuous_capture, monitor::get_default_monitor, CaptureResult, OcrEngine,
+    continuous_capture, monitor::get_default_monitor, CaptureMask, CaptureResult, OcrEngine,
 };
 use serde::Serialize;
 use std::collections::HashMap;
@@ -101,6 +101,7 @@ async fn main() -> Result<()> {
             },
             id,
             window_filters,
+            Arc::new(CaptureMask::default()),
             vec![],
             false,
             None, // activity_feed - None disables adaptive FPS
@@ -0,0 +1,253 @@
+//! Per-application capture policies.
+//!
+//! A JSON file lists rules matching windows by app name, title regex and browser URL. The first
+//! rule matching a window gives its policy, settings a rule leaves out keep their default:
+//!
+//! ```json
+//! [
+//!     { "app": "Figma", "ocr": false },
+//!     { "app": "Terminal", "video": false },
+//!     { "app": "Slack", "fps": 0.2 },
+//!     { "app": "1Password", "capture": false },
+//!     { "url": "github.com", "title": "(?i)pull request", "video_quality": "high" }
+//! ]
+//! ```
+//!
+//! The file is watched and read again when it changes, so policies apply without restarting.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::capture_screenshot_by_window::{CapturedWindow, WindowFilters};
+
+/// How often the policy file is checked for changes
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Quality preset of the video chunks, as given to `--video-quality`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoQuality {
+    Low,
+    Balanced,
+    High,
+    Max,
+}
+
+impl VideoQuality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Balanced => "balanced",
+            Self::High => "high",
+            Self::Max => "max",
+        }
+    }
+}
+
+/// How a window is captured.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CapturePolicy {
+    /// Capture the window at all, `false` blacks it out on the frames and never reads it
+    pub capture: bool,
+    /// Read the text of the window
+    pub ocr: bool,
+    /// Keep the window in the video, `false` masks it on the frames once it's been read
+    pub video: bool,
+    /// Capture rate while the window is focused, the global one when `None`
+    pub fps: Option<f64>,
+    /// Quality of the video chunks started while the window is focused, the global one when
+    /// `None`
+    pub video_quality: Option<VideoQuality>,
+}
+
+impl Default for CapturePolicy {
+    fn default() -> Self {
+        Self {
+            capture: true,
+            ocr: true,
+            video: true,
+            fps: None,
+            video_quality: None,
+        }
+    }
+}
+
+impl CapturePolicy {
+    /// Capture interval of the policy, `None` for the global one.
+    pub fn interval(&self) -> Option<Duration> {
+        self.fps
+            .filter(|fps| fps.is_finite() && *fps > 0.0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleConfig {
+    /// Matched like ignored windows, case insensitive substring of the app name
+    app: Option<String>,
+    /// Regex matched against the window title
+    title: Option<String>,
+    /// Matched like ignored URLs, by domain
+    url: Option<String>,
+    #[serde(flatten)]
+    policy: CapturePolicy,
+}
+
+struct PolicyRule {
+    app: Option<String>,
+    title: Option<Regex>,
+    url: Option<WindowFilters>,
+    policy: CapturePolicy,
+}
+
+impl PolicyRule {
+    fn matches(&self, app_name: &str, title: &str, browser_url: Option<&str>) -> bool {
+        self.app
+            .as_ref()
+            .is_none_or(|app| app_name.to_lowercase().contains(app))
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(title))
+            && self
+                .url
+                .as_ref()
+                .is_none_or(|filters| browser_url.is_some_and(|url| filters.is_url_blocked(url)))
+    }
+}
+
+/// Rules of a policy file.
+#[derive(Default)]
+pub struct CapturePolicies {
+    rules: Vec<PolicyRule>,
+}
+
+impl CapturePolicies {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let configs: Vec<RuleConfig> = serde_json::from_str(json)?;
+        let rules = configs
+            .into_iter()
+            .map(|config| {
+                let title = config
+                    .title
+                    .map(|title| Regex::new(&title).context("invalid title regex"))
+                    .transpose()?;
+                Ok(PolicyRule {
+                    app: config.app.map(|app| app.to_lowercase()),
+                    title,
+                    url: config.url.map(|url| WindowFilters::new(&[], &[], &[url])),
+                    policy: config.policy,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read capture policies {}", path.display()))?;
+        Self::from_json(&json)
+            .with_context(|| format!("invalid capture policies {}", path.display()))
+    }
+
+    /// Policy of the first rule matching the window, the default one when none does.
+    pub fn policy_for(
+        &self,
+        app_name: &str,
+        title: &str,
+        browser_url: Option<&str>,
+    ) -> CapturePolicy {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(app_name, title, browser_url))
+            .map(|rule| rule.policy.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Policies of a file, read again by a watcher thread when it's modified.
+pub struct CapturePolicyFile {
+    path: PathBuf,
+    policies: RwLock<Arc<CapturePolicies>>,
+}
+
+impl CapturePolicyFile {
+    /// Read the policies of `path`, an error when they're invalid, and watch the file until
+    /// the returned policies are dropped.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Arc<Self>> {
+        let path = path.into();
+        let modified = modified_time(&path);
+        let policies = CapturePolicies::load(&path)?;
+        info!(
+            "Loaded {} capture policies from {}",
+            policies.rules.len(),
+            path.display()
+        );
+        let file = Arc::new(Self {
+            path,
+            policies: RwLock::new(Arc::new(policies)),
+        });
+        let watched = Arc::downgrade(&file);
+        std::thread::Builder::new()
+            .name("capture-policies".to_string())
+            .spawn(move || watch(watched, modified))?;
+        Ok(file)
+    }
+
+    /// Current policies. They're never read here, so capture doesn't wait on the file.
+    pub fn current(&self) -> Arc<CapturePolicies> {
+        self.policies.read().unwrap().clone()
+    }
+
+    /// Read the policies again. Invalid policies are logged and the previous ones kept.
+    pub fn reload(&self) {
+        match CapturePolicies::load(&self.path) {
+            Ok(policies) => {
+                info!(
+                    "Reloaded {} capture policies from {}",
+                    policies.rules.len(),
+                    self.path.display()
+                );
+                *self.policies.write().unwrap() = Arc::new(policies);
+            }
+            Err(e) => error!("{:#}, keeping the previous capture policies", e),
+        }
+    }
+}
+
+/// Reload the policies of `file` whenever its modification time changes, until it's dropped.
+fn watch(file: Weak<CapturePolicyFile>, mut modified: Option<SystemTime>) {
+    loop {
+        std::thread::sleep(RELOAD_CHECK_INTERVAL);
+        let Some(file) = file.upgrade() else {
+            return;
+        };
+        let current = modified_time(&file.path);
+        if current != modified {
+            modified = current;
+            file.reload();
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Policy of the frame holding `windows`: the one of the focused window, or of the first window
+/// when none is.
+pub fn frame_policy(windows: &[CapturedWindow]) -> CapturePolicy {
+    windows
+        .iter()
+        .find(|window| window.is_focused)
+        .or(windows.first())
+        .map(|window| window.policy.clone())
+        .unwrap_or_default()
+}
@@ -3,6 +3,7 @@ use once_cell::sync::Lazy;
 use std::collections::HashSet;
 use std::error::Error;
 use std::fmt;
+use std::sync::Arc;
 use tracing::debug;
 
 // On macOS, we have both sck-rs (for 12.3+) and xcap (fallback for older versions)
@@ -17,6 +18,7 @@ use xcap::{Window as XcapWindow, XCapError as XcapXCapError};
 use xcap::{Window, XCapError};
 
 use crate::browser_utils::create_url_detector;
+use crate::capture_policy::{CapturePolicy, CapturePolicyFile};
 use crate::monitor::SafeMonitor;
 
 #[cfg(target_os = "macos")]
@@ -30,6 +32,13 @@ const BROWSER_NAMES: [&str; 9] = [
     "chrome", "firefox", "safari", "edge", "brave", "arc", "chromium", "vivaldi", "opera",
 ];
 
+fn is_browser(app_name: &str) -> bool {
+    let app_name = app_name.to_lowercase();
+    BROWSER_NAMES
+        .iter()
+        .any(|&browser| app_name.contains(browser))
+}
+
 #[derive(Debug)]
 enum CaptureError {
     NoWindows,
@@ -213,12 +222,21 @@ pub struct CapturedWindow {
     pub window_y: i32,
     pub window_width: u32,
     pub window_height: u32,
+    /// Matched a blackout rule of the filters: the image is dropped and these parts of the
+    /// window, those not covered by windows in front of it, are masked on the frame, see
+    /// [`crate::masking`]
+    pub blackout: Option<Vec<Rect>>,
+    /// Policy of the window in the capture policies of the filters
+    pub policy: CapturePolicy,
 }
 
 pub struct WindowFilters {
     ignore_set: HashSet<String>,
     include_set: HashSet<String>,
     ignored_urls: HashSet<String>,
+    /// Windows to mask on the frames, matched like ignored windows and URLs
+    blackout: Option<Box<WindowFilters>>,
+    policies: Option<Arc<CapturePolicyFile>>,
 }
 
 impl WindowFilters {
@@ -227,6 +245,57 @@ impl WindowFilters {
             ignore_set: ignore_list.iter().map(|s| s.to_lowercase()).collect(),
             include_set: include_list.iter().map(|s| s.to_lowercase()).collect(),
             ignored_urls: ignored_urls.iter().map(|s| s.to_lowercase()).collect(),
+            blackout: None,
+            policies: None,
+        }
+    }
+
+    /// Capture windows according to the policies of `policies`.
+    pub fn with_policies(mut self, policies: Option<Arc<CapturePolicyFile>>) -> Self {
+        self.policies = policies;
+        self
+    }
+
+    /// Capture policy of a window, the default one without policies.
+    pub fn policy_for(
+        &self,
+        app_name: &str,
+        title: &str,
+        browser_url: Option<&str>,
+    ) -> CapturePolicy {
+        self.policies
+            .as_ref()
+            .map(|policies| policies.current().policy_for(app_name, title, browser_url))
+            .unwrap_or_default()
+    }
+
+    /// Black out the windows whose app name or title contains one of `windows`, and the
+    /// browser windows showing one of `urls`, wherever they are on screen.
+    pub fn with_blackout(mut self, windows: &[String], urls: &[String]) -> Self {
+        self.blackout = if windows.is_empty() && urls.is_empty() {
+            None
+        } else {
+            Some(Box::new(WindowFilters::new(windows, &[], urls)))
+        };
+        self
+    }
+
+    /// Check if a window matches a blackout rule or a policy never capturing it. Browser
+    /// windows without a known URL are matched by title, like
+    /// [`Self::is_title_suggesting_blocked_url`].
+    pub fn is_blacked_out(&self, app_name: &str, title: &str, browser_url: Option<&str>) -> bool {
+        if !self.policy_for(app_name, title, browser_url).capture {
+            return true;
+        }
+        let Some(blackout) = &self.blackout else {
+            return false;
+        };
+        if !blackout.is_valid(app_name, title) {
+            return true;
+        }
+        match browser_url {
+            Some(url) => blackout.is_url_blocked(url),
+            None => is_browser(app_name) && blackout.is_title_suggesting_blocked_url(title),
         }
     }
 
@@ -433,6 +502,66 @@ impl Rect {
             0
         }
     }
+
+    /// Parts of this rectangle not covered by `other`, at most four
+    pub fn subtract(&self, other: &Rect) -> Vec<Rect> {
+        if !self.overlaps(other) {
+            return vec![*self];
+        }
+        let self_right = self.x + self.width as i32;
+        let self_bottom = self.y + self.height as i32;
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = self_right.min(other.x + other.width as i32);
+        let bottom = self_bottom.min(other.y + other.height as i32);
+
+        // Full width bands above and below the covered area, then the sides of it
+        let parts = [
+            (self.x, self.y, self_right, top),
+            (self.x, bottom, self_right, self_bottom),
+            (self.x, top, left, bottom),
+            (right, top, self_right, bottom),
+        ];
+        parts
+            .into_iter()
+            .filter(|(part_left, part_top, part_right, part_bottom)| {
+                part_right > part_left && part_bottom > part_top
+            })
+            .map(|(part_left, part_top, part_right, part_bottom)| Rect {
+                x: part_left,
+                y: part_top,
+                width: (part_right - part_left) as u32,
+                height: (part_bottom - part_top) as u32,
+            })
+            .collect()
+    }
+}
+
+/// Parts of the window of `process_id` at `bounds` not covered by the windows in front of it.
+/// `stacked_windows` are the process ids and bounds of the windows on screen, front to back.
+/// The whole window is returned when it isn't among them, since what covers it is unknown.
+pub fn visible_region(
+    bounds: &Rect,
+    process_id: i32,
+    stacked_windows: &[(i32, Rect)],
+) -> Vec<Rect> {
+    let Some(position) = stacked_windows.iter().position(|(pid, window)| {
+        *pid == process_id
+            && window.x == bounds.x
+            && window.y == bounds.y
+            && window.width == bounds.width
+            && window.height == bounds.height
+    }) else {
+        return vec![*bounds];
+    };
+    stacked_windows[..position]
+        .iter()
+        .fold(vec![*bounds], |region, (_, above)| {
+            region
+                .iter()
+                .flat_map(|part| part.subtract(above))
+                .collect()
+        })
 }
 
 /// Info about a window from CGWindowList (z-ordered, front to back)
@@ -896,6 +1025,17 @@ pub async fn capture_all_visible_windows(
     #[cfg(target_os = "macos")]
     let (cg_windows, overlay_pids) = get_cg_window_list();
 
+    // Windows on screen front to back, to mask only the visible part of blacked out windows.
+    // Only CGWindowList tells the z-order: elsewhere blacked out windows are masked whole.
+    #[cfg(target_os = "macos")]
+    let stacked_windows: Vec<(i32, Rect)> = cg_windows
+        .iter()
+        .filter(|w| w.layer == 0 && !SKIP_APPS.contains(w.owner_name.as_str()))
+        .map(|w| (w.pid, w.bounds))
+        .collect();
+    #[cfg(not(target_os = "macos"))]
+    let stacked_windows: Vec<(i32, Rect)> = Vec::new();
+
     // Build the monitor bounds for window-to-monitor matching
     let monitor_bounds = Rect {
         x: monitor.x(),
@@ -980,6 +1120,24 @@ pub async fn capture_all_visible_windows(
             is_focused && is_on_this_monitor
         };
 
+        // Blacked out windows are masked wherever they show on this monitor, focused or not,
+        // and their image is never kept
+        if is_on_this_monitor && window_filters.is_blacked_out(&app_name, &window_name, None) {
+            debug!(
+                "Privacy filter: Blacking out window {} ({})",
+                app_name, window_name
+            );
+            all_captured_images.push(blacked_out_window(
+                app_name,
+                window_name,
+                process_id,
+                is_focused,
+                window_bounds,
+                visible_region(&window_bounds, process_id, &stacked_windows),
+            ));
+            continue;
+        }
+
         // Apply filters
         // Note: Empty window_name/app_name check fixes frame-window mismatch bug where apps like Arc
         // have internal windows with empty titles that create duplicate DB records
@@ -1016,6 +1174,18 @@ pub async fn capture_all_visible_windows(
 
             // Check if URL should be blocked for privacy (e.g., banking sites)
             if let Some(ref url) = browser_url {
+                if window_filters.is_blacked_out(&app_name, &window_name, Some(url)) {
+                    tracing::info!("Privacy filter: Blacking out window showing URL: {}", url);
+                    all_captured_images.push(blacked_out_window(
+                        app_name,
+                        window_name,
+                        process_id,
+                        is_focused,
+                        window_bounds,
+                        visible_region(&window_bounds, process_id, &stacked_windows),
+                    ));
+                    continue;
+                }
                 if window_filters.is_url_blocked(url) {
                     tracing::info!(
                         "Privacy filter: Skipping window due to blocked URL: {}",
@@ -1043,6 +1213,7 @@ pub async fn capture_all_visible_windows(
                 continue;
             }
 
+            let policy = window_filters.policy_for(&app_name, &window_name, browser_url.as_deref());
             all_captured_images.push(CapturedWindow {
                 image,
                 app_name,
@@ -1054,6 +1225,8 @@ pub async fn capture_all_visible_windows(
                 window_y,
                 window_width,
                 window_height,
+                blackout: None,
+                policy,
             });
         }
     }
@@ -1061,6 +1234,31 @@ pub async fn capture_all_visible_windows(
     Ok(all_captured_images)
 }
 
+/// Blacked out window at `bounds` showing `visible`, without its image.
+fn blacked_out_window(
+    app_name: String,
+    window_name: String,
+    process_id: i32,
+    is_focused: bool,
+    bounds: Rect,
+    visible: Vec<Rect>,
+) -> CapturedWindow {
+    CapturedWindow {
+        image: DynamicImage::new_rgba8(0, 0),
+        app_name,
+        window_name,
+        process_id,
+        is_focused,
+        browser_url: None,
+        window_x: bounds.x,
+        window_y: bounds.y,
+        window_width: bounds.width,
+        window_height: bounds.height,
+        blackout: Some(visible),
+        policy: CapturePolicy::default(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1361,6 +1559,39 @@ mod tests {
         assert!(filters.is_valid("Arc", "GitHub"));
     }
 
+    // ==================== is_blacked_out tests ====================
+
+    #[test]
+    fn test_blackout_disabled_by_default() {
+        let filters = WindowFilters::new(&[], &[], &[]);
+        assert!(!filters.is_blacked_out("1Password", "Vault", None));
+        let filters = WindowFilters::new(&[], &[], &[]).with_blackout(&[], &[]);
+        assert!(!filters.is_blacked_out("1Password", "Vault", None));
+    }
+
+    #[test]
+    fn test_blackout_by_app_name_or_title() {
+        let filters =
+            WindowFilters::new(&[], &[], &[]).with_blackout(&["1password".to_string()], &[]);
+        assert!(filters.is_blacked_out("1Password 8", "Vault", None));
+        assert!(filters.is_blacked_out("Arc", "1Password - Sign in", None));
+        assert!(!filters.is_blacked_out("Arc", "GitHub", None));
+        // Blackout rules don't filter windows out of the capture
+        assert!(filters.is_valid("1Password 8", "Vault"));
+    }
+
+    #[test]
+    fn test_blackout_by_url() {
+        let filters =
+            WindowFilters::new(&[], &[], &[]).with_blackout(&[], &["chase.com".to_string()]);
+        assert!(filters.is_blacked_out("Arc", "Home", Some("https://secure.chase.com/login")));
+        assert!(!filters.is_blacked_out("Arc", "Home", Some("https://purchase.com")));
+        // Browser windows without URL are matched by title
+        assert!(filters.is_blacked_out("Google Chrome", "Chase Bank - Accounts", None));
+        assert!(!filters.is_blacked_out("Notes", "Chase Bank - Accounts", None));
+        assert!(!filters.is_url_blocked("https://secure.chase.com/login"));
+    }
+
     // Note: The overlay_pids CGWindowLayer detection in capture_all_visible_windows
     // is macOS-only and requires actual system calls, so it can only be tested
     // as an integration test on macOS. The unit tests above verify the filter
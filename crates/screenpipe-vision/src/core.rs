@@ -1,14 +1,14 @@
-#[cfg(target_os = "macos")]
-use crate::apple::perform_ocr_apple;
+use crate::capture_policy::{frame_policy, VideoQuality};
 use crate::capture_screenshot_by_window::CapturedWindow;
+use crate::capture_screenshot_by_window::Rect;
 use crate::capture_screenshot_by_window::WindowFilters;
-use crate::custom_ocr::perform_ocr_custom;
 use crate::frame_comparison::{FrameComparer, FrameComparisonConfig};
-#[cfg(target_os = "windows")]
-use crate::microsoft::perform_ocr_windows;
+use crate::layout::analyze_layout;
+use crate::masking::{mask_pii_regions, pii_regions, CaptureMask, WindowPlacement};
 use crate::monitor::get_monitor_by_id;
-use crate::ocr_cache::{WindowCacheKey, WindowOcrCache};
-use crate::tesseract::perform_ocr_tesseract;
+use crate::ocr_backend::{create_ocr_backend, OcrBackend, OcrOutput};
+use crate::ocr_cache::{PreviousWindowOcr, WindowCacheKey, WindowOcrCache};
+use crate::region_ocr::recognize_changed_regions;
 use crate::utils::{capture_monitor_image, capture_windows, OcrEngine};
 use anyhow::Result;
 use base64::{engine::general_purpose, Engine as _};
@@ -16,8 +16,10 @@ use chrono::{DateTime, Utc};
 use image::codecs::jpeg::JpegEncoder;
 use image::DynamicImage;
 use image::GenericImageView;
+use image::GrayImage;
+use screenpipe_core::pii_removal::{remove_pii, remove_pii_from_text_json};
 use screenpipe_core::Language;
-use screenpipe_integrations::unstructured_ocr::perform_ocr_cloud;
+use screenpipe_db::OcrLayoutBlock;
 use serde::Deserialize;
 use serde::Deserializer;
 use serde::Serialize;
@@ -107,6 +109,9 @@ pub struct CaptureResult {
     /// Wall-clock timestamp captured atomically with the screenshot
     pub captured_at: DateTime<Utc>,
     pub window_ocr_results: Vec<WindowOcrResult>,
+    /// Video quality of the capture policy of the focused window, used for the video chunk
+    /// starting at this frame
+    pub video_quality: Option<VideoQuality>,
 }
 
 pub struct WindowOcrResult {
@@ -118,6 +123,8 @@ pub struct WindowOcrResult {
     pub focused: bool,
     pub confidence: f64,
     pub browser_url: Option<String>,
+    /// Paragraphs, tables and code blocks of the text, in reading order
+    pub layout: Vec<OcrLayoutBlock>,
 }
 
 pub struct OcrTaskData {
@@ -127,6 +134,8 @@ pub struct OcrTaskData {
     pub timestamp: Instant,
     /// Wall-clock timestamp captured atomically with the screenshot
     pub captured_at: DateTime<Utc>,
+    /// Monitor position in the desktop, to place the windows on `image`
+    pub monitor_bounds: Rect,
     pub result_tx: Sender<CaptureResult>,
 }
 
@@ -157,6 +166,7 @@ pub async fn continuous_capture(
     ocr_engine: OcrEngine,
     monitor_id: u32,
     window_filters: Arc<WindowFilters>,
+    capture_mask: Arc<CaptureMask>,
     languages: Vec<Language>,
     capture_unfocused_windows: bool,
     activity_feed: ActivityFeedOption,
@@ -196,6 +206,8 @@ pub async fn continuous_capture(
         }
     };
     let mut consecutive_capture_failures: u32 = 0;
+    // Capture interval of the policy of the last captured windows
+    let mut policy_interval: Option<Duration> = None;
     const MAX_CAPTURE_RETRIES: u32 = 3;
     const MAX_CONSECUTIVE_FAILURES: u32 = 30;
 
@@ -208,7 +220,7 @@ pub async fn continuous_capture(
         //    Window capture is deferred until after frame comparison to skip
         //    expensive per-window work on unchanged frames.
         let captured_at = Utc::now();
-        let (image, _capture_duration) = {
+        let (mut image, _capture_duration) = {
             let mut last_err = None;
             let mut captured = None;
 
@@ -273,6 +285,9 @@ pub async fn continuous_capture(
             }
         };
 
+        // Mask the static rectangles before anything looks at the frame
+        capture_mask.mask_frame(&mut image, monitor_id);
+
         // 4. Optimized frame comparison: downscales once (proportional to preserve
         //    ultrawide aspect ratios), hashes the thumbnail, then compares histograms.
         //    No full-resolution hash or redundant downscale needed.
@@ -303,18 +318,26 @@ pub async fn continuous_capture(
                 .unwrap_or(interval);
             #[cfg(not(feature = "adaptive-fps"))]
             let sleep_interval = interval;
-            tokio::time::sleep(sleep_interval).await;
+            tokio::time::sleep(policy_interval.unwrap_or(sleep_interval)).await;
             continue;
         }
 
         // 4b. Capture windows only for frames that passed the change threshold.
         //     This avoids expensive per-window screenshots + CGWindowList enumeration
         //     on unchanged frames (major CPU savings on multi-monitor setups).
-        let window_images = capture_windows(
-            &monitor,
-            &window_filters,
-            capture_unfocused_windows,
-        ).await;
+        let mut window_images =
+            capture_windows(&monitor, &window_filters, capture_unfocused_windows).await;
+
+        // Black out the windows matching a blackout rule, and mask the static rectangles
+        // on the window images before they're read
+        let monitor_bounds = Rect {
+            x: monitor.x(),
+            y: monitor.y(),
+            width: monitor.width(),
+            height: monitor.height(),
+        };
+        capture_mask.mask_windows(&mut image, &mut window_images, monitor_id, &monitor_bounds);
+        policy_interval = frame_policy(&window_images).interval();
 
         // Track the frame with maximum difference for OCR processing
         if current_diff > max_avg_value {
@@ -325,6 +348,7 @@ pub async fn continuous_capture(
                 frame_number: frame_counter,
                 timestamp: Instant::now(),
                 captured_at,
+                monitor_bounds,
                 result_tx: result_tx.clone(),
                 average: current_diff,
             });
@@ -338,6 +362,7 @@ pub async fn continuous_capture(
                 &ocr_engine,
                 languages.clone(),
                 ocr_cache.clone(),
+                &capture_mask,
             )
             .await
             {
@@ -367,7 +392,7 @@ pub async fn continuous_capture(
             .unwrap_or(interval);
         #[cfg(not(feature = "adaptive-fps"))]
         let sleep_interval = interval;
-        tokio::time::sleep(sleep_interval).await;
+        tokio::time::sleep(policy_interval.unwrap_or(sleep_interval)).await;
     }
 }
 
@@ -376,6 +401,7 @@ async fn process_max_average_frame(
     ocr_engine: &OcrEngine,
     languages: Vec<Language>,
     ocr_cache: Arc<Mutex<WindowOcrCache>>,
+    capture_mask: &CaptureMask,
 ) -> Result<(), ContinuousCaptureError> {
     let ocr_task_data = OcrTaskData {
         image: max_avg_frame.image,
@@ -383,10 +409,19 @@ async fn process_max_average_frame(
         frame_number: max_avg_frame.frame_number,
         timestamp: max_avg_frame.timestamp,
         captured_at: max_avg_frame.captured_at,
+        monitor_bounds: max_avg_frame.monitor_bounds,
         result_tx: max_avg_frame.result_tx,
     };
 
-    if let Err(e) = process_ocr_task(ocr_task_data, ocr_engine, languages, ocr_cache).await {
+    if let Err(e) = process_ocr_task(
+        ocr_task_data,
+        ocr_engine,
+        languages,
+        ocr_cache,
+        capture_mask,
+    )
+    .await
+    {
         error!("Error processing OCR task: {}", e);
         return Err(ContinuousCaptureError::ErrorProcessingOcr(e.to_string()));
     }
@@ -402,6 +437,7 @@ pub struct MaxAverageFrame {
     pub timestamp: Instant,
     /// Wall-clock timestamp captured atomically with the screenshot
     pub captured_at: DateTime<Utc>,
+    pub monitor_bounds: Rect,
     pub result_tx: Sender<CaptureResult>,
     pub average: f64,
 }
@@ -411,13 +447,15 @@ pub async fn process_ocr_task(
     ocr_engine: &OcrEngine,
     languages: Vec<Language>,
     ocr_cache: Arc<Mutex<WindowOcrCache>>,
+    capture_mask: &CaptureMask,
 ) -> Result<(), ContinuousCaptureError> {
     let OcrTaskData {
-        image,
+        mut image,
         window_images,
         frame_number,
         timestamp,
         captured_at,
+        monitor_bounds,
         result_tx,
     } = ocr_task_data;
 
@@ -436,7 +474,40 @@ pub async fn process_ocr_task(
     // Get screen dimensions for coordinate transformation
     let (screen_width, screen_height) = image.dimensions();
 
+    let ocr_backend = create_ocr_backend(ocr_engine)
+        .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string()))?;
+    let video_quality = frame_policy(&window_images).video_quality;
+
     for captured_window in window_images {
+        let placement = WindowPlacement::new(
+            &captured_window,
+            &monitor_bounds,
+            screen_width,
+            screen_height,
+        );
+
+        // Windows kept out of the video by their policy are masked on the frame, the window
+        // image is still read
+        if !captured_window.policy.video {
+            placement.mask(&mut image);
+        }
+
+        // Windows not read by their policy are recorded without text
+        if !captured_window.policy.ocr {
+            window_ocr_results.push(WindowOcrResult {
+                image: captured_window.image,
+                window_name: captured_window.window_name,
+                app_name: captured_window.app_name,
+                text: String::new(),
+                text_json: Vec::new(),
+                focused: captured_window.is_focused,
+                confidence: 0.0,
+                browser_url: captured_window.browser_url,
+                layout: Vec::new(),
+            });
+            continue;
+        }
+
         // Calculate hash for this window's image
         let window_image_hash =
             WindowOcrCache::calculate_image_hash(captured_window.image.as_bytes());
@@ -453,7 +524,7 @@ pub async fn process_ocr_task(
             cache.get(&cache_key)
         };
 
-        let ocr_result = if let Some(cached) = cached_result {
+        let (mut ocr_result, mut window_blocks) = if let Some(cached) = cached_result {
             // Cache hit - reuse previous OCR result
             cache_hits += 1;
             debug!(
@@ -464,7 +535,7 @@ pub async fn process_ocr_task(
             // Still need to transform coordinates for the current position
             let parsed_json = parse_json_output(&cached.text_json);
             let transformed_json = transform_ocr_coordinates_to_screen(
-                parsed_json,
+                parsed_json.clone(),
                 captured_window.window_x,
                 captured_window.window_y,
                 captured_window.window_width,
@@ -476,23 +547,31 @@ pub async fn process_ocr_task(
             total_confidence += cached.confidence;
             window_count += 1;
 
-            WindowOcrResult {
-                image: captured_window.image,
-                window_name: captured_window.window_name,
-                app_name: captured_window.app_name,
-                text: cached.text.clone(),
-                text_json: transformed_json,
-                focused: captured_window.is_focused,
-                confidence: cached.confidence,
-                browser_url: captured_window.browser_url,
-            }
+            (
+                WindowOcrResult {
+                    image: captured_window.image,
+                    window_name: captured_window.window_name,
+                    app_name: captured_window.app_name,
+                    text: cached.text.clone(),
+                    text_json: transformed_json,
+                    focused: captured_window.is_focused,
+                    confidence: cached.confidence,
+                    browser_url: captured_window.browser_url,
+                    layout: Vec::new(),
+                },
+                parsed_json,
+            )
         } else {
-            // Cache miss - perform OCR
+            // Cache miss - perform OCR of what changed since the last OCR of the window
             cache_misses += 1;
-            let result = process_window_ocr(
+            let previous = ocr_cache.lock().await.take_previous(&window_id);
+            let window_luma = captured_window.image.to_luma8();
+            let (result, window_output) = process_window_ocr(
                 captured_window,
-                ocr_engine,
+                ocr_backend.as_ref(),
                 &languages,
+                &window_luma,
+                previous.as_ref(),
                 &mut total_confidence,
                 &mut window_count,
                 screen_width,
@@ -501,16 +580,52 @@ pub async fn process_ocr_task(
             .await
             .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string()))?;
 
-            // Cache the result for future use (serialize JSON for storage)
+            // Cache the window-relative result for future use (serialize JSON for storage)
+            let window_blocks = window_output.blocks.clone();
             {
                 let mut cache = ocr_cache.lock().await;
-                let json_str = serde_json::to_string(&result.text_json).unwrap_or_default();
-                cache.insert(cache_key, result.text.clone(), json_str, result.confidence);
+                cache.insert(
+                    cache_key,
+                    window_output.text.clone(),
+                    window_output.text_json(),
+                    result.confidence,
+                );
+                cache.set_previous(window_id, window_luma, window_output);
             }
 
-            result
+            (result, window_blocks)
         };
 
+        // Mask PII on the window and the frame before they're stored, and redact it from the text
+        if capture_mask.mask_pii {
+            let (window_width, window_height) = ocr_result.image.dimensions();
+            let regions = pii_regions(
+                &window_blocks,
+                ocr_backend.block_coordinates(),
+                window_width,
+                window_height,
+            );
+            if !regions.is_empty() {
+                debug!(
+                    "Masking {} PII regions of window '{}'",
+                    regions.len(),
+                    ocr_result.window_name
+                );
+                mask_pii_regions(&mut image, &mut ocr_result.image, &placement, &regions);
+            }
+            ocr_result.text = remove_pii(&ocr_result.text);
+            ocr_result.text_json = remove_pii_from_text_json(&ocr_result.text_json);
+            window_blocks = remove_pii_from_text_json(&window_blocks);
+        }
+
+        let (window_width, window_height) = ocr_result.image.dimensions();
+        ocr_result.layout = analyze_layout(
+            &window_blocks,
+            ocr_backend.block_coordinates(),
+            window_width,
+            window_height,
+        );
+
         window_ocr_results.push(ocr_result);
     }
 
@@ -536,6 +651,7 @@ pub async fn process_ocr_task(
         timestamp,
         captured_at,
         window_ocr_results,
+        video_quality,
     };
 
     send_ocr_result(&result_tx, capture_result)
@@ -548,24 +664,33 @@ pub async fn process_ocr_task(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn process_window_ocr(
     captured_window: CapturedWindow,
-    ocr_engine: &OcrEngine,
+    ocr_backend: &dyn OcrBackend,
     languages: &[Language],
+    window_luma: &GrayImage,
+    previous: Option<&PreviousWindowOcr>,
     total_confidence: &mut f64,
     window_count: &mut u32,
     screen_width: u32,
     screen_height: u32,
-) -> Result<WindowOcrResult, ContinuousCaptureError> {
+) -> Result<(WindowOcrResult, OcrOutput), ContinuousCaptureError> {
     // Use the browser URL that was captured atomically with the screenshot
     // This prevents timing mismatches where URL is fetched after browser navigation
     let browser_url = captured_window.browser_url.clone();
 
     // Perform OCR based on the selected engine
-    let (window_text, window_json_output, confidence) =
-        perform_ocr_with_engine(ocr_engine, &captured_window.image, languages.to_vec())
-            .await
-            .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string()))?;
+    let ocr_output = recognize_changed_regions(
+        ocr_backend,
+        &captured_window.image,
+        window_luma,
+        previous,
+        languages,
+    )
+    .await
+    .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string()))?;
+    let confidence = ocr_output.confidence;
 
     // Update confidence metrics
     if let Some(conf) = confidence {
@@ -573,10 +698,9 @@ async fn process_window_ocr(
         *window_count += 1;
     }
 
-    // Parse the OCR JSON and transform coordinates from window-relative to screen-relative
-    let parsed_json = parse_json_output(&window_json_output);
+    // Transform coordinates from window-relative to screen-relative
     let transformed_json = transform_ocr_coordinates_to_screen(
-        parsed_json,
+        ocr_output.blocks.clone(),
         captured_window.window_x,
         captured_window.window_y,
         captured_window.window_width,
@@ -585,41 +709,20 @@ async fn process_window_ocr(
         screen_height,
     );
 
-    Ok(WindowOcrResult {
-        image: captured_window.image,
-        window_name: captured_window.window_name,
-        app_name: captured_window.app_name,
-        text: window_text,
-        text_json: transformed_json,
-        focused: captured_window.is_focused,
-        confidence: confidence.unwrap_or(0.0),
-        browser_url,
-    })
-}
-
-async fn perform_ocr_with_engine(
-    ocr_engine: &OcrEngine,
-    image: &DynamicImage,
-    languages: Vec<Language>,
-) -> Result<(String, String, Option<f64>), ContinuousCaptureError> {
-    match ocr_engine {
-        OcrEngine::Unstructured => perform_ocr_cloud(image, languages)
-            .await
-            .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string())),
-        OcrEngine::Tesseract => Ok(perform_ocr_tesseract(image, languages)),
-        #[cfg(target_os = "windows")]
-        OcrEngine::WindowsNative => perform_ocr_windows(image)
-            .await
-            .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string())),
-        #[cfg(target_os = "macos")]
-        OcrEngine::AppleNative => Ok(perform_ocr_apple(image, &languages)),
-        OcrEngine::Custom(config) => perform_ocr_custom(image, languages, config)
-            .await
-            .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string())),
-        _ => Err(ContinuousCaptureError::ErrorProcessingOcr(
-            "Unsupported OCR engine".to_string(),
-        )),
-    }
+    Ok((
+        WindowOcrResult {
+            image: captured_window.image,
+            window_name: captured_window.window_name,
+            app_name: captured_window.app_name,
+            text: ocr_output.text.clone(),
+            text_json: transformed_json,
+            focused: captured_window.is_focused,
+            confidence: confidence.unwrap_or(0.0),
+            browser_url,
+            layout: Vec::new(),
+        },
+        ocr_output,
+    ))
 }
 
 async fn send_ocr_result(
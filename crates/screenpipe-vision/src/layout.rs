@@ -0,0 +1,468 @@
+//! Layout analysis of the text read in a window.
+//!
+//! Words are grouped into lines by their vertical position, and lines are split into segments
+//! where two words are further apart than [`SEGMENT_GAP`] line heights. Runs of rows of aligned
+//! segments holding a few words each are tables. The other segments are split into columns at
+//! the gutters no segment crosses, then into paragraphs where the gap between two lines or
+//! their height changes. Paragraphs whose words all have the same width per character are
+//! monospace code.
+
+use std::collections::HashMap;
+
+use screenpipe_db::{LayoutBlockKind, OcrLayoutBlock};
+
+use crate::ocr_backend::BlockCoordinates;
+
+/// Gap between two words splitting a line into segments, in line heights
+const SEGMENT_GAP: f64 = 1.5;
+/// Gap between two lines starting a new paragraph, in line heights
+const PARAGRAPH_GAP: f64 = 0.8;
+/// Ratio of the heights of two lines starting a new paragraph, e.g. after a heading
+const PARAGRAPH_HEIGHT_RATIO: f64 = 1.4;
+/// Largest gap between two rows of a table, in line heights
+const TABLE_ROW_GAP: f64 = 2.0;
+/// Largest distance between the edges of two aligned cells, in line heights
+const CELL_ALIGNMENT: f64 = 1.0;
+/// Most words per cell on average for aligned segments to be a table rather than columns of text
+const TABLE_MAX_WORDS_PER_CELL: f64 = 4.0;
+/// Narrowest gap between two columns, in line heights
+const GUTTER_WIDTH: f64 = 1.5;
+/// Largest coefficient of variation of the width per character of the words of monospace text
+const MONOSPACE_MAX_VARIATION: f64 = 0.05;
+/// Fewest words of three characters or more to tell monospace text apart
+const MONOSPACE_MIN_WORDS: usize = 3;
+
+#[derive(Debug, Clone)]
+struct Word {
+    text: String,
+    left: f64,
+    top: f64,
+    right: f64,
+    bottom: f64,
+}
+
+impl Word {
+    fn height(&self) -> f64 {
+        self.bottom - self.top
+    }
+
+    fn center_y(&self) -> f64 {
+        (self.top + self.bottom) / 2.0
+    }
+}
+
+/// Words of a line close enough to each other to be read together.
+#[derive(Debug, Clone)]
+struct Segment {
+    words: Vec<Word>,
+    left: f64,
+    top: f64,
+    right: f64,
+    bottom: f64,
+}
+
+impl Segment {
+    fn new(words: Vec<Word>) -> Self {
+        let mut segment = Self {
+            words: Vec::new(),
+            left: f64::INFINITY,
+            top: f64::INFINITY,
+            right: f64::NEG_INFINITY,
+            bottom: f64::NEG_INFINITY,
+        };
+        for word in &words {
+            segment.left = segment.left.min(word.left);
+            segment.top = segment.top.min(word.top);
+            segment.right = segment.right.max(word.right);
+            segment.bottom = segment.bottom.max(word.bottom);
+        }
+        segment.words = words;
+        segment
+    }
+
+    fn height(&self) -> f64 {
+        self.bottom - self.top
+    }
+
+    fn center_y(&self) -> f64 {
+        (self.top + self.bottom) / 2.0
+    }
+
+    fn text(&self) -> String {
+        join_words(&self.words)
+    }
+
+    /// Whether the edges or centers of the segments are within `tolerance` of each other
+    fn aligns_with(&self, other: &Segment, tolerance: f64) -> bool {
+        (self.left - other.left).abs() <= tolerance
+            || (self.right - other.right).abs() <= tolerance
+            || ((self.left + self.right) - (other.left + other.right)).abs() / 2.0 <= tolerance
+    }
+}
+
+/// Paragraphs, tables and code blocks of the text read in a `width` x `height` window image, in
+/// reading order. Empty when the engine doesn't tell where its `blocks` are.
+pub fn analyze_layout(
+    blocks: &[HashMap<String, String>],
+    coordinates: Option<BlockCoordinates>,
+    width: u32,
+    height: u32,
+) -> Vec<OcrLayoutBlock> {
+    let Some(coordinates) = coordinates else {
+        return Vec::new();
+    };
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    let words: Vec<Word> = blocks
+        .iter()
+        .filter_map(|block| {
+            let text = block.get("text")?.trim();
+            let [left, top, block_width, block_height] =
+                coordinates.block_pixels(block, width, height)?;
+            (!text.is_empty() && block_width > 0.0 && block_height > 0.0).then(|| Word {
+                text: text.to_string(),
+                left,
+                top,
+                right: left + block_width,
+                bottom: top + block_height,
+            })
+        })
+        .collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+
+    let rows: Vec<Vec<Segment>> = group_lines(words).into_iter().map(split_line).collect();
+    let tables = find_tables(&rows);
+
+    let mut text_segments = Vec::new();
+    let mut table_blocks = Vec::new();
+    let mut row_index = 0;
+    for (start, end) in tables.iter().copied().chain([(rows.len(), rows.len())]) {
+        text_segments.extend(rows[row_index..start].iter().flatten().cloned());
+        if start < end {
+            table_blocks.push(&rows[start..end]);
+        }
+        row_index = end;
+    }
+
+    let line_height = median(rows.iter().flatten().map(Segment::height));
+    let gutters = find_gutters(&text_segments, line_height);
+    let column_of = |left: f64| gutters.iter().filter(|&&gutter| gutter < left).count() as u32;
+
+    let mut layout: Vec<OcrLayoutBlock> = table_blocks
+        .into_iter()
+        .map(|table_rows| {
+            let mut block = table_block(table_rows);
+            block.column = column_of(block.left);
+            block
+        })
+        .collect();
+
+    let mut columns: Vec<Vec<Segment>> = vec![Vec::new(); gutters.len() + 1];
+    for segment in text_segments {
+        columns[column_of(segment.left) as usize].push(segment);
+    }
+    for (column, mut segments) in columns.into_iter().enumerate() {
+        segments.sort_by(|a, b| a.top.total_cmp(&b.top).then(a.left.total_cmp(&b.left)));
+        for paragraph in group_paragraphs(segments) {
+            let mut block = text_block(&paragraph);
+            block.column = column as u32;
+            layout.push(block);
+        }
+    }
+
+    layout.sort_by(|a, b| a.column.cmp(&b.column).then(a.top.total_cmp(&b.top)));
+    for block in &mut layout {
+        block.left /= width as f64;
+        block.width /= width as f64;
+        block.top /= height as f64;
+        block.height /= height as f64;
+    }
+    layout
+}
+
+/// Group the words into lines, from the top.
+fn group_lines(mut words: Vec<Word>) -> Vec<Vec<Word>> {
+    words.sort_by(|a, b| a.center_y().total_cmp(&b.center_y()));
+    let mut lines: Vec<Vec<Word>> = Vec::new();
+    for word in words {
+        match lines.last_mut() {
+            Some(line) if on_line(line, &word) => line.push(word),
+            _ => lines.push(vec![word]),
+        }
+    }
+    for line in &mut lines {
+        line.sort_by(|a, b| a.left.total_cmp(&b.left));
+    }
+    lines
+}
+
+fn on_line(line: &[Word], word: &Word) -> bool {
+    let count = line.len() as f64;
+    let center = line.iter().map(Word::center_y).sum::<f64>() / count;
+    let height = line.iter().map(Word::height).sum::<f64>() / count;
+    (word.center_y() - center).abs() <= height.min(word.height()) / 2.0
+}
+
+/// Split a line, sorted from the left, where its words are far apart.
+fn split_line(line: Vec<Word>) -> Vec<Segment> {
+    let max_gap = SEGMENT_GAP * median(line.iter().map(Word::height));
+    let mut segments: Vec<Vec<Word>> = Vec::new();
+    for word in line {
+        match segments.last_mut() {
+            Some(segment) if word.left - segment[segment.len() - 1].right <= max_gap => {
+                segment.push(word)
+            }
+            _ => segments.push(vec![word]),
+        }
+    }
+    segments.into_iter().map(Segment::new).collect()
+}
+
+/// Ranges of `rows` forming tables: two rows or more of two cells or more aligned with the cells
+/// of the first row.
+fn find_tables(rows: &[Vec<Segment>]) -> Vec<(usize, usize)> {
+    let mut tables = Vec::new();
+    let mut start = 0;
+    while start < rows.len() {
+        let header = &rows[start];
+        let mut end = start + 1;
+        if header.len() >= 2 {
+            while end < rows.len() && continues_table(header, &rows[end - 1], &rows[end]) {
+                end += 1;
+            }
+        }
+        let cells = rows[start..end].iter().map(Vec::len).sum::<usize>() as f64;
+        let words = rows[start..end]
+            .iter()
+            .flatten()
+            .map(|segment| segment.words.len())
+            .sum::<usize>() as f64;
+        if end - start >= 2 && words / cells <= TABLE_MAX_WORDS_PER_CELL {
+            tables.push((start, end));
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+    tables
+}
+
+fn continues_table(header: &[Segment], previous: &[Segment], row: &[Segment]) -> bool {
+    let line_height = median(row.iter().map(Segment::height));
+    let row_top = row.iter().map(|s| s.top).fold(f64::INFINITY, f64::min);
+    let previous_bottom = previous
+        .iter()
+        .map(|s| s.bottom)
+        .fold(f64::NEG_INFINITY, f64::max);
+    row.len() >= 2
+        && row_top - previous_bottom <= TABLE_ROW_GAP * line_height
+        && row.iter().all(|cell| {
+            header
+                .iter()
+                .any(|column| cell.aligns_with(column, CELL_ALIGNMENT * line_height))
+        })
+}
+
+fn table_block(rows: &[Vec<Segment>]) -> OcrLayoutBlock {
+    let header = &rows[0];
+    let line_height = median(rows.iter().flatten().map(Segment::height));
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            let mut cells = vec![String::new(); header.len()];
+            for segment in row {
+                let column = header
+                    .iter()
+                    .position(|column| segment.aligns_with(column, CELL_ALIGNMENT * line_height))
+                    .unwrap_or(0);
+                if !cells[column].is_empty() {
+                    cells[column].push(' ');
+                }
+                cells[column].push_str(&segment.text());
+            }
+            cells
+        })
+        .collect();
+    let segments: Vec<&Segment> = rows.iter().flatten().collect();
+    let [left, top, width, height] = bounds(&segments);
+
+    OcrLayoutBlock {
+        kind: LayoutBlockKind::Table,
+        column: 0,
+        text: cells
+            .iter()
+            .map(|row| row.join("\t"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        rows: cells,
+        left,
+        top,
+        width,
+        height,
+    }
+}
+
+/// Positions between the columns of text no segment crosses, from the left.
+fn find_gutters(segments: &[Segment], line_height: f64) -> Vec<f64> {
+    let mut spans: Vec<(f64, f64)> = segments.iter().map(|s| (s.left, s.right)).collect();
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut gutters = Vec::new();
+    let mut covered_right = match spans.first() {
+        Some(&(_, right)) => right,
+        None => return gutters,
+    };
+    for (left, right) in spans.into_iter().skip(1) {
+        if left - covered_right >= GUTTER_WIDTH * line_height {
+            gutters.push((covered_right + left) / 2.0);
+        }
+        covered_right = covered_right.max(right);
+    }
+    gutters
+}
+
+/// Group the segments of a column, sorted from the top, into paragraphs.
+fn group_paragraphs(segments: Vec<Segment>) -> Vec<Vec<Segment>> {
+    let mut paragraphs: Vec<Vec<Segment>> = Vec::new();
+    for segment in segments {
+        let continues = paragraphs.last().is_some_and(|paragraph| {
+            let previous = &paragraph[paragraph.len() - 1];
+            let height_ratio =
+                previous.height().max(segment.height()) / previous.height().min(segment.height());
+            segment.top - previous.bottom <= PARAGRAPH_GAP * previous.height()
+                && height_ratio <= PARAGRAPH_HEIGHT_RATIO
+        });
+        match paragraphs.last_mut() {
+            Some(paragraph) if continues => paragraph.push(segment),
+            _ => paragraphs.push(vec![segment]),
+        }
+    }
+    paragraphs
+}
+
+fn text_block(paragraph: &[Segment]) -> OcrLayoutBlock {
+    let segments: Vec<&Segment> = paragraph.iter().collect();
+    let [left, top, width, height] = bounds(&segments);
+
+    // Segments of a paragraph on the same line are read together
+    let mut lines: Vec<Vec<&Word>> = Vec::new();
+    let mut line_center = f64::NEG_INFINITY;
+    for segment in paragraph {
+        match lines.last_mut() {
+            Some(line) if (segment.center_y() - line_center).abs() <= segment.height() / 2.0 => {
+                line.extend(&segment.words)
+            }
+            _ => {
+                lines.push(segment.words.iter().collect());
+                line_center = segment.center_y();
+            }
+        }
+    }
+    for line in &mut lines {
+        line.sort_by(|a, b| a.left.total_cmp(&b.left));
+    }
+
+    let (kind, text) = match monospace_char_width(paragraph) {
+        Some(char_width) => (LayoutBlockKind::Code, code_text(&lines, left, char_width)),
+        None => (
+            LayoutBlockKind::Paragraph,
+            lines
+                .iter()
+                .map(|line| join_words(line.iter().copied()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+    };
+
+    OcrLayoutBlock {
+        kind,
+        column: 0,
+        text,
+        rows: Vec::new(),
+        left,
+        top,
+        width,
+        height,
+    }
+}
+
+/// Width of a character of the paragraph, when its words are monospace.
+fn monospace_char_width(paragraph: &[Segment]) -> Option<f64> {
+    let widths: Vec<f64> = paragraph
+        .iter()
+        .flat_map(|segment| &segment.words)
+        .filter_map(|word| {
+            let chars = word.text.chars().count();
+            (chars >= 3).then(|| (word.right - word.left) / chars as f64)
+        })
+        .collect();
+    if widths.len() < MONOSPACE_MIN_WORDS {
+        return None;
+    }
+    let mean = widths.iter().sum::<f64>() / widths.len() as f64;
+    let variance = widths.iter().map(|w| (w - mean).powi(2)).sum::<f64>() / widths.len() as f64;
+    (mean > 0.0 && variance.sqrt() / mean <= MONOSPACE_MAX_VARIATION).then_some(mean)
+}
+
+/// Lines of code, indented and spaced by the number of characters fitting before their words.
+fn code_text(lines: &[Vec<&Word>], left: f64, char_width: f64) -> String {
+    let spaces = |width: f64| " ".repeat((width / char_width).round().max(0.0) as usize);
+    lines
+        .iter()
+        .map(|line| {
+            let mut text = String::new();
+            let mut previous_right = None;
+            for word in line {
+                match previous_right {
+                    Some(right) => {
+                        let gap = spaces(word.left - right);
+                        text.push_str(if gap.is_empty() { " " } else { &gap });
+                    }
+                    None => text.push_str(&spaces(word.left - left)),
+                }
+                text.push_str(&word.text);
+                previous_right = Some(word.right);
+            }
+            text
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn join_words<'a>(words: impl IntoIterator<Item = &'a Word>) -> String {
+    words
+        .into_iter()
+        .map(|word| word.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `left`, `top`, `width` and `height` of the segments.
+fn bounds(segments: &[&Segment]) -> [f64; 4] {
+    let left = segments
+        .iter()
+        .map(|s| s.left)
+        .fold(f64::INFINITY, f64::min);
+    let top = segments.iter().map(|s| s.top).fold(f64::INFINITY, f64::min);
+    let right = segments
+        .iter()
+        .map(|s| s.right)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let bottom = segments
+        .iter()
+        .map(|s| s.bottom)
+        .fold(f64::NEG_INFINITY, f64::max);
+    [left, top, right - left, bottom - top]
+}
+
+fn median(values: impl Iterator<Item = f64>) -> f64 {
+    let mut values: Vec<f64> = values.collect();
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    values[values.len() / 2]
+}
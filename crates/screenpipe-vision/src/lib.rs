@@ -7,20 +7,29 @@ pub use crate::core::ActivityFeedOption;
 
 #[cfg(target_os = "macos")]
 pub mod apple;
+pub mod capture_policy;
 pub mod core;
 pub mod custom_ocr;
 pub mod frame_comparison;
+pub mod layout;
+pub mod masking;
 #[cfg(target_os = "windows")]
 pub mod microsoft;
 pub mod monitor;
 pub use monitor::MonitorListError;
+pub mod ocr_backend;
 pub mod ocr_cache;
+pub mod onnx_ocr;
+pub mod region_ocr;
 pub mod tesseract;
 pub mod utils;
 #[cfg(target_os = "macos")]
 pub use apple::perform_ocr_apple;
 pub use core::{continuous_capture, process_ocr_task, CaptureResult, RealtimeVisionEvent};
 // pub use types::CaptureResult;
+pub use capture_policy::{CapturePolicy, CapturePolicyFile, VideoQuality};
+pub use masking::{CaptureMask, MaskRect};
+pub use ocr_backend::{create_ocr_backend, preload_onnx_ocr, OcrBackend, OcrOutput};
 pub use utils::OcrEngine;
 pub mod capture_screenshot_by_window;
 pub use custom_ocr::perform_ocr_custom;
@@ -0,0 +1,285 @@
+//! Capture-time masking.
+//!
+//! Masked areas are painted black on the monitor frame before it's encoded to video and on the
+//! window images before they're read, so they never reach the video files or the OCR table:
+//! - static rectangles of a monitor, see [`MaskRect`],
+//! - the parts of windows matching a blackout rule not covered by other windows, see
+//!   [`WindowFilters::with_blackout`],
+//! - words detected as PII, once the window has been read.
+//!
+//! [`WindowFilters::with_blackout`]: crate::capture_screenshot_by_window::WindowFilters::with_blackout
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use screenpipe_core::pii_removal::{detect_pii_regions, PiiRegion};
+
+use crate::capture_screenshot_by_window::{CapturedWindow, Rect};
+use crate::ocr_backend::BlockCoordinates;
+
+const MASK_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// Rectangle masked on the frames of a monitor, in pixels of the frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaskRect {
+    /// Monitor the rectangle is masked on, all of them when `None`
+    pub monitor_id: Option<u32>,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl MaskRect {
+    fn applies_to(&self, monitor_id: u32) -> bool {
+        self.monitor_id.is_none_or(|id| id == monitor_id)
+    }
+}
+
+impl FromStr for MaskRect {
+    type Err = anyhow::Error;
+
+    /// Parse `x,y,width,height`, prefixed with `monitor_id:` to mask a single monitor.
+    fn from_str(s: &str) -> Result<Self> {
+        let (monitor_id, rect) = match s.split_once(':') {
+            Some((monitor_id, rect)) => (Some(monitor_id.trim().parse()?), rect),
+            None => (None, s),
+        };
+        let values = rect
+            .split(',')
+            .map(|value| value.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let [x, y, width, height] = values[..] else {
+            return Err(anyhow!(
+                "expected [monitor_id:]x,y,width,height, got {:?}",
+                s
+            ));
+        };
+        Ok(Self {
+            monitor_id,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+/// What to mask at capture time.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureMask {
+    pub rects: Vec<MaskRect>,
+    /// Windows to black out, given to [`WindowFilters::with_blackout`] with `blackout_urls`
+    ///
+    /// [`WindowFilters::with_blackout`]: crate::capture_screenshot_by_window::WindowFilters::with_blackout
+    pub blackout_windows: Vec<String>,
+    pub blackout_urls: Vec<String>,
+    /// Mask the words detected as PII on the frames and window images, and redact them from
+    /// the text
+    pub mask_pii: bool,
+}
+
+impl CaptureMask {
+    /// Mask the static rectangles of `monitor_id` on its frame.
+    pub fn mask_frame(&self, frame: &mut DynamicImage, monitor_id: u32) {
+        for rect in self.rects.iter().filter(|rect| rect.applies_to(monitor_id)) {
+            fill(
+                frame,
+                rect.x as f64,
+                rect.y as f64,
+                rect.width as f64,
+                rect.height as f64,
+            );
+        }
+    }
+
+    /// Mask the visible part of the blacked out windows on the frame of the monitor at
+    /// `monitor`, and remove them from `windows`. The static rectangles are masked on the
+    /// images of the other windows.
+    pub fn mask_windows(
+        &self,
+        frame: &mut DynamicImage,
+        windows: &mut Vec<CapturedWindow>,
+        monitor_id: u32,
+        monitor: &Rect,
+    ) {
+        let (frame_width, frame_height) = frame.dimensions();
+        let scale_x = frame_scale(frame_width, monitor.width);
+        let scale_y = frame_scale(frame_height, monitor.height);
+        windows.retain_mut(|window| {
+            if let Some(visible) = &window.blackout {
+                for rect in visible {
+                    fill(
+                        frame,
+                        (rect.x - monitor.x) as f64 * scale_x,
+                        (rect.y - monitor.y) as f64 * scale_y,
+                        rect.width as f64 * scale_x,
+                        rect.height as f64 * scale_y,
+                    );
+                }
+                return false;
+            }
+            let placement = WindowPlacement::new(window, monitor, frame_width, frame_height);
+            for rect in self.rects.iter().filter(|rect| rect.applies_to(monitor_id)) {
+                let (left, top) = placement.window_point(rect.x as f64, rect.y as f64);
+                let (right, bottom) = placement.window_point(
+                    rect.x as f64 + rect.width as f64,
+                    rect.y as f64 + rect.height as f64,
+                );
+                fill(&mut window.image, left, top, right - left, bottom - top);
+            }
+            true
+        });
+    }
+}
+
+/// Where a window image lies on the frame of its monitor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowPlacement {
+    /// Window bounds in pixels of the frame
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Pixels of the frame per pixel of the window image
+    scale_x: f64,
+    scale_y: f64,
+}
+
+impl WindowPlacement {
+    /// Placement of `window` on the `frame_width` x `frame_height` frame of the monitor at
+    /// `monitor`. Window and monitor bounds are in desktop coordinates, which can differ from
+    /// pixels on HiDPI screens.
+    pub fn new(
+        window: &CapturedWindow,
+        monitor: &Rect,
+        frame_width: u32,
+        frame_height: u32,
+    ) -> Self {
+        let frame_scale_x = frame_scale(frame_width, monitor.width);
+        let frame_scale_y = frame_scale(frame_height, monitor.height);
+        let width = window.window_width as f64 * frame_scale_x;
+        let height = window.window_height as f64 * frame_scale_y;
+        let (image_width, image_height) = window.image.dimensions();
+
+        Self {
+            left: (window.window_x - monitor.x) as f64 * frame_scale_x,
+            top: (window.window_y - monitor.y) as f64 * frame_scale_y,
+            width,
+            height,
+            scale_x: if image_width == 0 {
+                1.0
+            } else {
+                width / image_width as f64
+            },
+            scale_y: if image_height == 0 {
+                1.0
+            } else {
+                height / image_height as f64
+            },
+        }
+    }
+
+    /// Mask the whole window on `frame`.
+    pub fn mask(&self, frame: &mut DynamicImage) {
+        fill(frame, self.left, self.top, self.width, self.height);
+    }
+
+    /// Point `x`, `y` of the window image on the frame.
+    pub fn frame_point(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.left + x * self.scale_x, self.top + y * self.scale_y)
+    }
+
+    /// Point `x`, `y` of the frame on the window image.
+    pub fn window_point(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            (x - self.left) / self.scale_x,
+            (y - self.top) / self.scale_y,
+        )
+    }
+}
+
+/// Pixels of the frame per unit of desktop coordinates, along a `frame` pixels wide monitor
+/// `monitor` units wide.
+fn frame_scale(frame: u32, monitor: u32) -> f64 {
+    if monitor == 0 {
+        1.0
+    } else {
+        frame as f64 / monitor as f64
+    }
+}
+
+/// Regions of the `width` x `height` window image holding PII, found in the `blocks` read
+/// in it. Blocks of engines not telling their coordinates are left to
+/// [`detect_pii_regions`] to guess.
+pub fn pii_regions(
+    blocks: &[HashMap<String, String>],
+    coordinates: Option<BlockCoordinates>,
+    width: u32,
+    height: u32,
+) -> Vec<PiiRegion> {
+    let Some(coordinates) = coordinates else {
+        return detect_pii_regions(blocks, width, height);
+    };
+    let pixel_blocks: Vec<HashMap<String, String>> = blocks
+        .iter()
+        .filter_map(|block| {
+            let [left, top, block_width, block_height] =
+                coordinates.block_pixels(block, width, height)?;
+            let mut block = block.clone();
+            for (key, value) in [
+                ("left", left),
+                ("top", top),
+                ("width", block_width),
+                ("height", block_height),
+            ] {
+                block.insert(key.to_string(), value.round().max(0.0).to_string());
+            }
+            Some(block)
+        })
+        .collect();
+    detect_pii_regions(&pixel_blocks, width, height)
+}
+
+/// Mask `regions` of the window image, and the same area of the frame it lies on.
+pub fn mask_pii_regions(
+    frame: &mut DynamicImage,
+    window_image: &mut DynamicImage,
+    placement: &WindowPlacement,
+    regions: &[PiiRegion],
+) {
+    for region in regions {
+        let (x, y, width, height) = (
+            region.x as f64,
+            region.y as f64,
+            region.width as f64,
+            region.height as f64,
+        );
+        fill(window_image, x, y, width, height);
+        let (left, top) = placement.frame_point(x, y);
+        let (right, bottom) = placement.frame_point(x + width, y + height);
+        fill(frame, left, top, right - left, bottom - top);
+    }
+}
+
+/// Paint the pixels of `image` touched by the rectangle black.
+pub fn fill(image: &mut DynamicImage, left: f64, top: f64, width: f64, height: f64) {
+    let (image_width, image_height) = image.dimensions();
+    let clamp = |value: f64, max: u32| value.clamp(0.0, max as f64) as u32;
+    let (x_start, y_start) = (
+        clamp(left.floor(), image_width),
+        clamp(top.floor(), image_height),
+    );
+    let (x_end, y_end) = (
+        clamp((left + width).ceil(), image_width),
+        clamp((top + height).ceil(), image_height),
+    );
+
+    for y in y_start..y_end {
+        for x in x_start..x_end {
+            image.put_pixel(x, y, MASK_COLOR);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! OCR backends.
+//!
+//! Every engine implements [`OcrBackend`]. Capture gets the backend of the configured
+//! [`OcrEngine`] from [`create_ocr_backend`], so adding an engine doesn't touch the pipeline.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use image::DynamicImage;
+use once_cell::sync::Lazy;
+use screenpipe_core::Language;
+use screenpipe_integrations::unstructured_ocr::perform_ocr_cloud;
+use tokio::sync::OnceCell;
+use tracing::error;
+
+#[cfg(target_os = "macos")]
+use crate::apple::perform_ocr_apple;
+use crate::custom_ocr::{perform_ocr_custom, CustomOcrConfig};
+#[cfg(target_os = "windows")]
+use crate::microsoft::perform_ocr_windows;
+use crate::onnx_ocr::{get_or_download_onnx_ocr_models, OnnxOcr};
+use crate::tesseract::perform_ocr_tesseract;
+use crate::utils::OcrEngine;
+
+/// Text read from an image.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OcrOutput {
+    pub text: String,
+    /// Text blocks with their position, stored as the `text_json` of the frame
+    pub blocks: Vec<HashMap<String, String>>,
+    pub confidence: Option<f64>,
+}
+
+impl OcrOutput {
+    /// Output of the engines returning their blocks as JSON.
+    pub fn from_json(text: String, json: &str, confidence: Option<f64>) -> Self {
+        let blocks = serde_json::from_str(json).unwrap_or_else(|e| {
+            error!("Failed to parse JSON output: {}", e);
+            Vec::new()
+        });
+        Self {
+            text,
+            blocks,
+            confidence,
+        }
+    }
+
+    pub fn text_json(&self) -> String {
+        serde_json::to_string(&self.blocks).unwrap_or_else(|_| "[]".to_string())
+    }
+}
+
+/// How an engine positions its blocks in the image it read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockCoordinates {
+    /// Pixels from the top left corner
+    Pixels,
+    /// Fractions of the image size from the top left corner
+    Normalized,
+    /// Fractions of the image size from the bottom left corner, as Apple Vision
+    NormalizedBottomLeft,
+}
+
+impl BlockCoordinates {
+    /// `left`, `top`, `width` and `height` of `block` in pixels from the top left corner of the
+    /// `image_width` x `image_height` image it was read in, `None` when it isn't positioned.
+    pub fn block_pixels(
+        self,
+        block: &HashMap<String, String>,
+        image_width: u32,
+        image_height: u32,
+    ) -> Option<[f64; 4]> {
+        let coordinate = |key: &str| block.get(key)?.parse::<f64>().ok();
+        let (left, top) = (coordinate("left")?, coordinate("top")?);
+        let (width, height) = (coordinate("width")?, coordinate("height")?);
+        let (image_width, image_height) = (image_width as f64, image_height as f64);
+
+        Some(match self {
+            BlockCoordinates::Pixels => [left, top, width, height],
+            BlockCoordinates::Normalized => [
+                left * image_width,
+                top * image_height,
+                width * image_width,
+                height * image_height,
+            ],
+            BlockCoordinates::NormalizedBottomLeft => [
+                left * image_width,
+                (1.0 - top - height) * image_height,
+                width * image_width,
+                height * image_height,
+            ],
+        })
+    }
+}
+
+#[async_trait]
+pub trait OcrBackend: Send + Sync {
+    /// Read the text of `image`, in one of `languages` when the engine supports choosing them.
+    async fn recognize(&self, image: &DynamicImage, languages: &[Language]) -> Result<OcrOutput>;
+
+    /// Coordinates of the `left`, `top`, `width` and `height` of the blocks, `None` when they
+    /// aren't known, which disables reading only the changed regions of windows.
+    fn block_coordinates(&self) -> Option<BlockCoordinates> {
+        None
+    }
+}
+
+/// Backend running `engine`, an error when it isn't available on this platform.
+pub fn create_ocr_backend(engine: &OcrEngine) -> Result<Arc<dyn OcrBackend>> {
+    match engine {
+        OcrEngine::Unstructured => Ok(Arc::new(UnstructuredBackend)),
+        OcrEngine::Tesseract => Ok(Arc::new(TesseractBackend)),
+        #[cfg(target_os = "windows")]
+        OcrEngine::WindowsNative => Ok(Arc::new(WindowsNativeBackend)),
+        #[cfg(target_os = "macos")]
+        OcrEngine::AppleNative => Ok(Arc::new(AppleNativeBackend)),
+        OcrEngine::Custom(config) => Ok(Arc::new(CustomBackend {
+            config: config.clone(),
+        })),
+        // Shared so the models are only loaded once
+        OcrEngine::Onnx => Ok(ONNX_BACKEND.clone()),
+        #[allow(unreachable_patterns)]
+        _ => Err(anyhow!("Unsupported OCR engine {:?}", engine)),
+    }
+}
+
+pub struct UnstructuredBackend;
+
+#[async_trait]
+impl OcrBackend for UnstructuredBackend {
+    async fn recognize(&self, image: &DynamicImage, languages: &[Language]) -> Result<OcrOutput> {
+        let (text, json, confidence) = perform_ocr_cloud(image, languages.to_vec()).await?;
+        Ok(OcrOutput::from_json(text, &json, confidence))
+    }
+}
+
+pub struct TesseractBackend;
+
+#[async_trait]
+impl OcrBackend for TesseractBackend {
+    async fn recognize(&self, image: &DynamicImage, languages: &[Language]) -> Result<OcrOutput> {
+        let (text, json, confidence) = perform_ocr_tesseract(image, languages.to_vec())?;
+        Ok(OcrOutput::from_json(text, &json, confidence))
+    }
+
+    fn block_coordinates(&self) -> Option<BlockCoordinates> {
+        Some(BlockCoordinates::Pixels)
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub struct WindowsNativeBackend;
+
+#[cfg(target_os = "windows")]
+#[async_trait]
+impl OcrBackend for WindowsNativeBackend {
+    async fn recognize(&self, image: &DynamicImage, _languages: &[Language]) -> Result<OcrOutput> {
+        let (text, json, confidence) = perform_ocr_windows(image).await?;
+        Ok(OcrOutput::from_json(text, &json, confidence))
+    }
+
+    fn block_coordinates(&self) -> Option<BlockCoordinates> {
+        Some(BlockCoordinates::Pixels)
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub struct AppleNativeBackend;
+
+#[cfg(target_os = "macos")]
+#[async_trait]
+impl OcrBackend for AppleNativeBackend {
+    async fn recognize(&self, image: &DynamicImage, languages: &[Language]) -> Result<OcrOutput> {
+        let (text, json, confidence) = perform_ocr_apple(image, languages);
+        Ok(OcrOutput::from_json(text, &json, confidence))
+    }
+
+    fn block_coordinates(&self) -> Option<BlockCoordinates> {
+        Some(BlockCoordinates::NormalizedBottomLeft)
+    }
+}
+
+pub struct CustomBackend {
+    config: CustomOcrConfig,
+}
+
+#[async_trait]
+impl OcrBackend for CustomBackend {
+    async fn recognize(&self, image: &DynamicImage, languages: &[Language]) -> Result<OcrOutput> {
+        let (text, json, confidence) =
+            perform_ocr_custom(image, languages.to_vec(), &self.config).await?;
+        Ok(OcrOutput::from_json(text, &json, confidence))
+    }
+}
+
+static ONNX_BACKEND: Lazy<Arc<OnnxBackend>> = Lazy::new(|| Arc::new(OnnxBackend::default()));
+
+/// Load the models of the ONNX engine, downloading them if needed, so that a missing or
+/// refused model fails at startup instead of on the first frame.
+pub async fn preload_onnx_ocr() -> Result<()> {
+    ONNX_BACKEND.ocr().await.map(|_| ())
+}
+
+/// Local PaddleOCR models, see [`crate::onnx_ocr`]. They read Chinese and English whatever
+/// the languages.
+#[derive(Default)]
+pub struct OnnxBackend {
+    /// Loaded once: a failed load is kept, not retried on every frame
+    ocr: OnceCell<Result<Arc<OnnxOcr>, String>>,
+}
+
+impl OnnxBackend {
+    async fn ocr(&self) -> Result<Arc<OnnxOcr>> {
+        self.ocr
+            .get_or_init(|| async {
+                let load = async {
+                    let dir = get_or_download_onnx_ocr_models().await?;
+                    let ocr = tokio::task::spawn_blocking(move || OnnxOcr::load(&dir)).await??;
+                    Ok::<_, anyhow::Error>(Arc::new(ocr))
+                };
+                load.await.map_err(|e| {
+                    error!("failed to load the onnx ocr models: {:#}", e);
+                    format!("{:#}", e)
+                })
+            })
+            .await
+            .clone()
+            .map_err(|e| anyhow!("onnx ocr models unavailable: {}", e))
+    }
+}
+
+#[async_trait]
+impl OcrBackend for OnnxBackend {
+    async fn recognize(&self, image: &DynamicImage, _languages: &[Language]) -> Result<OcrOutput> {
+        let ocr = self.ocr().await?;
+        let image = image.clone();
+        tokio::task::spawn_blocking(move || ocr.recognize(&image)).await?
+    }
+
+    fn block_coordinates(&self) -> Option<BlockCoordinates> {
+        Some(BlockCoordinates::Normalized)
+    }
+}
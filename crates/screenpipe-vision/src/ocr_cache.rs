@@ -2,6 +2,13 @@ use std::collections::HashMap;
 use std::hash::{DefaultHasher, Hash, Hasher};
 use std::time::{Duration, Instant};
 
+use image::GrayImage;
+
+use crate::ocr_backend::OcrOutput;
+
+/// Windows whose last capture is kept to read only what changes in the next one
+const MAX_PREVIOUS_WINDOWS: usize = 16;
+
 /// Cached OCR result for a window
 #[derive(Clone, Debug)]
 pub struct CachedOcrResult {
@@ -11,6 +18,14 @@ pub struct CachedOcrResult {
     pub cached_at: Instant,
 }
 
+/// Last OCR of a window with the grayscale capture it was read from
+#[derive(Clone, Debug)]
+pub struct PreviousWindowOcr {
+    pub image: GrayImage,
+    pub output: OcrOutput,
+    pub cached_at: Instant,
+}
+
 /// Key for identifying a window's content
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
 pub struct WindowCacheKey {
@@ -23,6 +38,8 @@ pub struct WindowCacheKey {
 /// Cache for window OCR results to avoid re-processing unchanged windows
 pub struct WindowOcrCache {
     cache: HashMap<WindowCacheKey, CachedOcrResult>,
+    /// Last OCR of each window, by window ID, see [`crate::region_ocr`]
+    previous: HashMap<String, PreviousWindowOcr>,
     /// Maximum age before a cached result is considered stale
     max_age: Duration,
     /// Maximum number of entries to prevent unbounded memory growth
@@ -36,6 +53,7 @@ impl WindowOcrCache {
     pub fn new(max_age: Duration, max_entries: usize) -> Self {
         Self {
             cache: HashMap::new(),
+            previous: HashMap::new(),
             max_age,
             max_entries,
             hits: 0,
@@ -99,6 +117,36 @@ impl WindowOcrCache {
         );
     }
 
+    /// Take the last OCR of a window, unless it is stale
+    pub fn take_previous(&mut self, window_id: &str) -> Option<PreviousWindowOcr> {
+        self.previous
+            .remove(window_id)
+            .filter(|previous| previous.cached_at.elapsed() < self.max_age)
+    }
+
+    /// Store the last OCR of a window
+    pub fn set_previous(&mut self, window_id: String, image: GrayImage, output: OcrOutput) {
+        if self.previous.len() >= MAX_PREVIOUS_WINDOWS && !self.previous.contains_key(&window_id) {
+            if let Some(oldest) = self
+                .previous
+                .iter()
+                .min_by_key(|(_, v)| v.cached_at)
+                .map(|(k, _)| k.clone())
+            {
+                self.previous.remove(&oldest);
+            }
+        }
+
+        self.previous.insert(
+            window_id,
+            PreviousWindowOcr {
+                image,
+                output,
+                cached_at: Instant::now(),
+            },
+        );
+    }
+
     /// Remove the oldest cache entry
     fn evict_oldest(&mut self) {
         if let Some(oldest_key) = self
@@ -114,6 +162,7 @@ impl WindowOcrCache {
     /// Clear all cached entries
     pub fn clear(&mut self) {
         self.cache.clear();
+        self.previous.clear();
         self.hits = 0;
         self.misses = 0;
     }
@@ -137,6 +186,8 @@ impl WindowOcrCache {
         let now = Instant::now();
         self.cache
             .retain(|_, v| now.duration_since(v.cached_at) < self.max_age);
+        self.previous
+            .retain(|_, v| now.duration_since(v.cached_at) < self.max_age);
     }
 }
 
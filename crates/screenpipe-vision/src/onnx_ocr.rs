@@ -0,0 +1,511 @@
+//! Local OCR running PaddleOCR detection and recognition models with ONNX Runtime on the CPU.
+//!
+//! The detector finds the text lines of the image, each line is then cropped and read by the
+//! recognizer. There is no page segmentation pass like Tesseract's, which makes it much faster
+//! on the large windows full of text that are captured.
+//!
+//! The models are the PP-OCRv4 "ch" ones: they read Chinese and English only, whatever
+//! `--language` is set to.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use image::{imageops::FilterType, DynamicImage, RgbImage};
+use ndarray::Array4;
+use ort::{GraphOptimizationLevel, Session};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use crate::ocr_backend::OcrOutput;
+
+/// Directory to load the models from instead of downloading them, holding `det.onnx`,
+/// `rec.onnx` and the `dict.txt` characters of the recognizer.
+pub const ONNX_OCR_MODELS_ENV: &str = "SCREENPIPE_ONNX_OCR_MODELS";
+
+/// A file of the models, downloaded from `url` and refused unless its SHA-256 is `sha256`.
+struct ModelFile {
+    filename: &'static str,
+    url: &'static str,
+    sha256: &'static str,
+}
+
+/// PP-OCRv4 models, reading Chinese and English. A model whose `sha256` is empty isn't
+/// downloaded, it has to be provided in the directory of [`ONNX_OCR_MODELS_ENV`].
+const MODELS: [ModelFile; 3] = [
+    ModelFile {
+        filename: "det.onnx",
+        url: "https://huggingface.co/SWHL/RapidOCR/resolve/main/PP-OCRv4/ch_PP-OCRv4_det_infer.onnx",
+        sha256: "",
+    },
+    ModelFile {
+        filename: "rec.onnx",
+        url: "https://huggingface.co/SWHL/RapidOCR/resolve/main/PP-OCRv4/ch_PP-OCRv4_rec_infer.onnx",
+        sha256: "",
+    },
+    ModelFile {
+        filename: "dict.txt",
+        url: "https://raw.githubusercontent.com/PaddlePaddle/PaddleOCR/release/2.7/ppocr/utils/ppocr_keys_v1.txt",
+        sha256: "",
+    },
+];
+
+/// Threads each model runs inference on, OCR shares the CPU with capture and audio
+const INFERENCE_THREADS: usize = 2;
+
+/// Longest side images are scaled down to before detection
+const DET_MAX_SIDE: u32 = 1280;
+/// Probability above which a pixel of the detection map is text
+const DET_THRESHOLD: f32 = 0.3;
+/// Mean probability under which a detected region is dropped
+const DET_BOX_THRESHOLD: f32 = 0.5;
+/// How much the detected regions, which cover the shrunk text, are grown back
+const DET_UNCLIP_RATIO: f32 = 1.5;
+const DET_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const DET_STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+const REC_HEIGHT: u32 = 48;
+/// Lines are padded to this width at least, as the recognizer was trained on
+const REC_MIN_WIDTH: u32 = 320;
+const REC_MAX_WIDTH: u32 = 3200;
+/// Lines read with a lower mean probability are dropped
+const REC_MIN_CONFIDENCE: f32 = 0.5;
+
+/// A rectangle of an image, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextRegion {
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TextRegion {
+    fn vertical_center(&self) -> u32 {
+        self.top + self.height / 2
+    }
+
+    fn is_on_same_line(&self, other: &TextRegion) -> bool {
+        self.vertical_center().abs_diff(other.vertical_center()) < self.height.min(other.height) / 2
+    }
+}
+
+/// A character read by the recognizer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedChar {
+    pub text: String,
+    /// Output step of the recognizer the character was read at, from left to right
+    pub timestep: usize,
+}
+
+pub struct OnnxOcr {
+    detector: Session,
+    recognizer: Session,
+    /// Characters of the recognizer classes, the first class being the CTC blank
+    characters: Vec<String>,
+}
+
+impl OnnxOcr {
+    /// Load the models from `dir`, see [`ONNX_OCR_MODELS_ENV`].
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut characters: Vec<String> = std::fs::read_to_string(dir.join("dict.txt"))
+            .context("failed to read ocr characters")?
+            .lines()
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .collect();
+        characters.push(" ".to_string());
+
+        Ok(Self {
+            detector: create_session(&dir.join("det.onnx"))?,
+            recognizer: create_session(&dir.join("rec.onnx"))?,
+            characters,
+        })
+    }
+
+    /// Read the text of `image`, one word block per word with coordinates normalized to it.
+    pub fn recognize(&self, image: &DynamicImage) -> Result<OcrOutput> {
+        let image = image.to_rgb8();
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Ok(OcrOutput::default());
+        }
+
+        let regions = self.detect(&image)?;
+        debug!("detected {} text lines", regions.len());
+
+        let mut lines = Vec::new();
+        let mut blocks = Vec::new();
+        let mut total_confidence = 0.0;
+        for region in regions {
+            let crop = image::imageops::crop_imm(
+                &image,
+                region.left,
+                region.top,
+                region.width,
+                region.height,
+            )
+            .to_image();
+            let Some((chars, confidence, timestep_width)) = self.read_line(&crop)? else {
+                continue;
+            };
+
+            let line_num = lines.len() + 1;
+            let mut line = String::new();
+            for (word_index, word) in split_words(&chars).into_iter().enumerate() {
+                let text: String = word.iter().map(|c| c.text.as_str()).collect();
+                let start = word[0].timestep as f32 * timestep_width;
+                let end = (word[word.len() - 1].timestep + 1) as f32 * timestep_width;
+                let left = region.left as f32 + start.min(region.width as f32);
+                let right = region.left as f32 + end.min(region.width as f32);
+
+                blocks.push(HashMap::from([
+                    ("text".to_string(), text.clone()),
+                    ("conf".to_string(), format!("{:.2}", confidence)),
+                    ("left".to_string(), (left / width as f32).to_string()),
+                    (
+                        "top".to_string(),
+                        (region.top as f32 / height as f32).to_string(),
+                    ),
+                    (
+                        "width".to_string(),
+                        ((right - left) / width as f32).to_string(),
+                    ),
+                    (
+                        "height".to_string(),
+                        (region.height as f32 / height as f32).to_string(),
+                    ),
+                    ("level".to_string(), "5".to_string()),
+                    ("page_num".to_string(), "1".to_string()),
+                    ("block_num".to_string(), "1".to_string()),
+                    ("par_num".to_string(), "1".to_string()),
+                    ("line_num".to_string(), line_num.to_string()),
+                    ("word_num".to_string(), (word_index + 1).to_string()),
+                ]));
+
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(&text);
+            }
+
+            total_confidence += confidence as f64;
+            lines.push(line);
+        }
+
+        let confidence = if lines.is_empty() {
+            0.0
+        } else {
+            total_confidence / lines.len() as f64
+        };
+        Ok(OcrOutput {
+            text: lines.join("\n"),
+            blocks,
+            confidence: Some(confidence),
+        })
+    }
+
+    /// Text lines of `image`, in reading order.
+    fn detect(&self, image: &RgbImage) -> Result<Vec<TextRegion>> {
+        let (width, height) = image.dimensions();
+        let scale = (DET_MAX_SIDE as f32 / width.max(height) as f32).min(1.0);
+        let map_width = round_to_multiple_of_32(width as f32 * scale);
+        let map_height = round_to_multiple_of_32(height as f32 * scale);
+        let resized = image::imageops::resize(image, map_width, map_height, FilterType::Triangle);
+
+        // The models were trained on BGR images
+        let input = Array4::from_shape_fn(
+            (1, 3, map_height as usize, map_width as usize),
+            |(_, c, y, x)| {
+                let value = resized.get_pixel(x as u32, y as u32)[2 - c] as f32 / 255.0;
+                (value - DET_MEAN[c]) / DET_STD[c]
+            },
+        );
+        let outputs = self.detector.run(ort::inputs![input.view()]?)?;
+        let probabilities: Vec<f32> = outputs[0]
+            .try_extract_tensor::<f32>()
+            .context("failed to extract detection map")?
+            .iter()
+            .copied()
+            .collect();
+        if probabilities.len() != (map_width * map_height) as usize {
+            return Err(anyhow!(
+                "unexpected detection map size {}",
+                probabilities.len()
+            ));
+        }
+
+        let scale_x = width as f32 / map_width as f32;
+        let scale_y = height as f32 / map_height as f32;
+        Ok(
+            find_text_regions(&probabilities, map_width as usize, map_height as usize)
+                .into_iter()
+                .filter_map(|region| {
+                    let left = ((region.left as f32 * scale_x) as u32).min(width - 1);
+                    let top = ((region.top as f32 * scale_y) as u32).min(height - 1);
+                    let right =
+                        (((region.left + region.width) as f32 * scale_x).ceil() as u32).min(width);
+                    let bottom =
+                        (((region.top + region.height) as f32 * scale_y).ceil() as u32).min(height);
+                    (right > left && bottom > top).then_some(TextRegion {
+                        left,
+                        top,
+                        width: right - left,
+                        height: bottom - top,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Characters of a line, their mean probability and the width in pixels of `line`
+    /// covered by each output step, or `None` when nothing was read confidently.
+    fn read_line(&self, line: &RgbImage) -> Result<Option<(Vec<DecodedChar>, f32, f32)>> {
+        let (width, height) = line.dimensions();
+        let resized_width = ((REC_HEIGHT as f32 * width as f32 / height as f32).ceil() as u32)
+            .clamp(1, REC_MAX_WIDTH);
+        let input_width = resized_width.max(REC_MIN_WIDTH);
+        let resized =
+            image::imageops::resize(line, resized_width, REC_HEIGHT, FilterType::Triangle);
+
+        let input = Array4::from_shape_fn(
+            (1, 3, REC_HEIGHT as usize, input_width as usize),
+            |(_, c, y, x)| {
+                if x as u32 >= resized_width {
+                    return 0.0;
+                }
+                let value = resized.get_pixel(x as u32, y as u32)[2 - c] as f32 / 255.0;
+                (value - 0.5) / 0.5
+            },
+        );
+        let outputs = self.recognizer.run(ort::inputs![input.view()]?)?;
+        let output = outputs[0]
+            .try_extract_tensor::<f32>()
+            .context("failed to extract recognized characters")?;
+        let shape = output.shape().to_vec();
+        if shape.len() != 3 || shape[1] == 0 {
+            return Err(anyhow!("unexpected recognizer output shape {:?}", shape));
+        }
+        let probabilities: Vec<f32> = output.iter().copied().collect();
+
+        let (chars, confidence) = ctc_decode(&probabilities, shape[2], &self.characters);
+        if chars.is_empty() || confidence < REC_MIN_CONFIDENCE {
+            return Ok(None);
+        }
+        let timestep_width =
+            input_width as f32 / shape[1] as f32 * width as f32 / resized_width as f32;
+        Ok(Some((chars, confidence, timestep_width)))
+    }
+}
+
+/// Regions of the detection `probabilities` map where text was found, in reading order.
+pub fn find_text_regions(probabilities: &[f32], width: usize, height: usize) -> Vec<TextRegion> {
+    let mut visited = vec![false; probabilities.len()];
+    let mut regions = Vec::new();
+    let mut stack = Vec::new();
+
+    for start in 0..width * height {
+        if visited[start] || probabilities[start] <= DET_THRESHOLD {
+            continue;
+        }
+        visited[start] = true;
+        stack.push(start);
+
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, 0, 0);
+        let mut score = 0.0;
+        let mut pixels = 0;
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index % width, index / width);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+            score += probabilities[index];
+            pixels += 1;
+
+            let neighbours = [
+                (x > 0).then(|| index - 1),
+                (x + 1 < width).then(|| index + 1),
+                (y > 0).then(|| index - width),
+                (y + 1 < height).then(|| index + width),
+            ];
+            for neighbour in neighbours.into_iter().flatten() {
+                if !visited[neighbour] && probabilities[neighbour] > DET_THRESHOLD {
+                    visited[neighbour] = true;
+                    stack.push(neighbour);
+                }
+            }
+        }
+
+        let (region_width, region_height) = (max_x - min_x + 1, max_y - min_y + 1);
+        if region_width.min(region_height) < 3 || score / (pixels as f32) < DET_BOX_THRESHOLD {
+            continue;
+        }
+
+        // Grow the region by the margin the detector was trained to shrink text by
+        let margin = ((region_width * region_height) as f32 * DET_UNCLIP_RATIO
+            / (2 * (region_width + region_height)) as f32)
+            .round() as usize;
+        let left = min_x.saturating_sub(margin);
+        let top = min_y.saturating_sub(margin);
+        let right = (max_x + margin).min(width - 1);
+        let bottom = (max_y + margin).min(height - 1);
+        regions.push(TextRegion {
+            left: left as u32,
+            top: top as u32,
+            width: (right - left + 1) as u32,
+            height: (bottom - top + 1) as u32,
+        });
+    }
+
+    sort_reading_order(&mut regions);
+    regions
+}
+
+/// Sort `regions` top to bottom, and left to right within a line.
+fn sort_reading_order(regions: &mut [TextRegion]) {
+    regions.sort_by_key(|region| (region.top, region.left));
+    for i in 1..regions.len() {
+        let mut j = i;
+        while j > 0
+            && regions[j].is_on_same_line(&regions[j - 1])
+            && regions[j].left < regions[j - 1].left
+        {
+            regions.swap(j, j - 1);
+            j -= 1;
+        }
+    }
+}
+
+/// Greedy CTC decoding of the `[timesteps, num_classes]` recognizer `probabilities`.
+///
+/// Class 0 is the blank and class `i` the character `characters[i - 1]`. Returns the read
+/// characters and their mean probability.
+pub fn ctc_decode(
+    probabilities: &[f32],
+    num_classes: usize,
+    characters: &[String],
+) -> (Vec<DecodedChar>, f32) {
+    let mut chars = Vec::new();
+    let mut total_probability = 0.0;
+    let mut previous_class = 0;
+
+    for (timestep, step) in probabilities.chunks_exact(num_classes).enumerate() {
+        let (class, probability) =
+            step.iter()
+                .enumerate()
+                .fold((0, f32::MIN), |best, (class, &probability)| {
+                    if probability > best.1 {
+                        (class, probability)
+                    } else {
+                        best
+                    }
+                });
+
+        if class != 0 && class != previous_class {
+            if let Some(text) = characters.get(class - 1) {
+                chars.push(DecodedChar {
+                    text: text.clone(),
+                    timestep,
+                });
+                total_probability += probability;
+            }
+        }
+        previous_class = class;
+    }
+
+    let confidence = if chars.is_empty() {
+        0.0
+    } else {
+        total_probability / chars.len() as f32
+    };
+    (chars, confidence)
+}
+
+/// Split the characters of a line on spaces.
+fn split_words(chars: &[DecodedChar]) -> Vec<&[DecodedChar]> {
+    chars
+        .split(|c| c.text.trim().is_empty())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+fn round_to_multiple_of_32(value: f32) -> u32 {
+    ((value / 32.0).round() as u32).max(1) * 32
+}
+
+fn create_session(path: &Path) -> Result<Session> {
+    let session = Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .with_intra_threads(INFERENCE_THREADS)?
+        .with_inter_threads(1)?
+        .commit_from_file(path)
+        .with_context(|| format!("failed to load ocr model {:?}", path))?;
+    Ok(session)
+}
+
+/// Directory of the models, downloading the missing ones unless [`ONNX_OCR_MODELS_ENV`] is set.
+pub async fn get_or_download_onnx_ocr_models() -> Result<PathBuf> {
+    if let Ok(dir) = std::env::var(ONNX_OCR_MODELS_ENV) {
+        return Ok(PathBuf::from(dir));
+    }
+
+    let dir = dirs::cache_dir()
+        .ok_or_else(|| anyhow!("failed to get cache dir"))?
+        .join("screenpipe")
+        .join("models")
+        .join("paddleocr");
+    tokio::fs::create_dir_all(&dir).await?;
+
+    for model in &MODELS {
+        if model.sha256.is_empty() {
+            return Err(anyhow!(
+                "ocr model {} has no pinned sha-256 and isn't downloaded, put det.onnx, rec.onnx \
+                 and dict.txt in a directory and set {} to it",
+                model.filename,
+                ONNX_OCR_MODELS_ENV
+            ));
+        }
+        let path = dir.join(model.filename);
+        if path.exists() {
+            // Files cached before the digests were pinned are checked too
+            if sha256_hex(&tokio::fs::read(&path).await?) == model.sha256 {
+                continue;
+            }
+            warn!(
+                "ocr model {} doesn't match its digest, downloading it again",
+                model.filename
+            );
+        }
+
+        info!(
+            "downloading ocr model {} from {}",
+            model.filename, model.url
+        );
+        let response = reqwest::get(model.url).await?.error_for_status()?;
+        let data = response.bytes().await?;
+        let digest = sha256_hex(&data);
+        if digest != model.sha256 {
+            return Err(anyhow!(
+                "ocr model {} from {} has sha-256 {}, expected {:?}. Models checked otherwise \
+                 can be loaded from the directory in {}",
+                model.filename,
+                model.url,
+                digest,
+                model.sha256,
+                ONNX_OCR_MODELS_ENV
+            ));
+        }
+
+        // Write next to the model first so an interrupted download isn't taken for a model
+        let partial_path = dir.join(format!("{}.part", model.filename));
+        tokio::fs::write(&partial_path, &data).await?;
+        tokio::fs::rename(&partial_path, &path).await?;
+        info!("saved ocr model {} ({} bytes)", model.filename, data.len());
+    }
+
+    Ok(dir)
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
@@ -0,0 +1,440 @@
+//! OCR of the regions of a window that changed since it was last read.
+//!
+//! The window is split in tiles compared to its previous capture. Only the rectangles around
+//! the changed tiles are read again, and the blocks read in them replace the blocks of the
+//! previous result they cover. A new line in a large document or chat window then costs the
+//! OCR of that line instead of the whole window.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use image::{DynamicImage, GrayImage};
+use screenpipe_core::Language;
+use tracing::debug;
+
+use crate::ocr_backend::{BlockCoordinates, OcrBackend, OcrOutput};
+use crate::ocr_cache::PreviousWindowOcr;
+
+/// Side of the tiles compared, in pixels
+pub const TILE_SIZE: u32 = 32;
+/// Luma difference above which a pixel changed, smaller ones being antialiasing or compression
+const PIXEL_THRESHOLD: u8 = 24;
+/// Share of the window above which it is read entirely, reading many regions costing more
+pub const MAX_CHANGED_FRACTION: f64 = 0.5;
+/// Times regions are grown over the blocks they cut before giving up on the regions
+const MAX_EXPANSIONS: usize = 8;
+
+/// A rectangle of a window, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl DirtyRect {
+    fn right(&self) -> u32 {
+        self.left + self.width
+    }
+
+    fn bottom(&self) -> u32 {
+        self.top + self.height
+    }
+
+    fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    fn union(&self, other: &DirtyRect) -> DirtyRect {
+        let left = self.left.min(other.left);
+        let top = self.top.min(other.top);
+        DirtyRect {
+            left,
+            top,
+            width: self.right().max(other.right()) - left,
+            height: self.bottom().max(other.bottom()) - top,
+        }
+    }
+
+    fn intersects(&self, other: &DirtyRect) -> bool {
+        self.left < other.right()
+            && other.left < self.right()
+            && self.top < other.bottom()
+            && other.top < self.bottom()
+    }
+
+    fn intersects_box(&self, block: &BlockBox) -> bool {
+        (self.left as f64) < block.right()
+            && block.left < self.right() as f64
+            && (self.top as f64) < block.bottom()
+            && block.top < self.bottom() as f64
+    }
+
+    fn contains_box(&self, block: &BlockBox) -> bool {
+        self.left as f64 <= block.left
+            && block.right() <= self.right() as f64
+            && self.top as f64 <= block.top
+            && block.bottom() <= self.bottom() as f64
+    }
+}
+
+/// Position of a block in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BlockBox {
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+}
+
+impl BlockBox {
+    fn right(&self) -> f64 {
+        self.left + self.width
+    }
+
+    fn bottom(&self) -> f64 {
+        self.top + self.height
+    }
+
+    fn vertical_center(&self) -> f64 {
+        self.top + self.height / 2.0
+    }
+
+    /// Smallest rectangle of a `width` x `height` window containing the block.
+    fn to_rect(self, width: u32, height: u32) -> DirtyRect {
+        let left = (self.left.floor().max(0.0) as u32).min(width);
+        let top = (self.top.floor().max(0.0) as u32).min(height);
+        let right = (self.right().ceil().max(0.0) as u32).clamp(left, width);
+        let bottom = (self.bottom().ceil().max(0.0) as u32).clamp(top, height);
+        DirtyRect {
+            left,
+            top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+}
+
+/// Rectangles around the tiles of `current` that changed since `previous`, grown by a tile
+/// so the text they cut is read whole. `None` when the images don't have the same size.
+pub fn dirty_rects(previous: &GrayImage, current: &GrayImage) -> Option<Vec<DirtyRect>> {
+    if previous.dimensions() != current.dimensions() {
+        return None;
+    }
+    let (width, height) = current.dimensions();
+    let tiles_x = width.div_ceil(TILE_SIZE) as usize;
+    let tiles_y = height.div_ceil(TILE_SIZE) as usize;
+    let (previous, current) = (previous.as_raw(), current.as_raw());
+
+    let mut dirty = vec![false; tiles_x * tiles_y];
+    for tile_y in 0..tiles_y {
+        for tile_x in 0..tiles_x {
+            let x0 = tile_x * TILE_SIZE as usize;
+            let x1 = (x0 + TILE_SIZE as usize).min(width as usize);
+            let y0 = tile_y * TILE_SIZE as usize;
+            let y1 = (y0 + TILE_SIZE as usize).min(height as usize);
+            dirty[tile_y * tiles_x + tile_x] = (y0..y1).any(|y| {
+                let row = y * width as usize;
+                previous[row + x0..row + x1]
+                    .iter()
+                    .zip(&current[row + x0..row + x1])
+                    .any(|(a, b)| a.abs_diff(*b) > PIXEL_THRESHOLD)
+            });
+        }
+    }
+
+    // Group the touching changed tiles
+    let mut visited = vec![false; dirty.len()];
+    let mut rects = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..dirty.len() {
+        if !dirty[start] || visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        stack.push(start);
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (tiles_x, tiles_y, 0, 0);
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index % tiles_x, index / tiles_x);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+            for ny in y.saturating_sub(1)..=(y + 1).min(tiles_y - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(tiles_x - 1) {
+                    let neighbour = ny * tiles_x + nx;
+                    if dirty[neighbour] && !visited[neighbour] {
+                        visited[neighbour] = true;
+                        stack.push(neighbour);
+                    }
+                }
+            }
+        }
+
+        let left = (min_x.saturating_sub(1) as u32) * TILE_SIZE;
+        let top = (min_y.saturating_sub(1) as u32) * TILE_SIZE;
+        let right = ((max_x as u32 + 2) * TILE_SIZE).min(width);
+        let bottom = ((max_y as u32 + 2) * TILE_SIZE).min(height);
+        rects.push(DirtyRect {
+            left,
+            top,
+            width: right - left,
+            height: bottom - top,
+        });
+    }
+
+    Some(merge_overlapping(rects))
+}
+
+/// Merge the rectangles of `rects` that overlap until none do.
+fn merge_overlapping(mut rects: Vec<DirtyRect>) -> Vec<DirtyRect> {
+    'merge: loop {
+        for i in 0..rects.len() {
+            for j in i + 1..rects.len() {
+                if rects[i].intersects(&rects[j]) {
+                    rects[i] = rects[i].union(&rects[j]);
+                    rects.swap_remove(j);
+                    continue 'merge;
+                }
+            }
+        }
+        return rects;
+    }
+}
+
+fn parse_coordinate(block: &HashMap<String, String>, key: &str) -> Option<f64> {
+    block.get(key)?.parse().ok()
+}
+
+/// Position of `block` in a `width` x `height` window.
+fn block_box(
+    block: &HashMap<String, String>,
+    coordinates: BlockCoordinates,
+    width: u32,
+    height: u32,
+) -> Option<BlockBox> {
+    let [left, top, width, height] = coordinates.block_pixels(block, width, height)?;
+    Some(BlockBox {
+        left,
+        top,
+        width,
+        height,
+    })
+}
+
+/// `block` read in `region` positioned in the `width` x `height` window.
+fn block_in_window(
+    mut block: HashMap<String, String>,
+    coordinates: BlockCoordinates,
+    region: &DirtyRect,
+    width: u32,
+    height: u32,
+) -> HashMap<String, String> {
+    let (Some(left), Some(top), Some(block_width), Some(block_height)) = (
+        parse_coordinate(&block, "left"),
+        parse_coordinate(&block, "top"),
+        parse_coordinate(&block, "width"),
+        parse_coordinate(&block, "height"),
+    ) else {
+        return block;
+    };
+    let (region_left, region_top) = (region.left as f64, region.top as f64);
+    let (region_width, region_height) = (region.width as f64, region.height as f64);
+    let (width, height) = (width as f64, height as f64);
+
+    let (left, top, block_width, block_height) = match coordinates {
+        BlockCoordinates::Pixels => {
+            block.insert("left".to_string(), (left + region_left).round().to_string());
+            block.insert("top".to_string(), (top + region_top).round().to_string());
+            return block;
+        }
+        BlockCoordinates::Normalized => (
+            (region_left + left * region_width) / width,
+            (region_top + top * region_height) / height,
+            block_width * region_width / width,
+            block_height * region_height / height,
+        ),
+        BlockCoordinates::NormalizedBottomLeft => (
+            (region_left + left * region_width) / width,
+            (height - region_top - region_height + top * region_height) / height,
+            block_width * region_width / width,
+            block_height * region_height / height,
+        ),
+    };
+    block.insert("left".to_string(), left.to_string());
+    block.insert("top".to_string(), top.to_string());
+    block.insert("width".to_string(), block_width.to_string());
+    block.insert("height".to_string(), block_height.to_string());
+    block
+}
+
+/// Regions of a `current` window capture to read again after `previous`, `None` when the
+/// whole window should be read. Empty when nothing changed.
+pub fn changed_regions(
+    previous_image: &GrayImage,
+    previous: &OcrOutput,
+    coordinates: BlockCoordinates,
+    current: &GrayImage,
+) -> Option<Vec<DirtyRect>> {
+    let (width, height) = current.dimensions();
+    let mut rects = dirty_rects(previous_image, current)?;
+    if rects.is_empty() {
+        return Some(rects);
+    }
+
+    // Without the position of all the previous text, the unchanged text can't be kept
+    if previous.blocks.is_empty() && !previous.text.trim().is_empty() {
+        return None;
+    }
+    let boxes = previous
+        .blocks
+        .iter()
+        .map(|block| block_box(block, coordinates, width, height))
+        .collect::<Option<Vec<_>>>()?;
+
+    // Grow the regions over the previous blocks they cut, for these to be read whole
+    let mut expansions = 0;
+    loop {
+        let mut grown = false;
+        for rect in rects.iter_mut() {
+            for block in &boxes {
+                if rect.intersects_box(block) && !rect.contains_box(block) {
+                    *rect = rect.union(&block.to_rect(width, height));
+                    grown = true;
+                }
+            }
+        }
+        rects = merge_overlapping(rects);
+        if !grown {
+            break;
+        }
+        expansions += 1;
+        if expansions == MAX_EXPANSIONS {
+            return None;
+        }
+    }
+
+    let changed_area: u64 = rects.iter().map(DirtyRect::area).sum();
+    if changed_area as f64 > (width as u64 * height as u64) as f64 * MAX_CHANGED_FRACTION {
+        return None;
+    }
+    Some(rects)
+}
+
+/// The `previous` output of a `width` x `height` window with its blocks in the `regions`
+/// replaced by the outputs read in them.
+pub fn merge_region_outputs(
+    previous: &OcrOutput,
+    regions: Vec<(DirtyRect, OcrOutput)>,
+    coordinates: BlockCoordinates,
+    width: u32,
+    height: u32,
+) -> OcrOutput {
+    let mut blocks: Vec<HashMap<String, String>> = previous
+        .blocks
+        .iter()
+        .filter(|block| {
+            block_box(block, coordinates, width, height)
+                .is_none_or(|block| !regions.iter().any(|(rect, _)| rect.intersects_box(&block)))
+        })
+        .cloned()
+        .collect();
+
+    let mut confidences: Vec<f64> = previous.confidence.into_iter().collect();
+    for (rect, output) in regions {
+        confidences.extend(output.confidence);
+        blocks.extend(
+            output
+                .blocks
+                .into_iter()
+                .map(|block| block_in_window(block, coordinates, &rect, width, height)),
+        );
+    }
+
+    let (blocks, text) = reading_order(blocks, coordinates, width, height);
+    OcrOutput {
+        text,
+        blocks,
+        confidence: (!confidences.is_empty())
+            .then(|| confidences.iter().sum::<f64>() / confidences.len() as f64),
+    }
+}
+
+/// `blocks` sorted top to bottom and left to right, and their text with one line per line.
+fn reading_order(
+    blocks: Vec<HashMap<String, String>>,
+    coordinates: BlockCoordinates,
+    width: u32,
+    height: u32,
+) -> (Vec<HashMap<String, String>>, String) {
+    let mut positioned: Vec<(BlockBox, HashMap<String, String>)> = blocks
+        .into_iter()
+        .filter_map(|block| Some((block_box(&block, coordinates, width, height)?, block)))
+        .collect();
+    positioned.sort_by(|(a, _), (b, _)| a.top.total_cmp(&b.top));
+
+    let mut lines: Vec<Vec<(BlockBox, HashMap<String, String>)>> = Vec::new();
+    for (block_box, block) in positioned {
+        match lines.last_mut() {
+            Some(line)
+                if block_box.vertical_center() >= line[0].0.top
+                    && block_box.vertical_center() <= line[0].0.bottom() =>
+            {
+                line.push((block_box, block))
+            }
+            _ => lines.push(vec![(block_box, block)]),
+        }
+    }
+
+    let mut sorted = Vec::new();
+    let mut text_lines = Vec::new();
+    for mut line in lines {
+        line.sort_by(|(a, _), (b, _)| a.left.total_cmp(&b.left));
+        let words: Vec<String> = line
+            .iter()
+            .filter_map(|(_, block)| block.get("text"))
+            .filter(|text| !text.trim().is_empty())
+            .cloned()
+            .collect();
+        if !words.is_empty() {
+            text_lines.push(words.join(" "));
+        }
+        sorted.extend(line.into_iter().map(|(_, block)| block));
+    }
+    (sorted, text_lines.join("\n"))
+}
+
+/// Read a window `image` by OCR of what changed since `previous` when the backend positions
+/// its blocks, or entirely otherwise. `luma` is the grayscale of `image`.
+pub async fn recognize_changed_regions(
+    backend: &dyn OcrBackend,
+    image: &DynamicImage,
+    luma: &GrayImage,
+    previous: Option<&PreviousWindowOcr>,
+    languages: &[Language],
+) -> Result<OcrOutput> {
+    if let (Some(previous), Some(coordinates)) = (previous, backend.block_coordinates()) {
+        if let Some(regions) = changed_regions(&previous.image, &previous.output, coordinates, luma)
+        {
+            if regions.is_empty() {
+                return Ok(previous.output.clone());
+            }
+            debug!("reading {} changed regions of window", regions.len());
+            let mut outputs = Vec::with_capacity(regions.len());
+            for region in regions {
+                let crop = image.crop_imm(region.left, region.top, region.width, region.height);
+                outputs.push((region, backend.recognize(&crop, languages).await?));
+            }
+            return Ok(merge_region_outputs(
+                &previous.output,
+                outputs,
+                coordinates,
+                image.width(),
+                image.height(),
+            ));
+        }
+    }
+
+    backend.recognize(image, languages).await
+}
@@ -1,3 +1,4 @@
+use anyhow::{anyhow, Result};
 use image::DynamicImage;
 use rusty_tesseract::{Args, DataOutput, Image};
 use screenpipe_core::{Language, TESSERACT_LANGUAGES};
@@ -6,7 +7,7 @@ use std::collections::HashMap;
 pub fn perform_ocr_tesseract(
     image: &DynamicImage,
     languages: Vec<Language>,
-) -> (String, String, Option<f64>) {
+) -> Result<(String, String, Option<f64>)> {
     let language_string = match languages.is_empty() {
         true => "eng".to_string(),
         _ => TESSERACT_LANGUAGES
@@ -29,10 +30,12 @@ pub fn perform_ocr_tesseract(
         oem: Some(1), //1: Neural nets LSTM engine only,    3: Default, based on what is available. (Default)
     };
 
-    let ocr_image = Image::from_dynamic_image(image).unwrap();
+    let ocr_image = Image::from_dynamic_image(image)
+        .map_err(|e| anyhow!("failed to prepare image for tesseract: {}", e))?;
 
     // Extract data output
-    let data_output = rusty_tesseract::image_to_data(&ocr_image, &args).unwrap();
+    let data_output = rusty_tesseract::image_to_data(&ocr_image, &args)
+        .map_err(|e| anyhow!("tesseract failed: {}", e))?;
     // let tsv_output = data_output_to_tsv(&data_output);
 
     // Extract text from data output
@@ -41,7 +44,7 @@ pub fn perform_ocr_tesseract(
 
     let overall_confidence = calculate_overall_confidence(&data_output);
 
-    (text, json_output, Some(overall_confidence))
+    Ok((text, json_output, Some(overall_confidence)))
 }
 
 fn data_output_to_text(data_output: &DataOutput) -> String {
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use screenpipe_vision::capture_screenshot_by_window::{visible_region, CapturedWindow, Rect};
+use screenpipe_vision::masking::{mask_pii_regions, pii_regions, WindowPlacement};
+use screenpipe_vision::ocr_backend::BlockCoordinates;
+use screenpipe_vision::{CaptureMask, MaskRect};
+
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+fn white(width: u32, height: u32) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+        width,
+        height,
+        Rgba([255, 255, 255, 255]),
+    ))
+}
+
+fn window(x: i32, y: i32, width: u32, height: u32, image: DynamicImage) -> CapturedWindow {
+    CapturedWindow {
+        image,
+        app_name: "app".to_string(),
+        window_name: "window".to_string(),
+        process_id: 1,
+        is_focused: true,
+        browser_url: None,
+        window_x: x,
+        window_y: y,
+        window_width: width,
+        window_height: height,
+        blackout: None,
+        policy: Default::default(),
+    }
+}
+
+fn is_black(image: &DynamicImage, x: u32, y: u32) -> bool {
+    image.get_pixel(x, y) == BLACK
+}
+
+#[test]
+fn test_parse_mask_rect() {
+    assert_eq!(
+        "10,20,300,40".parse::<MaskRect>().unwrap(),
+        MaskRect {
+            monitor_id: None,
+            x: 10,
+            y: 20,
+            width: 300,
+            height: 40
+        }
+    );
+    assert_eq!(
+        "2: 0, 0, 1920, 30".parse::<MaskRect>().unwrap().monitor_id,
+        Some(2)
+    );
+    assert!("10,20,300".parse::<MaskRect>().is_err());
+    assert!("a:10,20,300,40".parse::<MaskRect>().is_err());
+}
+
+#[test]
+fn test_static_rects_are_masked_on_their_monitor() {
+    let mask = CaptureMask {
+        rects: vec![
+            "0,0,10,10".parse().unwrap(),
+            "1:50,50,10,10".parse().unwrap(),
+        ],
+        ..Default::default()
+    };
+
+    let mut frame = white(100, 100);
+    mask.mask_frame(&mut frame, 0);
+    assert!(is_black(&frame, 9, 9));
+    assert!(!is_black(&frame, 10, 10));
+    assert!(!is_black(&frame, 55, 55));
+
+    mask.mask_frame(&mut frame, 1);
+    assert!(is_black(&frame, 55, 55));
+}
+
+#[test]
+fn test_mask_windows() {
+    let mask = CaptureMask {
+        rects: vec!["0,0,40,40".parse().unwrap()],
+        ..Default::default()
+    };
+    // Monitor at 1000,0 in the desktop, captured at twice its size
+    let monitor = Rect {
+        x: 1000,
+        y: 0,
+        width: 100,
+        height: 100,
+    };
+    let mut frame = white(200, 200);
+    let visible = window(1010, 10, 50, 50, white(100, 100));
+    let mut blacked_out = window(1050, 50, 25, 25, DynamicImage::new_rgba8(0, 0));
+    blacked_out.blackout = Some(vec![Rect {
+        x: 1050,
+        y: 50,
+        width: 25,
+        height: 25,
+    }]);
+    let mut windows = vec![visible, blacked_out];
+
+    mask.mask_windows(&mut frame, &mut windows, 0, &monitor);
+
+    // The blacked out window is masked on the frame and dropped
+    assert_eq!(windows.len(), 1);
+    assert!(is_black(&frame, 100, 100) && is_black(&frame, 149, 149));
+    assert!(!is_black(&frame, 150, 150));
+    // The static rect covers 20..40 of the frame at the top left of the window
+    let image = &windows[0].image;
+    assert!(is_black(image, 0, 0) && is_black(image, 19, 19));
+    assert!(!is_black(image, 20, 20));
+}
+
+#[test]
+fn test_visible_region_of_overlapped_window() {
+    let bounds = Rect {
+        x: 0,
+        y: 0,
+        width: 100,
+        height: 100,
+    };
+    let stacked_windows = [
+        // In front, covering the right half
+        (
+            2,
+            Rect {
+                x: 50,
+                y: -10,
+                width: 100,
+                height: 200,
+            },
+        ),
+        (1, bounds),
+        // Behind, covering all of it
+        (
+            3,
+            Rect {
+                x: 0,
+                y: 0,
+                width: 100,
+                height: 100,
+            },
+        ),
+    ];
+
+    let region = visible_region(&bounds, 1, &stacked_windows);
+    let area: u64 = region
+        .iter()
+        .map(|rect| rect.width as u64 * rect.height as u64)
+        .sum();
+    assert_eq!(area, 50 * 100);
+    assert!(region.iter().all(|rect| rect.x + rect.width as i32 <= 50));
+
+    // Not in the list: what covers it is unknown, so all of it is masked
+    let region = visible_region(&bounds, 4, &stacked_windows);
+    assert_eq!(region.len(), 1);
+    assert_eq!(region[0].width * region[0].height, 100 * 100);
+}
+
+#[test]
+fn test_mask_windows_skips_the_covered_part_of_blacked_out_windows() {
+    let monitor = Rect {
+        x: 0,
+        y: 0,
+        width: 100,
+        height: 100,
+    };
+    let bounds = Rect {
+        x: 0,
+        y: 0,
+        width: 100,
+        height: 100,
+    };
+    // A window in front covers the middle of the blacked out one
+    let above = Rect {
+        x: 25,
+        y: 25,
+        width: 50,
+        height: 50,
+    };
+    let mut frame = white(100, 100);
+    let mut blacked_out = window(0, 0, 100, 100, DynamicImage::new_rgba8(0, 0));
+    blacked_out.blackout = Some(visible_region(&bounds, 1, &[(2, above), (1, bounds)]));
+    let mut windows = vec![blacked_out];
+
+    CaptureMask::default().mask_windows(&mut frame, &mut windows, 0, &monitor);
+
+    assert!(windows.is_empty());
+    assert!(is_black(&frame, 0, 0) && is_black(&frame, 24, 50) && is_black(&frame, 99, 99));
+    assert!(is_black(&frame, 50, 24) && is_black(&frame, 75, 50));
+    assert!(!is_black(&frame, 25, 25) && !is_black(&frame, 50, 50) && !is_black(&frame, 74, 74));
+}
+
+#[test]
+fn test_pii_is_masked_on_the_window_and_the_frame() {
+    let monitor = Rect {
+        x: 0,
+        y: 0,
+        width: 200,
+        height: 200,
+    };
+    let mut frame = white(200, 200);
+    let mut captured = window(100, 100, 100, 100, white(100, 100));
+    let blocks = vec![
+        HashMap::from([
+            ("text".to_string(), "hello".to_string()),
+            ("left".to_string(), "0.1".to_string()),
+            ("top".to_string(), "0.1".to_string()),
+            ("width".to_string(), "0.2".to_string()),
+            ("height".to_string(), "0.1".to_string()),
+        ]),
+        HashMap::from([
+            ("text".to_string(), "john.doe@example.com".to_string()),
+            ("left".to_string(), "0.1".to_string()),
+            ("top".to_string(), "0.5".to_string()),
+            ("width".to_string(), "0.5".to_string()),
+            ("height".to_string(), "0.1".to_string()),
+        ]),
+    ];
+
+    let regions = pii_regions(
+        &blocks,
+        Some(BlockCoordinates::Normalized),
+        captured.image.width(),
+        captured.image.height(),
+    );
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0].pii_type, "EMAIL");
+    // Top left origin, unlike the bottom left origin detect_pii_regions assumes
+    assert!(regions[0].y <= 50 && regions[0].y + regions[0].height >= 60);
+
+    let placement = WindowPlacement::new(&captured, &monitor, 200, 200);
+    mask_pii_regions(&mut frame, &mut captured.image, &placement, &regions);
+    assert!(is_black(&captured.image, 30, 55));
+    assert!(!is_black(&captured.image, 30, 15));
+    assert!(is_black(&frame, 130, 155));
+    assert!(!is_black(&frame, 30, 55));
+}
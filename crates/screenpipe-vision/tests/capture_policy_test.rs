@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use image::DynamicImage;
+use screenpipe_vision::capture_policy::{frame_policy, CapturePolicies};
+use screenpipe_vision::capture_screenshot_by_window::{CapturedWindow, WindowFilters};
+use screenpipe_vision::{CapturePolicy, CapturePolicyFile, VideoQuality};
+
+const POLICIES: &str = r#"[
+    { "app": "Figma", "ocr": false },
+    { "app": "terminal", "video": false },
+    { "app": "Slack", "fps": 0.5 },
+    { "app": "1Password", "capture": false },
+    { "url": "github.com", "title": "(?i)pull request", "video_quality": "high" },
+    { "url": "github.com", "fps": 2 }
+]"#;
+
+fn window(app_name: &str, is_focused: bool, policy: CapturePolicy) -> CapturedWindow {
+    CapturedWindow {
+        image: DynamicImage::new_rgba8(1, 1),
+        app_name: app_name.to_string(),
+        window_name: "window".to_string(),
+        process_id: 1,
+        is_focused,
+        browser_url: None,
+        window_x: 0,
+        window_y: 0,
+        window_width: 1,
+        window_height: 1,
+        blackout: None,
+        policy,
+    }
+}
+
+#[test]
+fn test_policies_match_app_title_and_url() {
+    let policies = CapturePolicies::from_json(POLICIES).unwrap();
+
+    assert!(!policies.policy_for("Figma", "Design", None).ocr);
+    assert!(policies.policy_for("Figma", "Design", None).video);
+    assert!(!policies.policy_for("Terminal", "zsh", None).video);
+    assert!(!policies.policy_for("1Password 8", "Vault", None).capture);
+    assert_eq!(
+        policies.policy_for("Notes", "Notes", None),
+        CapturePolicy::default()
+    );
+
+    let url = Some("https://github.com/org/repo/pull/1");
+    let pull_request = policies.policy_for("Arc", "Pull Request #1", url);
+    assert_eq!(pull_request.video_quality, Some(VideoQuality::High));
+    // The first matching rule wins, the others aren't merged in
+    assert_eq!(pull_request.fps, None);
+    assert_eq!(policies.policy_for("Arc", "Issues", url).fps, Some(2.0));
+    assert_eq!(
+        policies.policy_for("Arc", "Pull Request #1", None),
+        CapturePolicy::default()
+    );
+}
+
+#[test]
+fn test_invalid_policies() {
+    assert!(CapturePolicies::from_json(r#"[{ "title": "(" }]"#).is_err());
+    assert!(CapturePolicies::from_json(r#"[{ "app": "a", "ocr": "no" }]"#).is_err());
+    assert!(CapturePolicies::from_json(r#"[{ "app": "a", "video_quality": "ultra" }]"#).is_err());
+    assert!(CapturePolicies::from_json(r#"{ "app": "a" }"#).is_err());
+}
+
+#[test]
+fn test_policy_interval() {
+    let policy = |fps| CapturePolicy {
+        fps,
+        ..Default::default()
+    };
+    assert_eq!(policy(None).interval(), None);
+    assert_eq!(policy(Some(0.5)).interval(), Some(Duration::from_secs(2)));
+    assert_eq!(policy(Some(0.0)).interval(), None);
+    assert_eq!(policy(Some(-1.0)).interval(), None);
+}
+
+#[test]
+fn test_frame_policy_is_the_focused_window_one() {
+    let slow = CapturePolicy {
+        fps: Some(0.2),
+        ..Default::default()
+    };
+    let no_video = CapturePolicy {
+        video: false,
+        ..Default::default()
+    };
+
+    let windows = vec![
+        window("a", false, no_video.clone()),
+        window("b", true, slow.clone()),
+    ];
+    assert_eq!(frame_policy(&windows), slow);
+    assert_eq!(frame_policy(&windows[..1]), no_video);
+    assert_eq!(frame_policy(&[]), CapturePolicy::default());
+}
+
+#[test]
+fn test_policy_file_reload() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), r#"[{ "app": "Figma", "ocr": false }]"#).unwrap();
+    let policies = CapturePolicyFile::open(file.path()).unwrap();
+    assert!(!policies.current().policy_for("Figma", "", None).ocr);
+
+    std::fs::write(file.path(), r#"[{ "app": "Figma", "video": false }]"#).unwrap();
+    policies.reload();
+    let figma = policies.current().policy_for("Figma", "", None);
+    assert!(figma.ocr && !figma.video);
+
+    // Invalid policies keep the previous ones
+    std::fs::write(file.path(), "[{").unwrap();
+    policies.reload();
+    assert!(!policies.current().policy_for("Figma", "", None).video);
+
+    assert!(CapturePolicyFile::open(file.path()).is_err());
+}
+
+#[test]
+fn test_never_captured_windows_are_blacked_out() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), r#"[{ "app": "1Password", "capture": false }]"#).unwrap();
+    let policies = CapturePolicyFile::open(file.path()).unwrap();
+
+    let filters = WindowFilters::new(&[], &[], &[]);
+    assert!(!filters.is_blacked_out("1Password", "Vault", None));
+
+    let filters = filters.with_policies(Some(policies));
+    assert!(filters.is_blacked_out("1Password", "Vault", None));
+    assert!(!filters.is_blacked_out("Notes", "Vault", None));
+    assert!(filters.policy_for("1Password", "Vault", None).video);
+}
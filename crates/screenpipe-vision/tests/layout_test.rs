@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use screenpipe_db::{LayoutBlockKind, OcrLayoutBlock};
+use screenpipe_vision::layout::analyze_layout;
+use screenpipe_vision::ocr_backend::BlockCoordinates;
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+const LINE_HEIGHT: u32 = 12;
+
+fn word(text: &str, left: u32, top: u32, width: u32) -> HashMap<String, String> {
+    HashMap::from([
+        ("text".to_string(), text.to_string()),
+        ("left".to_string(), left.to_string()),
+        ("top".to_string(), top.to_string()),
+        ("width".to_string(), width.to_string()),
+        ("height".to_string(), LINE_HEIGHT.to_string()),
+    ])
+}
+
+/// Words of a line in a proportional font
+fn prose(text: &str, left: u32, top: u32) -> Vec<HashMap<String, String>> {
+    let char_width = |c: char| match c {
+        'i' | 'l' | 'j' | 't' | 'f' | 'r' | 'I' | '.' | ',' => 4,
+        'm' | 'w' | 'M' | 'W' => 11,
+        c if c.is_uppercase() => 9,
+        _ => 7,
+    };
+    let mut x = left;
+    text.split(' ')
+        .map(|text| {
+            let width = text.chars().map(char_width).sum();
+            let block = word(text, x, top, width);
+            x += width + 5;
+            block
+        })
+        .collect()
+}
+
+/// Words of a line in a monospace font of 10 pixels per character
+fn monospace(text: &str, left: u32, top: u32) -> Vec<HashMap<String, String>> {
+    let mut blocks = Vec::new();
+    let mut x = left;
+    for text in text.split(' ') {
+        if !text.is_empty() {
+            blocks.push(word(text, x, top, 10 * text.len() as u32));
+        }
+        x += 10 * (text.len() as u32 + 1);
+    }
+    blocks
+}
+
+fn analyze(blocks: &[HashMap<String, String>]) -> Vec<OcrLayoutBlock> {
+    analyze_layout(blocks, Some(BlockCoordinates::Pixels), WIDTH, HEIGHT)
+}
+
+#[test]
+fn test_paragraphs_are_split_at_gaps() {
+    let blocks = [
+        prose("The quick brown fox jumps", 10, 10),
+        prose("over the lazy dog", 10, 26),
+        prose("A second paragraph starts here", 10, 60),
+    ]
+    .concat();
+
+    let layout = analyze(&blocks);
+    assert_eq!(layout.len(), 2);
+    assert!(layout
+        .iter()
+        .all(|block| block.kind == LayoutBlockKind::Paragraph));
+    assert_eq!(
+        layout[0].text,
+        "The quick brown fox jumps\nover the lazy dog"
+    );
+    assert_eq!(layout[1].text, "A second paragraph starts here");
+    // Bounds are fractions of the window image
+    assert_eq!(layout[0].left, 10.0 / WIDTH as f64);
+    assert_eq!(layout[0].top, 10.0 / HEIGHT as f64);
+    assert_eq!(layout[0].height, 28.0 / HEIGHT as f64);
+}
+
+#[test]
+fn test_columns_are_read_one_after_the_other() {
+    let blocks = [
+        prose("left column text that goes on and on", 10, 10),
+        prose("right column with more words in it", 450, 10),
+        prose("and keeps going on the next line", 10, 26),
+        prose("also continuing on its second line", 450, 26),
+    ]
+    .concat();
+
+    let layout = analyze(&blocks);
+    assert_eq!(layout.len(), 2);
+    assert_eq!(layout[0].column, 0);
+    assert_eq!(
+        layout[0].text,
+        "left column text that goes on and on\nand keeps going on the next line"
+    );
+    assert_eq!(layout[1].column, 1);
+    assert_eq!(
+        layout[1].text,
+        "right column with more words in it\nalso continuing on its second line"
+    );
+}
+
+#[test]
+fn test_aligned_short_cells_are_a_table() {
+    let mut blocks = Vec::new();
+    for (row, cells) in [
+        ["Name", "Role", "Team"],
+        ["Alice", "Staff Engineer", "Core"],
+        ["Bob", "Designer", "Apps"],
+    ]
+    .iter()
+    .enumerate()
+    {
+        let top = 10 + 20 * row as u32;
+        for (column, cell) in cells.iter().enumerate() {
+            blocks.extend(prose(cell, 10 + 200 * column as u32, top));
+        }
+    }
+    blocks.extend(prose("Three people are on the project", 10, 100));
+
+    let layout = analyze(&blocks);
+    assert_eq!(layout.len(), 2);
+    let table = &layout[0];
+    assert_eq!(table.kind, LayoutBlockKind::Table);
+    assert_eq!(
+        table.rows,
+        vec![
+            vec!["Name", "Role", "Team"],
+            vec!["Alice", "Staff Engineer", "Core"],
+            vec!["Bob", "Designer", "Apps"],
+        ]
+    );
+    assert_eq!(
+        table.text,
+        "Name\tRole\tTeam\nAlice\tStaff Engineer\tCore\nBob\tDesigner\tApps"
+    );
+    assert_eq!(layout[1].kind, LayoutBlockKind::Paragraph);
+}
+
+#[test]
+fn test_monospace_text_is_code_with_its_indentation() {
+    let blocks = [
+        monospace("fn main() {", 10, 10),
+        monospace("    let value = 1;", 10, 26),
+        monospace("}", 10, 42),
+    ]
+    .concat();
+
+    let layout = analyze(&blocks);
+    assert_eq!(layout.len(), 1);
+    assert_eq!(layout[0].kind, LayoutBlockKind::Code);
+    assert_eq!(layout[0].text, "fn main() {\n    let value = 1;\n}");
+}
+
+#[test]
+fn test_normalized_blocks() {
+    let blocks = vec![HashMap::from([
+        ("text".to_string(), "hello".to_string()),
+        ("left".to_string(), "0.25".to_string()),
+        ("top".to_string(), "0.5".to_string()),
+        ("width".to_string(), "0.1".to_string()),
+        ("height".to_string(), "0.02".to_string()),
+    ])];
+
+    let layout = analyze_layout(&blocks, Some(BlockCoordinates::Normalized), WIDTH, HEIGHT);
+    assert_eq!(layout.len(), 1);
+    assert_eq!(layout[0].text, "hello");
+    assert!((layout[0].left - 0.25).abs() < 1e-9);
+    assert!((layout[0].top - 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_no_layout_without_positions() {
+    let blocks = prose("some text", 10, 10);
+    assert!(analyze_layout(&blocks, None, WIDTH, HEIGHT).is_empty());
+    assert!(analyze(&[]).is_empty());
+}
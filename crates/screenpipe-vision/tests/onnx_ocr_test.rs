@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use screenpipe_vision::onnx_ocr::{
+    ctc_decode, find_text_regions, get_or_download_onnx_ocr_models, TextRegion,
+};
+use screenpipe_vision::{create_ocr_backend, OcrEngine};
+
+fn fill(map: &mut [f32], width: usize, region: (usize, usize, usize, usize), probability: f32) {
+    let (left, top, right, bottom) = region;
+    for y in top..bottom {
+        for x in left..right {
+            map[y * width + x] = probability;
+        }
+    }
+}
+
+#[test]
+fn test_text_regions_are_found_in_reading_order() {
+    let (width, height) = (200, 100);
+    let mut map = vec![0.0; width * height];
+    // Two words on the first line, the right one a bit higher, and a second line
+    fill(&mut map, width, (120, 10, 180, 20), 0.9);
+    fill(&mut map, width, (10, 12, 100, 22), 0.9);
+    fill(&mut map, width, (10, 50, 150, 60), 0.8);
+    // Noise too faint or too small to be text
+    fill(&mut map, width, (10, 80, 100, 90), 0.4);
+    fill(&mut map, width, (190, 90, 191, 91), 0.9);
+
+    let regions = find_text_regions(&map, width, height);
+    assert_eq!(regions.len(), 3);
+    assert!(regions[0].left < 10 && regions[0].top < 12);
+    assert!(regions[1].left < 120 && regions[1].left > 100);
+    assert!(regions[2].top < 50 && regions[2].top > 30);
+
+    // Regions are grown back around the shrunk text the detector outputs
+    let TextRegion { width, height, .. } = regions[2];
+    assert!(width > 140 && height > 10);
+}
+
+#[test]
+fn test_ctc_decode_merges_repeats_and_skips_blanks() {
+    let characters: Vec<String> = ["a", "b", " "].iter().map(|c| c.to_string()).collect();
+    let step = |class: usize| {
+        let mut probabilities = vec![0.0; 4];
+        probabilities[class] = 1.0;
+        probabilities
+    };
+    // a a _ a b _ " " b
+    let probabilities: Vec<f32> = [1, 1, 0, 1, 2, 0, 3, 2]
+        .into_iter()
+        .flat_map(step)
+        .collect();
+
+    let (chars, confidence) = ctc_decode(&probabilities, 4, &characters);
+    let text: String = chars.iter().map(|c| c.text.as_str()).collect();
+    assert_eq!(text, "aab b");
+    assert_eq!(
+        chars.iter().map(|c| c.timestep).collect::<Vec<_>>(),
+        vec![0, 3, 4, 6, 7]
+    );
+    assert_eq!(confidence, 1.0);
+}
+
+#[tokio::test]
+#[ignore] // Downloads the models
+async fn test_onnx_ocr() {
+    get_or_download_onnx_ocr_models().await.unwrap();
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("testing_OCR.png");
+    let image = image::open(path).unwrap();
+
+    let backend = create_ocr_backend(&OcrEngine::Onnx).unwrap();
+    let output = backend.recognize(&image, &[]).await.unwrap();
+
+    println!("OCR text: {}", output.text);
+    assert!(output.text.contains("ocr_handles"));
+    assert!(output.confidence.unwrap() > 0.5);
+    assert!(!output.blocks.is_empty());
+    for block in &output.blocks {
+        let left: f64 = block["left"].parse().unwrap();
+        let width: f64 = block["width"].parse().unwrap();
+        assert!((0.0..=1.0).contains(&left) && left + width <= 1.0 + 1e-6);
+    }
+}
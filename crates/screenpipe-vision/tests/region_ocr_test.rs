@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use image::{DynamicImage, GrayImage, Luma};
+use screenpipe_core::Language;
+use screenpipe_vision::ocr_backend::{BlockCoordinates, OcrBackend, OcrOutput};
+use screenpipe_vision::ocr_cache::PreviousWindowOcr;
+use screenpipe_vision::region_ocr::{
+    changed_regions, dirty_rects, merge_region_outputs, recognize_changed_regions, DirtyRect,
+};
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 480;
+
+fn blank() -> GrayImage {
+    GrayImage::from_pixel(WIDTH, HEIGHT, Luma([255]))
+}
+
+/// `image` with a dark rectangle drawn at `left`, `top`
+fn draw(mut image: GrayImage, left: u32, top: u32, width: u32, height: u32) -> GrayImage {
+    for y in top..top + height {
+        for x in left..left + width {
+            image.put_pixel(x, y, Luma([0]));
+        }
+    }
+    image
+}
+
+fn word(text: &str, left: u32, top: u32, width: u32, height: u32) -> HashMap<String, String> {
+    HashMap::from([
+        ("text".to_string(), text.to_string()),
+        ("left".to_string(), left.to_string()),
+        ("top".to_string(), top.to_string()),
+        ("width".to_string(), width.to_string()),
+        ("height".to_string(), height.to_string()),
+    ])
+}
+
+fn texts(output: &OcrOutput) -> Vec<&str> {
+    output.blocks.iter().map(|b| b["text"].as_str()).collect()
+}
+
+#[test]
+fn test_no_dirty_rects_for_identical_images() {
+    let image = draw(blank(), 10, 10, 100, 12);
+    assert_eq!(dirty_rects(&image, &image.clone()), Some(vec![]));
+}
+
+#[test]
+fn test_dirty_rects_of_changed_tiles() {
+    let previous = draw(blank(), 10, 10, 100, 12);
+    let current = draw(previous.clone(), 200, 300, 40, 12);
+
+    let rects = dirty_rects(&previous, &current).unwrap();
+    assert_eq!(rects.len(), 1);
+    let rect = rects[0];
+    // The changed tiles grown by a tile
+    assert!(rect.left <= 200 && rect.left + rect.width >= 240);
+    assert!(rect.top <= 300 && rect.top + rect.height >= 312);
+    assert!(rect.width <= 128 && rect.height <= 96);
+
+    // Small changes far apart are read separately
+    let current = draw(current, 560, 40, 10, 10);
+    assert_eq!(dirty_rects(&previous, &current).unwrap().len(), 2);
+
+    // A resized window is read entirely
+    assert_eq!(dirty_rects(&previous, &GrayImage::new(320, 240)), None);
+}
+
+#[test]
+fn test_changed_regions_cover_the_words_they_cut() {
+    let previous_image = draw(blank(), 10, 100, 300, 12);
+    let previous = OcrOutput {
+        text: "a long line".to_string(),
+        blocks: vec![word("a", 10, 100, 20, 12), word("long", 40, 100, 270, 12)],
+        confidence: Some(90.0),
+    };
+    // The end of the long word changed
+    let current = draw(previous_image.clone(), 290, 100, 30, 12);
+
+    let regions = changed_regions(
+        &previous_image,
+        &previous,
+        BlockCoordinates::Pixels,
+        &current,
+    )
+    .unwrap();
+    assert_eq!(regions.len(), 1);
+    assert!(regions[0].left <= 40 && regions[0].left + regions[0].width >= 310);
+
+    // Most of the window changed
+    let current = draw(blank(), 0, 0, WIDTH, 300);
+    assert_eq!(
+        changed_regions(
+            &previous_image,
+            &previous,
+            BlockCoordinates::Pixels,
+            &current
+        ),
+        None
+    );
+
+    // Text without positions can't be kept
+    let unpositioned = OcrOutput {
+        blocks: vec![],
+        ..previous
+    };
+    let current = draw(previous_image.clone(), 290, 100, 30, 12);
+    assert_eq!(
+        changed_regions(
+            &previous_image,
+            &unpositioned,
+            BlockCoordinates::Pixels,
+            &current
+        ),
+        None
+    );
+}
+
+#[test]
+fn test_merge_replaces_the_blocks_of_changed_regions() {
+    let previous = OcrOutput {
+        text: "hello world\nsecond line".to_string(),
+        blocks: vec![
+            word("hello", 10, 10, 50, 12),
+            word("world", 70, 10, 50, 12),
+            word("second", 10, 40, 60, 12),
+            word("line", 80, 40, 40, 12),
+        ],
+        confidence: Some(80.0),
+    };
+    let region = DirtyRect {
+        left: 64,
+        top: 0,
+        width: 128,
+        height: 32,
+    };
+    let region_output = OcrOutput {
+        text: "there".to_string(),
+        blocks: vec![word("there", 6, 10, 50, 12)],
+        confidence: Some(90.0),
+    };
+
+    let merged = merge_region_outputs(
+        &previous,
+        vec![(region, region_output)],
+        BlockCoordinates::Pixels,
+        WIDTH,
+        HEIGHT,
+    );
+    assert_eq!(merged.text, "hello there\nsecond line");
+    assert_eq!(texts(&merged), vec!["hello", "there", "second", "line"]);
+    assert_eq!(merged.blocks[1]["left"], "70");
+    assert_eq!(merged.confidence, Some(85.0));
+}
+
+#[test]
+fn test_merge_normalized_blocks() {
+    let previous = OcrOutput {
+        text: "top".to_string(),
+        blocks: vec![HashMap::from([
+            ("text".to_string(), "top".to_string()),
+            ("left".to_string(), "0.1".to_string()),
+            ("top".to_string(), "0.1".to_string()),
+            ("width".to_string(), "0.1".to_string()),
+            ("height".to_string(), "0.05".to_string()),
+        ])],
+        confidence: Some(0.9),
+    };
+    let region = DirtyRect {
+        left: 320,
+        top: 240,
+        width: 320,
+        height: 240,
+    };
+    let region_output = OcrOutput {
+        text: "new".to_string(),
+        blocks: vec![HashMap::from([
+            ("text".to_string(), "new".to_string()),
+            ("left".to_string(), "0.5".to_string()),
+            ("top".to_string(), "0.5".to_string()),
+            ("width".to_string(), "0.2".to_string()),
+            ("height".to_string(), "0.1".to_string()),
+        ])],
+        confidence: Some(0.7),
+    };
+
+    let merged = merge_region_outputs(
+        &previous,
+        vec![(region, region_output)],
+        BlockCoordinates::Normalized,
+        WIDTH,
+        HEIGHT,
+    );
+    assert_eq!(merged.text, "top\nnew");
+    let new = &merged.blocks[1];
+    assert_eq!(new["left"].parse::<f64>().unwrap(), 0.75);
+    assert_eq!(new["top"].parse::<f64>().unwrap(), 0.75);
+    assert_eq!(new["width"].parse::<f64>().unwrap(), 0.1);
+    assert_eq!(new["height"].parse::<f64>().unwrap(), 0.05);
+}
+
+/// Backend reading one word at the top left of any image, recording the sizes it read
+#[derive(Default)]
+struct FakeBackend {
+    reads: Mutex<Vec<(u32, u32)>>,
+}
+
+#[async_trait]
+impl OcrBackend for FakeBackend {
+    async fn recognize(&self, image: &DynamicImage, _languages: &[Language]) -> Result<OcrOutput> {
+        self.reads
+            .lock()
+            .unwrap()
+            .push((image.width(), image.height()));
+        Ok(OcrOutput {
+            text: "new".to_string(),
+            blocks: vec![word("new", 0, 0, 10, 10)],
+            confidence: Some(50.0),
+        })
+    }
+
+    fn block_coordinates(&self) -> Option<BlockCoordinates> {
+        Some(BlockCoordinates::Pixels)
+    }
+}
+
+#[tokio::test]
+async fn test_only_changed_regions_are_read() {
+    let backend = FakeBackend::default();
+    let previous_image = draw(blank(), 10, 10, 100, 12);
+    let previous = PreviousWindowOcr {
+        image: previous_image.clone(),
+        output: OcrOutput {
+            text: "old".to_string(),
+            blocks: vec![word("old", 10, 10, 100, 12)],
+            confidence: Some(50.0),
+        },
+        cached_at: Instant::now(),
+    };
+    let current = draw(previous_image.clone(), 300, 400, 40, 12);
+    let image = DynamicImage::ImageLuma8(current.clone());
+
+    let output = recognize_changed_regions(&backend, &image, &current, Some(&previous), &[])
+        .await
+        .unwrap();
+    assert_eq!(output.text, "old\nnew");
+    let reads = backend.reads.lock().unwrap().clone();
+    assert_eq!(reads.len(), 1);
+    assert!(reads[0].0 < WIDTH / 4 && reads[0].1 < HEIGHT / 4);
+
+    // Unchanged windows reuse the previous output, new ones are read entirely
+    let unchanged = DynamicImage::ImageLuma8(previous_image.clone());
+    let output =
+        recognize_changed_regions(&backend, &unchanged, &previous_image, Some(&previous), &[])
+            .await
+            .unwrap();
+    assert_eq!(output, previous.output);
+    recognize_changed_regions(&backend, &image, &current, None, &[])
+        .await
+        .unwrap();
+    assert_eq!(backend.reads.lock().unwrap()[1], (WIDTH, HEIGHT));
+}